@@ -19,7 +19,7 @@ PROJECT_DIR="$(cd "$1" && pwd)"
 WORK_DIR="$(mktemp -d /tmp/fmt-cmp-XXXXXX)"
 DPRINT_DIR="$WORK_DIR/dprint"
 SPOTLESS_DIR="$WORK_DIR/spotless-runner"
-WASM_PATH="$REPO_ROOT/target/wasm32-unknown-unknown/release/dprint_plugin_java.wasm"
+WASM_PATH="$REPO_ROOT/target/wasm32-unknown-unknown/release-wasm/dprint_plugin_java.wasm"
 
 echo "=== PJF Comparison ==="
 echo "Project:  $PROJECT_DIR"
@@ -28,7 +28,7 @@ echo ""
 
 # --- Step 1: Build WASM plugin ---
 echo "Building WASM plugin..."
-(cd "$REPO_ROOT" && cargo build --release --target wasm32-unknown-unknown --features wasm 2>&1 | tail -1)
+(cd "$REPO_ROOT" && cargo build --profile release-wasm --target wasm32-unknown-unknown --features wasm 2>&1 | tail -1)
 
 if [ ! -f "$WASM_PATH" ]; then
   echo "Error: WASM build failed — $WASM_PATH not found"
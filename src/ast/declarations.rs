@@ -0,0 +1,121 @@
+use tree_sitter::Node;
+
+use super::child_of_kind;
+
+/// A `class_declaration`:
+/// `modifiers class Name<T> extends S implements I permits P { ... }`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClassDecl<'a> {
+    pub node: Node<'a>,
+    pub modifiers: Option<Node<'a>>,
+    pub name: Node<'a>,
+    pub type_parameters: Option<Node<'a>>,
+    /// The `extends` clause.
+    pub superclass: Option<Node<'a>>,
+    /// The `implements` clause.
+    pub interfaces: Option<Node<'a>>,
+    pub permits: Option<Node<'a>>,
+    pub body: Node<'a>,
+}
+
+impl<'a> ClassDecl<'a> {
+    pub fn cast(node: Node<'a>) -> Option<Self> {
+        if node.kind() != "class_declaration" {
+            return None;
+        }
+        Some(Self {
+            node,
+            modifiers: child_of_kind(node, "modifiers"),
+            name: node.child_by_field_name("name")?,
+            type_parameters: node.child_by_field_name("type_parameters"),
+            superclass: node.child_by_field_name("superclass"),
+            interfaces: node.child_by_field_name("interfaces"),
+            permits: node.child_by_field_name("permits"),
+            body: node.child_by_field_name("body")?,
+        })
+    }
+}
+
+/// A `method_declaration` or `constructor_declaration`, which share their
+/// signature: `modifiers <T> Type name(params) [] throws E { ... }`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MethodDecl<'a> {
+    pub node: Node<'a>,
+    pub modifiers: Option<Node<'a>>,
+    pub type_parameters: Option<Node<'a>>,
+    /// The return type; `None` for a constructor.
+    pub return_type: Option<Node<'a>>,
+    pub name: Node<'a>,
+    pub parameters: Node<'a>,
+    /// The `[]` of the old array return syntax, `int values()[]`.
+    pub dimensions: Option<Node<'a>>,
+    pub throws: Option<Node<'a>>,
+    /// The block; `None` for an abstract or interface method.
+    pub body: Option<Node<'a>>,
+}
+
+impl<'a> MethodDecl<'a> {
+    pub fn cast(node: Node<'a>) -> Option<Self> {
+        if !matches!(
+            node.kind(),
+            "method_declaration" | "constructor_declaration"
+        ) {
+            return None;
+        }
+        Some(Self {
+            node,
+            modifiers: child_of_kind(node, "modifiers"),
+            type_parameters: node.child_by_field_name("type_parameters"),
+            return_type: node.child_by_field_name("type"),
+            name: node.child_by_field_name("name")?,
+            parameters: node.child_by_field_name("parameters")?,
+            dimensions: node.child_by_field_name("dimensions"),
+            throws: child_of_kind(node, "throws"),
+            body: node.child_by_field_name("body"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    #[test]
+    fn class_parts() {
+        let source = "@A public class C<T> extends B implements I, J { }";
+        let tree = parse(source);
+        let class = ClassDecl::cast(tree.root_node().child(0).unwrap()).unwrap();
+        let text = |node: Option<Node>| node.map(|n| &source[n.byte_range()]);
+        assert_eq!(text(class.modifiers), Some("@A public"));
+        assert_eq!(text(Some(class.name)), Some("C"));
+        assert_eq!(text(class.type_parameters), Some("<T>"));
+        assert_eq!(text(class.superclass), Some("extends B"));
+        assert_eq!(text(class.interfaces), Some("implements I, J"));
+        assert_eq!(class.permits, None);
+        assert!(ClassDecl::cast(tree.root_node()).is_none());
+    }
+
+    #[test]
+    fn method_and_constructor_parts() {
+        let source = "class C { C() throws E {} abstract <T> int[] f(T t); }";
+        let tree = parse(source);
+        let body = tree.root_node().child(0).unwrap();
+        let body = body.child_by_field_name("body").unwrap();
+        let mut cursor = body.walk();
+        let members: Vec<_> = body.named_children(&mut cursor).collect();
+        let constructor = MethodDecl::cast(members[0]).unwrap();
+        assert_eq!(constructor.return_type, None);
+        assert_eq!(
+            &source[constructor.throws.unwrap().byte_range()],
+            "throws E"
+        );
+        assert!(constructor.body.is_some());
+
+        let method = MethodDecl::cast(members[1]).unwrap();
+        assert_eq!(&source[method.return_type.unwrap().byte_range()], "int[]");
+        assert_eq!(&source[method.name.byte_range()], "f");
+        assert_eq!(&source[method.parameters.byte_range()], "(T t)");
+        assert!(method.type_parameters.is_some() && method.body.is_none());
+    }
+}
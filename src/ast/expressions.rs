@@ -0,0 +1,122 @@
+use tree_sitter::Node;
+
+/// One `.method(args)` call of a flattened method invocation chain.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChainSegment<'a> {
+    pub name: Node<'a>,
+    pub type_args: Option<Node<'a>>,
+    pub arg_list: Option<Node<'a>>,
+    /// A line comment on the line the call ends on.
+    pub trailing_comment: Option<Node<'a>>,
+}
+
+/// A chain of method invocations like `a.b().c().d()`, from the nested
+/// `method_invocation` nodes tree-sitter builds for it.
+#[derive(Debug, Clone)]
+pub(crate) struct MethodChain<'a> {
+    /// What the first call is made on, `a` above. For a chain that starts
+    /// with a bare call, `b().c()`, the root is that call.
+    pub root: Node<'a>,
+    /// The calls after the root, first call first.
+    pub segments: Vec<ChainSegment<'a>>,
+}
+
+impl<'a> MethodChain<'a> {
+    /// Flatten the chain ending in `node`. Any node casts: one that is not a
+    /// `method_invocation` is a chain of only its root.
+    pub fn cast(node: Node<'a>) -> Self {
+        // Collect the chain in reverse (innermost first), then reverse at the end.
+        let mut segments = Vec::new();
+        let mut current = node;
+        loop {
+            if current.kind() != "method_invocation" {
+                segments.reverse();
+                return Self {
+                    root: current,
+                    segments,
+                };
+            }
+            if let Some(name) = current.child_by_field_name("name") {
+                segments.push(ChainSegment {
+                    name,
+                    type_args: current.child_by_field_name("type_arguments"),
+                    arg_list: current.child_by_field_name("arguments"),
+                    trailing_comment: trailing_line_comment(current),
+                });
+            }
+            let Some(object) = current.child_by_field_name("object") else {
+                // A bare call at the root of the chain is the root itself;
+                // its handler formats it as a simple invocation
+                segments.pop();
+                segments.reverse();
+                return Self {
+                    root: current,
+                    segments,
+                };
+            };
+            current = object;
+        }
+    }
+}
+
+/// A line comment on the row `node` ends on, with only comments between them.
+pub(crate) fn trailing_line_comment(node: Node<'_>) -> Option<Node<'_>> {
+    let node_end_row = node.end_position().row;
+
+    // Look for a line_comment sibling that starts on the same row
+    let mut next = node.next_sibling();
+    while let Some(sibling) = next {
+        if sibling.kind() == "line_comment" {
+            if sibling.start_position().row == node_end_row {
+                return Some(sibling);
+            }
+            return None; // Comment on different line
+        }
+        if !sibling.is_extra() {
+            return None; // Non-comment node in the way
+        }
+        next = sibling.next_sibling();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    fn chain_of(source: &str, f: impl FnOnce(MethodChain, &str)) {
+        let tree = parse(source);
+        let mut node = tree.root_node();
+        while node.kind() != "method_invocation" {
+            node = node.named_child(node.named_child_count() - 1).unwrap();
+        }
+        f(MethodChain::cast(node), source);
+    }
+
+    #[test]
+    fn flattens_calls_in_order() {
+        chain_of("class A { Object x = a.<T>b().c(1); }", |chain, source| {
+            assert_eq!(&source[chain.root.byte_range()], "a");
+            let names: Vec<&str> = chain
+                .segments
+                .iter()
+                .map(|s| &source[s.name.byte_range()])
+                .collect();
+            assert_eq!(names, ["b", "c"]);
+            assert!(chain.segments[0].type_args.is_some());
+            assert_eq!(
+                &source[chain.segments[1].arg_list.unwrap().byte_range()],
+                "(1)"
+            );
+        });
+    }
+
+    #[test]
+    fn bare_first_call_is_the_root() {
+        chain_of("class A { Object x = b().c(); }", |chain, source| {
+            assert_eq!(&source[chain.root.byte_range()], "b()");
+            assert_eq!(chain.segments.len(), 1);
+        });
+    }
+}
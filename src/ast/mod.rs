@@ -0,0 +1,24 @@
+//! Typed views of the tree-sitter nodes the formatter takes apart.
+//!
+//! Each view wraps a node of one kind and names its parts, so a handler
+//! reads `class.superclass` instead of walking the node's children and
+//! matching on `kind()`. Views are built with `cast`, which returns `None`
+//! for a node of another kind. Comments between the parts belong to no
+//! view; handlers that keep them still walk the node.
+//!
+//! Views exist for class, method and constructor declarations and for
+//! method chains, whose handlers take them apart the most. Other handlers
+//! still walk their nodes' children.
+
+mod declarations;
+mod expressions;
+
+pub(crate) use declarations::{ClassDecl, MethodDecl};
+pub(crate) use expressions::{ChainSegment, MethodChain, trailing_line_comment};
+
+/// The first child of `node` of `kind`, for parts the grammar gives no
+/// field name.
+fn child_of_kind<'a>(node: tree_sitter::Node<'a>, kind: &str) -> Option<tree_sitter::Node<'a>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).find(|c| c.kind() == kind)
+}
@@ -0,0 +1,229 @@
+//! Format Java files without the dprint host, e.g. in a pre-commit hook or a
+//! CI container that cannot run the WASM plugin.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue, GlobalConfiguration};
+use dprint_plugin_java::configuration::{Configuration, resolve_config, validate_resolved};
+use dprint_plugin_java::{FormatOutcome, format_named_stdin, format_text_with_report};
+
+const USAGE: &str = "\
+Usage: dprint-java-fmt [--check | --write] [--config key=value]... [FILE]...
+
+Formats each FILE, or stdin when there are none, and prints the result.
+
+Options:
+  --check                    List files that are not formatted and exit with 1
+  --write                    Rewrite files that are not formatted
+  --config key=value         Set a plugin option, e.g. --config lineWidth=100
+  --stdin-file-path PATH     Name of the file read from stdin (default Stdin.java)
+  -h, --help                 Print this help
+
+Exits with 2 when a file cannot be read or formatted.";
+
+/// What to do with the formatted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Print it to stdout.
+    Print,
+    /// Report whether it differs from the input.
+    Check,
+    /// Write it back to the file.
+    Write,
+}
+
+#[derive(Debug)]
+struct Args {
+    mode: Mode,
+    options: ConfigKeyMap,
+    stdin_file_path: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+/// Parse the command line, or return `None` when help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        mode: Mode::Print,
+        options: ConfigKeyMap::new(),
+        stdin_file_path: PathBuf::from("Stdin.java"),
+        files: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mode = match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--check" => Mode::Check,
+            "--write" => Mode::Write,
+            "--config" => {
+                let option = args.next().ok_or("--config needs a key=value argument")?;
+                let (key, value) = option
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid --config `{option}`: expected key=value"))?;
+                parsed.options.insert(key.to_string(), config_value(value));
+                continue;
+            }
+            "--stdin-file-path" => {
+                let path = args.next().ok_or("--stdin-file-path needs a path")?;
+                parsed.stdin_file_path = PathBuf::from(path);
+                continue;
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("Unknown option `{arg}`"));
+            }
+            _ => {
+                parsed.files.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        if parsed.mode != Mode::Print && parsed.mode != mode {
+            return Err("--check and --write cannot be used together".to_string());
+        }
+        parsed.mode = mode;
+    }
+    if parsed.mode == Mode::Write && parsed.files.is_empty() {
+        return Err("--write needs files to rewrite".to_string());
+    }
+    Ok(Some(parsed))
+}
+
+/// A `--config` value as the dprint config file would hold it.
+fn config_value(value: &str) -> ConfigKeyValue {
+    match value {
+        "true" | "false" => ConfigKeyValue::from_bool(value == "true"),
+        _ => value.parse().map_or_else(
+            |_| ConfigKeyValue::from_str(value),
+            ConfigKeyValue::from_i32,
+        ),
+    }
+}
+
+/// Resolve the `--config` options, or describe why they are invalid.
+fn resolve(options: ConfigKeyMap) -> Result<Configuration, String> {
+    let result = validate_resolved(resolve_config(options, &GlobalConfiguration::default()));
+    if result.diagnostics.is_empty() {
+        return Ok(result.config);
+    }
+    let messages: Vec<String> = result
+        .diagnostics
+        .iter()
+        .map(|d| format!("{}: {}", d.property_name, d.message))
+        .collect();
+    Err(messages.join("\n"))
+}
+
+fn format_stdin(args: &Args, config: &Configuration) -> u8 {
+    let name = &args.stdin_file_path;
+    if args.mode == Mode::Print {
+        return match format_named_stdin(name, io::stdin().lock(), io::stdout().lock(), config) {
+            FormatOutcome::Unchanged | FormatOutcome::Reformatted => 0,
+            FormatOutcome::Error(e) => {
+                eprintln!("{}: {e:#}", name.display());
+                2
+            }
+        };
+    }
+
+    let mut file_text = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut file_text) {
+        eprintln!("{}: {e}", name.display());
+        return 2;
+    }
+    check_file(name, &file_text, config)
+}
+
+/// Format `path`, noting on stderr when `maxChangedLinesPercent` left it
+/// unformatted.
+fn format_text(
+    path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> anyhow::Result<Option<String>> {
+    let (formatted, report) = format_text_with_report(path, file_text, config)?;
+    if let Some(report) = report {
+        eprintln!("{}: skipped: {report}", path.display());
+    }
+    Ok(formatted)
+}
+
+/// Format `path` for `--check`: print it if it is not formatted.
+fn check_file(path: &Path, file_text: &str, config: &Configuration) -> u8 {
+    match format_text(path, file_text, config) {
+        Ok(None) => 0,
+        Ok(Some(_)) => {
+            println!("{}", path.display());
+            1
+        }
+        Err(e) => {
+            eprintln!("{}: {e:#}", path.display());
+            2
+        }
+    }
+}
+
+fn format_file(path: &Path, mode: Mode, config: &Configuration) -> u8 {
+    let file_text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return 2;
+        }
+    };
+    if mode == Mode::Check {
+        return check_file(path, &file_text, config);
+    }
+
+    let formatted = match format_text(path, &file_text, config) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("{}: {e:#}", path.display());
+            return 2;
+        }
+    };
+    let written = match (mode, formatted) {
+        (Mode::Write, Some(formatted)) => std::fs::write(path, formatted),
+        (Mode::Write, None) => Ok(()),
+        (_, formatted) => {
+            io::stdout().write_all(formatted.as_ref().unwrap_or(&file_text).as_bytes())
+        }
+    };
+    match written {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            2
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let config = match resolve(std::mem::take(&mut args.options)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("Invalid configuration:\n{message}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let code = if args.files.is_empty() {
+        format_stdin(&args, &config)
+    } else {
+        args.files
+            .iter()
+            .map(|path| format_file(path, args.mode, &config))
+            .max()
+            .unwrap_or(0)
+    };
+    ExitCode::from(code)
+}
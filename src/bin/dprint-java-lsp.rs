@@ -0,0 +1,17 @@
+//! Serve the formatter as a language server on stdin and stdout, for editors
+//! that speak LSP but have no dprint integration.
+
+use std::io;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match dprint_plugin_java::lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+        Ok(true) => ExitCode::SUCCESS,
+        // `exit` without a `shutdown` first
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("dprint-java-lsp: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -1,9 +1,15 @@
+use std::collections::BTreeMap;
+
 use dprint_core::configuration::NewLineKind;
 use dprint_core::configuration::ParseConfigurationError;
 use serde::Deserialize;
 use serde::Serialize;
 
 /// Formatting style presets inspired by palantir-java-format.
+///
+/// A preset only changes defaults; options set explicitly still win. Every
+/// preset continues wrapped lines two indent levels deeper, so +8 columns
+/// for palantir and AOSP and +4 for google.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum JavaStyle {
@@ -11,7 +17,8 @@ pub enum JavaStyle {
     Palantir,
     /// 100-char line width, 2-space indent (google-java-format default).
     Google,
-    /// 100-char line width, 4-space indent (Android Open Source Project).
+    /// 100-char line width, 4-space indent and Android imports grouped
+    /// first (Android Open Source Project).
     Aosp,
 }
 
@@ -38,10 +45,131 @@ impl JavaStyle {
             JavaStyle::Google => 2,
         }
     }
+
+    /// The default `import_order`. palantir and google keep regular imports
+    /// in one group; AOSP puts Android packages first and `java`/`javax`
+    /// last, as `google-java-format --aosp` does, though other third-party
+    /// packages share one group rather than one per top-level package.
+    #[must_use]
+    pub fn import_order(self) -> Vec<String> {
+        match self {
+            JavaStyle::Palantir | JavaStyle::Google => Vec::new(),
+            JavaStyle::Aosp => [
+                "android",
+                "androidx",
+                "com.android",
+                "dalvik",
+                "libcore",
+                "*",
+                "java",
+                "javax",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+        }
+    }
+}
+
+/// How a wrapped argument list that does not fit on one continuation line is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArgumentPacking {
+    /// PJF layout: fill a single continuation line, otherwise one argument per line.
+    Greedy,
+    /// Pack simple arguments onto as few continuation lines as possible,
+    /// choosing break points that keep the line lengths even.
+    Balanced,
+}
+
+dprint_core::generate_str_to_from![ArgumentPacking, [Greedy, "greedy"], [Balanced, "balanced"]];
+
+/// Where the calls of a wrapped method chain start their lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MethodChainWrapStyle {
+    /// One continuation indent past the chain's line (PJF behaviour).
+    ContinuationIndent,
+    /// Under the first `.` of the chain's line: `sb.append(a)` puts
+    /// `.append(b)` below `.append(a)`.
+    AlignDots,
+}
+
+dprint_core::generate_str_to_from![
+    MethodChainWrapStyle,
+    [ContinuationIndent, "continuationIndent"],
+    [AlignDots, "alignDots"]
+];
+
+/// Where the opening brace of a class, method or statement body goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BraceStyle {
+    /// K&R: `if (x) {`, with `} else {` on one line (PJF behaviour).
+    SameLine,
+    /// Allman: the `{` starts its own line, and `else`/`catch`/`finally`
+    /// start the line after a closing `}`.
+    NextLine,
+}
+
+dprint_core::generate_str_to_from![BraceStyle, [SameLine, "sameLine"], [NextLine, "nextLine"]];
+
+/// Where annotations on an enum constant go relative to the constant's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnnotationPlacement {
+    /// Each annotation on its own line above the constant (PJF behaviour).
+    OwnLine,
+    /// Annotations before the name on the constant's line: `@Deprecated FOO("x"),`.
+    Inline,
+}
+
+dprint_core::generate_str_to_from![
+    AnnotationPlacement,
+    [OwnLine, "ownLine"],
+    [Inline, "inline"]
+];
+
+/// Where static imports go relative to regular imports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StaticImportPosition {
+    /// One block of static imports before the regular imports (PJF behaviour).
+    Top,
+    /// One block of static imports after the regular imports.
+    Bottom,
+    /// Static imports sorted among the regular imports by their path.
+    Mixed,
+}
+
+dprint_core::generate_str_to_from![
+    StaticImportPosition,
+    [Top, "top"],
+    [Bottom, "bottom"],
+    [Mixed, "mixed"]
+];
+
+/// How the members of a class are spaced when the class carries an annotation
+/// listed in `annotationPolicies`, such as Lombok's `@Builder` or `@AutoValue`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemberPolicy {
+    /// Keep the source's blank lines between members, but add none between
+    /// methods and other members with bodies.
+    SourceBlankLines,
+    /// Remove blank lines between consecutive fields, and space the other
+    /// members as usual.
+    CompactFields,
 }
 
+dprint_core::generate_str_to_from![
+    MemberPolicy,
+    [SourceBlankLines, "sourceBlankLines"],
+    [CompactFields, "compactFields"]
+];
+
 /// Resolved configuration for the Java formatter plugin.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Configuration {
     /// Maximum line width before wrapping.
@@ -50,13 +178,164 @@ pub struct Configuration {
     pub indent_width: u8,
     /// Whether to use tabs instead of spaces.
     pub use_tabs: bool,
+    /// Columns a tab takes up when `use_tabs` is set, for fitting lines into
+    /// `line_width`. Defaults to `indent_width`, or 4 when that is 0.
+    pub tab_width: u8,
     /// Newline character to use.
     pub new_line_kind: NewLineKind,
-    /// Whether to format Javadoc comments.
+    /// Whether to format Javadoc comments, both `/** */` and `///` Markdown ones.
     pub format_javadoc: bool,
     /// Character threshold at which method chains get broken across lines.
     /// Lines with chained method calls exceeding this width will be wrapped.
+    /// Defaults to `line_width - 40` (80 at the default width of 120). A
+    /// threshold past `line_width` is flagged by `validate` and formats as
+    /// `line_width`; see [`Configuration::chain_threshold`].
     pub method_chain_threshold: u32,
+    /// Number of calls after which a method chain breaks every call onto its
+    /// own line, even when it fits. `0` leaves chains to the width rules.
+    pub method_chain_min_calls_to_break: u32,
+    /// Whether a chain that calls `stream()` or `parallelStream()` breaks
+    /// every call after it onto its own line, even when it fits.
+    pub break_stream_pipelines: bool,
+    /// Where the calls of a wrapped chain start their lines. Chains that
+    /// wrap right after their receiver, and those whose calls open a block or
+    /// would not fit at the aligned column, keep the continuation indent.
+    pub method_chain_wrap_style: MethodChainWrapStyle,
     /// Whether to prefer inlining lambdas on a single line when they fit.
     pub inline_lambdas: bool,
+    /// Layout for argument lists that overflow a single continuation line.
+    pub argument_packing: ArgumentPacking,
+    /// Placement of opening braces for type, method and statement bodies.
+    /// Lambda bodies, anonymous classes and array initializers always keep `{` inline.
+    pub brace_style: BraceStyle,
+    /// Whether an `else` after the closing `}` of an `if` block starts the
+    /// next line instead of following the `}`. Defaults to whether
+    /// `brace_style` is `NextLine`.
+    pub else_on_new_line: bool,
+    /// Whether a `catch` starts the line after the `}` before it. Defaults to
+    /// whether `brace_style` is `NextLine`.
+    pub catch_on_new_line: bool,
+    /// Whether a `finally` starts the line after the `}` before it. Defaults
+    /// to whether `brace_style` is `NextLine`.
+    pub finally_on_new_line: bool,
+    /// Whether the `while` of a do-while statement starts the line after the
+    /// `}` of its body. Defaults to whether `brace_style` is `NextLine`.
+    pub while_on_new_line: bool,
+    /// Placement of annotations on enum constants.
+    pub enum_constant_annotations: AnnotationPlacement,
+    /// Whether to pad the labels of arrow switch cases so the `->` tokens of a
+    /// switch line up.
+    pub align_switch_arrows: bool,
+    /// Package prefixes that split regular imports into groups, in order,
+    /// with a blank line between groups. `*` places every import no prefix
+    /// matches; without it those imports come last. Empty keeps one group.
+    /// Static imports are placed by `static_import_position`.
+    pub import_order: Vec<String>,
+    /// Where static imports go relative to regular imports.
+    pub static_import_position: StaticImportPosition,
+    /// Whether to sort imports. When false, imports keep their source order
+    /// and blank lines, collapsed to one, and `import_order` and
+    /// `static_import_position` are ignored.
+    pub sort_imports: bool,
+    /// Whether to drop single-type imports whose simple name never appears
+    /// in the file's code or Javadoc references. Static and wildcard imports
+    /// are always kept.
+    pub remove_unused_imports: bool,
+    /// Whether a brace-less `if` or `else` body already written on the line of
+    /// its keyword, like `if (done) return;`, stays there when it fits. When
+    /// false, such bodies go on their own indented line.
+    pub allow_single_line_if: bool,
+    /// Whether a method or constructor whose body is a single simple
+    /// statement, like `int x() { return x; }`, stays on one line when the
+    /// source already has it there and it fits. If the parameters wrap, the
+    /// body stays after the `)` when it fits there. When false, such bodies
+    /// go on their own indented line.
+    pub keep_simple_methods_on_one_line: bool,
+    /// Whether an anonymous class whose only member is such a simple method,
+    /// like `new Comparator<>() { public int compare(A a, A b) { return 0; } }`,
+    /// stays on one line when the source already has it there and it fits.
+    /// Methods with annotations, such as `@Override`, always expand.
+    pub keep_simple_anonymous_classes_on_one_line: bool,
+    /// Whether a double-brace initialization, an anonymous class whose only
+    /// member is an instance initializer, prints as `new HashMap<>() {{`
+    /// with the statements indented once and `}}` to close. When false, the
+    /// initializer block goes on its own line inside the class body.
+    pub tight_double_brace_initialization: bool,
+    /// Member spacing for classes annotated with one of these annotations,
+    /// keyed by simple or qualified name: `lombok.Builder` matches both
+    /// `@Builder` and `@lombok.Builder`.
+    pub annotation_policies: BTreeMap<String, MemberPolicy>,
+    /// Whether to re-wrap method chains. When false, a chain breaks before a
+    /// `.` exactly where the source does; its arguments are still formatted.
+    pub format_chains: bool,
+    /// Whether a chain of field accesses without calls, like `a.b.c.d`, wraps
+    /// at its dots when it is too wide, as method chains do. Requires
+    /// `format_chains`. When false, such chains stay on one line.
+    pub wrap_field_access_chains: bool,
+    /// Names of calls, like `build` or `toList`, that stay on the line of the
+    /// segment before them when they end a wrapped chain and take no
+    /// arguments. Empty gives every call of a wrapped chain its own line.
+    pub chain_terminal_calls: Vec<String>,
+    /// Whether to rewrite the import block. When false, imports keep their
+    /// source order and `sort_imports`, `import_order`,
+    /// `static_import_position` and `remove_unused_imports` have no effect.
+    pub format_imports: bool,
+    /// Whether to lay out annotation arguments, such as long string
+    /// concatenations in `@Query(...)`. When false, an annotation's argument
+    /// list keeps its source line breaks and relative indentation.
+    pub format_strings_in_annotations: bool,
+    /// Whether a file with syntax errors is still formatted. Each statement
+    /// or member containing an error is kept exactly as written and the rest
+    /// of the file is formatted. When false, such files fail to format.
+    pub format_with_syntax_errors: bool,
+    /// Whether to check that formatting kept the code's tokens. The output
+    /// is parsed again and its tokens compared with the input's, ignoring
+    /// whitespace, comments and the changes the formatter makes on purpose,
+    /// such as modifier order and import sorting. When they differ, the file
+    /// is left unchanged and the difference is reported alongside it, as a
+    /// [`SkipReason`](crate::SkipReason) or diagnostic rather than an error.
+    pub verify_tokens: bool,
+    /// Whether a file containing a construct the formatter has no handler
+    /// for, and so would print as written, fails to format instead. Finding
+    /// such constructs takes another generation pass over each file.
+    pub strict: bool,
+    /// Most lines of a file, as a percentage, that formatting may change. A
+    /// file whose formatting would change more is left as it is and reported,
+    /// so a large codebase can adopt the formatter a little at a time. `100`
+    /// formats every file.
+    pub max_changed_lines_percent: u8,
+    /// Most blank lines kept in a row where the source has them.
+    pub max_blank_lines: u8,
+    /// Blank lines between the `package` declaration and what follows it.
+    pub blank_lines_after_package: u8,
+    /// Blank lines before the closing `}` of a class, interface, enum or
+    /// record body that has members.
+    pub blank_lines_before_class_end: u8,
+    /// Blank lines between members of a type body when either has a body,
+    /// like methods and nested classes. Fields keep the source's blank lines.
+    pub blank_lines_between_members: u8,
+    /// Debugging aid: annotate each wrap decision with a `/* wrapped: ... */`
+    /// comment explaining why the line broke. Never enable for real formatting.
+    pub debug_wrap_reasons: bool,
+}
+
+impl Configuration {
+    /// Columns one indent level takes up: a tab of `tab_width` when
+    /// `use_tabs` is set, otherwise `indent_width` spaces.
+    #[must_use]
+    pub fn indent_columns(&self) -> u8 {
+        if self.use_tabs {
+            self.tab_width
+        } else {
+            self.indent_width
+        }
+    }
+
+    /// The column method chains wrap at: `method_chain_threshold`, or
+    /// `line_width` if that is less, since a threshold past the line width
+    /// would never be reached before the line overflows.
+    #[must_use]
+    pub fn chain_threshold(&self) -> usize {
+        self.method_chain_threshold.min(self.line_width) as usize
+    }
 }
@@ -0,0 +1,344 @@
+use dprint_core::configuration::{
+    ConfigKeyMap, ConfigKeyValue, ConfigurationDiagnostic, GlobalConfiguration,
+};
+
+use super::Configuration;
+use super::resolve_config;
+use super::validate_resolved;
+
+/// Prefix of the ids of the Java formatter settings in an Eclipse profile.
+const SETTING_PREFIX: &str = "org.eclipse.jdt.core.formatter.";
+
+/// Eclipse settings that map to a plugin option of the same meaning, with
+/// the option and how the value converts.
+const DIRECT_SETTINGS: &[(&str, &str, ValueKind)] = &[
+    ("lineSplit", "lineWidth", ValueKind::Number),
+    (
+        "number_of_empty_lines_to_preserve",
+        "maxBlankLines",
+        ValueKind::Number,
+    ),
+    (
+        "blank_lines_after_package",
+        "blankLinesAfterPackage",
+        ValueKind::Number,
+    ),
+    (
+        "blank_lines_before_method",
+        "blankLinesBetweenMembers",
+        ValueKind::Number,
+    ),
+    (
+        "blank_lines_after_last_class_body_declaration",
+        "blankLinesBeforeClassEnd",
+        ValueKind::Number,
+    ),
+    (
+        "comment.format_javadoc_comments",
+        "formatJavadoc",
+        ValueKind::Bool,
+    ),
+    (
+        "keep_simple_if_on_one_line",
+        "allowSingleLineIf",
+        ValueKind::Bool,
+    ),
+    (
+        "insert_new_line_before_else_in_if_statement",
+        "elseOnNewLine",
+        ValueKind::Insert,
+    ),
+    (
+        "insert_new_line_before_catch_in_try_statement",
+        "catchOnNewLine",
+        ValueKind::Insert,
+    ),
+    (
+        "insert_new_line_before_finally_in_try_statement",
+        "finallyOnNewLine",
+        ValueKind::Insert,
+    ),
+    (
+        "insert_new_line_before_while_in_do_statement",
+        "whileOnNewLine",
+        ValueKind::Insert,
+    ),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    Number,
+    Bool,
+    /// `insert` or `do not insert`.
+    Insert,
+}
+
+/// A setting of an Eclipse profile that has no plugin option, or whose
+/// value the plugin cannot express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmappedSetting {
+    /// The setting's id without the `org.eclipse.jdt.core.formatter.` prefix.
+    pub id: String,
+    pub value: String,
+}
+
+/// The result of [`import_eclipse_profile`].
+#[derive(Debug)]
+pub struct EclipseImport {
+    /// The plugin options the profile maps to, as a dprint config file would
+    /// hold them.
+    pub options: ConfigKeyMap,
+    /// The configuration `options` resolve to.
+    pub config: Configuration,
+    /// Settings that were not mapped, in profile order. Most of an exported
+    /// profile's settings cover layout the plugin does not make
+    /// configurable.
+    pub unmapped: Vec<UnmappedSetting>,
+    /// Problems with the profile or with the values of mapped settings.
+    pub diagnostics: Vec<ConfigurationDiagnostic>,
+}
+
+/// Map the settings of an Eclipse formatter profile, as exported to
+/// `eclipse-formatter.xml`, to plugin options.
+///
+/// The indentation, line width, brace position, keyword placement and
+/// blank line settings are mapped. Brace positions map to `braceStyle` when every
+/// `brace_position_for_*` setting agrees with the type declaration's; the
+/// others are reported as unmapped. Of a file with several profiles only
+/// the first is read.
+#[must_use]
+pub fn import_eclipse_profile(xml: &str) -> EclipseImport {
+    let mut diagnostics = Vec::new();
+    let settings = profile_settings(xml);
+    if settings.is_empty() {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "profile".to_string(),
+            message: "No `<setting id=\"...\" value=\"...\"/>` elements found".to_string(),
+        });
+    }
+
+    let mut options = ConfigKeyMap::new();
+    let mut unmapped = Vec::new();
+    let get = |id: &str| {
+        settings
+            .iter()
+            .find(|(key, _)| key == id)
+            .map(|(_, value)| value.as_str())
+    };
+
+    // Eclipse indents by `indentation.size` only with mixed tabs and spaces
+    let tab_char = get("tabulation.char");
+    let indent_id = if tab_char == Some("mixed") {
+        "indentation.size"
+    } else {
+        "tabulation.size"
+    };
+    let brace_style = get("brace_position_for_type_declaration").and_then(brace_style_of);
+
+    for (id, value) in &settings {
+        let option = match id.as_str() {
+            "tabulation.char" => match value.as_str() {
+                "tab" => Some(("useTabs", ConfigKeyValue::from_bool(true))),
+                "space" => Some(("useTabs", ConfigKeyValue::from_bool(false))),
+                _ => None,
+            },
+            "tabulation.size" | "indentation.size" if id == indent_id => value
+                .parse()
+                .ok()
+                .map(|width| ("indentWidth", ConfigKeyValue::from_i32(width))),
+            // Wrapped lines always continue two indent levels deeper
+            "continuation_indentation" if value == "2" => continue,
+            _ if id.starts_with("brace_position_for_") => brace_style
+                .filter(|&style| brace_style_of(value) == Some(style))
+                .map(|style| ("braceStyle", ConfigKeyValue::from_str(style))),
+            _ => DIRECT_SETTINGS
+                .iter()
+                .find(|(eclipse_id, _, _)| eclipse_id == id)
+                .and_then(|&(_, option, kind)| {
+                    let value = match kind {
+                        ValueKind::Number => ConfigKeyValue::from_i32(value.parse().ok()?),
+                        ValueKind::Bool => ConfigKeyValue::from_bool(value == "true"),
+                        ValueKind::Insert => ConfigKeyValue::from_bool(value == "insert"),
+                    };
+                    Some((option, value))
+                }),
+        };
+        match option {
+            Some((option, value)) => {
+                options.insert(option.to_string(), value);
+            }
+            None => unmapped.push(UnmappedSetting {
+                id: id.clone(),
+                value: value.clone(),
+            }),
+        }
+    }
+
+    let result = validate_resolved(resolve_config(
+        options.clone(),
+        &GlobalConfiguration::default(),
+    ));
+    diagnostics.extend(result.diagnostics);
+    EclipseImport {
+        options,
+        config: result.config,
+        unmapped,
+        diagnostics,
+    }
+}
+
+/// The `braceStyle` an Eclipse brace position is, if any.
+fn brace_style_of(value: &str) -> Option<&'static str> {
+    match value {
+        "end_of_line" => Some("sameLine"),
+        "next_line" => Some("nextLine"),
+        _ => None,
+    }
+}
+
+/// The `(id, value)` of each Java formatter setting of the first profile in
+/// `xml`, with the id's common prefix removed.
+fn profile_settings(xml: &str) -> Vec<(String, String)> {
+    let profile = xml.find("</profile>").map_or(xml, |end| &xml[..end]);
+    let mut settings = Vec::new();
+    let mut rest = profile;
+    while let Some(start) = rest.find("<setting") {
+        let element = &rest[start + "<setting".len()..];
+        let end = element.find('>').unwrap_or(element.len());
+        let (id, value) = (
+            attribute(&element[..end], "id"),
+            attribute(&element[..end], "value"),
+        );
+        if let (Some(id), Some(value)) = (id, value)
+            && let Some(id) = id.strip_prefix(SETTING_PREFIX)
+        {
+            settings.push((id.to_string(), value));
+        }
+        rest = &element[end..];
+    }
+    settings
+}
+
+/// The unescaped value of attribute `name` in the text of an element's tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    loop {
+        let at = rest.find(name)?;
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(after) = after.strip_prefix('=') else {
+            continue;
+        };
+        let after = after.trim_start();
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &after[1..];
+        let value = &value[..value.find(quote)?];
+        return Some(
+            value
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::BraceStyle;
+    use super::*;
+
+    fn profile(settings: &[(&str, &str)]) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<profiles version=\"21\">\n<profile kind=\"CodeFormatterProfile\" name=\"Team\" version=\"21\">\n",
+        );
+        for (id, value) in settings {
+            xml.push_str(&format!(
+                "<setting id=\"org.eclipse.jdt.core.formatter.{id}\" value=\"{value}\"/>\n"
+            ));
+        }
+        xml.push_str("</profile>\n</profiles>\n");
+        xml
+    }
+
+    #[test]
+    fn maps_common_settings() {
+        let import = import_eclipse_profile(&profile(&[
+            ("tabulation.char", "space"),
+            ("tabulation.size", "2"),
+            ("indentation.size", "4"),
+            ("lineSplit", "100"),
+            ("continuation_indentation", "2"),
+            ("brace_position_for_type_declaration", "next_line"),
+            ("brace_position_for_method_declaration", "next_line"),
+            ("number_of_empty_lines_to_preserve", "2"),
+            ("comment.format_javadoc_comments", "true"),
+            (
+                "insert_new_line_before_else_in_if_statement",
+                "do not insert",
+            ),
+            ("insert_new_line_before_catch_in_try_statement", "insert"),
+        ]));
+        assert!(import.diagnostics.is_empty(), "{:?}", import.diagnostics);
+        assert!(!import.config.use_tabs);
+        assert_eq!(import.config.indent_width, 2);
+        assert_eq!(import.config.line_width, 100);
+        assert_eq!(import.config.brace_style, BraceStyle::NextLine);
+        assert_eq!(import.config.max_blank_lines, 2);
+        assert!(import.config.format_javadoc);
+        assert!(!import.config.else_on_new_line);
+        assert!(import.config.catch_on_new_line);
+        assert!(import.config.finally_on_new_line);
+        assert_eq!(
+            import.unmapped,
+            [UnmappedSetting {
+                id: "indentation.size".to_string(),
+                value: "4".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_settings_without_an_equivalent() {
+        let import = import_eclipse_profile(&profile(&[
+            ("brace_position_for_type_declaration", "end_of_line"),
+            ("brace_position_for_block", "next_line_shifted"),
+            ("continuation_indentation", "1"),
+            ("comment.line_length", "80"),
+        ]));
+        assert_eq!(import.config.brace_style, BraceStyle::SameLine);
+        let ids: Vec<&str> = import.unmapped.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "brace_position_for_block",
+                "continuation_indentation",
+                "comment.line_length"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_values_and_empty_profiles() {
+        let import = import_eclipse_profile(&profile(&[("lineSplit", "0")]));
+        assert_eq!(import.diagnostics.len(), 1);
+        assert_eq!(import.diagnostics[0].property_name, "lineWidth");
+
+        let import = import_eclipse_profile("<profiles/>");
+        assert_eq!(import.diagnostics[0].property_name, "profile");
+    }
+
+    #[test]
+    fn reads_attributes_in_any_order_and_quote_style() {
+        assert_eq!(
+            attribute(" value='a &amp; b' id=\"x\"/", "value").as_deref(),
+            Some("a & b")
+        );
+        assert_eq!(attribute(" xid=\"1\" id=\"2\"", "id").as_deref(), Some("2"));
+    }
+}
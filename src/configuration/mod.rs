@@ -1,6 +1,10 @@
 #[allow(clippy::module_inception)]
 mod configuration;
+mod eclipse;
 mod resolve_config;
+mod validate;
 
 pub use configuration::*;
+pub use eclipse::*;
 pub use resolve_config::*;
+pub use validate::*;
@@ -1,15 +1,30 @@
+use std::collections::BTreeMap;
+
 use dprint_core::configuration::ConfigKeyMap;
+use dprint_core::configuration::ConfigKeyValue;
+use dprint_core::configuration::ConfigurationDiagnostic;
 use dprint_core::configuration::GlobalConfiguration;
 use dprint_core::configuration::NewLineKind;
 use dprint_core::configuration::ResolveConfigurationResult;
 use dprint_core::configuration::get_unknown_property_diagnostics;
 use dprint_core::configuration::get_value;
 
+use super::AnnotationPlacement;
+use super::ArgumentPacking;
+use super::BraceStyle;
 use super::Configuration;
 use super::JavaStyle;
+use super::MemberPolicy;
+use super::MethodChainWrapStyle;
+use super::StaticImportPosition;
+
+/// Distance between `lineWidth` and the default `methodChainThreshold`.
+/// At PJF's 120 columns this yields its fixed chain column limit of 80.
+const METHOD_CHAIN_THRESHOLD_MARGIN: u32 = 40;
 
 /// Resolve raw configuration key-value pairs into a typed `Configuration`.
 #[must_use]
+#[allow(clippy::too_many_lines)]
 pub fn resolve_config(
     config: ConfigKeyMap,
     global_config: &GlobalConfiguration,
@@ -37,6 +52,12 @@ pub fn resolve_config(
         global_config.use_tabs.unwrap_or(false),
         &mut diagnostics,
     );
+    let tab_width = get_value(
+        &mut config,
+        "tabWidth",
+        default_tab_width(indent_width),
+        &mut diagnostics,
+    );
     let new_line_kind = get_value(
         &mut config,
         "newLineKind",
@@ -44,9 +65,135 @@ pub fn resolve_config(
         &mut diagnostics,
     );
     let format_javadoc = get_value(&mut config, "formatJavadoc", false, &mut diagnostics);
-    let method_chain_threshold =
-        get_value(&mut config, "methodChainThreshold", 80u32, &mut diagnostics);
+    let method_chain_threshold = get_value(
+        &mut config,
+        "methodChainThreshold",
+        line_width.saturating_sub(METHOD_CHAIN_THRESHOLD_MARGIN),
+        &mut diagnostics,
+    );
+    let method_chain_min_calls_to_break = get_value(
+        &mut config,
+        "methodChainMinCallsToBreak",
+        0,
+        &mut diagnostics,
+    );
+    let break_stream_pipelines =
+        get_value(&mut config, "breakStreamPipelines", false, &mut diagnostics);
+    let method_chain_wrap_style = get_value(
+        &mut config,
+        "methodChainWrapStyle",
+        MethodChainWrapStyle::ContinuationIndent,
+        &mut diagnostics,
+    );
     let inline_lambdas = get_value(&mut config, "inlineLambdas", true, &mut diagnostics);
+    let argument_packing = get_value(
+        &mut config,
+        "argumentPacking",
+        ArgumentPacking::Greedy,
+        &mut diagnostics,
+    );
+    let brace_style = get_value(
+        &mut config,
+        "braceStyle",
+        BraceStyle::SameLine,
+        &mut diagnostics,
+    );
+    let keywords_on_new_line = brace_style == BraceStyle::NextLine;
+    let else_on_new_line = get_value(
+        &mut config,
+        "elseOnNewLine",
+        keywords_on_new_line,
+        &mut diagnostics,
+    );
+    let catch_on_new_line = get_value(
+        &mut config,
+        "catchOnNewLine",
+        keywords_on_new_line,
+        &mut diagnostics,
+    );
+    let finally_on_new_line = get_value(
+        &mut config,
+        "finallyOnNewLine",
+        keywords_on_new_line,
+        &mut diagnostics,
+    );
+    let while_on_new_line = get_value(
+        &mut config,
+        "whileOnNewLine",
+        keywords_on_new_line,
+        &mut diagnostics,
+    );
+    let enum_constant_annotations = get_value(
+        &mut config,
+        "enumConstantAnnotations",
+        AnnotationPlacement::OwnLine,
+        &mut diagnostics,
+    );
+    let align_switch_arrows = get_value(&mut config, "alignSwitchArrows", false, &mut diagnostics);
+    let import_order = get_import_order(&mut config, style.import_order(), &mut diagnostics);
+    let static_import_position = get_value(
+        &mut config,
+        "staticImportPosition",
+        StaticImportPosition::Top,
+        &mut diagnostics,
+    );
+    let sort_imports = get_value(&mut config, "sortImports", true, &mut diagnostics);
+    let remove_unused_imports =
+        get_value(&mut config, "removeUnusedImports", false, &mut diagnostics);
+    let allow_single_line_if = get_value(&mut config, "allowSingleLineIf", false, &mut diagnostics);
+    let keep_simple_methods_on_one_line = get_value(
+        &mut config,
+        "keepSimpleMethodsOnOneLine",
+        false,
+        &mut diagnostics,
+    );
+    let keep_simple_anonymous_classes_on_one_line = get_value(
+        &mut config,
+        "keepSimpleAnonymousClassesOnOneLine",
+        false,
+        &mut diagnostics,
+    );
+    let tight_double_brace_initialization = get_value(
+        &mut config,
+        "tightDoubleBraceInitialization",
+        false,
+        &mut diagnostics,
+    );
+    let annotation_policies =
+        get_annotation_policies(&mut config, BTreeMap::new(), &mut diagnostics);
+    let format_chains = get_value(&mut config, "formatChains", true, &mut diagnostics);
+    let wrap_field_access_chains = get_value(
+        &mut config,
+        "wrapFieldAccessChains",
+        false,
+        &mut diagnostics,
+    );
+    let chain_terminal_calls = get_chain_terminal_calls(&mut config, Vec::new(), &mut diagnostics);
+    let format_imports = get_value(&mut config, "formatImports", true, &mut diagnostics);
+    let format_strings_in_annotations = get_value(
+        &mut config,
+        "formatStringsInAnnotations",
+        true,
+        &mut diagnostics,
+    );
+    let format_with_syntax_errors = get_value(
+        &mut config,
+        "formatWithSyntaxErrors",
+        false,
+        &mut diagnostics,
+    );
+    let verify_tokens = get_value(&mut config, "verifyTokens", false, &mut diagnostics);
+    let strict = get_value(&mut config, "strict", false, &mut diagnostics);
+    let max_changed_lines_percent =
+        get_value(&mut config, "maxChangedLinesPercent", 100, &mut diagnostics);
+    let max_blank_lines = get_value(&mut config, "maxBlankLines", 1, &mut diagnostics);
+    let blank_lines_after_package =
+        get_value(&mut config, "blankLinesAfterPackage", 1, &mut diagnostics);
+    let blank_lines_before_class_end =
+        get_value(&mut config, "blankLinesBeforeClassEnd", 0, &mut diagnostics);
+    let blank_lines_between_members =
+        get_value(&mut config, "blankLinesBetweenMembers", 1, &mut diagnostics);
+    let debug_wrap_reasons = get_value(&mut config, "debugWrapReasons", false, &mut diagnostics);
 
     diagnostics.extend(get_unknown_property_diagnostics(config));
 
@@ -55,18 +202,449 @@ pub fn resolve_config(
             line_width,
             indent_width,
             use_tabs,
+            tab_width,
             new_line_kind,
             format_javadoc,
             method_chain_threshold,
+            method_chain_min_calls_to_break,
+            break_stream_pipelines,
+            method_chain_wrap_style,
             inline_lambdas,
+            argument_packing,
+            brace_style,
+            else_on_new_line,
+            catch_on_new_line,
+            finally_on_new_line,
+            while_on_new_line,
+            enum_constant_annotations,
+            align_switch_arrows,
+            import_order,
+            static_import_position,
+            sort_imports,
+            remove_unused_imports,
+            allow_single_line_if,
+            keep_simple_methods_on_one_line,
+            keep_simple_anonymous_classes_on_one_line,
+            tight_double_brace_initialization,
+            annotation_policies,
+            format_chains,
+            wrap_field_access_chains,
+            chain_terminal_calls,
+            format_imports,
+            format_strings_in_annotations,
+            format_with_syntax_errors,
+            verify_tokens,
+            strict,
+            max_changed_lines_percent,
+            max_blank_lines,
+            blank_lines_after_package,
+            blank_lines_before_class_end,
+            blank_lines_between_members,
+            debug_wrap_reasons,
         },
         diagnostics,
     }
 }
 
+/// Resolve per-file overrides on top of an already resolved `base` configuration.
+///
+/// Accepts the same keys as [`resolve_config`] except `style`, with values
+/// missing from `overrides` taken from `base`. If `base` uses the derived
+/// `methodChainThreshold`, a `lineWidth` override moves the threshold with it,
+/// and an `indentWidth` override moves a derived `tabWidth`;
+/// likewise a `braceStyle` override moves the `...OnNewLine` keyword options
+/// `base` left at their defaults.
+#[must_use]
+#[allow(clippy::too_many_lines)]
+pub fn resolve_overrides(
+    base: &Configuration,
+    overrides: ConfigKeyMap,
+) -> ResolveConfigurationResult<Configuration> {
+    let mut config = overrides;
+    let mut diagnostics = Vec::new();
+
+    let line_width = get_value(&mut config, "lineWidth", base.line_width, &mut diagnostics);
+    let derived_threshold = base.method_chain_threshold
+        == base
+            .line_width
+            .saturating_sub(METHOD_CHAIN_THRESHOLD_MARGIN);
+    let method_chain_threshold = get_value(
+        &mut config,
+        "methodChainThreshold",
+        if derived_threshold {
+            line_width.saturating_sub(METHOD_CHAIN_THRESHOLD_MARGIN)
+        } else {
+            base.method_chain_threshold
+        },
+        &mut diagnostics,
+    );
+    let brace_style = get_value(
+        &mut config,
+        "braceStyle",
+        base.brace_style,
+        &mut diagnostics,
+    );
+    let base_keywords_on_new_line = base.brace_style == BraceStyle::NextLine;
+    let mut keyword_on_new_line = |key: &str, base_value: bool| {
+        let default = if base_value == base_keywords_on_new_line {
+            brace_style == BraceStyle::NextLine
+        } else {
+            base_value
+        };
+        get_value(&mut config, key, default, &mut diagnostics)
+    };
+    let else_on_new_line = keyword_on_new_line("elseOnNewLine", base.else_on_new_line);
+    let catch_on_new_line = keyword_on_new_line("catchOnNewLine", base.catch_on_new_line);
+    let finally_on_new_line = keyword_on_new_line("finallyOnNewLine", base.finally_on_new_line);
+    let while_on_new_line = keyword_on_new_line("whileOnNewLine", base.while_on_new_line);
+
+    let indent_width = get_value(
+        &mut config,
+        "indentWidth",
+        base.indent_width,
+        &mut diagnostics,
+    );
+    let tab_width = get_value(
+        &mut config,
+        "tabWidth",
+        if base.tab_width == default_tab_width(base.indent_width) {
+            default_tab_width(indent_width)
+        } else {
+            base.tab_width
+        },
+        &mut diagnostics,
+    );
+
+    let resolved = Configuration {
+        line_width,
+        indent_width,
+        use_tabs: get_value(&mut config, "useTabs", base.use_tabs, &mut diagnostics),
+        tab_width,
+        new_line_kind: get_value(
+            &mut config,
+            "newLineKind",
+            base.new_line_kind,
+            &mut diagnostics,
+        ),
+        format_javadoc: get_value(
+            &mut config,
+            "formatJavadoc",
+            base.format_javadoc,
+            &mut diagnostics,
+        ),
+        method_chain_threshold,
+        method_chain_min_calls_to_break: get_value(
+            &mut config,
+            "methodChainMinCallsToBreak",
+            base.method_chain_min_calls_to_break,
+            &mut diagnostics,
+        ),
+        break_stream_pipelines: get_value(
+            &mut config,
+            "breakStreamPipelines",
+            base.break_stream_pipelines,
+            &mut diagnostics,
+        ),
+        method_chain_wrap_style: get_value(
+            &mut config,
+            "methodChainWrapStyle",
+            base.method_chain_wrap_style,
+            &mut diagnostics,
+        ),
+        inline_lambdas: get_value(
+            &mut config,
+            "inlineLambdas",
+            base.inline_lambdas,
+            &mut diagnostics,
+        ),
+        argument_packing: get_value(
+            &mut config,
+            "argumentPacking",
+            base.argument_packing,
+            &mut diagnostics,
+        ),
+        brace_style,
+        else_on_new_line,
+        catch_on_new_line,
+        finally_on_new_line,
+        while_on_new_line,
+        enum_constant_annotations: get_value(
+            &mut config,
+            "enumConstantAnnotations",
+            base.enum_constant_annotations,
+            &mut diagnostics,
+        ),
+        align_switch_arrows: get_value(
+            &mut config,
+            "alignSwitchArrows",
+            base.align_switch_arrows,
+            &mut diagnostics,
+        ),
+        import_order: get_import_order(&mut config, base.import_order.clone(), &mut diagnostics),
+        static_import_position: get_value(
+            &mut config,
+            "staticImportPosition",
+            base.static_import_position,
+            &mut diagnostics,
+        ),
+        sort_imports: get_value(
+            &mut config,
+            "sortImports",
+            base.sort_imports,
+            &mut diagnostics,
+        ),
+        remove_unused_imports: get_value(
+            &mut config,
+            "removeUnusedImports",
+            base.remove_unused_imports,
+            &mut diagnostics,
+        ),
+        allow_single_line_if: get_value(
+            &mut config,
+            "allowSingleLineIf",
+            base.allow_single_line_if,
+            &mut diagnostics,
+        ),
+        keep_simple_methods_on_one_line: get_value(
+            &mut config,
+            "keepSimpleMethodsOnOneLine",
+            base.keep_simple_methods_on_one_line,
+            &mut diagnostics,
+        ),
+        keep_simple_anonymous_classes_on_one_line: get_value(
+            &mut config,
+            "keepSimpleAnonymousClassesOnOneLine",
+            base.keep_simple_anonymous_classes_on_one_line,
+            &mut diagnostics,
+        ),
+        tight_double_brace_initialization: get_value(
+            &mut config,
+            "tightDoubleBraceInitialization",
+            base.tight_double_brace_initialization,
+            &mut diagnostics,
+        ),
+        annotation_policies: get_annotation_policies(
+            &mut config,
+            base.annotation_policies.clone(),
+            &mut diagnostics,
+        ),
+        format_chains: get_value(
+            &mut config,
+            "formatChains",
+            base.format_chains,
+            &mut diagnostics,
+        ),
+        wrap_field_access_chains: get_value(
+            &mut config,
+            "wrapFieldAccessChains",
+            base.wrap_field_access_chains,
+            &mut diagnostics,
+        ),
+        chain_terminal_calls: get_chain_terminal_calls(
+            &mut config,
+            base.chain_terminal_calls.clone(),
+            &mut diagnostics,
+        ),
+        format_imports: get_value(
+            &mut config,
+            "formatImports",
+            base.format_imports,
+            &mut diagnostics,
+        ),
+        format_strings_in_annotations: get_value(
+            &mut config,
+            "formatStringsInAnnotations",
+            base.format_strings_in_annotations,
+            &mut diagnostics,
+        ),
+        format_with_syntax_errors: get_value(
+            &mut config,
+            "formatWithSyntaxErrors",
+            base.format_with_syntax_errors,
+            &mut diagnostics,
+        ),
+        verify_tokens: get_value(
+            &mut config,
+            "verifyTokens",
+            base.verify_tokens,
+            &mut diagnostics,
+        ),
+        strict: get_value(&mut config, "strict", base.strict, &mut diagnostics),
+        max_changed_lines_percent: get_value(
+            &mut config,
+            "maxChangedLinesPercent",
+            base.max_changed_lines_percent,
+            &mut diagnostics,
+        ),
+        max_blank_lines: get_value(
+            &mut config,
+            "maxBlankLines",
+            base.max_blank_lines,
+            &mut diagnostics,
+        ),
+        blank_lines_after_package: get_value(
+            &mut config,
+            "blankLinesAfterPackage",
+            base.blank_lines_after_package,
+            &mut diagnostics,
+        ),
+        blank_lines_before_class_end: get_value(
+            &mut config,
+            "blankLinesBeforeClassEnd",
+            base.blank_lines_before_class_end,
+            &mut diagnostics,
+        ),
+        blank_lines_between_members: get_value(
+            &mut config,
+            "blankLinesBetweenMembers",
+            base.blank_lines_between_members,
+            &mut diagnostics,
+        ),
+        debug_wrap_reasons: get_value(
+            &mut config,
+            "debugWrapReasons",
+            base.debug_wrap_reasons,
+            &mut diagnostics,
+        ),
+    };
+
+    diagnostics.extend(get_unknown_property_diagnostics(config));
+
+    ResolveConfigurationResult {
+        config: resolved,
+        diagnostics,
+    }
+}
+
+/// The default `tabWidth`: a tab as wide as an indent of spaces would be.
+fn default_tab_width(indent_width: u8) -> u8 {
+    if indent_width == 0 { 4 } else { indent_width }
+}
+
+/// Read the list option `key`: an array of strings, or the same strings
+/// separated by commas, which is how a per-file override spells a list.
+/// `None` when it is not set, or is neither and `expected` was reported.
+fn get_string_list(
+    config: &mut ConfigKeyMap,
+    key: &str,
+    expected: &str,
+    diagnostics: &mut Vec<ConfigurationDiagnostic>,
+) -> Option<Vec<String>> {
+    let value = config.shift_remove(key)?;
+    let values = match value {
+        ConfigKeyValue::String(text) => Some(text.split(',').map(str::to_string).collect()),
+        ConfigKeyValue::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                ConfigKeyValue::String(text) => Some(text),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    if values.is_none() {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: key.to_string(),
+            message: expected.to_string(),
+        });
+    }
+    values
+}
+
+/// Read `importOrder`: an array of package prefixes.
+fn get_import_order(
+    config: &mut ConfigKeyMap,
+    default: Vec<String>,
+    diagnostics: &mut Vec<ConfigurationDiagnostic>,
+) -> Vec<String> {
+    let Some(prefixes) = get_string_list(
+        config,
+        "importOrder",
+        "Expected an array of package prefixes",
+        diagnostics,
+    ) else {
+        return default;
+    };
+    prefixes
+        .iter()
+        .map(|prefix| prefix.trim().trim_end_matches('.').to_string())
+        .filter(|prefix| !prefix.is_empty())
+        .collect()
+}
+
+/// Read `chainTerminalCalls`: an array of method names.
+fn get_chain_terminal_calls(
+    config: &mut ConfigKeyMap,
+    default: Vec<String>,
+    diagnostics: &mut Vec<ConfigurationDiagnostic>,
+) -> Vec<String> {
+    let Some(names) = get_string_list(
+        config,
+        "chainTerminalCalls",
+        "Expected an array of method names",
+        diagnostics,
+    ) else {
+        return default;
+    };
+    names
+        .iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Read `annotationPolicies`: an object from annotation name to member
+/// policy, or `name:policy` pairs separated by commas in a per-file override.
+fn get_annotation_policies(
+    config: &mut ConfigKeyMap,
+    default: BTreeMap<String, MemberPolicy>,
+    diagnostics: &mut Vec<ConfigurationDiagnostic>,
+) -> BTreeMap<String, MemberPolicy> {
+    let Some(value) = config.shift_remove("annotationPolicies") else {
+        return default;
+    };
+    let entries: Option<Vec<(String, String)>> = match value {
+        ConfigKeyValue::String(text) => text
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (name, policy) = entry.split_once(':')?;
+                Some((name.to_string(), policy.to_string()))
+            })
+            .collect(),
+        ConfigKeyValue::Object(map) => map
+            .into_iter()
+            .map(|(name, policy)| match policy {
+                ConfigKeyValue::String(policy) => Some((name, policy)),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    };
+    let policies = entries.and_then(|entries| {
+        entries
+            .into_iter()
+            .map(|(name, policy)| {
+                let name = name.trim().trim_start_matches('@').to_string();
+                let policy = policy.trim().parse::<MemberPolicy>().ok()?;
+                (!name.is_empty()).then_some((name, policy))
+            })
+            .collect::<Option<BTreeMap<_, _>>>()
+    });
+    policies.unwrap_or_else(|| {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "annotationPolicies".to_string(),
+            message: "Expected an object from annotation name to \"sourceBlankLines\" or \"compactFields\""
+                .to_string(),
+        });
+        default
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::configuration::validate_resolved;
     use dprint_core::configuration::ConfigKeyValue;
 
     #[test]
@@ -80,6 +658,138 @@ mod tests {
         assert!(!result.config.use_tabs);
         assert!(result.config.inline_lambdas);
         assert_eq!(result.config.method_chain_threshold, 80);
+        assert_eq!(result.config.argument_packing, ArgumentPacking::Greedy);
+        assert_eq!(result.config.brace_style, BraceStyle::SameLine);
+        assert_eq!(
+            result.config.enum_constant_annotations,
+            AnnotationPlacement::OwnLine
+        );
+        assert!(!result.config.align_switch_arrows);
+        assert!(result.config.import_order.is_empty());
+        assert_eq!(
+            result.config.static_import_position,
+            StaticImportPosition::Top
+        );
+        assert!(result.config.sort_imports);
+        assert!(!result.config.remove_unused_imports);
+        assert!(!result.config.allow_single_line_if);
+        assert!(!result.config.keep_simple_methods_on_one_line);
+        assert!(!result.config.keep_simple_anonymous_classes_on_one_line);
+        assert!(!result.config.tight_double_brace_initialization);
+        assert!(result.config.annotation_policies.is_empty());
+        assert!(result.config.format_chains);
+        assert!(!result.config.wrap_field_access_chains);
+        assert!(result.config.format_imports);
+        assert!(result.config.format_strings_in_annotations);
+        assert!(!result.config.format_with_syntax_errors);
+        assert!(!result.config.verify_tokens);
+        assert!(!result.config.strict);
+        assert_eq!(result.config.max_changed_lines_percent, 100);
+        assert_eq!(result.config.max_blank_lines, 1);
+        assert_eq!(result.config.blank_lines_after_package, 1);
+        assert_eq!(result.config.blank_lines_before_class_end, 0);
+        assert_eq!(result.config.blank_lines_between_members, 1);
+    }
+
+    #[test]
+    fn annotation_policies_from_object_or_string() {
+        let config = ConfigKeyMap::from([(
+            "annotationPolicies".to_string(),
+            ConfigKeyValue::Object(ConfigKeyMap::from([
+                (
+                    "lombok.Builder".to_string(),
+                    ConfigKeyValue::from_str("compactFields"),
+                ),
+                (
+                    "@AutoValue".to_string(),
+                    ConfigKeyValue::from_str("sourceBlankLines"),
+                ),
+            ])),
+        )]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(
+            result.config.annotation_policies,
+            BTreeMap::from([
+                ("AutoValue".to_string(), MemberPolicy::SourceBlankLines),
+                ("lombok.Builder".to_string(), MemberPolicy::CompactFields),
+            ])
+        );
+
+        let overrides = ConfigKeyMap::from([(
+            "annotationPolicies".to_string(),
+            ConfigKeyValue::from_str("Builder:compactFields"),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(
+            result.config.annotation_policies,
+            BTreeMap::from([("Builder".to_string(), MemberPolicy::CompactFields)])
+        );
+
+        let overrides = ConfigKeyMap::from([(
+            "annotationPolicies".to_string(),
+            ConfigKeyValue::from_str("Builder:tight"),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert_eq!(result.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn import_order_from_array_or_string() {
+        let prefixes = ["java", "javax.", "*", " com.example "];
+        let config = ConfigKeyMap::from([(
+            "importOrder".to_string(),
+            ConfigKeyValue::Array(prefixes.into_iter().map(ConfigKeyValue::from_str).collect()),
+        )]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(
+            result.config.import_order,
+            vec!["java", "javax", "*", "com.example"]
+        );
+
+        let overrides = ConfigKeyMap::from([(
+            "importOrder".to_string(),
+            ConfigKeyValue::from_str("java,*"),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.import_order, vec!["java", "*"]);
+
+        let config = ConfigKeyMap::from([("importOrder".to_string(), ConfigKeyValue::from_i32(1))]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert_eq!(result.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn chain_terminal_calls_from_array_or_string() {
+        let config = ConfigKeyMap::from([(
+            "chainTerminalCalls".to_string(),
+            ConfigKeyValue::Array(vec![
+                ConfigKeyValue::from_str("build"),
+                ConfigKeyValue::from_str(" toList "),
+            ]),
+        )]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.chain_terminal_calls, vec!["build", "toList"]);
+
+        let overrides = ConfigKeyMap::from([(
+            "chainTerminalCalls".to_string(),
+            ConfigKeyValue::from_str("get,"),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.chain_terminal_calls, vec!["get"]);
+
+        let config = ConfigKeyMap::from([(
+            "chainTerminalCalls".to_string(),
+            ConfigKeyValue::from_bool(true),
+        )]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.config.chain_terminal_calls.is_empty());
     }
 
     #[test]
@@ -91,6 +801,43 @@ mod tests {
         assert!(result.diagnostics.is_empty());
         assert_eq!(result.config.line_width, 100);
         assert_eq!(result.config.indent_width, 2);
+        assert_eq!(result.config.method_chain_threshold, 60);
+    }
+
+    #[test]
+    fn aosp_style_groups_android_imports() {
+        let config = ConfigKeyMap::from([("style".to_string(), ConfigKeyValue::from_str("aosp"))]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.line_width, 100);
+        assert_eq!(result.config.indent_width, 4);
+        assert_eq!(
+            result.config.import_order,
+            [
+                "android",
+                "androidx",
+                "com.android",
+                "dalvik",
+                "libcore",
+                "*",
+                "java",
+                "javax"
+            ]
+        );
+        assert_eq!(
+            result.config.static_import_position,
+            StaticImportPosition::Top
+        );
+
+        let config = ConfigKeyMap::from([
+            ("style".to_string(), ConfigKeyValue::from_str("aosp")),
+            (
+                "importOrder".to_string(),
+                ConfigKeyValue::from_str("java,*"),
+            ),
+        ]);
+        let result = resolve_config(config, &GlobalConfiguration::default());
+        assert_eq!(result.config.import_order, ["java", "*"]);
     }
 
     #[test]
@@ -106,6 +853,266 @@ mod tests {
         assert_eq!(result.config.indent_width, 2);
     }
 
+    #[test]
+    fn method_chain_threshold_follows_line_width() {
+        let config = ConfigKeyMap::from([("lineWidth".to_string(), ConfigKeyValue::from_i32(80))]);
+        let global = GlobalConfiguration::default();
+        let result = resolve_config(config, &global);
+        assert_eq!(result.config.method_chain_threshold, 40);
+
+        let config = ConfigKeyMap::from([
+            ("lineWidth".to_string(), ConfigKeyValue::from_i32(80)),
+            (
+                "methodChainThreshold".to_string(),
+                ConfigKeyValue::from_i32(70),
+            ),
+        ]);
+        let result = resolve_config(config, &global);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.method_chain_threshold, 70);
+    }
+
+    #[test]
+    fn method_chain_threshold_past_line_width_is_reported() {
+        let config = ConfigKeyMap::from([
+            ("lineWidth".to_string(), ConfigKeyValue::from_i32(100)),
+            (
+                "methodChainThreshold".to_string(),
+                ConfigKeyValue::from_i32(130),
+            ),
+        ]);
+        let result = validate_resolved(resolve_config(config, &GlobalConfiguration::default()));
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].property_name, "methodChainThreshold");
+        assert_eq!(result.config.method_chain_threshold, 130);
+        assert_eq!(result.config.chain_threshold(), 100);
+    }
+
+    #[test]
+    fn forced_chain_breaks_are_off_by_default() {
+        let global = GlobalConfiguration::default();
+        let result = resolve_config(ConfigKeyMap::new(), &global);
+        assert_eq!(result.config.method_chain_min_calls_to_break, 0);
+        assert!(!result.config.break_stream_pipelines);
+
+        let config = ConfigKeyMap::from([(
+            "methodChainMinCallsToBreak".to_string(),
+            ConfigKeyValue::from_i32(3),
+        )]);
+        let result = resolve_config(config, &global);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.method_chain_min_calls_to_break, 3);
+
+        let overrides = ConfigKeyMap::from([(
+            "breakStreamPipelines".to_string(),
+            ConfigKeyValue::from_bool(true),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert!(result.config.break_stream_pipelines);
+        assert_eq!(result.config.method_chain_min_calls_to_break, 3);
+    }
+
+    #[test]
+    fn argument_packing_values() {
+        let config = ConfigKeyMap::from([(
+            "argumentPacking".to_string(),
+            ConfigKeyValue::from_str("balanced"),
+        )]);
+        let global = GlobalConfiguration::default();
+        let result = resolve_config(config, &global);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.argument_packing, ArgumentPacking::Balanced);
+
+        let config = ConfigKeyMap::from([(
+            "argumentPacking".to_string(),
+            ConfigKeyValue::from_str("optimal"),
+        )]);
+        let result = resolve_config(config, &global);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.config.argument_packing, ArgumentPacking::Greedy);
+        assert_eq!(result.config.brace_style, BraceStyle::SameLine);
+    }
+
+    #[test]
+    fn method_chain_wrap_style_values() {
+        let global = GlobalConfiguration::default();
+        let result = resolve_config(ConfigKeyMap::new(), &global);
+        assert_eq!(
+            result.config.method_chain_wrap_style,
+            MethodChainWrapStyle::ContinuationIndent
+        );
+
+        let config = ConfigKeyMap::from([(
+            "methodChainWrapStyle".to_string(),
+            ConfigKeyValue::from_str("alignDots"),
+        )]);
+        let result = resolve_config(config, &global);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(
+            result.config.method_chain_wrap_style,
+            MethodChainWrapStyle::AlignDots
+        );
+
+        let overrides = ConfigKeyMap::from([(
+            "methodChainWrapStyle".to_string(),
+            ConfigKeyValue::from_str("aligned"),
+        )]);
+        let result = resolve_overrides(&result.config, overrides);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(
+            result.config.method_chain_wrap_style,
+            MethodChainWrapStyle::AlignDots
+        );
+    }
+
+    #[test]
+    fn overrides_keep_unset_values() {
+        let global = GlobalConfiguration::default();
+        let base = resolve_config(
+            ConfigKeyMap::from([(
+                "braceStyle".to_string(),
+                ConfigKeyValue::from_str("nextLine"),
+            )]),
+            &global,
+        )
+        .config;
+        let overrides = ConfigKeyMap::from([
+            ("lineWidth".to_string(), ConfigKeyValue::from_str("100")),
+            (
+                "formatJavadoc".to_string(),
+                ConfigKeyValue::from_str("true"),
+            ),
+        ]);
+        let result = resolve_overrides(&base, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.config.line_width, 100);
+        assert!(result.config.format_javadoc);
+        assert_eq!(result.config.brace_style, BraceStyle::NextLine);
+        assert_eq!(result.config.indent_width, 4);
+    }
+
+    #[test]
+    fn overrides_move_derived_chain_threshold() {
+        let global = GlobalConfiguration::default();
+        let base = resolve_config(ConfigKeyMap::new(), &global).config;
+        let overrides =
+            ConfigKeyMap::from([("lineWidth".to_string(), ConfigKeyValue::from_str("100"))]);
+        let result = resolve_overrides(&base, overrides);
+        assert_eq!(result.config.method_chain_threshold, 60);
+
+        let base = resolve_config(
+            ConfigKeyMap::from([(
+                "methodChainThreshold".to_string(),
+                ConfigKeyValue::from_str("70"),
+            )]),
+            &global,
+        )
+        .config;
+        let overrides =
+            ConfigKeyMap::from([("lineWidth".to_string(), ConfigKeyValue::from_str("100"))]);
+        let result = resolve_overrides(&base, overrides);
+        assert_eq!(result.config.method_chain_threshold, 70);
+    }
+
+    #[test]
+    fn keywords_on_new_line_follow_brace_style() {
+        let global = GlobalConfiguration::default();
+        let result = resolve_config(ConfigKeyMap::new(), &global);
+        assert!(!result.config.else_on_new_line);
+        assert!(!result.config.while_on_new_line);
+
+        let config = ConfigKeyMap::from([
+            (
+                "braceStyle".to_string(),
+                ConfigKeyValue::from_str("nextLine"),
+            ),
+            (
+                "catchOnNewLine".to_string(),
+                ConfigKeyValue::from_bool(false),
+            ),
+        ]);
+        let result = resolve_config(config, &global);
+        assert!(result.diagnostics.is_empty());
+        assert!(result.config.else_on_new_line);
+        assert!(!result.config.catch_on_new_line);
+        assert!(result.config.finally_on_new_line);
+        assert!(result.config.while_on_new_line);
+    }
+
+    #[test]
+    fn overrides_move_derived_keywords_on_new_line() {
+        let global = GlobalConfiguration::default();
+        let base = resolve_config(
+            ConfigKeyMap::from([("elseOnNewLine".to_string(), ConfigKeyValue::from_bool(true))]),
+            &global,
+        )
+        .config;
+        let overrides = ConfigKeyMap::from([(
+            "braceStyle".to_string(),
+            ConfigKeyValue::from_str("nextLine"),
+        )]);
+        let result = resolve_overrides(&base, overrides);
+        assert!(result.diagnostics.is_empty());
+        assert!(result.config.else_on_new_line);
+        assert!(result.config.catch_on_new_line);
+
+        let base = resolve_config(
+            ConfigKeyMap::from([
+                (
+                    "braceStyle".to_string(),
+                    ConfigKeyValue::from_str("nextLine"),
+                ),
+                (
+                    "elseOnNewLine".to_string(),
+                    ConfigKeyValue::from_bool(false),
+                ),
+            ]),
+            &global,
+        )
+        .config;
+        let overrides = ConfigKeyMap::from([(
+            "braceStyle".to_string(),
+            ConfigKeyValue::from_str("sameLine"),
+        )]);
+        let result = resolve_overrides(&base, overrides);
+        assert!(!result.config.else_on_new_line);
+        assert!(!result.config.catch_on_new_line);
+    }
+
+    #[test]
+    fn tab_width_follows_indent_width() {
+        let global = GlobalConfiguration::default();
+        let config = ConfigKeyMap::from([("indentWidth".to_string(), ConfigKeyValue::from_i32(2))]);
+        let base = resolve_config(config, &global).config;
+        assert_eq!(base.tab_width, 2);
+
+        let overrides =
+            ConfigKeyMap::from([("indentWidth".to_string(), ConfigKeyValue::from_str("0"))]);
+        let result = resolve_overrides(&base, overrides);
+        assert_eq!(result.config.tab_width, 4);
+
+        let config = ConfigKeyMap::from([
+            ("useTabs".to_string(), ConfigKeyValue::from_bool(true)),
+            ("tabWidth".to_string(), ConfigKeyValue::from_i32(8)),
+        ]);
+        let result = resolve_config(config, &global);
+        assert_eq!(result.config.indent_width, 4);
+        assert_eq!(result.config.indent_columns(), 8);
+    }
+
+    #[test]
+    fn overrides_report_invalid_and_unknown_keys() {
+        let base = resolve_config(ConfigKeyMap::new(), &GlobalConfiguration::default()).config;
+        let overrides = ConfigKeyMap::from([
+            ("lineWidth".to_string(), ConfigKeyValue::from_str("wide")),
+            ("style".to_string(), ConfigKeyValue::from_str("google")),
+        ]);
+        let result = resolve_overrides(&base, overrides);
+        assert_eq!(result.diagnostics.len(), 2);
+        assert_eq!(result.config.line_width, 120);
+    }
+
     #[test]
     fn unknown_property_diagnostic() {
         let config =
@@ -0,0 +1,169 @@
+use dprint_core::configuration::ConfigurationDiagnostic;
+use dprint_core::configuration::ResolveConfigurationResult;
+
+use super::Configuration;
+use crate::generation::CONTINUATION_INDENT_LEVELS;
+
+/// Check a resolved configuration for settings that contradict each other.
+///
+/// Every value may be valid on its own and still produce surprising output in
+/// combination, such as a `methodChainThreshold` past `lineWidth` or an
+/// `indentWidth` whose continuation indent fills `lineWidth`. Editor
+/// extensions can call this to flag such settings before any file is formatted.
+#[must_use]
+pub fn validate(config: &Configuration) -> Vec<ConfigurationDiagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if config.line_width == 0 {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "lineWidth".to_string(),
+            message: "Expected a line width greater than 0".to_string(),
+        });
+    }
+
+    if config.indent_width == 0 && !config.use_tabs {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "indentWidth".to_string(),
+            message: "Expected an indent width greater than 0 when indenting with spaces"
+                .to_string(),
+        });
+    }
+
+    if config.tab_width == 0 && config.use_tabs {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "tabWidth".to_string(),
+            message: "Expected a tab width greater than 0 when indenting with tabs".to_string(),
+        });
+    }
+
+    let continuation_width = usize::from(config.indent_columns()) * CONTINUATION_INDENT_LEVELS;
+    if config.line_width > 0 && continuation_width >= config.line_width as usize {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: if config.use_tabs {
+                "tabWidth"
+            } else {
+                "indentWidth"
+            }
+            .to_string(),
+            message: format!(
+                "A continuation indent of {continuation_width} leaves no room for code within a line width of {}",
+                config.line_width
+            ),
+        });
+    }
+
+    if config.method_chain_threshold > config.line_width {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "methodChainThreshold".to_string(),
+            message: format!(
+                "Method chain threshold {} is past the line width of {}, so chains only wrap when they overflow the line",
+                config.method_chain_threshold, config.line_width
+            ),
+        });
+    }
+
+    if config.max_changed_lines_percent > 100 {
+        diagnostics.push(ConfigurationDiagnostic {
+            property_name: "maxChangedLinesPercent".to_string(),
+            message: format!(
+                "Expected a percentage from 0 to 100, got {}",
+                config.max_changed_lines_percent
+            ),
+        });
+    }
+
+    diagnostics
+}
+
+/// Check a resolved configuration with [`validate`] once every value in it
+/// resolved, since contradictions are only meaningful between valid values.
+/// Every path that accepts user settings runs its result through this.
+#[must_use]
+pub fn validate_resolved(
+    mut result: ResolveConfigurationResult<Configuration>,
+) -> ResolveConfigurationResult<Configuration> {
+    if result.diagnostics.is_empty() {
+        result.diagnostics = validate(&result.config);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::resolve_config;
+    use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue, GlobalConfiguration};
+
+    fn default_config() -> Configuration {
+        resolve_config(ConfigKeyMap::new(), &GlobalConfiguration::default()).config
+    }
+
+    #[test]
+    fn default_configs_are_valid() {
+        assert!(validate(&default_config()).is_empty());
+        for style in ["palantir", "google", "aosp"] {
+            let config =
+                ConfigKeyMap::from([("style".to_string(), ConfigKeyValue::from_str(style))]);
+            let result = resolve_config(config, &GlobalConfiguration::default());
+            assert!(validate(&result.config).is_empty(), "{style}");
+        }
+    }
+
+    #[test]
+    fn chain_threshold_past_line_width() {
+        let config = Configuration {
+            method_chain_threshold: 130,
+            ..default_config()
+        };
+        let diagnostics = validate(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].property_name, "methodChainThreshold");
+        assert_eq!(config.chain_threshold(), 120);
+    }
+
+    #[test]
+    fn indent_wider_than_line() {
+        let config = Configuration {
+            line_width: 12,
+            indent_width: 8,
+            method_chain_threshold: 10,
+            ..default_config()
+        };
+        let diagnostics = validate(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].property_name, "indentWidth");
+    }
+
+    #[test]
+    fn tabs_are_measured_by_tab_width() {
+        let config = Configuration {
+            line_width: 12,
+            indent_width: 2,
+            use_tabs: true,
+            tab_width: 8,
+            method_chain_threshold: 10,
+            ..default_config()
+        };
+        let diagnostics = validate(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].property_name, "tabWidth");
+
+        let config = Configuration {
+            use_tabs: true,
+            tab_width: 0,
+            ..default_config()
+        };
+        assert_eq!(validate(&config)[0].property_name, "tabWidth");
+    }
+
+    #[test]
+    fn changed_lines_percent_past_100() {
+        let config = Configuration {
+            max_changed_lines_percent: 150,
+            ..default_config()
+        };
+        let diagnostics = validate(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].property_name, "maxChangedLinesPercent");
+    }
+}
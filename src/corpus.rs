@@ -0,0 +1,222 @@
+//! Release verification: format a corpus of Java files with this build and
+//! compare the result with what an earlier release made of the same files.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::configuration::Configuration;
+use crate::diff;
+use crate::error::{FormatError, IoError};
+use crate::format_text::format_text;
+
+/// Directory under the corpus holding the Java files to format.
+const INPUT_DIR: &str = "input";
+
+/// Directory under the corpus holding the earlier release's output, at the
+/// same relative paths as the inputs.
+const EXPECTED_DIR: &str = "expected";
+
+/// A corpus file this build formats differently from the earlier release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// Path of the input, relative to the corpus' `input` directory.
+    pub path: PathBuf,
+    /// Lines that differ, counting a rewritten line once.
+    pub changed_lines: usize,
+    /// Unified diff from the stored output to this build's.
+    pub diff: String,
+}
+
+/// What [`diff_versions`] found, in path order.
+#[derive(Debug, Default)]
+pub struct CorpusDiff {
+    /// Inputs formatted and compared.
+    pub files: usize,
+    /// Inputs whose output changed.
+    pub changed: Vec<ChangedFile>,
+    /// Inputs with no stored output to compare with.
+    pub missing: Vec<PathBuf>,
+    /// Inputs this build could not format, with why.
+    pub failed: Vec<(PathBuf, FormatError)>,
+}
+
+impl CorpusDiff {
+    /// Lines changed across every changed file.
+    #[must_use]
+    pub fn changed_lines(&self) -> usize {
+        self.changed.iter().map(|file| file.changed_lines).sum()
+    }
+
+    /// Whether every input formats as the earlier release formatted it.
+    #[must_use]
+    pub fn is_unchanged(&self) -> bool {
+        self.changed.is_empty() && self.missing.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for CorpusDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} files changed ({} lines)",
+            self.changed.len(),
+            self.files,
+            self.changed_lines()
+        )?;
+        if !self.missing.is_empty() {
+            write!(f, ", {} without stored output", self.missing.len())?;
+        }
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+        }
+        for file in &self.changed {
+            write!(
+                f,
+                "\n  {}: {} lines",
+                file.path.display(),
+                file.changed_lines
+            )?;
+        }
+        for path in &self.missing {
+            write!(f, "\n  {}: no stored output", path.display())?;
+        }
+        for (path, error) in &self.failed {
+            write!(f, "\n  {}: {error}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Format every `.java` file under `corpus_dir/input` with `config` and
+/// compare each result with the file at the same relative path under
+/// `corpus_dir/expected`, the output of an earlier release.
+///
+/// To record a release's output, copy `input` to `expected` and run that
+/// release's `dprint-java-fmt --write` over the copy with the same options.
+/// A file the formatter leaves unchanged is compared as its input.
+///
+/// # Errors
+///
+/// Returns [`FormatError::Io`] if a corpus directory or a stored output
+/// cannot be read. Inputs that cannot be formatted are reported in
+/// [`CorpusDiff::failed`] instead.
+pub fn diff_versions(corpus_dir: &Path, config: &Configuration) -> Result<CorpusDiff, FormatError> {
+    let input_dir = corpus_dir.join(INPUT_DIR);
+    let expected_dir = corpus_dir.join(EXPECTED_DIR);
+    let mut paths = Vec::new();
+    collect_java_files(&input_dir, &mut paths)?;
+    paths.sort();
+
+    let mut result = CorpusDiff {
+        files: paths.len(),
+        ..CorpusDiff::default()
+    };
+    for path in paths {
+        let relative = path.strip_prefix(&input_dir).unwrap_or(&path).to_path_buf();
+        let input = std::fs::read_to_string(&path).map_err(|e| io_error(&e, &path))?;
+        let expected_path = expected_dir.join(&relative);
+        let expected = match std::fs::read_to_string(&expected_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                result.missing.push(relative);
+                continue;
+            }
+            Err(e) => return Err(io_error(&e, &expected_path)),
+        };
+        let output = match format_text(&path, &input, config) {
+            Ok(formatted) => formatted.unwrap_or(input),
+            Err(e) => {
+                result.failed.push((relative, e));
+                continue;
+            }
+        };
+        if output != expected {
+            result.changed.push(ChangedFile {
+                changed_lines: diff::changed_lines(&expected, &output),
+                diff: diff::unified_diff(&expected, &output, &relative.to_string_lossy()),
+                path: relative,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Add the `.java` files under `dir`, at any depth, to `paths`.
+fn collect_java_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), FormatError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| io_error(&e, dir))?;
+    for entry in entries {
+        let path = entry.map_err(|e| io_error(&e, dir))?.path();
+        if path.is_dir() {
+            collect_java_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "java") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `error`, which happened reading `path`, as a [`FormatError`].
+fn io_error(error: &std::io::Error, path: &Path) -> FormatError {
+    FormatError::Io(IoError::new(error, Some(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dprint_core::configuration::{ConfigKeyMap, GlobalConfiguration};
+
+    use crate::configuration::resolve_config;
+
+    #[test]
+    fn reports_changed_missing_and_failed_files() {
+        let dir = std::env::temp_dir().join(format!("diff_versions_{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write("input/Same.java", "class A {  }");
+        write("expected/Same.java", "class A {}\n");
+        write("input/pkg/Changed.java", "class B {\n    int x ;\n}\n");
+        write("expected/pkg/Changed.java", "class B {\n    int  x;\n}\n");
+        write("input/New.java", "class C {}\n");
+        write("input/Broken.java", "class D {");
+        write("expected/Broken.java", "class D {");
+        write("input/notes.txt", "not Java");
+
+        let config = resolve_config(ConfigKeyMap::new(), &GlobalConfiguration::default()).config;
+        let result = diff_versions(&dir, &config);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let result = result.unwrap();
+
+        assert_eq!(result.files, 4);
+        assert_eq!(result.changed.len(), 1);
+        let changed = &result.changed[0];
+        assert_eq!(changed.path, Path::new("pkg/Changed.java"));
+        assert_eq!(changed.changed_lines, 1);
+        assert!(changed.diff.contains("-    int  x;\n+    int x;\n"));
+        assert_eq!(result.missing, [PathBuf::from("New.java")]);
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(result.failed[0].0, Path::new("Broken.java"));
+        assert!(!result.is_unchanged());
+        assert!(
+            result
+                .to_string()
+                .starts_with("1 of 4 files changed (1 lines), 1 without stored output, 1 failed\n")
+        );
+    }
+
+    #[test]
+    fn missing_input_directory_is_an_error() {
+        let config = resolve_config(ConfigKeyMap::new(), &GlobalConfiguration::default()).config;
+        let Err(FormatError::Io(error)) = diff_versions(Path::new("/nonexistent/corpus"), &config)
+        else {
+            panic!("expected an I/O error");
+        };
+        assert_eq!(
+            error.path.as_deref(),
+            Some(Path::new("/nonexistent/corpus/input"))
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt::Write as _;
+#[cfg(feature = "lsp")]
+use std::ops::Range;
+
+/// Lines of unchanged text shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// What happens to one line on the way from the old text to the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// A unified diff turning `old` into `new`, with `---`/`+++` headers naming
+/// `path`. Empty when the texts are equal.
+pub(crate) fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edit_script(&old_lines, &new_lines);
+
+    let mut out = String::new();
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| edits[i] != Edit::Keep)
+        .collect();
+    if changes.is_empty() {
+        return out;
+    }
+    let _ = write!(out, "--- a/{path}\n+++ b/{path}\n");
+
+    // Line numbers in each text where each edit starts
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Keep => (old_line, new_line) = (old_line + 1, new_line + 1),
+            Edit::Delete => old_line += 1,
+            Edit::Insert => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    // Changes closer than twice the context share a hunk
+    let mut group_start = 0;
+    for i in 1..=changes.len() {
+        if i < changes.len() && changes[i] - changes[i - 1] <= 2 * CONTEXT_LINES {
+            continue;
+        }
+        let start = changes[group_start].saturating_sub(CONTEXT_LINES);
+        let end = (changes[i - 1] + CONTEXT_LINES + 1).min(edits.len());
+        write_hunk(
+            &mut out,
+            &edits[start..end],
+            positions[start],
+            &old_lines,
+            &new_lines,
+        );
+        group_start = i;
+    }
+    out
+}
+
+fn write_hunk(
+    out: &mut String,
+    edits: &[Edit],
+    (old_start, new_start): (usize, usize),
+    old_lines: &[&str],
+    new_lines: &[&str],
+) {
+    let old_count = edits.iter().filter(|&&e| e != Edit::Insert).count();
+    let new_count = edits.iter().filter(|&&e| e != Edit::Delete).count();
+    // An empty range names the line before it
+    let range = |start: usize, count: usize| match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    };
+    let _ = writeln!(
+        out,
+        "@@ -{} +{} @@",
+        range(old_start, old_count),
+        range(new_start, new_count)
+    );
+
+    let (mut old_line, mut new_line) = (old_start, new_start);
+    for edit in edits {
+        let (prefix, line) = match edit {
+            Edit::Keep => {
+                old_line += 1;
+                new_line += 1;
+                (' ', old_lines[old_line - 1])
+            }
+            Edit::Delete => {
+                old_line += 1;
+                ('-', old_lines[old_line - 1])
+            }
+            Edit::Insert => {
+                new_line += 1;
+                ('+', new_lines[new_line - 1])
+            }
+        };
+        out.push(prefix);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// A run of changed lines: lines `old` of the old text become lines `new` of
+/// the new one. Either range may be empty.
+#[cfg(feature = "lsp")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Hunk {
+    pub(crate) old: Range<usize>,
+    pub(crate) new: Range<usize>,
+}
+
+/// The runs of changed lines between `old_lines` and `new_lines`, without
+/// context, in order.
+#[cfg(feature = "lsp")]
+pub(crate) fn hunks(old_lines: &[&str], new_lines: &[&str]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old_line, mut new_line) = (0, 0);
+    let mut in_hunk = false;
+    for edit in edit_script(old_lines, new_lines) {
+        if edit == Edit::Keep {
+            (old_line, new_line) = (old_line + 1, new_line + 1);
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            hunks.push(Hunk {
+                old: old_line..old_line,
+                new: new_line..new_line,
+            });
+            in_hunk = true;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+        if edit == Edit::Delete {
+            old_line += 1;
+            hunk.old.end = old_line;
+        } else {
+            new_line += 1;
+            hunk.new.end = new_line;
+        }
+    }
+    hunks
+}
+
+/// How many lines differ between `old` and `new`: the larger of the number of
+/// lines removed from `old` and added to `new`, so a rewritten line counts
+/// once.
+pub(crate) fn changed_lines(old: &str, new: &str) -> usize {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edit_script(&old_lines, &new_lines);
+    let count = |kind: Edit| edits.iter().filter(|&&e| e == kind).count();
+    count(Edit::Delete).max(count(Edit::Insert))
+}
+
+/// The shortest list of edits turning `old` into `new` (Myers' algorithm).
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // Lines the texts share at either end need no search
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut edits = vec![Edit::Keep; prefix];
+    edits.extend(middle_edits(a, b));
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    edits
+}
+
+/// The edits between `a` and `b`, named as in Myers' paper, which share no
+/// first or last line.
+#[allow(
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss,
+    clippy::many_single_char_names
+)]
+fn middle_edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    if max == 0 {
+        return Vec::new();
+    }
+    // `v[k + max]` is the furthest `x` reached on diagonal `k = x - y`; the
+    // rows of `trace` keep `v` as it was before each round, for diagonals
+    // `-d..=d` only
+    let index = |k: isize| (k + max) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v[index(-d)..=index(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end through the rounds
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, row) in trace.iter().enumerate().skip(1).rev() {
+        let d = d as isize;
+        let at = |k: isize| row[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if x == prev_x {
+            Edit::Insert
+        } else {
+            Edit::Delete
+        });
+        (x, y) = (prev_x, prev_y);
+    }
+    // What is left is the snake of round 0, from the start
+    edits.extend(std::iter::repeat_n(Edit::Keep, x as usize));
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_texts_have_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "A.java"), "");
+    }
+
+    #[test]
+    fn hunks_keep_three_lines_of_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\nTWO\n3\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            unified_diff(old, new, "A.java"),
+            "--- a/A.java\n+++ b/A.java\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+TWO\n 3\n 4\n 5\n\
+             @@ -9,4 +9,3 @@\n 9\n 10\n 11\n-12\n"
+        );
+    }
+
+    #[test]
+    fn marks_missing_final_newline() {
+        assert_eq!(
+            unified_diff("class A {}", "class A {}\n", "A.java"),
+            "--- a/A.java\n+++ b/A.java\n@@ -1 +1 @@\n-class A {}\n\\ No newline at end of file\n+class A {}\n"
+        );
+    }
+
+    #[test]
+    fn counts_a_rewritten_line_once() {
+        assert_eq!(changed_lines("a\nb\nc\n", "a\nB\nc\n"), 1);
+        assert_eq!(changed_lines("a\nb\n", "a\n\nb\nc\n"), 2);
+    }
+
+    #[cfg(feature = "lsp")]
+    #[test]
+    fn hunks_cover_changed_runs() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "B", "c", "d", "e"];
+        assert_eq!(
+            hunks(&old, &new),
+            [
+                Hunk {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Hunk {
+                    old: 4..4,
+                    new: 4..5
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn edit_script_is_minimal() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let edits = edit_script(&old, &new);
+        let changes = edits.iter().filter(|&&e| e != Edit::Keep).count();
+        assert_eq!(changes, 5);
+    }
+}
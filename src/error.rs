@@ -0,0 +1,623 @@
+use std::fmt;
+use std::fmt::Write as _;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::diff;
+
+/// Number of source lines shown above the offending line in a rendered snippet.
+const CONTEXT_LINES: usize = 2;
+
+/// A syntax error found while parsing the input.
+///
+/// The `Display` output includes a source excerpt with a caret under the
+/// offending token, so the message is actionable when printed by the dprint CLI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Human-readable description, e.g. "unexpected `)`" or "missing `;`".
+    pub message: String,
+    /// 1-based line of the offending token.
+    pub line: usize,
+    /// 1-based column (in characters) of the offending token.
+    pub column: usize,
+    /// Byte range of the offending token in the input; empty for a missing one.
+    pub byte_range: Range<usize>,
+    /// Rendered source excerpt with a caret marker.
+    pub snippet: String,
+}
+
+impl ParseError {
+    /// Build an error describing the first `ERROR` or `MISSING` node in `tree`.
+    ///
+    /// Returns `None` if the tree contains no syntax errors.
+    #[must_use]
+    pub fn from_tree(tree: &tree_sitter::Tree, source: &str) -> Option<Self> {
+        first_error_node(tree.root_node()).map(|node| Self::from_node(node, source))
+    }
+
+    /// Build an error for each `ERROR` or `MISSING` node in `tree`, in source
+    /// order.
+    #[must_use]
+    pub fn all_from_tree(tree: &tree_sitter::Tree, source: &str) -> Vec<Self> {
+        error_nodes(tree.root_node())
+            .into_iter()
+            .map(|node| Self::from_node(node, source))
+            .collect()
+    }
+
+    fn from_node(node: tree_sitter::Node, source: &str) -> Self {
+        let message = if node.is_missing() {
+            format!("missing `{}`", node.kind())
+        } else {
+            let text = &source[node.start_byte()..node.end_byte()];
+            let first_line = text.lines().next().unwrap_or("").trim();
+            if first_line.is_empty() {
+                "unexpected end of input".to_string()
+            } else {
+                format!("unexpected `{first_line}`")
+            }
+        };
+
+        let start = node.start_position();
+        let line_text = source.lines().nth(start.row).unwrap_or("");
+        let column = line_text
+            .get(..start.column)
+            .map_or(start.column, |prefix| prefix.chars().count());
+        // Missing nodes are zero-width; `render_snippet` still draws one caret.
+        let marker_len = if node.start_position().row == node.end_position().row {
+            line_text
+                .get(start.column..node.end_position().column)
+                .map_or(0, |t| t.chars().count())
+        } else {
+            line_text
+                .get(start.column..)
+                .map_or(0, |t| t.chars().count())
+        };
+
+        Self {
+            message,
+            line: start.row + 1,
+            column: column + 1,
+            byte_range: node.byte_range(),
+            snippet: render_snippet(source, start.row, start.column, marker_len),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Syntax error: {} (line {}, column {})",
+            self.message, self.line, self.column
+        )?;
+        write!(f, "{}", self.snippet)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Formatted output whose tokens differ from the input's, found when
+/// `verifyTokens` is on.
+///
+/// The location and snippet point into the input, at the first token that
+/// the output drops or changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMismatch {
+    /// Human-readable description, e.g. "`final` became `static`".
+    pub message: String,
+    /// 1-based line of the input token.
+    pub line: usize,
+    /// 1-based column (in characters) of the input token.
+    pub column: usize,
+    /// Rendered input excerpt with a caret marker.
+    pub snippet: String,
+}
+
+impl TokenMismatch {
+    /// Build a mismatch described by `message` at byte `start` of `source`,
+    /// marking `len` bytes.
+    pub(crate) fn new(message: String, source: &str, start: usize, len: usize) -> Self {
+        let start = start.min(source.len());
+        let (line, column) = line_and_column(source, start);
+        let marker_len = source[start..]
+            .get(..len)
+            .and_then(|text| text.lines().next())
+            .map_or(0, |text| text.chars().count());
+        Self {
+            message,
+            line,
+            column,
+            snippet: render_snippet(source, line - 1, byte_column(source, start), marker_len),
+        }
+    }
+}
+
+impl fmt::Display for TokenMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Formatting would change the code, so the file was left unchanged: {} (line {}, column {})",
+            self.message, self.line, self.column
+        )?;
+        write!(f, "{}", self.snippet)
+    }
+}
+
+impl std::error::Error for TokenMismatch {}
+
+/// A construct the formatter has no handler for, found when `strict` is on.
+///
+/// Without `strict` such a construct is printed as written; the location and
+/// snippet point at its first line in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedConstruct {
+    /// The tree-sitter kind of the node, e.g. `"template_expression"`.
+    pub kind: &'static str,
+    /// 1-based line the node starts on.
+    pub line: usize,
+    /// 1-based column (in characters) the node starts at.
+    pub column: usize,
+    /// The node's byte range in the input.
+    pub byte_range: Range<usize>,
+    /// Rendered input excerpt with a caret marker.
+    pub snippet: String,
+}
+
+impl UnsupportedConstruct {
+    /// Describe the node of `kind` at `byte_range` of `source`.
+    pub(crate) fn new(kind: &'static str, byte_range: Range<usize>, source: &str) -> Self {
+        let start = byte_range.start.min(source.len());
+        let (line, column) = line_and_column(source, start);
+        let marker_len = source[start..byte_range.end.min(source.len())]
+            .lines()
+            .next()
+            .map_or(0, |text| text.chars().count());
+        Self {
+            kind,
+            line,
+            column,
+            byte_range,
+            snippet: render_snippet(source, line - 1, byte_column(source, start), marker_len),
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedConstruct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Unsupported construct `{}` (line {}, column {})",
+            self.kind, self.line, self.column
+        )?;
+        write!(f, "{}", self.snippet)
+    }
+}
+
+impl std::error::Error for UnsupportedConstruct {}
+
+/// A read or write that failed in one of the I/O functions, such as
+/// [`format_stream`](crate::format_stream).
+///
+/// Holds what [`io::Error`] reports rather than the error itself, so that
+/// [`FormatError`] stays comparable and cloneable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoError {
+    /// The file that could not be read, when there is one.
+    pub path: Option<PathBuf>,
+    /// The kind of failure.
+    pub kind: io::ErrorKind,
+    /// The operating system's description of the failure.
+    pub message: String,
+}
+
+impl IoError {
+    /// Describe `error`, which happened reading `path` if it is given.
+    pub(crate) fn new(error: &io::Error, path: Option<&Path>) -> Self {
+        Self {
+            path: path.map(Path::to_path_buf),
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "Failed to read {}: {}", path.display(), self.message),
+            None => write!(f, "I/O error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for IoError {}
+
+/// Why a file could not be formatted.
+///
+/// Every error the `format_text*` and I/O functions return is one of these,
+/// so a tool can match on the kind of failure rather than on its message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The input has a syntax error, and `formatWithSyntaxErrors` is off.
+    Parse(ParseError),
+    /// Formatting the output again would change it.
+    UnstableOutput(UnstableFormat),
+    /// With `verifyTokens` on, the output's tokens differ from the input's:
+    /// the formatter mishandles a construct in the file. Only
+    /// [`format_text_checked`](crate::format_text_checked) fails with this;
+    /// the other functions keep the file as written and report it.
+    TokenMismatch(TokenMismatch),
+    /// With `strict` on, the input contains a construct the formatter has no
+    /// handler for.
+    UnsupportedConstruct(UnsupportedConstruct),
+    /// The `// dprint-java:` comment on the first line is malformed.
+    InvalidFileConfig(String),
+    /// The formatter panicked, or failed in another way only a bug in it
+    /// can cause. Holds the panic's message.
+    InternalPanic(String),
+    /// The Java grammar could not be loaded, or the parser gave up on the
+    /// input. Holds what failed.
+    ParserFailed(String),
+    /// Reading the input or writing the output failed.
+    Io(IoError),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(error) => error.fmt(f),
+            Self::UnstableOutput(error) => error.fmt(f),
+            Self::TokenMismatch(error) => error.fmt(f),
+            Self::UnsupportedConstruct(error) => error.fmt(f),
+            Self::InvalidFileConfig(message) | Self::ParserFailed(message) => f.write_str(message),
+            Self::InternalPanic(message) => write!(f, "Internal formatter error: {message}"),
+            Self::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<ParseError> for FormatError {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<UnstableFormat> for FormatError {
+    fn from(error: UnstableFormat) -> Self {
+        Self::UnstableOutput(error)
+    }
+}
+
+impl From<TokenMismatch> for FormatError {
+    fn from(error: TokenMismatch) -> Self {
+        Self::TokenMismatch(error)
+    }
+}
+
+impl From<UnsupportedConstruct> for FormatError {
+    fn from(error: UnsupportedConstruct) -> Self {
+        Self::UnsupportedConstruct(error)
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(IoError::new(&error, None))
+    }
+}
+
+/// A member kept as written because formatting it needed more work than one
+/// member is allowed, as machine-generated calls nested hundreds deep can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberTooComplex {
+    /// 1-based line the member starts on.
+    pub line: usize,
+    /// 1-based column (in characters) the member starts at.
+    pub column: usize,
+    /// Rendered source excerpt with a caret under the member's first line.
+    pub snippet: String,
+}
+
+impl MemberTooComplex {
+    /// Describe the member at byte `start` of `source`.
+    pub(crate) fn new(source: &str, start: usize) -> Self {
+        let start = start.min(source.len());
+        let (line, column) = line_and_column(source, start);
+        let marker_len = source[start..]
+            .lines()
+            .next()
+            .map_or(0, |text| text.trim_end().chars().count());
+        Self {
+            line,
+            column,
+            snippet: render_snippet(source, line - 1, byte_column(source, start), marker_len),
+        }
+    }
+}
+
+impl fmt::Display for MemberTooComplex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Member too complex to format, kept as written (line {}, column {})",
+            self.line, self.column
+        )?;
+        write!(f, "{}", self.snippet)
+    }
+}
+
+/// A problem that did not stop a file from being formatted; the code it is
+/// about was kept as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// A syntax error, with `formatWithSyntaxErrors` on.
+    Syntax(ParseError),
+    /// A member that ran out of its work budget.
+    MemberTooComplex(MemberTooComplex),
+    /// With `verifyTokens` on, output whose tokens differ from the input's,
+    /// so the whole file was kept as written.
+    TokenMismatch(TokenMismatch),
+}
+
+impl Diagnostic {
+    /// 1-based line the diagnostic points at.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        match self {
+            Self::Syntax(error) => error.line,
+            Self::MemberTooComplex(member) => member.line,
+            Self::TokenMismatch(mismatch) => mismatch.line,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(error) => error.fmt(f),
+            Self::MemberTooComplex(member) => member.fmt(f),
+            Self::TokenMismatch(mismatch) => mismatch.fmt(f),
+        }
+    }
+}
+
+/// Formatted output that a second formatting pass would change again.
+///
+/// dprint formats its plugins' output a second time and rejects output that
+/// changes; this names the line that changes so the construct that does not
+/// settle can be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnstableFormat {
+    /// 1-based line of the output that the second pass changes first.
+    pub line: usize,
+    /// The member or type that line is in, e.g. `Outer.Inner.method`.
+    pub member: Option<String>,
+    /// The line as the formatter printed it.
+    pub before: String,
+    /// The line after formatting the output again.
+    pub after: String,
+    /// Unified diff from the output to the output formatted again.
+    pub diff: String,
+}
+
+impl UnstableFormat {
+    /// Build the error for `output`, whose syntax tree is `tree`, and
+    /// `reformatted`, the result of formatting it again.
+    pub(crate) fn new(output: &str, reformatted: &str, tree: &tree_sitter::Tree) -> Self {
+        let (before, after): (Vec<&str>, Vec<&str>) =
+            (output.lines().collect(), reformatted.lines().collect());
+        let row = before
+            .iter()
+            .zip(&after)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| before.len().min(after.len()));
+        let line_text = before.get(row).copied().unwrap_or("");
+        let column = line_text.len() - line_text.trim_start().len();
+        let point = tree_sitter::Point::new(row, column);
+        let member = tree
+            .root_node()
+            .descendant_for_point_range(point, point)
+            .and_then(|node| enclosing_member(node, output));
+        Self {
+            line: row + 1,
+            member,
+            before: line_text.to_string(),
+            after: after.get(row).copied().unwrap_or("").to_string(),
+            diff: diff::unified_diff(output, reformatted, "output"),
+        }
+    }
+}
+
+impl fmt::Display for UnstableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Formatting is not stable: formatting the output again changes line {}",
+            self.line
+        )?;
+        if let Some(member) = &self.member {
+            write!(f, " in `{member}`")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "- {}", self.before)?;
+        write!(f, "+ {}", self.after)
+    }
+}
+
+impl std::error::Error for UnstableFormat {}
+
+/// The dotted name of the types and member around `node`, e.g.
+/// `Outer.Inner.method`.
+fn enclosing_member(node: tree_sitter::Node, source: &str) -> Option<String> {
+    let mut names = Vec::new();
+    let mut current = Some(node);
+    while let Some(node) = current {
+        let name = match node.kind() {
+            "field_declaration" | "constant_declaration" => node
+                .child_by_field_name("declarator")
+                .and_then(|declarator| declarator.child_by_field_name("name")),
+            "class_declaration"
+            | "interface_declaration"
+            | "enum_declaration"
+            | "record_declaration"
+            | "annotation_type_declaration"
+            | "method_declaration"
+            | "constructor_declaration"
+            | "compact_constructor_declaration"
+            | "annotation_type_element_declaration"
+            | "enum_constant" => node.child_by_field_name("name"),
+            _ => None,
+        };
+        if let Some(name) = name {
+            names.push(&source[name.byte_range()]);
+        }
+        current = node.parent();
+    }
+    names.reverse();
+    (!names.is_empty()).then(|| names.join("."))
+}
+
+/// Find the first node (in source order) that is an `ERROR` or `MISSING` node.
+fn first_error_node(node: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor).find_map(first_error_node)
+}
+
+/// All `ERROR` and `MISSING` nodes under `node`, in source order, without
+/// the nodes nested inside an `ERROR` node.
+pub(crate) fn error_nodes(node: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    if node.is_error() || node.is_missing() {
+        return vec![node];
+    }
+    if !node.has_error() {
+        return Vec::new();
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor).flat_map(error_nodes).collect()
+}
+
+/// The 1-based line and column (in characters) of byte `offset` of `source`.
+pub(crate) fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (
+        source[..line_start].matches('\n').count() + 1,
+        source[line_start..offset].chars().count() + 1,
+    )
+}
+
+/// How many bytes byte `offset` of `source` is from the start of its line.
+fn byte_column(source: &str, offset: usize) -> usize {
+    offset - source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Render up to `CONTEXT_LINES` lines of context followed by the line at `row`,
+/// with a caret marker under `marker_len` characters starting at byte `column`.
+pub(crate) fn render_snippet(source: &str, row: usize, column: usize, marker_len: usize) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let first = row.saturating_sub(CONTEXT_LINES);
+    let last = row.min(lines.len().saturating_sub(1));
+    let gutter = (last + 1).to_string().len();
+
+    let mut out = String::new();
+    for (i, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+        let _ = writeln!(out, "{:>gutter$} | {}", i + 1, text);
+    }
+
+    // Copy tabs from the line prefix so the caret lines up however tabs render.
+    let line_text = lines.get(row).copied().unwrap_or("");
+    let padding: String = line_text
+        .get(..column)
+        .unwrap_or(line_text)
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let _ = write!(
+        out,
+        "{:>gutter$} | {}{}",
+        "",
+        padding,
+        "^".repeat(marker_len.max(1))
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    #[test]
+    fn snippet_shows_two_lines_of_context() {
+        let source = "line one\nline two\nline three\nline four\n";
+        let snippet = render_snippet(source, 3, 5, 4);
+        assert_eq!(
+            snippet,
+            "2 | line two\n3 | line three\n4 | line four\n  |      ^^^^"
+        );
+    }
+
+    #[test]
+    fn snippet_at_first_line_has_no_context() {
+        let snippet = render_snippet("int x = ;\n", 0, 8, 1);
+        assert_eq!(snippet, "1 | int x = ;\n  |         ^");
+    }
+
+    #[test]
+    fn snippet_preserves_tabs_in_caret_padding() {
+        let snippet = render_snippet("\tfoo(;\n", 0, 5, 1);
+        assert_eq!(snippet, "1 | \tfoo(;\n  | \t    ^");
+    }
+
+    #[test]
+    fn no_error_for_valid_tree() {
+        let source = "class A {}\n";
+        assert!(ParseError::from_tree(&parse(source), source).is_none());
+    }
+
+    #[test]
+    fn all_errors_in_source_order() {
+        let source = "class A {\n    void f() {\n        int x = 1\n        int y = 2\n    }\n}\n";
+        let errors = ParseError::all_from_tree(&parse(source), source);
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [3, 4]);
+        assert_eq!(
+            errors[0],
+            ParseError::from_tree(&parse(source), source).unwrap()
+        );
+    }
+
+    #[test]
+    fn unstable_format_names_line_and_member() {
+        let output = "class A {\n    class B {\n        void f() {\n            g(1, 2);\n        }\n    }\n}\n";
+        let reformatted = output.replace("g(1, 2);", "g(\n                1, 2);");
+        let error = UnstableFormat::new(output, &reformatted, &parse(output));
+        assert_eq!(error.line, 4);
+        assert_eq!(error.member.as_deref(), Some("A.B.f"));
+        assert_eq!(
+            error.to_string(),
+            "Formatting is not stable: formatting the output again changes line 4 in `A.B.f`\n-             g(1, 2);\n+             g("
+        );
+    }
+
+    #[test]
+    fn display_includes_location_and_caret() {
+        let source = "class A {\n    void f() {\n        int x = 1\n    }\n}\n";
+        let error = ParseError::from_tree(&parse(source), source).unwrap();
+        assert_eq!(error.message, "missing `;`");
+        assert_eq!(error.line, 3);
+        let rendered = error.to_string();
+        assert!(rendered.starts_with("Syntax error: missing `;` (line 3, column"));
+        assert!(rendered.contains("3 |         int x = 1\n"));
+        assert!(rendered.ends_with('^'));
+    }
+}
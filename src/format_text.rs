@@ -1,58 +1,913 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{Read, Write};
+use std::ops::Range;
 use std::path::Path;
+use std::rc::Rc;
 
-use anyhow::Result;
-use dprint_core::configuration::resolve_new_line_kind;
+use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue, resolve_new_line_kind};
 use dprint_core::formatting::PrintOptions;
 
-use crate::configuration::Configuration;
-use crate::generation::generate;
+use crate::configuration::{Configuration, resolve_overrides, validate_resolved};
+use crate::diff;
+use crate::error::{
+    self, Diagnostic, FormatError, MemberTooComplex, ParseError, TokenMismatch, UnstableFormat,
+    UnsupportedConstruct,
+};
+use crate::generation::{
+    MemberCache, fallbacks, generate, generate_with_member_cache, generate_with_source_map,
+};
+use crate::source_map::SourceMap;
+use crate::verify::{self, Tokens};
+
+/// Start of a first-line comment that overrides configuration for one file:
+/// `// dprint-java: lineWidth=100 formatJavadoc=false`.
+const FILE_CONFIG_PREFIX: &str = "// dprint-java:";
+
+/// What the formatting functions return: their output, or why the file could
+/// not be formatted.
+type Result<T> = std::result::Result<T, FormatError>;
+
+/// Most passes [`format_text`] runs over its own output looking for text
+/// that formats to itself.
+const MAX_FORMAT_PASSES: usize = 5;
 
 /// Format a Java source file. Returns `Ok(None)` if no changes were made.
 ///
+/// A first line starting with `// dprint-java:` overrides `config` for this
+/// file with its space-separated `key=value` pairs.
+///
+/// Where one pass would not be stable, the output is formatted again, up to
+/// a few passes, until it no longer changes, so dprint's stability check
+/// sees the converged text. If the passes cycle instead, the last output
+/// before the cycle is returned.
+///
 /// # Errors
 ///
-/// Returns an error if the source cannot be parsed or formatted.
+/// Returns a [`FormatError`] if the source cannot be parsed or formatted, or
+/// if its `// dprint-java:` comment is malformed. Syntax errors are reported
+/// as [`FormatError::Parse`], whose message includes a source snippet.
 pub fn format_text(
+    file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<Option<String>> {
+    format_text_with_report(file_path, file_text, config).map(|(formatted, _)| formatted)
+}
+
+/// Format a Java source file with one generation pass, without the passes
+/// [`format_text`] adds until the output stops changing, so tests can tell
+/// whether a single pass is stable on its own. Returns `Ok(None)` if no
+/// changes were made.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`], and
+/// [`FormatError::TokenMismatch`] when `verifyTokens` finds the output's
+/// tokens differ from the input's.
+#[doc(hidden)]
+pub fn format_text_single_pass(
     _file_path: &Path,
     file_text: &str,
     config: &Configuration,
 ) -> Result<Option<String>> {
-    let formatted = format_text_inner(file_text, config)?;
-    if formatted == file_text {
-        Ok(None)
+    let formatted = format_text_inner(file_text, config, None)?;
+    Ok((formatted != file_text).then_some(formatted))
+}
+
+/// A file left as it is because formatting would change more of its lines
+/// than `maxChangedLinesPercent` allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeLimitExceeded {
+    /// Lines formatting would change.
+    pub changed_lines: usize,
+    /// Lines in the file.
+    pub total_lines: usize,
+    /// The `maxChangedLinesPercent` the file was formatted with.
+    pub max_percent: u8,
+}
+
+impl fmt::Display for ChangeLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "formatting would change {} of {} lines, more than the {}% maxChangedLinesPercent allows",
+            self.changed_lines, self.total_lines, self.max_percent
+        )
+    }
+}
+
+/// Why [`format_text_with_report`] left a file as it is although formatting
+/// would change it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Formatting would change more lines than `maxChangedLinesPercent`
+    /// allows.
+    ChangeLimit(ChangeLimitExceeded),
+    /// With `verifyTokens` on, the output's tokens differ from the input's.
+    TokenMismatch(TokenMismatch),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChangeLimit(report) => report.fmt(f),
+            Self::TokenMismatch(mismatch) => mismatch.fmt(f),
+        }
+    }
+}
+
+/// Format a Java source file like [`format_text`], and say why it was left
+/// unformatted when formatting would change more of its lines than
+/// `maxChangedLinesPercent` allows, or, with `verifyTokens` on, would change
+/// its tokens.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_text_with_report(
+    _file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<(Option<String>, Option<SkipReason>)> {
+    let converged = format_until_stable(file_text, config)?;
+    if let Some(mismatch) = converged.mismatch {
+        return Ok((None, Some(SkipReason::TokenMismatch(mismatch))));
+    }
+    let (formatted, report) = limit_changes(file_text, converged.text, config)?;
+    Ok((formatted, report.map(SkipReason::ChangeLimit)))
+}
+
+/// Format a Java source file like [`format_text`], but fail when the
+/// output would not be stable.
+///
+/// dprint formats a plugin's output again and rejects it if it changes,
+/// without saying where. This runs that check itself, so the error names
+/// the line that changes and the member it is in.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`],
+/// [`FormatError::UnstableOutput`] when formatting the output again would
+/// change it, and, with `verifyTokens` on, [`FormatError::TokenMismatch`]
+/// when the output's tokens differ from the input's, which [`format_text`]
+/// only reports through [`format_text_with_report`].
+pub fn format_text_checked(
+    _file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<Option<String>> {
+    let converged = format_until_stable(file_text, config)?;
+    if let Some(mismatch) = converged.mismatch {
+        return Err(mismatch.into());
+    }
+    if let Some(next) = converged.next {
+        let tree = parse(&converged.text, None)?;
+        return Err(UnstableFormat::new(&converged.text, &next, &tree).into());
+    }
+    limit_changes(file_text, converged.text, config).map(|(formatted, _)| formatted)
+}
+
+/// Format a Java source file like [`format_text`] and return the changes as
+/// a unified diff, with `a/` and `b/` headers naming `file_path`, that a CI
+/// bot can post as a review comment. Returns `Ok(None)` if no changes were
+/// made.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_to_diff(
+    file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<Option<String>> {
+    let Some(formatted) = format_text(file_path, file_text, config)? else {
+        return Ok(None);
+    };
+    let path = file_path.to_string_lossy().replace('\\', "/");
+    Ok(Some(diff::unified_diff(file_text, &formatted, &path)))
+}
+
+/// Format a Java source file like [`format_text`], and list what it kept
+/// as written.
+///
+/// With `formatWithSyntaxErrors` on, a file with syntax errors is formatted
+/// except for the statement or member around each error, which is kept as
+/// written, and each error is returned as a non-fatal diagnostic. With it
+/// off, such a file fails to format as it does with [`format_text`].
+///
+/// A member that needs more work to format than one member is allowed,
+/// like a machine-generated call nested hundreds deep, is kept as written
+/// too, so one such member cannot hold up the whole file. Each is returned
+/// as a diagnostic, in line order with the syntax errors, as is the token
+/// mismatch that keeps a whole file as written with `verifyTokens` on.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_text_with_diagnostics(
+    file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<(Option<String>, Vec<Diagnostic>)> {
+    let (formatted, report) = format_text_with_report(file_path, file_text, config)?;
+    let tree = parse(file_text, None)?;
+    let mut diagnostics: Vec<Diagnostic> = ParseError::all_from_tree(&tree, file_text)
+        .into_iter()
+        .map(Diagnostic::Syntax)
+        .collect();
+    if let Some(SkipReason::TokenMismatch(mismatch)) = report {
+        diagnostics.push(Diagnostic::TokenMismatch(mismatch));
+    }
+    let config = &*file_config(file_text, config)?;
+    diagnostics.extend(
+        catch_panic(|| fallbacks(file_text, &tree, config))?
+            .skipped_members
+            .into_iter()
+            .map(|range| {
+                Diagnostic::MemberTooComplex(MemberTooComplex::new(file_text, range.start))
+            }),
+    );
+    diagnostics.sort_by_key(Diagnostic::line);
+    Ok((formatted, diagnostics))
+}
+
+/// A node the formatter has no handler for, so it is printed as written
+/// with only the indentation of its later lines changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnhandledNode {
+    /// The tree-sitter kind of the node, e.g. `"template_expression"`.
+    pub kind: &'static str,
+    /// 1-based line the node starts on.
+    pub line: usize,
+    /// 1-based column (in characters) the node starts at.
+    pub column: usize,
+    /// The node's byte range in the input.
+    pub byte_range: Range<usize>,
+}
+
+/// Format a Java source file like [`format_text`], and list the nodes in it
+/// that no handler formats, in source order.
+///
+/// Tokens such as identifiers and literals are printed as written by
+/// design and are not listed; the list shows which constructs of a code
+/// base the formatter does not support yet. Nodes inside `@formatter:off`
+/// regions or around syntax errors are not listed either.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_text_with_coverage(
+    file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<(Option<String>, Vec<UnhandledNode>)> {
+    let formatted = format_text(file_path, file_text, config)?;
+    let tree = parse(file_text, None)?;
+    let config = &*file_config(file_text, config)?;
+    let unhandled = catch_panic(|| fallbacks(file_text, &tree, config))?
+        .unhandled_nodes
+        .into_iter()
+        .map(|(kind, byte_range)| {
+            let (line, column) = error::line_and_column(file_text, byte_range.start);
+            UnhandledNode {
+                kind,
+                line,
+                column,
+                byte_range,
+            }
+        })
+        .collect();
+    Ok((formatted, unhandled))
+}
+
+/// Check whether a Java source file is already formatted, as a check-only
+/// run over many files would.
+///
+/// The answer is the one [`format_text`] gives: the text is formatted over
+/// the same passes and kept under the same `maxChangedLinesPercent`. Without
+/// a change limit, text that cannot be the formatter's output, such as a
+/// file without a final newline, is rejected before it is parsed, and
+/// otherwise the comparison stops at the first line that differs.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`], except for
+/// text rejected before parsing, which is reported as unformatted even if
+/// it has syntax errors.
+pub fn is_formatted(_file_path: &Path, file_text: &str, config: &Configuration) -> Result<bool> {
+    let max_percent = file_config(file_text, config)?.max_changed_lines_percent;
+    // The formatter ends every file but an empty one with a newline, but
+    // under a change limit the file may still be left as it is
+    if max_percent >= 100 && !file_text.is_empty() && !file_text.ends_with('\n') {
+        return Ok(false);
+    }
+    let formatted = format_until_stable(file_text, config)?.text;
+    if max_percent >= 100 {
+        // Any changed line is a change, so the first one settles it
+        return Ok(file_text
+            .split_inclusive('\n')
+            .eq(formatted.split_inclusive('\n')));
+    }
+    let (formatted, _) = limit_changes(file_text, formatted, config)?;
+    Ok(formatted.is_none())
+}
+
+/// Format a Java source file and map each line of the result back to the
+/// input bytes it came from.
+///
+/// Unlike [`format_text`], the formatted text is returned even when it is
+/// unchanged, so the map always describes the returned text. Tools that lint
+/// the formatted output can use the map to report diagnostics against the
+/// original file. Only one formatting pass runs, since a later pass would
+/// map back to the earlier output rather than to the original file. When
+/// `verifyTokens` keeps the file as written, each line maps to itself.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_text_with_source_map(
+    _file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+) -> Result<(String, SourceMap)> {
+    let source_map = Rc::new(RefCell::new(SourceMap::default()));
+    let formatted = match format_text_inner(file_text, config, Some(&source_map)) {
+        Err(FormatError::TokenMismatch(_)) => {
+            let mut source_map = SourceMap::default();
+            let mut start = 0;
+            for (line, text) in file_text.split_inclusive('\n').enumerate() {
+                source_map.record(line, start);
+                source_map.record(line, start + text.trim_end().len());
+                start += text.len();
+            }
+            source_map.finish(file_text, file_text.lines().count());
+            return Ok((file_text.to_string(), source_map));
+        }
+        result => result?,
+    };
+    let mut source_map = source_map.take();
+    source_map.finish(file_text, formatted.lines().count());
+    Ok((formatted, source_map))
+}
+
+/// Printer settings that change how formatted text is rendered but not how
+/// it is laid out, for previews such as an editor pane narrower than
+/// `lineWidth`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrinterOptions {
+    /// The width the printer wraps at, in place of `lineWidth`. Which
+    /// constructs wrap is still decided against `lineWidth`.
+    pub max_width: Option<u32>,
+    /// The text printed for each indentation level, in place of
+    /// `indentWidth` spaces or a tab. Alignment after the indentation, such
+    /// as the ` * ` of a Javadoc line, is printed as it is.
+    pub indent: Option<String>,
+}
+
+/// Format a Java source file like [`format_text`] and print the result with
+/// `printer` overriding the printer settings `config` implies.
+///
+/// The formatted text is returned even when it is unchanged. With an
+/// `indent` other than spaces or a tab, it is meant for display rather
+/// than as Java source: text blocks are indented with it too. A file
+/// [`format_text`] leaves as it is, for `maxChangedLinesPercent` or a
+/// `verifyTokens` mismatch, is returned unchanged.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`].
+pub fn format_text_with_printer_options(
+    file_path: &Path,
+    file_text: &str,
+    config: &Configuration,
+    printer: &PrinterOptions,
+) -> Result<String> {
+    let formatted = match format_text_with_report(file_path, file_text, config)? {
+        (_, Some(_)) => return Ok(file_text.to_string()),
+        (formatted, None) => formatted.unwrap_or_else(|| file_text.to_string()),
+    };
+    if *printer == PrinterOptions::default() {
+        return Ok(formatted);
+    }
+    let config = &*file_config(&formatted, config)?;
+    let tree = parse(&formatted, None)?;
+    let mut print_options = build_print_options(&formatted, config);
+    if let Some(max_width) = printer.max_width {
+        print_options.max_width = max_width;
+    }
+    // Printing with tabs makes each level one character to replace
+    print_options.use_tabs |= printer.indent.is_some();
+    let output = catch_panic(|| {
+        let print_items = generate(&formatted, &tree, config);
+        dprint_core::formatting::format(|| print_items, print_options)
+    })?;
+    let Some(indent) = &printer.indent else {
+        return Ok(output);
+    };
+    let mut rendered = String::with_capacity(output.len());
+    for line in output.split_inclusive('\n') {
+        let content = line.trim_start_matches('\t');
+        for _ in 0..line.len() - content.len() {
+            rendered.push_str(indent);
+        }
+        rendered.push_str(content);
+    }
+    Ok(rendered)
+}
+
+/// What [`format_text_incremental`] keeps between calls for one file: the
+/// syntax tree of the file's current text and the formatted output of the
+/// members of its top-level types.
+///
+/// Start with [`FormatSnapshot::default`] and pass the same snapshot to
+/// every call for the file.
+#[derive(Debug, Default)]
+pub struct FormatSnapshot {
+    tree: Option<tree_sitter::Tree>,
+    /// The configuration the cached members were formatted with.
+    config: Option<Configuration>,
+    members: Rc<RefCell<MemberCache>>,
+    reused_members: usize,
+}
+
+impl FormatSnapshot {
+    /// The syntax tree of the file's text after the last call: the formatted
+    /// text when it changed, and the input otherwise. `None` before the first
+    /// call.
+    #[must_use]
+    pub fn tree(&self) -> Option<&tree_sitter::Tree> {
+        self.tree.as_ref()
+    }
+
+    /// How many members of top-level types the last call copied from an
+    /// earlier call's output instead of formatting them again.
+    #[must_use]
+    pub fn reused_members(&self) -> usize {
+        self.reused_members
+    }
+}
+
+/// Format a Java source file like [`format_text`], reusing the work of the
+/// previous call with the same `snapshot`.
+///
+/// `edits` are the edits that turned the text `snapshot` describes, the
+/// previous call's output or its input if it was unchanged, into
+/// `file_text`. They let the parser reuse the old syntax tree. Members of
+/// top-level types, such as methods and fields, whose text is the same as
+/// in an earlier call are not formatted again: their earlier output is
+/// copied. This makes formatting on save cheap for large files, where an
+/// edit usually touches one member.
+///
+/// The output is the same as [`format_text`] gives. Edits that do not match
+/// the text make the parse, and so the output, wrong.
+///
+/// # Errors
+///
+/// Returns an error for the same reasons as [`format_text`]. The snapshot
+/// then describes `file_text`, so the next call's edits start from it.
+pub fn format_text_incremental(
+    snapshot: &mut FormatSnapshot,
+    edits: &[tree_sitter::InputEdit],
+    file_text: &str,
+    config: &Configuration,
+) -> Result<Option<String>> {
+    let file_config = file_config(file_text, config)?;
+    if snapshot.config.as_ref() != Some(&*file_config) {
+        snapshot.members = Rc::default();
+        snapshot.config = Some(file_config.clone().into_owned());
+    }
+    let mut old_tree = snapshot.tree.take().map(|mut tree| {
+        for edit in edits {
+            tree.edit(edit);
+        }
+        tree
+    });
+
+    let members = Rc::clone(&snapshot.members);
+    let mut last_parsed: Option<(String, tree_sitter::Tree)> = None;
+    let result = converge(file_text, config, |text| {
+        let tree = parse(text, old_tree.take().as_ref())?;
+        last_parsed = Some((text.to_string(), tree.clone()));
+        format_tree(text, tree, &file_config, None, Some(&members))
+    });
+    snapshot.reused_members = members.borrow_mut().retain_used();
+
+    let formatted = match result {
+        Ok(converged) => converged.text,
+        Err(e) => {
+            snapshot.tree = last_parsed.map(|(_, tree)| tree);
+            return Err(e);
+        }
+    };
+    let (formatted, _) = limit_changes(file_text, formatted, config)?;
+    let text = formatted.as_deref().unwrap_or(file_text);
+    snapshot.tree = Some(match last_parsed {
+        Some((parsed, tree)) if parsed == text => tree,
+        _ => parse(text, None)?,
+    });
+    Ok(formatted)
+}
+
+/// Format Java source read from `reader`, writing the result to `writer`.
+///
+/// The formatted text is always written, even when unchanged, so this can be
+/// used as a stdin-to-stdout filter. Returns whether the output differs from
+/// the input. Each pass releases its syntax tree and print items before it
+/// prints, and earlier passes are remembered only by hash, so peak memory
+/// stays close to one copy of the input plus two of the output: the last
+/// pass and the one checking it.
+///
+/// Print items are not printed in chunks: dprint's printer resolves the
+/// conditions of a file's items against each other, so each pass holds the
+/// items for the whole file until it has printed them.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails, or for the same reasons as
+/// [`format_text`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn format_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    config: &Configuration,
+) -> Result<bool> {
+    let mut file_text = String::new();
+    reader.read_to_string(&mut file_text)?;
+
+    let formatted = format_until_stable(&file_text, config)?.text;
+    let (formatted, _) = limit_changes(&file_text, formatted, config)?;
+    let changed = formatted.is_some();
+    let output = formatted.unwrap_or(file_text);
+
+    writer.write_all(output.as_bytes())?;
+    writer.flush()?;
+    Ok(changed)
+}
+
+/// Format the Java files at `paths` in parallel on rayon's global thread
+/// pool, all with `config`.
+///
+/// Each file is read and formatted on its own, as [`format_text`] would,
+/// and nothing is written back. The results come back in the order of
+/// `paths`, so one bad file does not stop the others.
+///
+/// Each result is an error if its file cannot be read, or for the same
+/// reasons as [`format_text`].
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn format_files<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    config: &Configuration,
+) -> Vec<Result<Option<String>>> {
+    use rayon::prelude::*;
+
+    paths
+        .par_iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let file_text = std::fs::read_to_string(path)
+                .map_err(|e| FormatError::Io(error::IoError::new(&e, Some(path))))?;
+            format_text(path, &file_text, config)
+        })
+        .collect()
+}
+
+/// Outcome of [`format_named_stdin`], with the process exit status a
+/// pre-commit hook or `--check` style run reports for it.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub enum FormatOutcome {
+    /// The input was already formatted, or is not a Java file.
+    Unchanged,
+    /// The formatted text differs from the input.
+    Reformatted,
+    /// Reading, formatting or writing failed. The input is written back
+    /// unchanged when formatting fails.
+    Error(FormatError),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FormatOutcome {
+    /// `0` when unchanged, `1` when reformatted and `2` on error.
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FormatOutcome::Unchanged => 0,
+            FormatOutcome::Reformatted => 1,
+            FormatOutcome::Error(_) => 2,
+        }
+    }
+}
+
+/// Format Java source read from `reader` as the file `name_hint`, writing
+/// the result to `writer`.
+///
+/// Built for pre-commit hooks that pipe a staged file through the
+/// formatter: the text is always written, a `name_hint` without a `.java`
+/// extension passes through unchanged as dprint would not route it here,
+/// and the hint is the file path [`format_text`] sees. `package-info.java`
+/// and `module-info.java` are recognized from their content, so the hint
+/// needs no other handling for them.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn format_named_stdin<R: Read, W: Write>(
+    name_hint: &Path,
+    mut reader: R,
+    mut writer: W,
+    config: &Configuration,
+) -> FormatOutcome {
+    let mut file_text = String::new();
+    if let Err(e) = reader.read_to_string(&mut file_text) {
+        return FormatOutcome::Error(e.into());
+    }
+
+    let is_java = name_hint
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("java"));
+    let (text, outcome) = if is_java {
+        match format_text(name_hint, &file_text, config) {
+            Ok(None) => (file_text, FormatOutcome::Unchanged),
+            Ok(Some(formatted)) => (formatted, FormatOutcome::Reformatted),
+            Err(e) => (file_text, FormatOutcome::Error(e)),
+        }
     } else {
-        Ok(Some(formatted))
+        (file_text, FormatOutcome::Unchanged)
+    };
+
+    match writer
+        .write_all(text.as_bytes())
+        .and_then(|()| writer.flush())
+    {
+        Ok(()) => outcome,
+        Err(e) => FormatOutcome::Error(e.into()),
+    }
+}
+
+/// Format `file_text`, then format the output again until it is a fixpoint,
+/// repeats an earlier output, or [`MAX_FORMAT_PASSES`] is reached.
+///
+/// `debugWrapReasons` markers explain the first pass's wraps, and would be
+/// reformatted as comments by a second, so with it on only one pass runs.
+fn format_until_stable(file_text: &str, config: &Configuration) -> Result<Converged> {
+    converge(file_text, config, |text| {
+        format_text_inner(text, config, None)
+    })
+}
+
+/// The output of [`converge`].
+struct Converged {
+    text: String,
+    /// The output of another pass over `text`, when it would change it.
+    next: Option<String>,
+    /// Why `text` is the input as it was: with `verifyTokens` on, the first
+    /// pass changed its tokens.
+    mismatch: Option<TokenMismatch>,
+}
+
+/// Run `format_pass` the way [`format_until_stable`] runs a formatting pass.
+fn converge(
+    file_text: &str,
+    config: &Configuration,
+    mut format_pass: impl FnMut(&str) -> Result<String>,
+) -> Result<Converged> {
+    let mut formatted = match format_pass(file_text) {
+        Err(FormatError::TokenMismatch(mismatch)) => {
+            return Ok(Converged {
+                text: file_text.to_string(),
+                next: None,
+                mismatch: Some(mismatch),
+            });
+        }
+        result => result?,
+    };
+    if formatted == file_text || config.debug_wrap_reasons {
+        return Ok(Converged {
+            text: formatted,
+            next: None,
+            mismatch: None,
+        });
+    }
+    // Earlier outputs are only compared with later ones, so their hashes do:
+    // keeping the texts would hold every pass in memory
+    let mut earlier: Vec<u64> = Vec::new();
+    for _ in 1..MAX_FORMAT_PASSES {
+        // Output that no longer parses is a formatter bug; keep the last
+        // output rather than fail the file
+        let Ok(next) = format_pass(&formatted) else {
+            return Ok(Converged {
+                text: formatted,
+                next: None,
+                mismatch: None,
+            });
+        };
+        if next == formatted {
+            return Ok(Converged {
+                text: formatted,
+                next: None,
+                mismatch: None,
+            });
+        }
+        if earlier.contains(&text_hash(&next)) {
+            return Ok(Converged {
+                text: formatted,
+                next: Some(next),
+                mismatch: None,
+            });
+        }
+        earlier.push(text_hash(&formatted));
+        formatted = next;
     }
+    // Out of passes: one more tells whether the last output settled
+    let next = format_pass(&formatted)
+        .ok()
+        .filter(|next| *next != formatted);
+    Ok(Converged {
+        text: formatted,
+        next,
+        mismatch: None,
+    })
 }
 
-fn format_text_inner(file_text: &str, config: &Configuration) -> Result<String> {
-    let mut parser = tree_sitter::Parser::new();
-    parser
-        .set_language(&tree_sitter_java::LANGUAGE.into())
-        .map_err(|e| anyhow::anyhow!("Failed to load Java grammar: {e}"))?;
+fn text_hash(text: &str) -> u64 {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// `formatted` if it differs from `file_text` and changes no more of its
+/// lines than its file's `maxChangedLinesPercent` allows, and otherwise
+/// `None`, with a report when the limit was the reason.
+fn limit_changes(
+    file_text: &str,
+    formatted: String,
+    config: &Configuration,
+) -> Result<(Option<String>, Option<ChangeLimitExceeded>)> {
+    if formatted == file_text {
+        return Ok((None, None));
+    }
+    let max_percent = file_config(file_text, config)?.max_changed_lines_percent;
+    if max_percent >= 100 {
+        return Ok((Some(formatted), None));
+    }
+    let changed_lines = diff::changed_lines(file_text, &formatted);
+    let total_lines = file_text.lines().count();
+    if changed_lines * 100 > usize::from(max_percent) * total_lines {
+        let report = ChangeLimitExceeded {
+            changed_lines,
+            total_lines,
+            max_percent,
+        };
+        return Ok((None, Some(report)));
+    }
+    Ok((Some(formatted), None))
+}
 
-    let tree = parser
-        .parse(file_text, None)
-        .ok_or_else(|| anyhow::anyhow!("Failed to parse Java source"))?;
+fn format_text_inner(
+    file_text: &str,
+    config: &Configuration,
+    source_map: Option<&Rc<RefCell<SourceMap>>>,
+) -> Result<String> {
+    let config = &*file_config(file_text, config)?;
+    let tree = parse(file_text, None)?;
+    format_tree(file_text, tree, config, source_map, None)
+}
 
-    if tree.root_node().has_error() {
-        // For now, return the source unchanged if there are parse errors.
-        // A production formatter might still attempt best-effort formatting.
-        return Ok(file_text.to_string());
+/// Format `file_text` from its syntax tree under its file's configuration.
+fn format_tree(
+    file_text: &str,
+    tree: tree_sitter::Tree,
+    config: &Configuration,
+    source_map: Option<&Rc<RefCell<SourceMap>>>,
+    member_cache: Option<&Rc<RefCell<MemberCache>>>,
+) -> Result<String> {
+    if !config.format_with_syntax_errors
+        && let Some(error) = ParseError::from_tree(&tree, file_text)
+    {
+        return Err(error.into());
+    }
+    if config.strict
+        && let Some((kind, byte_range)) = catch_panic(|| fallbacks(file_text, &tree, config))?
+            .unhandled_nodes
+            .into_iter()
+            .next()
+    {
+        return Err(UnsupportedConstruct::new(kind, byte_range, file_text).into());
     }
 
-    let print_items = generate(file_text, &tree, config);
+    let input_tokens = config
+        .verify_tokens
+        .then(|| Tokens::collect(&tree, file_text));
     let print_options = build_print_options(file_text, config);
+    let formatted = catch_panic(|| {
+        let print_items = match (source_map, member_cache) {
+            (Some(source_map), _) => {
+                generate_with_source_map(file_text, &tree, config, Rc::clone(source_map))
+            }
+            (None, Some(member_cache)) => {
+                generate_with_member_cache(file_text, &tree, config, Rc::clone(member_cache))
+            }
+            (None, None) => generate(file_text, &tree, config),
+        };
+        // The IR owns its text, so the tree can be freed before printing.
+        drop(tree);
+        dprint_core::formatting::format(|| print_items, print_options)
+    })?;
+    if let Some(input_tokens) = input_tokens
+        && formatted != file_text
+    {
+        let output_tokens = Tokens::collect(&parse(&formatted, None)?, &formatted);
+        verify::check(
+            &input_tokens,
+            &output_tokens,
+            file_text,
+            config.remove_unused_imports,
+        )?;
+    }
+    if let Some(member_cache) = member_cache {
+        member_cache.borrow_mut().finish_pass(&formatted);
+    }
+    Ok(formatted)
+}
 
-    Ok(dprint_core::formatting::format(
-        || print_items,
-        print_options,
-    ))
+/// Run `f`, returning a panic in it as [`FormatError::InternalPanic`] so a
+/// formatter bug fails one file rather than the process. Builds that abort
+/// on panic, like the WASM plugin's, still abort.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic without a message".to_string());
+        FormatError::InternalPanic(message)
+    })
+}
+
+thread_local! {
+    /// The Java parser, created on the first file and reused for the rest, so
+    /// loading the grammar is neither paid at plugin startup nor once per file.
+    static PARSER: RefCell<Option<tree_sitter::Parser>> = const { RefCell::new(None) };
+}
+
+/// Parse `file_text`, reusing the unchanged parts of `old_tree` when given,
+/// which must already have been edited to match `file_text`.
+fn parse(file_text: &str, old_tree: Option<&tree_sitter::Tree>) -> Result<tree_sitter::Tree> {
+    PARSER.with_borrow_mut(|parser| {
+        if parser.is_none() {
+            let mut new_parser = tree_sitter::Parser::new();
+            new_parser
+                .set_language(&tree_sitter_java::LANGUAGE.into())
+                .map_err(|e| {
+                    FormatError::ParserFailed(format!("Failed to load Java grammar: {e}"))
+                })?;
+            *parser = Some(new_parser);
+        }
+        parser
+            .as_mut()
+            .and_then(|parser| parser.parse(file_text, old_tree))
+            .ok_or_else(|| FormatError::ParserFailed("Failed to parse Java source".to_string()))
+    })
+}
+
+/// The configuration for `file_text`: `config` with the overrides from a
+/// `// dprint-java:` comment on the first line applied, if there is one.
+fn file_config<'c>(file_text: &str, config: &'c Configuration) -> Result<Cow<'c, Configuration>> {
+    let first_line = file_text.lines().next().unwrap_or("");
+    let Some(settings) = first_line
+        .trim_start_matches('\u{feff}')
+        .trim()
+        .strip_prefix(FILE_CONFIG_PREFIX)
+    else {
+        return Ok(Cow::Borrowed(config));
+    };
+
+    let mut overrides = ConfigKeyMap::new();
+    for setting in settings.split_whitespace() {
+        let Some((key, value)) = setting.split_once('=') else {
+            return Err(FormatError::InvalidFileConfig(format!(
+                "Invalid `{FILE_CONFIG_PREFIX}` setting `{setting}`: expected key=value"
+            )));
+        };
+        overrides.insert(key.to_string(), ConfigKeyValue::from_str(value));
+    }
+
+    let result = validate_resolved(resolve_overrides(config, overrides));
+    if let Some(diagnostic) = result.diagnostics.first() {
+        return Err(FormatError::InvalidFileConfig(format!(
+            "Invalid `{FILE_CONFIG_PREFIX}` setting `{}`: {}",
+            diagnostic.property_name, diagnostic.message
+        )));
+    }
+    Ok(Cow::Owned(result.config))
 }
 
 fn build_print_options(file_text: &str, config: &Configuration) -> PrintOptions {
     PrintOptions {
-        indent_width: config.indent_width,
+        indent_width: config.indent_columns(),
         max_width: config.line_width,
         use_tabs: config.use_tabs,
         new_line_text: resolve_new_line_kind(file_text, config.new_line_kind),
@@ -62,18 +917,57 @@ fn build_print_options(file_text: &str, config: &Configuration) -> PrintOptions
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configuration::Configuration;
+    use crate::configuration::{
+        AnnotationPlacement, ArgumentPacking, BraceStyle, Configuration, MethodChainWrapStyle,
+        StaticImportPosition,
+    };
     use dprint_core::configuration::NewLineKind;
+    use std::collections::BTreeMap;
 
     fn default_config() -> Configuration {
         Configuration {
             line_width: 120,
             indent_width: 4,
             use_tabs: false,
+            tab_width: 4,
             new_line_kind: NewLineKind::LineFeed,
             format_javadoc: false,
             method_chain_threshold: 80,
+            method_chain_min_calls_to_break: 0,
+            break_stream_pipelines: false,
+            method_chain_wrap_style: MethodChainWrapStyle::ContinuationIndent,
             inline_lambdas: true,
+            argument_packing: ArgumentPacking::Greedy,
+            brace_style: BraceStyle::SameLine,
+            else_on_new_line: false,
+            catch_on_new_line: false,
+            finally_on_new_line: false,
+            while_on_new_line: false,
+            enum_constant_annotations: AnnotationPlacement::OwnLine,
+            align_switch_arrows: false,
+            import_order: Vec::new(),
+            static_import_position: StaticImportPosition::Top,
+            sort_imports: true,
+            remove_unused_imports: false,
+            allow_single_line_if: false,
+            keep_simple_methods_on_one_line: false,
+            keep_simple_anonymous_classes_on_one_line: false,
+            tight_double_brace_initialization: false,
+            annotation_policies: BTreeMap::new(),
+            format_chains: true,
+            wrap_field_access_chains: false,
+            chain_terminal_calls: Vec::new(),
+            format_imports: true,
+            format_strings_in_annotations: true,
+            format_with_syntax_errors: false,
+            verify_tokens: false,
+            strict: false,
+            max_changed_lines_percent: 100,
+            max_blank_lines: 1,
+            blank_lines_after_package: 1,
+            blank_lines_before_class_end: 0,
+            blank_lines_between_members: 1,
+            debug_wrap_reasons: false,
         }
     }
 
@@ -95,13 +989,577 @@ mod tests {
     }
 
     #[test]
-    fn handles_parse_error_gracefully() {
+    fn reports_parse_error_with_snippet() {
         let input = "public class { broken syntax";
-        let result = format_text(Path::new("Bad.java"), input, &default_config()).unwrap();
-        // Should return None (unchanged) for parse errors
+        let error = format_text(Path::new("Bad.java"), input, &default_config()).unwrap_err();
+        let FormatError::Parse(parse_error) = &error else {
+            panic!("expected a parse error: {error:?}");
+        };
+        assert_eq!(parse_error.line, 1);
+        assert_eq!(parse_error.byte_range, 0..input.len());
+        let rendered = error.to_string();
+        assert!(rendered.starts_with("Syntax error: "));
+        assert!(rendered.contains("1 | public class { broken syntax\n"));
+        assert!(rendered.contains('^'));
+    }
+
+    #[test]
+    fn verify_tokens_allows_intended_changes() {
+        let input =
+            "import b.B;\nimport a.A;\nclass X {\n  final static B b = new B(); // b\n  A a;\n}\n";
+        let config = Configuration {
+            verify_tokens: true,
+            ..default_config()
+        };
+        let formatted = format_text(Path::new("X.java"), input, &config).unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some(
+                "import a.A;\nimport b.B;\n\nclass X {\n    static final B b = new B(); // b\n    A a;\n}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn formats_around_syntax_errors_when_enabled() {
+        let input = "class A {\n    void f() {\n        int x =   1\n        int y   = 2;\n        g(  );\n    }\n}\n";
+        let config = Configuration {
+            format_with_syntax_errors: true,
+            ..default_config()
+        };
+        let (formatted, diagnostics) =
+            format_text_with_diagnostics(Path::new("A.java"), input, &config).unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some(
+                "class A {\n    void f() {\n        int x =   1\n        int y = 2;\n        g();\n    }\n}\n"
+            )
+        );
+        let [Diagnostic::Syntax(error)] = diagnostics.as_slice() else {
+            panic!("expected one syntax error: {diagnostics:?}");
+        };
+        assert_eq!(error.message, "missing `;`");
+        assert_eq!(error.line, 3);
+
+        let error = format_text_with_diagnostics(Path::new("A.java"), input, &default_config())
+            .unwrap_err();
+        assert!(matches!(error, FormatError::Parse(_)));
+    }
+
+    #[test]
+    fn keeps_members_too_complex_to_format_as_written() {
+        let nested = (0..200).fold("x".to_string(), |inner, _| format!("f(a, b, {inner})"));
+        let input = format!(
+            "class A {{\n    void f() {{\n        call({nested});\n    }}\n\n    void g( ) {{ }}\n}}\n"
+        );
+        let expected = input.replace("g( ) { }", "g() {}");
+        // A call nested this deep needs more stack than a test thread has
+        let (formatted, diagnostics) = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                format_text_with_diagnostics(Path::new("A.java"), &input, &default_config())
+                    .unwrap()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(formatted, Some(expected));
+        let [Diagnostic::MemberTooComplex(member)] = diagnostics.as_slice() else {
+            panic!("expected one skipped member: {diagnostics:?}");
+        };
+        assert_eq!((member.line, member.column), (2, 5));
+    }
+
+    #[test]
+    fn printer_options_change_rendering_only() {
+        let input = "class A {\n  void f() {\n    g(1);\n  }\n}\n";
+        let printer = PrinterOptions {
+            max_width: Some(20),
+            indent: Some("| ".to_string()),
+        };
+        let rendered = format_text_with_printer_options(
+            Path::new("A.java"),
+            input,
+            &default_config(),
+            &printer,
+        )
+        .unwrap();
+        assert_eq!(rendered, "class A {\n| void f() {\n| | g(1);\n| }\n}\n");
+
+        let rendered = format_text_with_printer_options(
+            Path::new("A.java"),
+            input,
+            &default_config(),
+            &PrinterOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "class A {\n    void f() {\n        g(1);\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn lists_nodes_without_a_handler() {
+        let input = "class A {\n    Object o = String.class;\n}\n";
+        let (formatted, unhandled) =
+            format_text_with_coverage(Path::new("A.java"), input, &default_config()).unwrap();
+        assert_eq!(formatted, None);
+        assert_eq!(
+            unhandled,
+            [UnhandledNode {
+                kind: "class_literal",
+                line: 2,
+                column: 16,
+                byte_range: 25..37,
+            }]
+        );
+    }
+
+    #[test]
+    fn strict_fails_on_nodes_without_a_handler() {
+        let input = "class A {\n    Object o = String.class;\n}\n";
+        let config = Configuration {
+            strict: true,
+            ..default_config()
+        };
+        let Err(FormatError::UnsupportedConstruct(error)) =
+            format_text(Path::new("A.java"), input, &config)
+        else {
+            panic!("expected an unsupported construct");
+        };
+        assert_eq!(error.kind, "class_literal");
+        assert_eq!((error.line, error.column), (2, 16));
+        assert_eq!(error.byte_range, 25..37);
+        assert!(
+            error
+                .to_string()
+                .starts_with("Unsupported construct `class_literal` (line 2, column 16)\n")
+        );
+    }
+
+    #[test]
+    fn format_stream_writes_formatted_output() {
+        let input = "public class Foo{int x;}";
+        let mut output = Vec::new();
+        let changed = format_stream(input.as_bytes(), &mut output, &default_config()).unwrap();
+        assert!(changed);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "public class Foo {\n    int x;\n}\n"
+        );
+    }
+
+    #[test]
+    fn format_stream_writes_unchanged_input() {
+        let input = "public class Foo {}\n";
+        let mut output = Vec::new();
+        let changed = format_stream(input.as_bytes(), &mut output, &default_config()).unwrap();
+        assert!(!changed);
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
+
+    #[test]
+    fn formats_until_output_is_stable() {
+        // One pass wraps the nested ternary, which then fits on the line
+        // the wrapped arguments give it
+        let input = "class A {\n    void f() {\n        if (new ValueTo(new ValueItem(getValue -> (mapToGet ? \"onGet\" : value), 4674 ? onItem ? \"withCount\" : 4682 : (\"mapValue\"), mapToOn(1518, (getTo), (mapTo))))) {\n            toItem();\n        }\n    }\n}\n";
+        let config = Configuration {
+            line_width: 80,
+            ..default_config()
+        };
+        let output = format_text(Path::new("A.java"), input, &config)
+            .unwrap()
+            .unwrap();
+        assert!(
+            output.contains(
+                "                4674 ? onItem ? \"withCount\" : 4682 : (\"mapValue\"),\n"
+            )
+        );
+        assert_eq!(
+            format_text(Path::new("A.java"), &output, &config).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn token_mismatch_keeps_the_input() {
+        let input = "class A {}\n";
+        let mismatch = TokenMismatch::new("`A` became `B`".to_string(), input, 6, 1);
+        let converged =
+            converge(input, &default_config(), |_| Err(mismatch.clone().into())).unwrap();
+        assert_eq!(converged.text, input);
+        assert_eq!(converged.next, None);
+        assert_eq!(converged.mismatch, Some(mismatch));
+    }
+
+    #[test]
+    fn wrapped_chain_segments_settle_in_one_pass() {
+        // Arguments inside a wrapped segment, and calls whose receiver an
+        // earlier pass wrapped, are measured the same on every pass
+        let inputs = [
+            "class A {\n    void f() {\n        if (a) {\n            if (b) {\n                if (c) {\n                    if (d) {\n                        String someLongVariableName = executor\n                                .thenCompose((resultValueWithLongName, throwableError) -> {\n                                    statementNumber0(value);\n                                    statementNumber1(value, x, x, x, x, x, x, x, x, x, x, x, x);\n                                    return value;\n                                });\n                    }\n                }\n            }\n        }\n    }\n}\n",
+            "class A {\n    void f() {\n        if (a) {\n            if (b) {\n                if (c) {\n                    String someLongVariableName = client.sendAsync(request, BodyHandlers.ofString()).thenCompose(x -> transform(x, aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa)).thenApply(x -> transform(x, bbbbbbbbbbbbbbbbbbbb)).handle((value, error) -> {\n                        return value;\n                    }).join();\n                }\n            }\n        }\n    }\n}\n",
+        ];
+        let config = default_config();
+        for input in inputs {
+            let first = format_text_inner(input, &config, None).unwrap();
+            let second = format_text_inner(&first, &config, None).unwrap();
+            assert_eq!(first, second, "{input}");
+        }
+    }
+
+    #[test]
+    fn format_text_checked_matches_format_text_when_stable() {
+        let input = "class A {\n  int x=1;\n}\n";
+        let config = default_config();
+        assert_eq!(
+            format_text_checked(Path::new("A.java"), input, &config).unwrap(),
+            format_text(Path::new("A.java"), input, &config).unwrap()
+        );
+    }
+
+    #[test]
+    fn format_to_diff_shows_changed_lines() {
+        let input = "class A {\n  int x=1;\n}\n";
+        let diff = format_to_diff(Path::new("src/A.java"), input, &default_config())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            diff,
+            "--- a/src/A.java\n+++ b/src/A.java\n@@ -1,3 +1,3 @@\n class A {\n-  int x=1;\n+    int x = 1;\n }\n"
+        );
+        assert_eq!(
+            format_to_diff(Path::new("A.java"), "class A {}\n", &default_config()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_files_over_the_change_limit() {
+        let input = "class A {\n  int x=1;\n  int y = 2;\n}\n";
+        let config = Configuration {
+            max_changed_lines_percent: 20,
+            ..default_config()
+        };
+        let (formatted, report) =
+            format_text_with_report(Path::new("A.java"), input, &config).unwrap();
+        assert_eq!(formatted, None);
+        assert_eq!(
+            report,
+            Some(SkipReason::ChangeLimit(ChangeLimitExceeded {
+                changed_lines: 2,
+                total_lines: 4,
+                max_percent: 20,
+            }))
+        );
+        assert!(is_formatted(Path::new("A.java"), input, &config).unwrap());
+
+        let config = Configuration {
+            max_changed_lines_percent: 50,
+            ..config
+        };
+        let (formatted, report) =
+            format_text_with_report(Path::new("A.java"), input, &config).unwrap();
+        assert!(formatted.is_some());
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn printer_options_keep_files_format_text_leaves() {
+        let input = "class A {\n  int x=1;\n  int y = 2;\n}\n";
+        let config = Configuration {
+            max_changed_lines_percent: 20,
+            ..default_config()
+        };
+        let printer = PrinterOptions {
+            max_width: Some(40),
+            indent: None,
+        };
+        let rendered =
+            format_text_with_printer_options(Path::new("A.java"), input, &config, &printer)
+                .unwrap();
+        assert_eq!(rendered, input);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn format_files_keeps_path_order() {
+        let dir = std::env::temp_dir().join(format!("format_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let formatted = dir.join("Formatted.java");
+        let unformatted = dir.join("Unformatted.java");
+        std::fs::write(&formatted, "class A {}\n").unwrap();
+        std::fs::write(&unformatted, "class B {  }").unwrap();
+        let paths = [unformatted, dir.join("Missing.java"), formatted];
+
+        let results = format_files(&paths, &default_config());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap().as_deref(),
+            Some("class B {}\n")
+        );
+        assert!(matches!(
+            &results[1],
+            Err(FormatError::Io(error)) if error.kind == std::io::ErrorKind::NotFound
+        ));
+        assert_eq!(results[2].as_ref().unwrap(), &None);
+    }
+
+    #[test]
+    fn incremental_format_reuses_untouched_members() {
+        let config = default_config();
+        let input = "class A {\n    int x = 1;\n\n    void f() {\n        g( 1 );\n    }\n\n    void h() {\n        g(2);\n    }\n}\n";
+        let mut snapshot = FormatSnapshot::default();
+        let first = format_text_incremental(&mut snapshot, &[], input, &config).unwrap();
+        assert_eq!(
+            first,
+            format_text(Path::new("A.java"), input, &config).unwrap()
+        );
+
+        // Edit the formatted text: `g(1)` becomes `g(10)` inside `f`
+        let formatted = first.unwrap();
+        let start_byte = formatted.find("g(1)").unwrap() + 3;
+        let edited = format!("{}0{}", &formatted[..start_byte], &formatted[start_byte..]);
+        let edit = tree_sitter::InputEdit {
+            start_byte,
+            old_end_byte: start_byte,
+            new_end_byte: start_byte + 1,
+            start_position: tree_sitter::Point::new(4, 11),
+            old_end_position: tree_sitter::Point::new(4, 11),
+            new_end_position: tree_sitter::Point::new(4, 12),
+        };
+        let second = format_text_incremental(&mut snapshot, &[edit], &edited, &config).unwrap();
+        assert_eq!(second, None);
+        assert_eq!(snapshot.reused_members(), 2);
+        assert_eq!(
+            snapshot.tree().unwrap().root_node().end_byte(),
+            edited.len()
+        );
+    }
+
+    #[test]
+    fn ends_with_exactly_one_newline() {
+        let config = default_config();
+        let cases = [
+            ("class A {}", "class A {}\n"),
+            ("class A {}\n\n\n", "class A {}\n"),
+            ("class A {}\n// end", "class A {}\n// end\n"),
+            ("class A {}\n// end\n\n", "class A {}\n// end\n"),
+            ("class A {} // end\n\n", "class A {} // end\n"),
+            ("import a.B;", "import a.B;\n"),
+            ("package a;\nimport a.B;\n\n", "package a;\n\nimport a.B;\n"),
+        ];
+        for (input, expected) in cases {
+            let result = format_text(Path::new("A.java"), input, &config).unwrap();
+            assert_eq!(result.as_deref(), Some(expected), "{input:?}");
+            let result = format_text(Path::new("A.java"), expected, &config).unwrap();
+            assert_eq!(result, None, "{expected:?}");
+        }
+    }
+
+    #[test]
+    fn degenerate_inputs_format_without_panicking() {
+        let config = default_config();
+        let cases = [
+            ("", None),
+            ("  \n\t\n\n", Some("")),
+            ("\u{feff}", Some("")),
+            ("// only a comment", Some("// only a comment\n")),
+            ("/* a */\n\n\n/* b */\n", Some("/* a */\n\n/* b */\n")),
+            ("package a;", Some("package a;\n")),
+            ("package a;\n\n\n", Some("package a;\n")),
+            ("import a.B;\n// after", Some("import a.B;\n// after\n")),
+            (";", Some(";\n")),
+        ];
+        for (input, expected) in cases {
+            let path = Path::new("A.java");
+            let result = format_text(path, input, &config).unwrap();
+            assert_eq!(result.as_deref(), expected, "{input:?}");
+            let output = expected.unwrap_or(input);
+            assert!(is_formatted(path, output, &config).unwrap(), "{output:?}");
+            format_text_checked(path, input, &config).unwrap();
+            format_to_diff(path, input, &config).unwrap();
+            format_text_with_source_map(path, input, &config).unwrap();
+            format_text_incremental(&mut FormatSnapshot::default(), &[], input, &config).unwrap();
+        }
+    }
+
+    #[test]
+    fn crlf_trailing_newline_is_unchanged() {
+        let config = Configuration {
+            new_line_kind: NewLineKind::CarriageReturnLineFeed,
+            ..default_config()
+        };
+        let result = format_text(Path::new("A.java"), "class A {}\r\n", &config).unwrap();
+        assert_eq!(result, None);
+        let result = format_text(Path::new("A.java"), "class A {}\r\n\r\n", &config).unwrap();
+        assert_eq!(result.as_deref(), Some("class A {}\r\n"));
+    }
+
+    #[test]
+    fn is_formatted_matches_format_text() {
+        let config = default_config();
+        assert!(is_formatted(Path::new("Foo.java"), "public class Foo {}\n", &config).unwrap());
+        assert!(!is_formatted(Path::new("Foo.java"), "public class Foo{}\n", &config).unwrap());
+        assert!(is_formatted(Path::new("Bad.java"), "public class {\n", &config).is_err());
+    }
+
+    #[test]
+    fn is_formatted_rejects_missing_final_newline_before_parsing() {
+        let config = default_config();
+        assert!(!is_formatted(Path::new("Foo.java"), "public class Foo {}", &config).unwrap());
+        assert!(!is_formatted(Path::new("Bad.java"), "public class {", &config).unwrap());
+    }
+
+    #[test]
+    fn is_formatted_applies_the_change_limit_to_a_missing_final_newline() {
+        let config = Configuration {
+            max_changed_lines_percent: 20,
+            ..default_config()
+        };
+        // Too many changed lines to format, then only the final newline
+        for (input, formatted) in [
+            ("class A {\n  int x=1;\n  int y=2;\n}", true),
+            (
+                "class A {\n    int x = 1;\n    int y = 2;\n    int z = 3;\n    int w = 4;\n}",
+                false,
+            ),
+        ] {
+            let unchanged = format_text(Path::new("A.java"), input, &config)
+                .unwrap()
+                .is_none();
+            assert_eq!(unchanged, formatted, "{input:?}");
+            assert_eq!(
+                is_formatted(Path::new("A.java"), input, &config).unwrap(),
+                formatted,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn format_named_stdin_reports_outcomes() {
+        let config = default_config();
+        let run = |name: &str, input: &str| {
+            let mut output = Vec::new();
+            let outcome =
+                format_named_stdin(Path::new(name), input.as_bytes(), &mut output, &config);
+            (outcome.exit_code(), String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            run("Foo.java", "class Foo{}"),
+            (1, "class Foo {}\n".to_string())
+        );
+        assert_eq!(
+            run("Foo.java", "class Foo {}\n"),
+            (0, "class Foo {}\n".to_string())
+        );
+        assert_eq!(run("Bad.java", "class {"), (2, "class {".to_string()));
+        assert_eq!(
+            run("notes.txt", "class Foo{}"),
+            (0, "class Foo{}".to_string())
+        );
+    }
+
+    #[test]
+    fn package_info_keeps_imports_used_by_package_annotations() {
+        let config = Configuration {
+            remove_unused_imports: true,
+            ..default_config()
+        };
+        let input = "@ParametersAreNonnullByDefault\npackage com.example;\n\nimport java.util.List;\nimport javax.annotation.ParametersAreNonnullByDefault;\n";
+        let mut output = Vec::new();
+        let outcome = format_named_stdin(
+            Path::new("com/example/package-info.java"),
+            input.as_bytes(),
+            &mut output,
+            &config,
+        );
+        assert_eq!(outcome.exit_code(), 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "@ParametersAreNonnullByDefault\npackage com.example;\n\nimport javax.annotation.ParametersAreNonnullByDefault;\n"
+        );
+    }
+
+    #[test]
+    fn debug_wrap_reasons_annotates_wrapped_argument_list() {
+        let input = "class A {\n    void f() {\n        call(firstArgument, secondArgument, thirdArgument);\n    }\n}\n";
+        let config = Configuration {
+            line_width: 40,
+            debug_wrap_reasons: true,
+            ..default_config()
+        };
+        let result = format_text(Path::new("A.java"), input, &config)
+            .unwrap()
+            .unwrap();
+        assert!(result.contains("call( /* wrapped: argList width=58>40 */\n"));
+
+        let result = format_text(Path::new("A.java"), input, &default_config()).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn file_config_comment_overrides_line_width() {
+        let input = "// dprint-java: lineWidth=40\nclass A {\n    void f() {\n        call(firstArgument, secondArgument);\n    }\n}\n";
+        let result = format_text(Path::new("A.java"), input, &default_config())
+            .unwrap()
+            .unwrap();
+        assert!(result.contains(
+            "        call(\n                firstArgument,\n                secondArgument);\n"
+        ));
+
+        let input = input.replacen("lineWidth=40", "lineWidth=120", 1);
+        let result = format_text(Path::new("A.java"), &input, &default_config()).unwrap();
         assert!(result.is_none());
     }
 
+    #[test]
+    fn reports_panics_as_internal_errors() {
+        let error = catch_panic(|| panic!("no handler for {}", "x")).unwrap_err();
+        assert_eq!(
+            error,
+            FormatError::InternalPanic("no handler for x".to_string())
+        );
+        assert_eq!(
+            error.to_string(),
+            "Internal formatter error: no handler for x"
+        );
+    }
+
+    #[test]
+    fn file_config_comment_rejects_bad_settings() {
+        let input = "// dprint-java: lineWidth=wide\nclass A {}\n";
+        let error = format_text(Path::new("A.java"), input, &default_config()).unwrap_err();
+        assert!(matches!(error, FormatError::InvalidFileConfig(_)));
+        assert!(error.to_string().contains("`lineWidth`"));
+
+        let input = "// dprint-java: formatJavadoc\nclass A {}\n";
+        let error = format_text(Path::new("A.java"), input, &default_config()).unwrap_err();
+        assert!(error.to_string().contains("expected key=value"));
+    }
+
+    #[test]
+    fn file_config_comment_rejects_contradictory_settings() {
+        for setting in ["indentWidth=0", "lineWidth=0"] {
+            let input = format!("// dprint-java: {setting}\nclass A {{\n  int x;\n}}\n");
+            let error = format_text(Path::new("A.java"), &input, &default_config()).unwrap_err();
+            assert!(
+                matches!(error, FormatError::InvalidFileConfig(_)),
+                "{setting}"
+            );
+        }
+    }
+
+    #[test]
+    fn file_config_comment_only_on_first_line() {
+        let input = "class A {}\n// dprint-java: lineWidth=wide\n";
+        assert!(format_text(Path::new("A.java"), input, &default_config()).is_ok());
+    }
+
     #[test]
     fn formats_package_and_imports() {
         let input = "package com.example;\nimport java.util.List;\nimport java.util.Map;\npublic class Foo {}\n";
@@ -368,4 +1826,26 @@ public class Hello {
 ";
         format_and_check(input, expected);
     }
+
+    #[test]
+    fn maps_output_lines_to_input() {
+        let input = "class A {\nvoid m() { int x = 1;\n  call(x); }\n}\n";
+        let (formatted, source_map) =
+            format_text_with_source_map(Path::new("A.java"), input, &default_config()).unwrap();
+        assert_eq!(
+            format_text(Path::new("A.java"), input, &default_config())
+                .unwrap()
+                .as_deref(),
+            Some(formatted.as_str())
+        );
+
+        let lines: Vec<&str> = formatted.lines().collect();
+        assert_eq!(lines[2], "        int x = 1;");
+        assert_eq!(lines[3], "        call(x);");
+        assert_eq!(source_map.lines().len(), lines.len());
+        let input_text = |line| source_map.input_range(line).map(|range| &input[range]);
+        assert_eq!(input_text(2), Some("int x = 1;"));
+        assert_eq!(input_text(3), Some("call(x);"));
+        assert_eq!(source_map.input_range(lines.len()), None);
+    }
 }
@@ -0,0 +1,58 @@
+use dprint_core::formatting::PrintItems;
+
+use super::context::FormattingContext;
+use super::helpers::PrintItemsExt;
+
+/// Blank lines the source has between a node that ends on `prev_end_row` and
+/// one that starts on `start_row`, capped at `maxBlankLines`.
+///
+/// `prev_end_row` is `None` when nothing comes before, such as the first
+/// statement of a block whose `{` row is unknown.
+pub fn kept(context: &FormattingContext, prev_end_row: Option<usize>, start_row: usize) -> usize {
+    let source_blank_lines =
+        prev_end_row.map_or(0, |prev_row| start_row.saturating_sub(prev_row + 1));
+    source_blank_lines.min(usize::from(context.config.max_blank_lines))
+}
+
+/// Blank lines between two members of a type body: the kept source blank
+/// lines, and at least `blankLinesBetweenMembers` when `separated`, which
+/// the caller sets when either member has a body.
+pub fn between_members(
+    context: &FormattingContext,
+    prev_end_row: Option<usize>,
+    start_row: usize,
+    separated: bool,
+) -> usize {
+    let kept = kept(context, prev_end_row, start_row);
+    if separated {
+        kept.max(usize::from(context.config.blank_lines_between_members))
+    } else {
+        kept
+    }
+}
+
+/// Blank lines between the `package` declaration and what follows it.
+pub fn after_package(context: &FormattingContext) -> usize {
+    usize::from(context.config.blank_lines_after_package)
+}
+
+/// Blank lines between a top-level declaration, or the imports, and what
+/// follows it. Top-level types are always one blank line apart, whatever
+/// the source has.
+pub fn after_declaration() -> usize {
+    1
+}
+
+/// Blank lines before the `}` of a type body that has members.
+pub fn before_class_end(context: &FormattingContext) -> usize {
+    usize::from(context.config.blank_lines_before_class_end)
+}
+
+/// Emit `count` blank lines after a line that has already ended.
+pub fn gen_blank_lines(count: usize) -> PrintItems {
+    let mut items = PrintItems::new();
+    for _ in 0..count {
+        items.newline();
+    }
+    items
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use tree_sitter::Node;
+
+/// Where a comment sits relative to the code around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentPosition {
+    /// On its own line, before the node it belongs to.
+    Leading,
+    /// On the line the code before it ends on.
+    Trailing,
+    /// On its own line with no node after it, like a comment before `}` or `)`.
+    Dangling,
+}
+
+/// Every comment of a tree, attached to the node it belongs to.
+///
+/// Built once before generation so that handlers look a comment's place up
+/// instead of working it out from the siblings around it. Nodes are keyed by
+/// [`Node::id`].
+#[derive(Debug, Default)]
+pub struct CommentMap<'a> {
+    positions: HashMap<usize, CommentPosition>,
+    /// Leading comments, by the node they precede.
+    leading: HashMap<usize, Vec<Node<'a>>>,
+    /// Trailing comments, by the node they follow.
+    trailing: HashMap<usize, Vec<Node<'a>>>,
+    /// Trailing comments with no named node before them, like `( // note`,
+    /// by their parent.
+    after_open: HashMap<usize, Vec<Node<'a>>>,
+    /// Dangling comments, by their parent.
+    dangling: HashMap<usize, Vec<Node<'a>>>,
+}
+
+impl<'a> CommentMap<'a> {
+    /// Attach every comment under `root`.
+    #[must_use]
+    pub fn new(root: Node<'a>) -> Self {
+        let mut map = Self::default();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            let children: Vec<Node<'a>> = node.children(&mut cursor).collect();
+            map.attach_children(node, &children);
+            stack.extend(children.into_iter().filter(|c| c.child_count() > 0));
+        }
+        map
+    }
+
+    fn attach_children(&mut self, parent: Node<'a>, children: &[Node<'a>]) {
+        let mut prev_named: Option<Node<'a>> = None;
+        let mut prev_code_end_row = None;
+        let mut pending = Vec::new();
+        for child in children {
+            if !child.is_extra() {
+                if child.is_named() && !pending.is_empty() {
+                    self.attach(&pending, CommentPosition::Leading, child.id());
+                    pending.clear();
+                }
+                if child.is_named() {
+                    prev_named = Some(*child);
+                }
+                prev_code_end_row = Some(child.end_position().row);
+            } else if prev_code_end_row == Some(child.start_position().row) {
+                self.positions.insert(child.id(), CommentPosition::Trailing);
+                match prev_named {
+                    Some(owner) => self.trailing.entry(owner.id()),
+                    None => self.after_open.entry(parent.id()),
+                }
+                .or_default()
+                .push(*child);
+            } else {
+                pending.push(*child);
+            }
+        }
+        if !pending.is_empty() {
+            self.attach(&pending, CommentPosition::Dangling, parent.id());
+        }
+    }
+
+    fn attach(&mut self, comments: &[Node<'a>], position: CommentPosition, owner: usize) {
+        for comment in comments {
+            self.positions.insert(comment.id(), position);
+        }
+        let attached = match position {
+            CommentPosition::Leading => &mut self.leading,
+            CommentPosition::Trailing => &mut self.trailing,
+            CommentPosition::Dangling => &mut self.dangling,
+        };
+        attached.entry(owner).or_default().extend(comments);
+    }
+
+    /// Where `comment` sits, or `None` when it is not a comment of the tree.
+    #[must_use]
+    pub fn position(&self, comment: Node) -> Option<CommentPosition> {
+        self.positions.get(&comment.id()).copied()
+    }
+
+    /// Whether `comment` follows code on the same line.
+    #[must_use]
+    pub fn is_trailing(&self, comment: Node) -> bool {
+        self.position(comment) == Some(CommentPosition::Trailing)
+    }
+
+    /// The comments on their own lines before `node`.
+    #[must_use]
+    pub fn leading(&self, node: Node) -> &[Node<'a>] {
+        Self::get(&self.leading, node)
+    }
+
+    /// The comments after `node` on the line it ends on.
+    #[must_use]
+    pub fn trailing(&self, node: Node) -> &[Node<'a>] {
+        Self::get(&self.trailing, node)
+    }
+
+    /// The comments after the opening token of `parent`, on its line.
+    #[must_use]
+    pub fn after_open(&self, parent: Node) -> &[Node<'a>] {
+        Self::get(&self.after_open, parent)
+    }
+
+    /// The comments on their own lines after the last node in `parent`.
+    #[must_use]
+    pub fn dangling(&self, parent: Node) -> &[Node<'a>] {
+        Self::get(&self.dangling, parent)
+    }
+
+    fn get<'m>(comments: &'m HashMap<usize, Vec<Node<'a>>>, node: Node) -> &'m [Node<'a>] {
+        comments.get(&node.id()).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    fn texts<'s>(comments: &[Node], source: &'s str) -> Vec<&'s str> {
+        comments.iter().map(|c| &source[c.byte_range()]).collect()
+    }
+
+    fn find<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+        if node.kind() == kind {
+            return Some(node);
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node<'t>> = node.children(&mut cursor).collect();
+        children.into_iter().find_map(|child| find(child, kind))
+    }
+
+    #[test]
+    fn attaches_argument_comments() {
+        let source = "class A {\n  void f() {\n    call( // open\n        // lead\n        alpha, // first\n        beta /* last */\n        // end\n    );\n  }\n}\n";
+        let tree = parse(source);
+        let map = CommentMap::new(tree.root_node());
+        let args = find(tree.root_node(), "argument_list").unwrap();
+        let mut cursor = args.walk();
+        let named: Vec<Node> = args
+            .named_children(&mut cursor)
+            .filter(|n| !n.is_extra())
+            .collect();
+        let (alpha, beta) = (named[0], named[1]);
+
+        assert_eq!(texts(map.after_open(args), source), ["// open"]);
+        assert_eq!(texts(map.leading(alpha), source), ["// lead"]);
+        assert_eq!(texts(map.trailing(alpha), source), ["// first"]);
+        assert_eq!(texts(map.trailing(beta), source), ["/* last */"]);
+        assert_eq!(texts(map.dangling(args), source), ["// end"]);
+        assert!(map.leading(beta).is_empty());
+    }
+
+    #[test]
+    fn classifies_every_comment_once() {
+        let source = "// header\nclass A {\n  int x; // trailing\n  // before y\n  int y;\n  // dangling\n}\n";
+        let tree = parse(source);
+        let map = CommentMap::new(tree.root_node());
+        let body = find(tree.root_node(), "class_body").unwrap();
+        let mut cursor = body.walk();
+        let comments: Vec<Node> = body.children(&mut cursor).filter(Node::is_extra).collect();
+        let positions: Vec<_> = comments.iter().map(|c| map.position(*c)).collect();
+        assert_eq!(
+            positions,
+            [
+                Some(CommentPosition::Trailing),
+                Some(CommentPosition::Leading),
+                Some(CommentPosition::Dangling)
+            ]
+        );
+
+        let mut cursor = tree.root_node().walk();
+        let header = tree.root_node().children(&mut cursor).next().unwrap();
+        assert_eq!(map.position(header), Some(CommentPosition::Leading));
+    }
+}
@@ -1,9 +1,12 @@
-use dprint_core::formatting::PrintItems;
+use std::ops::Range;
 
-use crate::configuration::Configuration;
+use dprint_core::formatting::PrintItems;
 
+use super::blank_lines;
 use super::context::FormattingContext;
+use super::generate::gen_node;
 use super::helpers::PrintItemsExt;
+use super::javadoc;
 
 /// Format a line comment: `// ...`
 ///
@@ -11,7 +14,29 @@ use super::helpers::PrintItemsExt;
 /// space after the `//` prefix (unless the comment is empty or starts with `///`).
 /// ALWAYS emits a newline after the comment to prevent it from commenting out
 /// subsequent code on the same line.
+///
+/// Consecutive `///` lines documenting a declaration are printed together as
+/// one Markdown documentation comment, reflowed when `format_javadoc` is set.
 pub fn gen_line_comment(node: tree_sitter::Node, context: &FormattingContext) -> PrintItems {
+    if is_markdown_doc_line(node, context.source) {
+        // The first `///` line prints the whole documentation comment
+        if continues_markdown_doc(node, context.source) {
+            return PrintItems::new();
+        }
+        let lines: Vec<&str> = markdown_doc_lines(node, context.source)
+            .map(|line| &context.source[line.start_byte() + "///".len()..line.end_byte()])
+            .collect();
+        if context.config.format_javadoc && !context.is_suppressed(node) {
+            return javadoc::gen_markdown_doc(&lines, context);
+        }
+        let mut items = PrintItems::new();
+        for line in lines {
+            items.push_str(&format!("///{}", line.trim_end()));
+            items.newline();
+        }
+        return items;
+    }
+
     let mut items = PrintItems::new();
     let text = &context.source[node.start_byte()..node.end_byte()];
 
@@ -42,9 +67,54 @@ pub fn gen_line_comment(node: tree_sitter::Node, context: &FormattingContext) ->
     items
 }
 
+/// Node kinds whose comments can document the declaration that follows.
+const DOC_COMMENT_PARENTS: &[&str] = &[
+    "program",
+    "class_body",
+    "interface_body",
+    "enum_body",
+    "enum_body_declarations",
+    "annotation_type_body",
+];
+
+/// Whether `node` is a line of a Markdown documentation comment (JEP 467):
+/// a `///` comment on its own line in a class body or at the top level.
+fn is_markdown_doc_line(node: tree_sitter::Node, source: &str) -> bool {
+    let text = &source[node.start_byte()..node.end_byte()];
+    node.kind() == "line_comment"
+        && text.starts_with("///")
+        && !text.starts_with("////")
+        && node
+            .parent()
+            .is_some_and(|parent| DOC_COMMENT_PARENTS.contains(&parent.kind()))
+        && !is_trailing_comment(node)
+}
+
+/// Whether `node` is a `///` line continuing the Markdown documentation
+/// comment on the line above it. Consecutive `///` lines form one comment;
+/// a blank line starts a new one.
+fn continues_markdown_doc(node: tree_sitter::Node, source: &str) -> bool {
+    is_markdown_doc_line(node, source)
+        && node.prev_sibling().is_some_and(|prev| {
+            prev.end_position().row + 1 == node.start_position().row
+                && is_markdown_doc_line(prev, source)
+        })
+}
+
+/// The `///` lines of the Markdown documentation comment starting at `first`.
+fn markdown_doc_lines<'a>(
+    first: tree_sitter::Node<'a>,
+    source: &'a str,
+) -> impl Iterator<Item = tree_sitter::Node<'a>> {
+    std::iter::successors(Some(first), |line| {
+        line.next_sibling()
+            .filter(|next| continues_markdown_doc(*next, source))
+    })
+}
+
 /// Format a block comment: `/* ... */`
 ///
-/// If the comment starts with `/**` (Javadoc), delegates to `gen_javadoc`
+/// If the comment starts with `/**` (Javadoc), delegates to `javadoc::gen_javadoc`
 /// when `config.format_javadoc` is true. Otherwise preserves the comment
 /// content, only normalizing indentation of continuation lines.
 pub fn gen_block_comment(node: tree_sitter::Node, context: &FormattingContext) -> PrintItems {
@@ -52,7 +122,7 @@ pub fn gen_block_comment(node: tree_sitter::Node, context: &FormattingContext) -
 
     // Check if this is a Javadoc comment
     if text.starts_with("/**") && !text.starts_with("/***") && context.config.format_javadoc {
-        return gen_javadoc(node, context, context.config);
+        return javadoc::gen_javadoc(node, context);
     }
 
     // For non-Javadoc block comments, preserve content but normalize
@@ -128,373 +198,86 @@ fn strip_comment_line_trailing_ws(line: &str) -> String {
     trimmed.to_string()
 }
 
-/// Format a Javadoc comment with tag reflowing.
+/// Find the regions of `source` that must be emitted verbatim.
 ///
-/// Reformats `/** ... */` comments:
-/// - Normalizes the opening to `/**` on its own line (or keeps single-line if short)
-/// - Aligns continuation lines with ` * `
-/// - Reflows `@param`, `@return`, `@throws`/`@exception` tag descriptions
-/// - Preserves `{@code ...}` and `<pre>...</pre>` blocks verbatim
-/// - Wraps lines to fit within `config.line_width`
-#[allow(clippy::similar_names)]
-fn gen_javadoc(
-    node: tree_sitter::Node,
-    context: &FormattingContext,
-    config: &Configuration,
-) -> PrintItems {
-    let text = &context.source[node.start_byte()..node.end_byte()];
-
-    // Extract the inner content (strip /** and */)
-    let inner = extract_javadoc_content(text);
-
-    // Parse into structured segments
-    let segments = parse_javadoc_segments(&inner);
-
-    // Calculate available width for content (account for " * " prefix)
-    let indent_chars = context.indent_level() * (config.indent_width as usize);
-    let prefix_width = indent_chars + 3; // " * " is 3 chars
-    let max_content_width = if (config.line_width as usize) > prefix_width + 10 {
-        (config.line_width as usize) - prefix_width
-    } else {
-        60 // reasonable fallback
-    };
-
-    let mut items = PrintItems::new();
-
-    // Opening
-    items.push_str("/**");
-
-    for segment in &segments {
-        match segment {
-            JavadocSegment::Text(text) => {
-                let wrapped = wrap_text(text, max_content_width);
-                for line in &wrapped {
-                    items.newline();
-                    if line.is_empty() {
-                        items.push_str(" *");
-                    } else {
-                        items.push_str(&format!(" * {line}"));
+/// A `@formatter:off` comment starts a region that runs until the next
+/// `@formatter:on` comment, or to the end of the file if there is none. A
+/// `// dprint-ignore` comment on its own line covers only the statement,
+/// member or other node that follows it, skipping any comments in between.
+pub fn suppressed_ranges(root: tree_sitter::Node, source: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut off_start = None;
+    let mut cursor = root.walk();
+
+    loop {
+        let node = cursor.node();
+        if node.kind() == "line_comment" || node.kind() == "block_comment" {
+            let text = &source[node.start_byte()..node.end_byte()];
+            let body = text
+                .trim_start_matches('/')
+                .trim_start_matches('*')
+                .trim_end_matches('/')
+                .trim_end_matches('*')
+                .trim();
+            match body {
+                "@formatter:off" if off_start.is_none() => off_start = Some(node.end_byte()),
+                "@formatter:on" => {
+                    if let Some(start) = off_start.take() {
+                        ranges.push(start..node.start_byte());
                     }
                 }
-            }
-            JavadocSegment::Tag { name, args, desc } => {
-                items.newline();
-                let tag_line = format_tag_line(name, args.as_ref(), desc);
-                let wrapped = wrap_text(&tag_line, max_content_width);
-                for (i, line) in wrapped.iter().enumerate() {
-                    if i > 0 {
-                        items.newline();
+                // Only a comment on its own line applies to the node below it
+                "dprint-ignore" if !is_trailing_comment(node) => {
+                    let mut next = node.next_sibling();
+                    while let Some(sibling) = next.filter(tree_sitter::Node::is_extra) {
+                        next = sibling.next_sibling();
                     }
-                    if line.is_empty() {
-                        items.push_str(" *");
-                    } else {
-                        items.push_str(&format!(" * {line}"));
+                    if let Some(sibling) = next {
+                        ranges.push(sibling.start_byte()..sibling.end_byte());
                     }
                 }
-            }
-            JavadocSegment::PreBlock(content) => {
-                items.newline();
-                items.push_str(" * <pre>");
-                for line in content.split('\n') {
-                    items.newline();
-                    let line = line.strip_suffix('\r').unwrap_or(line);
-                    if line.is_empty() {
-                        items.push_str(" *");
-                    } else {
-                        items.push_str(&format!(" * {line}"));
-                    }
-                }
-                items.newline();
-                items.push_str(" * </pre>");
-            }
-            JavadocSegment::BlankLine => {
-                items.newline();
-                items.push_str(" *");
+                _ => {}
             }
         }
-    }
-
-    // Closing
-    items.newline();
-    items.push_str(" */");
 
-    items
-}
-
-/// Extract the inner text content from a Javadoc comment.
-///
-/// Strips the `/**` prefix and `*/` suffix, and normalizes each
-/// continuation line by removing the leading ` * ` prefix.
-fn extract_javadoc_content(text: &str) -> String {
-    // Remove /** and */
-    let inner = text
-        .strip_prefix("/**")
-        .unwrap_or(text)
-        .strip_suffix("*/")
-        .unwrap_or(text);
-
-    let mut lines = Vec::new();
-    for (i, line) in inner.split('\n').enumerate() {
-        let line = line.strip_suffix('\r').unwrap_or(line);
-        if i == 0 {
-            // First line (after /**) — just trim whitespace
-            let trimmed = line.trim();
-            if !trimmed.is_empty() {
-                lines.push(trimmed.to_string());
-            }
-        } else {
-            // Continuation lines: strip leading whitespace and optional `*`
-            let trimmed = line.trim_start();
-            if let Some(rest) = trimmed.strip_prefix('*') {
-                // Strip one leading space after * if present
-                let rest = rest.strip_prefix(' ').unwrap_or(rest);
-                lines.push(rest.to_string());
-            } else {
-                lines.push(trimmed.to_string());
-            }
-        }
-    }
-
-    // Remove trailing empty lines
-    while lines.last().is_some_and(|l| l.trim().is_empty()) {
-        lines.pop();
-    }
-
-    lines.join("\n")
-}
-
-/// Represents a parsed segment of a Javadoc comment.
-#[derive(Debug)]
-enum JavadocSegment {
-    /// Free-form description text.
-    Text(String),
-    /// A Javadoc tag like `@param`, `@return`, `@throws`.
-    Tag {
-        name: String,
-        args: Option<String>,
-        desc: String,
-    },
-    /// A `<pre>...</pre>` block preserved verbatim.
-    PreBlock(String),
-    /// A blank line separator.
-    BlankLine,
-}
-
-/// Parse Javadoc inner content into structured segments.
-fn parse_javadoc_segments(content: &str) -> Vec<JavadocSegment> {
-    let mut segments = Vec::new();
-    let lines: Vec<&str> = content.split('\n').collect();
-    let mut i = 0;
-
-    while i < lines.len() {
-        let line = lines[i];
-        let trimmed = line.trim();
-
-        // Blank line
-        if trimmed.is_empty() {
-            segments.push(JavadocSegment::BlankLine);
-            i += 1;
+        if cursor.goto_first_child() || cursor.goto_next_sibling() {
             continue;
         }
-
-        // <pre> block
-        if trimmed.starts_with("<pre>")
-            || trimmed.starts_with("{@code") && trimmed.contains("<pre>")
-        {
-            let mut pre_content = Vec::new();
-            // Find the content after <pre>
-            let after_pre = if let Some(pos) = trimmed.find("<pre>") {
-                &trimmed[pos + 5..]
-            } else {
-                ""
-            };
-            if !after_pre.is_empty() && !after_pre.trim().is_empty() {
-                pre_content.push(after_pre.to_string());
-            }
-            i += 1;
-            while i < lines.len() {
-                let l = lines[i].trim();
-                if l.contains("</pre>") {
-                    // Get content before </pre>
-                    if let Some(pos) = l.find("</pre>") {
-                        let before = &l[..pos];
-                        if !before.is_empty() {
-                            pre_content.push(before.to_string());
-                        }
-                    }
-                    i += 1;
-                    break;
+        loop {
+            if !cursor.goto_parent() {
+                if let Some(start) = off_start {
+                    ranges.push(start..source.len());
                 }
-                pre_content.push(lines[i].to_string());
-                i += 1;
+                return ranges;
             }
-            segments.push(JavadocSegment::PreBlock(pre_content.join("\n")));
-            continue;
-        }
-
-        // Tag line
-        if trimmed.starts_with('@') {
-            let (tag_name, tag_args, tag_desc) = parse_tag_line(trimmed);
-            // Collect continuation lines (non-blank, non-tag, non-pre lines)
-            let mut full_desc = tag_desc;
-            i += 1;
-            while i < lines.len() {
-                let next = lines[i].trim();
-                if next.is_empty() || next.starts_with('@') || next.starts_with("<pre>") {
-                    break;
-                }
-                full_desc.push(' ');
-                full_desc.push_str(next);
-                i += 1;
-            }
-            segments.push(JavadocSegment::Tag {
-                name: tag_name,
-                args: tag_args,
-                desc: full_desc,
-            });
-            continue;
-        }
-
-        // Regular text — collect consecutive non-blank, non-tag, non-pre lines
-        let mut text_parts = Vec::new();
-        while i < lines.len() {
-            let l = lines[i].trim();
-            if l.is_empty() || l.starts_with('@') || l.starts_with("<pre>") {
+            if cursor.goto_next_sibling() {
                 break;
             }
-            text_parts.push(l.to_string());
-            i += 1;
-        }
-        segments.push(JavadocSegment::Text(text_parts.join(" ")));
-    }
-
-    segments
-}
-
-/// Parse a single Javadoc tag line into (name, `optional_arg`, description).
-///
-/// Examples:
-/// - `@param name the name of the thing` -> ("@param", Some("name"), "the name of the thing")
-/// - `@return the result` -> ("@return", None, "the result")
-/// - `@throws IOException if I/O fails` -> ("@throws", Some("IOException"), "if I/O fails")
-fn parse_tag_line(line: &str) -> (String, Option<String>, String) {
-    let parts: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
-    let tag_name = parts[0].to_string();
-    let rest = if parts.len() > 1 { parts[1].trim() } else { "" };
-
-    // Tags that take an argument (parameter name, exception type)
-    match tag_name.as_str() {
-        "@param" | "@throws" | "@exception" | "@serialField" => {
-            let rest_parts: Vec<&str> = rest.splitn(2, char::is_whitespace).collect();
-            let arg = rest_parts[0].to_string();
-            let desc = if rest_parts.len() > 1 {
-                rest_parts[1].trim().to_string()
-            } else {
-                String::new()
-            };
-            (tag_name, Some(arg), desc)
         }
-        _ => (tag_name, None, rest.to_string()),
-    }
-}
-
-/// Format a tag line for output.
-fn format_tag_line(name: &str, args: Option<&String>, desc: &str) -> String {
-    let mut result = name.to_string();
-    if let Some(arg) = args {
-        result.push(' ');
-        result.push_str(arg);
     }
-    if !desc.is_empty() {
-        result.push(' ');
-        result.push_str(desc);
-    }
-    result
 }
 
-/// Word-wrap text to the given maximum width.
+/// Format a comment on a line of its own in a body or block, keeping the
+/// blank lines above it from the source.
 ///
-/// Preserves inline `{@code ...}` constructs as atomic units.
-/// Returns a vector of lines.
-fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
-    if text.is_empty() {
-        return vec![String::new()];
-    }
-
-    let words = split_preserving_inline_tags(text);
-    let mut lines = Vec::new();
-    let mut current_line = String::new();
-
-    for word in &words {
-        if current_line.is_empty() {
-            current_line.clone_from(word);
-        } else if current_line.len() + 1 + word.len() <= max_width {
-            current_line.push(' ');
-            current_line.push_str(word);
-        } else {
-            lines.push(std::mem::take(&mut current_line));
-            current_line.clone_from(word);
-        }
-    }
-
-    if !current_line.is_empty() {
-        lines.push(current_line);
-    }
-
-    if lines.is_empty() {
-        vec![String::new()]
-    } else {
-        lines
-    }
-}
-
-/// Split text into words, preserving `{@code ...}` and similar inline tags
-/// as single tokens.
-fn split_preserving_inline_tags(text: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
-    let chars: Vec<char> = text.chars().collect();
-    let mut i = 0;
-    let mut current_word = String::new();
-
-    while i < chars.len() {
-        if chars[i] == '{' && i + 1 < chars.len() && chars[i + 1] == '@' {
-            // Start of inline tag — collect until matching '}'
-            if !current_word.is_empty() {
-                // Flush the word accumulated before the tag
-                for w in current_word.split_whitespace() {
-                    tokens.push(w.to_string());
-                }
-                current_word.clear();
-            }
-            let mut tag = String::new();
-            let mut depth = 0;
-            while i < chars.len() {
-                tag.push(chars[i]);
-                if chars[i] == '{' {
-                    depth += 1;
-                } else if chars[i] == '}' {
-                    depth -= 1;
-                    if depth == 0 {
-                        i += 1;
-                        break;
-                    }
-                }
-                i += 1;
-            }
-            tokens.push(tag);
-        } else {
-            current_word.push(chars[i]);
-            i += 1;
-        }
-    }
-
-    if !current_word.is_empty() {
-        for w in current_word.split_whitespace() {
-            tokens.push(w.to_string());
-        }
-    }
-
-    tokens
+/// No newline is started after a line comment, which ends with one.
+pub fn gen_own_line_comment<'a>(
+    comment: tree_sitter::Node<'a>,
+    prev_was_line_comment: bool,
+    prev_end_row: Option<usize>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    if !prev_was_line_comment {
+        items.newline();
+    }
+    items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+        context,
+        prev_end_row,
+        comment.start_position().row,
+    )));
+    items.extend(gen_node(comment, context));
+    items
 }
 
 /// Determine if a comment is a trailing comment (on the same line as preceding code).
@@ -521,27 +304,63 @@ pub fn is_trailing_comment(node: tree_sitter::Node) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::configuration::{
+        AnnotationPlacement, ArgumentPacking, BraceStyle, Configuration, MethodChainWrapStyle,
+        StaticImportPosition,
+    };
+    use crate::test_support::parse;
     use dprint_core::configuration::NewLineKind;
+    use std::collections::BTreeMap;
 
     fn test_config() -> Configuration {
         Configuration {
             line_width: 80,
             indent_width: 4,
             use_tabs: false,
+            tab_width: 4,
             new_line_kind: NewLineKind::LineFeed,
             format_javadoc: true,
             method_chain_threshold: 80,
+            method_chain_min_calls_to_break: 0,
+            break_stream_pipelines: false,
+            method_chain_wrap_style: MethodChainWrapStyle::ContinuationIndent,
             inline_lambdas: true,
+            argument_packing: ArgumentPacking::Greedy,
+            brace_style: BraceStyle::SameLine,
+            else_on_new_line: false,
+            catch_on_new_line: false,
+            finally_on_new_line: false,
+            while_on_new_line: false,
+            enum_constant_annotations: AnnotationPlacement::OwnLine,
+            align_switch_arrows: false,
+            import_order: Vec::new(),
+            static_import_position: StaticImportPosition::Top,
+            sort_imports: true,
+            remove_unused_imports: false,
+            allow_single_line_if: false,
+            keep_simple_methods_on_one_line: false,
+            keep_simple_anonymous_classes_on_one_line: false,
+            tight_double_brace_initialization: false,
+            annotation_policies: BTreeMap::new(),
+            format_chains: true,
+            wrap_field_access_chains: false,
+            chain_terminal_calls: Vec::new(),
+            format_imports: true,
+            format_strings_in_annotations: true,
+            format_with_syntax_errors: false,
+            verify_tokens: false,
+            strict: false,
+            max_changed_lines_percent: 100,
+            max_blank_lines: 1,
+            blank_lines_after_package: 1,
+            blank_lines_before_class_end: 0,
+            blank_lines_between_members: 1,
+            debug_wrap_reasons: false,
         }
     }
 
     fn parse_and_get_comment(source: &str) -> (tree_sitter::Tree, String) {
-        let mut parser = tree_sitter::Parser::new();
-        parser
-            .set_language(&tree_sitter_java::LANGUAGE.into())
-            .unwrap();
-        let tree = parser.parse(source, None).unwrap();
-        (tree, source.to_string())
+        (parse(source), source.to_string())
     }
 
     #[test]
@@ -599,60 +418,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_extract_javadoc_content() {
-        let text = "/**\n * Hello world.\n * @param name the name\n */";
-        let content = extract_javadoc_content(text);
-        assert!(content.contains("Hello world."));
-        assert!(content.contains("@param name the name"));
-    }
-
-    #[test]
-    fn test_parse_tag_line_param() {
-        let (name, args, desc) = parse_tag_line("@param name the name of the thing");
-        assert_eq!(name, "@param");
-        assert_eq!(args, Some("name".to_string()));
-        assert_eq!(desc, "the name of the thing");
-    }
-
-    #[test]
-    fn test_parse_tag_line_return() {
-        let (name, args, desc) = parse_tag_line("@return the result");
-        assert_eq!(name, "@return");
-        assert_eq!(args, None);
-        assert_eq!(desc, "the result");
-    }
-
-    #[test]
-    fn test_wrap_text_short() {
-        let lines = wrap_text("hello world", 80);
-        assert_eq!(lines, vec!["hello world"]);
-    }
-
-    #[test]
-    fn test_wrap_text_long() {
-        let long = "this is a really long line that should definitely be wrapped because it exceeds the maximum width";
-        let lines = wrap_text(long, 40);
-        assert!(lines.len() > 1);
-        for line in &lines {
-            assert!(line.len() <= 40 || line.split_whitespace().count() == 1);
-        }
-    }
-
-    #[test]
-    fn test_wrap_preserves_inline_code() {
-        let text = "See {@code SomeClass} for details";
-        let lines = wrap_text(text, 80);
-        assert_eq!(lines.len(), 1);
-        assert!(lines[0].contains("{@code SomeClass}"));
-    }
-
-    #[test]
-    fn test_split_preserving_inline_tags() {
-        let tokens = split_preserving_inline_tags("See {@code SomeClass} for details");
-        assert_eq!(tokens, vec!["See", "{@code SomeClass}", "for", "details"]);
-    }
-
     #[test]
     fn test_is_trailing_comment() {
         let source = "class A {} // trailing\n";
@@ -678,4 +443,20 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_suppressed_ranges() {
+        let source = "class A {\n    // @formatter:off\n    int  a;\n    // @formatter:on\n    // dprint-ignore\n    int  b;\n    /* @formatter:off */\n    int  c;\n}\n";
+        let (tree, src) = parse_and_get_comment(source);
+        let ranges = suppressed_ranges(tree.root_node(), &src);
+        let texts: Vec<_> = ranges.iter().map(|r| src[r.clone()].trim()).collect();
+        assert_eq!(texts, vec!["int  a;", "int  b;", "int  c;\n}"]);
+    }
+
+    #[test]
+    fn test_trailing_dprint_ignore_is_not_suppression() {
+        let source = "class A {\n    int  a; // dprint-ignore\n    int  b;\n}\n";
+        let (tree, src) = parse_and_get_comment(source);
+        assert!(suppressed_ranges(tree.root_node(), &src).is_empty());
+    }
 }
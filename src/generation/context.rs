@@ -1,4 +1,24 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::ops::Range;
+use std::rc::Rc;
+
+use dprint_core::formatting::Condition;
+use dprint_core::formatting::ConditionProperties;
+use dprint_core::formatting::PrintItems;
+
+use crate::configuration::BraceStyle;
 use crate::configuration::Configuration;
+use crate::source_map::SourceMap;
+
+use super::comment_map::CommentMap;
+use super::helpers::PrintItemsExt;
+use super::member_cache::MemberCache;
+
+/// Number of indent levels a wrapped continuation line adds (PJF's `+8` at indent width 4).
+pub const CONTINUATION_INDENT_LEVELS: usize = 2;
 
 /// Formatting context that tracks state during CST traversal.
 ///
@@ -21,7 +41,7 @@ pub struct FormattingContext<'a> {
     parent_stack: Vec<&'static str>,
 
     /// Additional continuation indent levels (for chain wrapping).
-    /// When a method chain wraps, we add +2 levels of continuation indent
+    /// When a method chain wraps, we add `CONTINUATION_INDENT_LEVELS` of continuation indent
     /// that don't affect the base `indent_level` but need to be accounted for
     /// in width calculations for nested argument lists.
     continuation_indent_levels: usize,
@@ -44,6 +64,70 @@ pub struct FormattingContext<'a> {
     /// Indicates the current variable declarator starts on a continuation line
     /// (for example, after a wrapped generic type).
     declarator_on_new_line: bool,
+
+    /// Set while generating the segments of a chain that wrapped, each on a
+    /// continuation line of its own. Width estimates inside a segment then
+    /// stop at the segment instead of following the source's line breaks,
+    /// which differ between passes.
+    chain_segment_on_new_line: bool,
+
+    /// Number of live scope guards, used to check they are released innermost-first.
+    guard_depth: usize,
+
+    /// Byte ranges excluded from formatting by `@formatter:off`/`on` or
+    /// `dprint-ignore` comments.
+    suppressed: Vec<Range<usize>>,
+
+    /// Where each comment of the tree belongs.
+    comment_map: Rc<CommentMap<'a>>,
+
+    /// Work done by the wrap estimators, in bytes of source measured and
+    /// nodes walked.
+    work: Cell<usize>,
+
+    /// The `work` after which the member being generated is kept as written,
+    /// while one is.
+    work_limit: Option<usize>,
+
+    /// Byte ranges of the members kept as written for exceeding their work budget.
+    skipped_members: Vec<Range<usize>>,
+
+    /// Nodes with children that no handler formats, printed from their source text.
+    unhandled_nodes: Vec<tree_sitter::Node<'a>>,
+
+    /// Output-to-input line mapping filled in while printing, when requested.
+    source_map: Option<Rc<RefCell<SourceMap>>>,
+
+    /// Output of top-level type members kept between incremental passes.
+    member_cache: Option<Rc<RefCell<MemberCache>>>,
+}
+
+/// Boolean context flags that can be set for the duration of a scope with
+/// [`FormattingContext::with_flag`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextFlag {
+    /// The enclosing assignment or declarator wrapped at `=`, so the value
+    /// starts on a continuation line without the LHS prefix.
+    AssignmentWrapped,
+    /// The current variable declarator starts on a continuation line.
+    DeclaratorOnNewLine,
+    /// The enclosing chain wrapped before the current segment, so nothing
+    /// before the segment's `.` shares its line.
+    ChainSegmentOnNewLine,
+}
+
+/// What an enclosing expression or declaration told the code under it,
+/// set aside by [`FormattingContext::member_scope`] while a member is
+/// generated.
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct MemberState {
+    assignment_wrapped: bool,
+    declarator_on_new_line: bool,
+    chain_segment_on_new_line: bool,
+    override_prefix_width: Option<usize>,
+    track_type_args_wrapping: bool,
+    type_args_wrapped: bool,
 }
 
 impl<'a> FormattingContext<'a> {
@@ -61,6 +145,16 @@ impl<'a> FormattingContext<'a> {
             track_type_args_wrapping: false,
             type_args_wrapped: false,
             declarator_on_new_line: false,
+            chain_segment_on_new_line: false,
+            guard_depth: 0,
+            suppressed: Vec::new(),
+            comment_map: Rc::default(),
+            work: Cell::new(0),
+            work_limit: None,
+            skipped_members: Vec::new(),
+            unhandled_nodes: Vec::new(),
+            source_map: None,
+            member_cache: None,
         }
     }
 
@@ -71,12 +165,12 @@ impl<'a> FormattingContext<'a> {
     }
 
     /// Increase the indentation level by one.
-    pub fn indent(&mut self) {
+    fn indent(&mut self) {
         self.indent_level += 1;
     }
 
     /// Decrease the indentation level by one.
-    pub fn dedent(&mut self) {
+    fn dedent(&mut self) {
         if self.indent_level > 0 {
             self.indent_level -= 1;
         }
@@ -98,35 +192,141 @@ impl<'a> FormattingContext<'a> {
         self.parent_stack.last().copied()
     }
 
+    /// Number of nodes on the parent stack.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.parent_stack.len()
+    }
+
     /// Check if the given node kind is in the parent stack.
     #[must_use]
     pub fn has_ancestor(&self, kind: &'static str) -> bool {
         self.parent_stack.contains(&kind)
     }
 
-    /// Add continuation indent levels (for wrapped chains).
-    pub fn add_continuation_indent(&mut self, levels: usize) {
-        self.continuation_indent_levels += levels;
+    /// Indent by `levels` until the returned guard is dropped.
+    ///
+    /// The guard derefs to the context, so generate the indented scope through it.
+    pub fn indented(&mut self, levels: usize) -> IndentGuard<'_, 'a> {
+        for _ in 0..levels {
+            self.indent();
+        }
+        let depth = self.enter_guard();
+        IndentGuard {
+            context: self,
+            levels,
+            depth,
+        }
     }
 
-    /// Remove continuation indent levels (for wrapped chains).
-    pub fn remove_continuation_indent(&mut self, levels: usize) {
-        if self.continuation_indent_levels >= levels {
-            self.continuation_indent_levels -= levels;
+    /// Add one continuation indent (`CONTINUATION_INDENT_LEVELS`) until the
+    /// returned guard is dropped.
+    ///
+    /// Continuation indent doesn't change the base indent level but is
+    /// counted by `effective_indent_level` for width calculations in nested
+    /// argument lists.
+    pub fn with_continuation_indent(&mut self) -> ContinuationGuard<'_, 'a> {
+        self.continuation_indent_levels += CONTINUATION_INDENT_LEVELS;
+        let depth = self.enter_guard();
+        ContinuationGuard {
+            context: self,
+            levels: CONTINUATION_INDENT_LEVELS,
+            depth,
         }
     }
 
+    /// Set `flag` to `value` until the returned guard is dropped, then restore
+    /// the previous value.
+    pub fn with_flag(&mut self, flag: ContextFlag, value: bool) -> FlagGuard<'_, 'a> {
+        let previous = std::mem::replace(self.flag_mut(flag), value);
+        let depth = self.enter_guard();
+        FlagGuard {
+            context: self,
+            flag,
+            previous,
+            depth,
+        }
+    }
+
+    fn flag_mut(&mut self, flag: ContextFlag) -> &mut bool {
+        match flag {
+            ContextFlag::AssignmentWrapped => &mut self.assignment_wrapped,
+            ContextFlag::DeclaratorOnNewLine => &mut self.declarator_on_new_line,
+            ContextFlag::ChainSegmentOnNewLine => &mut self.chain_segment_on_new_line,
+        }
+    }
+
+    fn enter_guard(&mut self) -> usize {
+        self.guard_depth += 1;
+        self.guard_depth
+    }
+
+    fn exit_guard(&mut self, depth: usize) {
+        debug_assert_eq!(
+            self.guard_depth, depth,
+            "formatting context guards must be dropped innermost-first"
+        );
+        self.guard_depth -= 1;
+    }
+
+    /// Width in columns of one indent level: `tabWidth` when indenting with
+    /// tabs, `indentWidth` otherwise.
+    #[must_use]
+    pub fn indent_width(&self) -> usize {
+        self.config.indent_columns() as usize
+    }
+
+    /// Width in columns of one continuation indent under the configured indent width.
+    #[must_use]
+    pub fn continuation_width(&self) -> usize {
+        CONTINUATION_INDENT_LEVELS * self.indent_width()
+    }
+
+    /// Separator before an opening `{` of a type, method or statement body.
+    ///
+    /// A space for `braceStyle: sameLine`, a newline for `nextLine`.
+    #[must_use]
+    pub fn brace_separator(&self) -> PrintItems {
+        let mut items = PrintItems::new();
+        match self.config.brace_style {
+            BraceStyle::SameLine => items.space(),
+            BraceStyle::NextLine => items.newline(),
+        }
+        items
+    }
+
+    /// Separator between a closing `}` and a following `else`, `catch`,
+    /// `finally` or `while`: a newline when that keyword's `...OnNewLine`
+    /// option, passed as `on_new_line`, is set, otherwise a space.
+    #[must_use]
+    pub fn keyword_separator(&self, on_new_line: bool) -> PrintItems {
+        let mut items = PrintItems::new();
+        if on_new_line {
+            items.newline();
+        } else {
+            items.space();
+        }
+        items
+    }
+
+    /// Marker explaining a wrap decision, emitted only when `debug_wrap_reasons` is on.
+    ///
+    /// Produces ` /* wrapped: <construct> <detail> */`; `detail` is only evaluated
+    /// when the marker is emitted.
+    pub fn wrap_reason(&self, construct: &str, detail: impl FnOnce() -> String) -> PrintItems {
+        let mut items = PrintItems::new();
+        if self.config.debug_wrap_reasons {
+            items.push_str(&format!(" /* wrapped: {construct} {} */", detail()));
+        }
+        items
+    }
+
     /// Get the effective indent level including continuation indent.
     #[must_use]
     pub fn effective_indent_level(&self) -> usize {
         self.indent_level + self.continuation_indent_levels
     }
 
-    /// Set the `assignment_wrapped` flag.
-    pub fn set_assignment_wrapped(&mut self, wrapped: bool) {
-        self.assignment_wrapped = wrapped;
-    }
-
     /// Check if the current chain is inside an assignment that already wrapped at '='.
     #[must_use]
     pub fn is_assignment_wrapped(&self) -> bool {
@@ -162,9 +362,141 @@ impl<'a> FormattingContext<'a> {
         std::mem::take(&mut self.type_args_wrapped)
     }
 
-    /// Set whether the current variable declarator starts on a continuation line.
-    pub fn set_declarator_on_new_line(&mut self, value: bool) {
-        self.declarator_on_new_line = value;
+    /// Set aside the state enclosing code set for the code under it until
+    /// the returned guard is dropped, so a member, like a method of an
+    /// anonymous class, is generated from its own source alone. Indentation
+    /// is kept, and whatever the member leaves behind is dropped with the
+    /// guard.
+    pub(crate) fn member_scope(&mut self) -> MemberGuard<'_, 'a> {
+        let state = MemberState {
+            assignment_wrapped: std::mem::take(&mut self.assignment_wrapped),
+            declarator_on_new_line: std::mem::take(&mut self.declarator_on_new_line),
+            chain_segment_on_new_line: std::mem::take(&mut self.chain_segment_on_new_line),
+            override_prefix_width: self.override_prefix_width.take(),
+            track_type_args_wrapping: std::mem::take(&mut self.track_type_args_wrapping),
+            type_args_wrapped: std::mem::take(&mut self.type_args_wrapped),
+        };
+        let depth = self.enter_guard();
+        MemberGuard {
+            context: self,
+            state,
+            depth,
+        }
+    }
+
+    /// Set the byte ranges that are excluded from formatting.
+    pub fn set_suppressed_ranges(&mut self, ranges: Vec<Range<usize>>) {
+        self.suppressed = ranges;
+    }
+
+    /// Check whether `node` lies entirely inside a region excluded from formatting.
+    #[must_use]
+    pub fn is_suppressed(&self, node: tree_sitter::Node) -> bool {
+        self.suppressed
+            .iter()
+            .any(|range| range.start <= node.start_byte() && node.end_byte() <= range.end)
+    }
+
+    /// Check whether any part of `node` lies inside a region excluded from
+    /// formatting.
+    #[must_use]
+    pub fn overlaps_suppressed(&self, node: tree_sitter::Node) -> bool {
+        self.suppressed
+            .iter()
+            .any(|range| range.start < node.end_byte() && node.start_byte() < range.end)
+    }
+
+    /// Set where each comment of the tree belongs.
+    pub fn set_comment_map(&mut self, comment_map: CommentMap<'a>) {
+        self.comment_map = Rc::new(comment_map);
+    }
+
+    /// Where each comment of the tree belongs, shared so that it can be read
+    /// while generating.
+    #[must_use]
+    pub fn comment_map(&self) -> Rc<CommentMap<'a>> {
+        Rc::clone(&self.comment_map)
+    }
+
+    /// Count `units` of work toward the budget of the member being generated.
+    pub(crate) fn charge_work(&self, units: usize) {
+        self.work.set(self.work.get().saturating_add(units));
+    }
+
+    /// Check whether the member being generated has used up its work budget,
+    /// so estimators and handlers can stop early.
+    #[must_use]
+    pub(crate) fn is_over_work_budget(&self) -> bool {
+        self.work_limit.is_some_and(|limit| self.work.get() > limit)
+    }
+
+    /// Start a work budget of `budget` units until the returned guard is
+    /// dropped, unless one is already running: a member inside another counts
+    /// toward the budget of the outermost.
+    pub(crate) fn with_work_budget(&mut self, budget: usize) -> WorkBudgetGuard<'_, 'a> {
+        let started = self.work_limit.is_none();
+        if started {
+            self.work_limit = Some(self.work.get().saturating_add(budget));
+        }
+        let depth = self.enter_guard();
+        WorkBudgetGuard {
+            context: self,
+            started,
+            depth,
+        }
+    }
+
+    /// Note that the member at `range` was kept as written for exceeding its
+    /// work budget.
+    pub(crate) fn record_skipped_member(&mut self, range: Range<usize>) {
+        self.skipped_members.push(range);
+    }
+
+    /// Byte ranges of the members kept as written for exceeding their work budget.
+    #[must_use]
+    pub(crate) fn skipped_members(&self) -> &[Range<usize>] {
+        &self.skipped_members
+    }
+
+    /// Note that no handler formats `node`, so it was printed from its source text.
+    pub(crate) fn record_unhandled_node(&mut self, node: tree_sitter::Node<'a>) {
+        self.unhandled_nodes.push(node);
+    }
+
+    /// Nodes with children that no handler formats, in the order they were generated.
+    #[must_use]
+    pub(crate) fn unhandled_nodes(&self) -> &[tree_sitter::Node<'a>] {
+        &self.unhandled_nodes
+    }
+
+    /// Reuse and record the output of top-level type members in `member_cache`.
+    pub(crate) fn set_member_cache(&mut self, member_cache: Rc<RefCell<MemberCache>>) {
+        self.member_cache = Some(member_cache);
+    }
+
+    /// The member cache, when formatting incrementally.
+    pub(crate) fn member_cache(&self) -> Option<&Rc<RefCell<MemberCache>>> {
+        self.member_cache.as_ref()
+    }
+
+    /// Record into `source_map` which output line each node is printed on.
+    pub fn set_source_map(&mut self, source_map: Rc<RefCell<SourceMap>>) {
+        self.source_map = Some(source_map);
+    }
+
+    /// Wrap the items generated for `node` with markers that record the
+    /// output lines its start and end are printed on, if a source map was
+    /// requested.
+    #[must_use]
+    pub fn map_source(&self, node: tree_sitter::Node, items: PrintItems) -> PrintItems {
+        let Some(source_map) = &self.source_map else {
+            return items;
+        };
+        let mut mapped = PrintItems::new();
+        mapped.push_condition(source_marker(source_map, node.start_byte()));
+        mapped.extend(items);
+        mapped.push_condition(source_marker(source_map, node.end_byte()));
+        mapped
     }
 
     /// Check whether the current variable declarator starts on a continuation line.
@@ -172,23 +504,205 @@ impl<'a> FormattingContext<'a> {
     pub fn is_declarator_on_new_line(&self) -> bool {
         self.declarator_on_new_line
     }
+
+    /// Check whether the current chain segment starts a continuation line.
+    #[must_use]
+    pub fn is_chain_segment_on_new_line(&self) -> bool {
+        self.chain_segment_on_new_line
+    }
+}
+
+/// A print item that records input byte offset `byte` against the output
+/// line the printer is on when it reaches it. It prints nothing.
+fn source_marker(source_map: &Rc<RefCell<SourceMap>>, byte: usize) -> Condition {
+    let source_map = Rc::clone(source_map);
+    Condition::new(
+        "sourceMapMarker",
+        ConditionProperties {
+            condition: Rc::new(move |context| {
+                source_map
+                    .borrow_mut()
+                    .record(context.writer_info.line_number as usize, byte);
+                Some(false)
+            }),
+            true_path: None,
+            false_path: None,
+        },
+    )
+}
+
+/// Scope guard returned by [`FormattingContext::indented`].
+pub struct IndentGuard<'c, 'a> {
+    context: &'c mut FormattingContext<'a>,
+    levels: usize,
+    depth: usize,
+}
+
+impl Drop for IndentGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.context.exit_guard(self.depth);
+        for _ in 0..self.levels {
+            self.context.dedent();
+        }
+    }
+}
+
+/// Scope guard returned by [`FormattingContext::with_continuation_indent`].
+pub struct ContinuationGuard<'c, 'a> {
+    context: &'c mut FormattingContext<'a>,
+    levels: usize,
+    depth: usize,
+}
+
+impl Drop for ContinuationGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.context.exit_guard(self.depth);
+        debug_assert!(self.context.continuation_indent_levels >= self.levels);
+        self.context.continuation_indent_levels -= self.levels;
+    }
+}
+
+/// Scope guard returned by [`FormattingContext::with_flag`].
+pub struct FlagGuard<'c, 'a> {
+    context: &'c mut FormattingContext<'a>,
+    flag: ContextFlag,
+    previous: bool,
+    depth: usize,
+}
+
+impl Drop for FlagGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.context.exit_guard(self.depth);
+        *self.context.flag_mut(self.flag) = self.previous;
+    }
+}
+
+/// Scope guard returned by [`FormattingContext::member_scope`].
+pub(crate) struct MemberGuard<'c, 'a> {
+    context: &'c mut FormattingContext<'a>,
+    state: MemberState,
+    depth: usize,
+}
+
+impl Drop for MemberGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.context.exit_guard(self.depth);
+        let state = self.state;
+        self.context.assignment_wrapped = state.assignment_wrapped;
+        self.context.declarator_on_new_line = state.declarator_on_new_line;
+        self.context.chain_segment_on_new_line = state.chain_segment_on_new_line;
+        self.context.override_prefix_width = state.override_prefix_width;
+        self.context.track_type_args_wrapping = state.track_type_args_wrapping;
+        self.context.type_args_wrapped = state.type_args_wrapped;
+    }
 }
 
+/// Scope guard returned by [`FormattingContext::with_work_budget`].
+pub(crate) struct WorkBudgetGuard<'c, 'a> {
+    context: &'c mut FormattingContext<'a>,
+    started: bool,
+    depth: usize,
+}
+
+impl WorkBudgetGuard<'_, '_> {
+    /// Whether the budget this guard started has run out. A guard inside a
+    /// running budget never reports it, so only the outermost member is
+    /// kept as written.
+    #[must_use]
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.started && self.context.is_over_work_budget()
+    }
+}
+
+impl Drop for WorkBudgetGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.context.exit_guard(self.depth);
+        if self.started {
+            self.context.work_limit = None;
+        }
+    }
+}
+
+macro_rules! impl_guard_deref {
+    ($($guard:ident),*) => {$(
+        impl<'a> Deref for $guard<'_, 'a> {
+            type Target = FormattingContext<'a>;
+
+            fn deref(&self) -> &Self::Target {
+                self.context
+            }
+        }
+
+        impl DerefMut for $guard<'_, '_> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                self.context
+            }
+        }
+    )*};
+}
+
+impl_guard_deref!(
+    IndentGuard,
+    ContinuationGuard,
+    FlagGuard,
+    MemberGuard,
+    WorkBudgetGuard
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configuration::Configuration;
+    use crate::configuration::{
+        AnnotationPlacement, ArgumentPacking, BraceStyle, Configuration, MethodChainWrapStyle,
+        StaticImportPosition,
+    };
     use dprint_core::configuration::NewLineKind;
+    use std::collections::BTreeMap;
 
     fn test_config() -> Configuration {
         Configuration {
             line_width: 120,
             indent_width: 4,
             use_tabs: false,
+            tab_width: 4,
             new_line_kind: NewLineKind::LineFeed,
             format_javadoc: false,
             method_chain_threshold: 80,
+            method_chain_min_calls_to_break: 0,
+            break_stream_pipelines: false,
+            method_chain_wrap_style: MethodChainWrapStyle::ContinuationIndent,
             inline_lambdas: true,
+            argument_packing: ArgumentPacking::Greedy,
+            brace_style: BraceStyle::SameLine,
+            else_on_new_line: false,
+            catch_on_new_line: false,
+            finally_on_new_line: false,
+            while_on_new_line: false,
+            enum_constant_annotations: AnnotationPlacement::OwnLine,
+            align_switch_arrows: false,
+            import_order: Vec::new(),
+            static_import_position: StaticImportPosition::Top,
+            sort_imports: true,
+            remove_unused_imports: false,
+            allow_single_line_if: false,
+            keep_simple_methods_on_one_line: false,
+            keep_simple_anonymous_classes_on_one_line: false,
+            tight_double_brace_initialization: false,
+            annotation_policies: BTreeMap::new(),
+            format_chains: true,
+            wrap_field_access_chains: false,
+            chain_terminal_calls: Vec::new(),
+            format_imports: true,
+            format_strings_in_annotations: true,
+            format_with_syntax_errors: false,
+            verify_tokens: false,
+            strict: false,
+            max_changed_lines_percent: 100,
+            max_blank_lines: 1,
+            blank_lines_after_package: 1,
+            blank_lines_before_class_end: 0,
+            blank_lines_between_members: 1,
+            debug_wrap_reasons: false,
         }
     }
 
@@ -241,4 +755,73 @@ mod tests {
         ctx.pop_parent();
         assert_eq!(ctx.parent(), None);
     }
+
+    #[test]
+    fn guards_restore_state_on_drop() {
+        let config = test_config();
+        let mut ctx = FormattingContext::new("", &config);
+        {
+            let mut assigned = ctx.with_flag(ContextFlag::AssignmentWrapped, true);
+            let mut continued = assigned.with_continuation_indent();
+            let indented = continued.indented(1);
+            assert!(indented.is_assignment_wrapped());
+            assert_eq!(
+                indented.effective_indent_level(),
+                1 + CONTINUATION_INDENT_LEVELS
+            );
+        }
+        assert!(!ctx.is_assignment_wrapped());
+        assert_eq!(ctx.effective_indent_level(), 0);
+        assert_eq!(ctx.guard_depth, 0);
+    }
+
+    #[test]
+    fn member_scope_sets_aside_enclosing_state() {
+        let config = test_config();
+        let mut ctx = FormattingContext::new("", &config);
+        let mut assigned = ctx.with_flag(ContextFlag::AssignmentWrapped, true);
+        assigned.set_override_prefix_width(Some(12));
+        {
+            let mut member = assigned.member_scope();
+            assert!(!member.is_assignment_wrapped());
+            assert_eq!(member.take_override_prefix_width(), None);
+
+            // Whatever the member leaves behind goes with it
+            member.set_override_prefix_width(Some(3));
+            member.start_type_args_wrap_tracking();
+        }
+        assert!(assigned.is_assignment_wrapped());
+        assert_eq!(assigned.take_override_prefix_width(), Some(12));
+        assert!(!assigned.finish_type_args_wrap_tracking());
+    }
+
+    #[test]
+    fn work_budget_guard_reports_only_the_budget_it_started() {
+        let config = test_config();
+        let mut ctx = FormattingContext::new("", &config);
+        {
+            let mut outer = ctx.with_work_budget(10);
+            {
+                let inner = outer.with_work_budget(1_000);
+                inner.charge_work(11);
+                assert!(inner.is_over_work_budget());
+                assert!(!inner.is_exceeded());
+            }
+            assert!(outer.is_exceeded());
+        }
+        assert!(!ctx.is_over_work_budget());
+        assert_eq!(ctx.guard_depth, 0);
+    }
+
+    #[test]
+    fn flag_guard_restores_previous_value() {
+        let config = test_config();
+        let mut ctx = FormattingContext::new("", &config);
+        let mut outer = ctx.with_flag(ContextFlag::DeclaratorOnNewLine, true);
+        {
+            let inner = outer.with_flag(ContextFlag::DeclaratorOnNewLine, false);
+            assert!(!inner.is_declarator_on_new_line());
+        }
+        assert!(outer.is_declarator_on_new_line());
+    }
 }
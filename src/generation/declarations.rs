@@ -1,10 +1,21 @@
+use std::borrow::Cow;
+
 use dprint_core::formatting::PrintItems;
 
+use crate::ast::{ClassDecl, MethodDecl};
+use crate::configuration::{AnnotationPlacement, ArgumentPacking, MemberPolicy};
+
+use super::blank_lines;
 use super::comments;
-use super::context::FormattingContext;
+use super::context::{CONTINUATION_INDENT_LEVELS, ContextFlag, FormattingContext};
 use super::expressions;
-use super::generate::gen_node;
-use super::helpers::{PrintItemsExt, collapse_whitespace_len, gen_node_text, is_type_node};
+use super::generate::{gen_generic_list, gen_node};
+use super::helpers::{
+    MeasureMode, PrintItemsExt, SignatureMeasurer, balance_lines, collapse_whitespace_len,
+    declaration_line_text, display_width, gen_if_fits, gen_node_text, gen_verbatim_text,
+    is_type_node,
+};
+use super::statements;
 
 /// Format a package declaration: `package com.example;`
 pub fn gen_package_declaration<'a>(
@@ -16,6 +27,11 @@ pub fn gen_package_declaration<'a>(
 
     for child in node.children(&mut cursor) {
         match child.kind() {
+            // Annotations of a `package-info.java` package, one per line
+            "annotation" | "marker_annotation" => {
+                items.extend(gen_node(child, context));
+                items.newline();
+            }
             "package" => items.push_str("package"),
             "scoped_identifier" | "identifier" => {
                 items.space();
@@ -64,99 +80,138 @@ pub fn gen_class_declaration<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
+    let Some(class) = ClassDecl::cast(node) else {
+        return gen_verbatim_text(node, context.source);
+    };
     let mut items = PrintItems::new();
-    let mut cursor = node.walk();
-    let mut need_space = false;
 
     // Pre-calculate: estimate class declaration line width to decide extends/implements wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
-    let decl_width = estimate_class_decl_width(node, context.source);
+    let indent_width = context.indent_level() * context.indent_width();
+    let decl_width = estimate_class_decl_width(class.node, context.source);
     // +2 for trailing " {" after the class declaration
     let needs_wrapping = indent_width + decl_width + 2 > context.config.line_width as usize;
 
-    // When both extends and implements are present, prefer to wrap only before implements.
-    // Only wrap before extends if implements is not present and extends alone is too long.
-    let mut cursor2 = node.walk();
-    let has_superclass = node
-        .children(&mut cursor2)
-        .any(|c| c.kind() == "superclass");
-    let has_super_interfaces = node
-        .children(&mut cursor2)
-        .any(|c| c.kind() == "super_interfaces");
-
-    let wrap_extends = needs_wrapping && has_superclass && !has_super_interfaces;
-    let wrap_implements = needs_wrapping && has_super_interfaces;
+    // When extends is followed by implements or permits, prefer to wrap only before those.
+    // Only wrap before extends if it is the last clause and the line is too long.
+    let wrap_extends = needs_wrapping && class.interfaces.is_none() && class.permits.is_none();
+    let wrap_implements = needs_wrapping;
+    // A permits clause with comments always wraps, one type per line.
+    let wrap_permits = class
+        .permits
+        .is_some_and(|permits| needs_wrapping || has_comments(permits));
+    let mut permits_ends_line = false;
+
+    // Only need space after modifiers if they didn't end with newline
+    let mut need_space = false;
+    if let Some(modifiers) = class.modifiers {
+        let (modifier_items, ends_with_newline) = gen_modifiers(modifiers, context);
+        items.extend(modifier_items);
+        need_space = !ends_with_newline;
+    }
+    if need_space {
+        items.space();
+    }
+    items.push_str("class ");
+    items.extend(gen_node_text(class.name, context.source));
+    if let Some(type_parameters) = class.type_parameters {
+        items.extend(gen_type_parameters(type_parameters, context));
+    }
 
-    for child in node.children(&mut cursor) {
-        match child.kind() {
-            "modifiers" => {
-                let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
-                items.extend(modifier_items);
-                // Only need space if modifiers didn't end with newline
-                need_space = !ends_with_newline;
-            }
-            "class" => {
-                if need_space {
-                    items.space();
-                }
-                items.push_str("class");
-                need_space = true;
-            }
-            "identifier" => {
-                if need_space {
-                    items.space();
-                }
-                items.extend(gen_node_text(child, context.source));
-                need_space = true;
-            }
-            "type_parameters" => {
-                items.extend(gen_type_parameters(child, context));
-                need_space = true;
-            }
-            "superclass" => {
-                if wrap_extends {
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    context.add_continuation_indent(2);
-                    items.extend(gen_superclass(child, context));
-                    context.remove_continuation_indent(2);
-                    items.finish_indent();
-                    items.finish_indent();
-                } else {
-                    items.space();
-                    items.extend(gen_superclass(child, context));
-                }
-                need_space = true;
-            }
-            "super_interfaces" => {
-                if wrap_implements {
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    context.add_continuation_indent(2);
-                    items.extend(gen_super_interfaces(child, context));
-                    context.remove_continuation_indent(2);
-                    items.finish_indent();
-                    items.finish_indent();
-                } else {
-                    items.space();
-                    items.extend(gen_super_interfaces(child, context));
-                }
-                need_space = true;
-            }
-            "class_body" => {
-                items.space();
-                items.extend(gen_class_body(child, context));
-                need_space = false;
-            }
-            _ => {}
+    if let Some(superclass) = class.superclass {
+        items.extend(gen_clause(
+            superclass,
+            wrap_extends,
+            context,
+            gen_superclass,
+        ));
+    }
+    if let Some(interfaces) = class.interfaces {
+        items.extend(gen_clause(
+            interfaces,
+            wrap_implements,
+            context,
+            gen_super_interfaces,
+        ));
+    }
+    if let Some(permits) = class.permits {
+        if wrap_permits {
+            items.start_indent();
+            items.start_indent();
+            items.newline();
+            let (permits_items, ends_with_newline) =
+                gen_permits(permits, &mut context.with_continuation_indent());
+            items.extend(permits_items);
+            items.finish_indent();
+            items.finish_indent();
+            permits_ends_line = ends_with_newline;
+        } else {
+            items.space();
+            items.extend(gen_permits(permits, context).0);
         }
     }
 
+    if !permits_ends_line {
+        items.extend(context.brace_separator());
+    }
+    let policy = class_member_policy(&class, context);
+    items.extend(gen_body_with_members(class.body, policy, context));
+
     items
 }
 
+/// Emit an `extends` or `implements` clause of a class declaration, on a
+/// continuation line when `wrap` is set.
+fn gen_clause<'a>(
+    clause: tree_sitter::Node<'a>,
+    wrap: bool,
+    context: &mut FormattingContext<'a>,
+    generate: fn(tree_sitter::Node<'a>, &mut FormattingContext<'a>) -> PrintItems,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    if wrap {
+        items.start_indent();
+        items.start_indent();
+        items.newline();
+        items.extend(generate(clause, &mut context.with_continuation_indent()));
+        items.finish_indent();
+        items.finish_indent();
+    } else {
+        items.space();
+        items.extend(generate(clause, context));
+    }
+    items
+}
+
+/// The `annotationPolicies` entry for the first annotation on a class that
+/// has one. A qualified name and a simple name match when their last
+/// segments agree, so `lombok.Builder` matches `@Builder`.
+fn class_member_policy(class: &ClassDecl, context: &FormattingContext) -> Option<MemberPolicy> {
+    let policies = &context.config.annotation_policies;
+    if policies.is_empty() {
+        return None;
+    }
+    let modifiers = class.modifiers?;
+    let mut cursor = modifiers.walk();
+    modifiers
+        .named_children(&mut cursor)
+        .filter(|c| matches!(c.kind(), "annotation" | "marker_annotation"))
+        .filter_map(|annotation| annotation.child_by_field_name("name"))
+        .find_map(|name| {
+            let name: String = context.source[name.byte_range()]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            policies.iter().find_map(|(key, policy)| {
+                let matches = if key.contains('.') && name.contains('.') {
+                    *key == name
+                } else {
+                    key.rsplit('.').next() == name.rsplit('.').next()
+                };
+                matches.then_some(*policy)
+            })
+        })
+}
+
 /// Format an interface declaration.
 pub fn gen_interface_declaration<'a>(
     node: tree_sitter::Node<'a>,
@@ -167,10 +222,11 @@ pub fn gen_interface_declaration<'a>(
     let mut need_space = false;
 
     // Pre-calculate: estimate interface declaration line width to decide extends wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    let indent_width = context.indent_level() * context.indent_width();
     let decl_width = estimate_class_decl_width(node, context.source);
     // +2 for trailing " {" after the interface declaration
     let wrap_clauses = indent_width + decl_width + 2 > context.config.line_width as usize;
+    let mut permits_ends_line = false;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -203,9 +259,10 @@ pub fn gen_interface_declaration<'a>(
                     items.start_indent();
                     items.start_indent();
                     items.newline();
-                    context.add_continuation_indent(2);
-                    items.extend(gen_extends_interfaces(child, context));
-                    context.remove_continuation_indent(2);
+                    items.extend(gen_extends_interfaces(
+                        child,
+                        &mut context.with_continuation_indent(),
+                    ));
                     items.finish_indent();
                     items.finish_indent();
                 } else {
@@ -214,8 +271,28 @@ pub fn gen_interface_declaration<'a>(
                 }
                 need_space = true;
             }
+            "permits" => {
+                // A permits clause with comments always wraps, one type per line.
+                if wrap_clauses || has_comments(child) {
+                    items.start_indent();
+                    items.start_indent();
+                    items.newline();
+                    let (permits_items, ends_with_newline) =
+                        gen_permits(child, &mut context.with_continuation_indent());
+                    items.extend(permits_items);
+                    items.finish_indent();
+                    items.finish_indent();
+                    permits_ends_line = ends_with_newline;
+                } else {
+                    items.space();
+                    items.extend(gen_permits(child, context).0);
+                }
+                need_space = true;
+            }
             "interface_body" => {
-                items.space();
+                if !permits_ends_line {
+                    items.extend(context.brace_separator());
+                }
                 items.extend(gen_interface_body(child, context));
                 need_space = false;
             }
@@ -236,9 +313,10 @@ pub fn gen_enum_declaration<'a>(
     let mut need_space = false;
 
     // Pre-calculate: estimate enum declaration line width to decide implements wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    let indent_width = context.indent_level() * context.indent_width();
     let decl_width = estimate_class_decl_width(node, context.source);
-    let wrap_clauses = indent_width + decl_width > context.config.line_width as usize;
+    // +2 for trailing " {" after the enum declaration
+    let wrap_clauses = indent_width + decl_width + 2 > context.config.line_width as usize;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -267,7 +345,10 @@ pub fn gen_enum_declaration<'a>(
                     items.start_indent();
                     items.start_indent();
                     items.newline();
-                    items.extend(gen_super_interfaces(child, context));
+                    items.extend(gen_super_interfaces(
+                        child,
+                        &mut context.with_continuation_indent(),
+                    ));
                     items.finish_indent();
                     items.finish_indent();
                 } else {
@@ -277,7 +358,7 @@ pub fn gen_enum_declaration<'a>(
                 need_space = true;
             }
             "enum_body" => {
-                items.space();
+                items.extend(context.brace_separator());
                 items.extend(gen_enum_body(child, context));
                 need_space = false;
             }
@@ -298,9 +379,10 @@ pub fn gen_record_declaration<'a>(
     let mut need_space = false;
 
     // Pre-calculate: estimate record declaration line width to decide implements wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    let indent_width = context.indent_level() * context.indent_width();
     let decl_width = estimate_class_decl_width(node, context.source);
-    let wrap_clauses = indent_width + decl_width > context.config.line_width as usize;
+    // +2 for trailing " {" after the record declaration
+    let wrap_clauses = indent_width + decl_width + 2 > context.config.line_width as usize;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -333,7 +415,10 @@ pub fn gen_record_declaration<'a>(
                     items.start_indent();
                     items.start_indent();
                     items.newline();
-                    items.extend(gen_super_interfaces(child, context));
+                    items.extend(gen_super_interfaces(
+                        child,
+                        &mut context.with_continuation_indent(),
+                    ));
                     items.finish_indent();
                     items.finish_indent();
                 } else {
@@ -343,7 +428,7 @@ pub fn gen_record_declaration<'a>(
                 need_space = true;
             }
             "class_body" => {
-                items.space();
+                items.extend(context.brace_separator());
                 items.extend(gen_class_body(child, context));
                 need_space = false;
             }
@@ -386,7 +471,7 @@ pub fn gen_annotation_type_declaration<'a>(
                 need_space = true;
             }
             "annotation_type_body" => {
-                items.space();
+                items.extend(context.brace_separator());
                 items.extend(gen_annotation_type_body(child, context));
                 need_space = false;
             }
@@ -399,237 +484,137 @@ pub fn gen_annotation_type_declaration<'a>(
 
 /// Format a method declaration.
 ///
-/// Handles wrapping of the throws clause onto a continuation line when the
-/// method signature would exceed `line_width`.
-#[allow(clippy::too_many_lines)]
+/// The signature is laid out by `gen_callable_signature`; this adds the body
+/// or the terminating `;` of an abstract method.
 pub fn gen_method_declaration<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let Some(method) = MethodDecl::cast(node) else {
+        return gen_verbatim_text(node, context.source);
+    };
+    let mut items = gen_callable_signature(&method, context);
+    match method.body {
+        Some(body) => {
+            items.extend(context.brace_separator());
+            items.extend(gen_node(body, context));
+        }
+        None => items.push_str(";"),
+    }
+    items
+}
+
+/// Format the signature shared by method and constructor declarations:
+/// modifiers, type parameters, return type, name, parameters and `throws`,
+/// stopping before the body.
+///
+/// Handles wrapping of the method name after a long return type and of the
+/// throws clause onto a continuation line when the signature would exceed
+/// `line_width`.
+fn gen_callable_signature<'a>(
+    method: &MethodDecl<'a>,
+    context: &mut FormattingContext<'a>,
 ) -> PrintItems {
     let mut items = PrintItems::new();
-    let mut cursor = node.walk();
-    let mut need_space = false;
 
-    // Pre-calculate: estimate method signature line width to decide throws wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
-    let sig_width = estimate_method_sig_width(node, context.source);
+    let indent_width = context.indent_level() * context.indent_width();
     let line_width = context.config.line_width as usize;
-    // +2 for the trailing " {" or ";" that follows the throws clause
-    let full_too_wide = indent_width + sig_width + 2 > line_width;
-    // PJF wraps throws when the line containing `) throws ... {` would exceed line_width.
-    // If params fit inline, this is the full flat sig width.
-    // If params are wrapped, the `)` is on the last param line (shorter).
-    let wrap_throws = if full_too_wide {
-        let mut c = node.walk();
-        let children_vec: Vec<_> = node.children(&mut c).collect();
-        // Compute width of signature WITHOUT the throws clause
-        let sig_no_throws: usize = {
-            let mut w = 0;
-            let mut c2 = node.walk();
-            for ch in node.children(&mut c2) {
-                match ch.kind() {
-                    "block" | "constructor_body" | ";" | "throws" => break,
-                    _ => {
-                        let text = &context.source[ch.start_byte()..ch.end_byte()];
-                        let last_line = text.lines().last().unwrap_or(text);
-                        if w > 0
-                            && ch.kind() != "formal_parameters"
-                            && ch.kind() != "("
-                            && ch.kind() != ")"
-                        {
-                            w += 1; // space
-                        }
-                        w += last_line.trim().len();
-                    }
-                }
-            }
-            w
-        };
-        let params_fit_inline = indent_width + sig_no_throws <= line_width;
-        if params_fit_inline {
-            // Params on one line: throws wraps based on full sig width
-            true
-        } else {
-            // Params will wrap. Check if `) throws ... {` fits on the last param line.
-            let throws_width: usize =
-                children_vec
-                    .iter()
-                    .find(|ch| ch.kind() == "throws")
-                    .map_or(0, |throws_node| {
-                        let text =
-                            &context.source[throws_node.start_byte()..throws_node.end_byte()];
-                        collapse_whitespace_len(text)
-                    });
-            if throws_width == 0 {
-                false
-            } else {
-                let last_param_width = children_vec
-                    .iter()
-                    .find(|ch| ch.kind() == "formal_parameters")
-                    .and_then(|params| {
-                        let mut pc = params.walk();
-                        params
-                            .children(&mut pc)
-                            .filter(|p| {
-                                p.kind() == "formal_parameter" || p.kind() == "spread_parameter"
-                            })
-                            .last()
-                            .map(|p| {
-                                let text = &context.source[p.start_byte()..p.end_byte()];
-                                collapse_whitespace_len(text)
-                            })
-                    })
-                    .unwrap_or(0);
-                let continuation_col = indent_width + 2 * context.config.indent_width as usize;
-                // Last param line: continuation + last_param + ") throws ... {"
-                continuation_col + last_param_width + 2 + throws_width + 2 > line_width
-            }
-        }
-    } else {
-        false
-    };
+    let wrap_throws = throws_on_new_line(method, indent_width, context);
+    let name_width = display_width(&context.source[method.name.byte_range()]);
 
     // PJF: wrap between return type and method name when the signature is too long.
     // Example: `public CompletableFuture<VeryLongResponse>\n        methodName(params) {`
-    let mut wrap_before_name = {
-        let mut cursor_pre = node.walk();
-        let children_pre: Vec<_> = node.children(&mut cursor_pre).collect();
-        // Find the method name (identifier) position
-        let name_idx = children_pre.iter().position(|c| c.kind() == "identifier");
-        if let Some(idx) = name_idx {
-            // Width of everything up to and including the return type
-            let mut return_type_width = 0;
-            for c in &children_pre[..idx] {
-                let text = &context.source[c.start_byte()..c.end_byte()];
-                let last_line = text.lines().last().unwrap_or(text);
-                if return_type_width > 0 {
-                    return_type_width += 1; // space
-                }
-                return_type_width += last_line.trim().len();
-            }
-            // Width of identifier + remaining sig (params, throws)
-            let name_text =
-                &context.source[children_pre[idx].start_byte()..children_pre[idx].end_byte()];
-            let name_width = name_text.len();
-            // Estimate params width
-            let params_width: usize = children_pre
-                .iter()
-                .find_map(|c| {
-                    if c.kind() == "formal_parameters" {
-                        let text = &context.source[c.start_byte()..c.end_byte()];
-                        Some(collapse_whitespace_len(text))
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(2); // "()" minimum
-            // PJF wraps before method name only when return_type + name + "(" alone
-            // doesn't fit (not just when the full sig with params is too long).
-            // If wrapping params alone can fix it, we don't wrap the name.
-            let name_line_width = indent_width + return_type_width + 1 + name_width + 1; // +1 for "("
-            let continuation_col = indent_width + 2 * context.config.indent_width as usize;
-            let name_at_continuation = continuation_col + name_width + params_width;
-            name_line_width > line_width && name_at_continuation <= line_width
-        } else {
-            false
-        }
+    // Constructors have no return type, so their name never wraps.
+    let mut wrap_before_name = method.return_type.is_some() && {
+        // Width of everything up to and including the return type
+        let return_type_width = SignatureMeasurer::new(context.source, MeasureMode::Collapsed)
+            .width_until(method.node, |c| c.kind() == "identifier");
+        let params_text = &context.source[method.parameters.byte_range()];
+        let params_width = collapse_whitespace_len(params_text);
+        // PJF wraps before method name only when return_type + name + "(" alone
+        // doesn't fit (not just when the full sig with params is too long).
+        // If wrapping params alone can fix it, we don't wrap the name.
+        let name_line_width = indent_width + return_type_width + 1 + name_width + 1; // +1 for "("
+        let continuation_col = indent_width + context.continuation_width();
+        let name_at_continuation = continuation_col + name_width + params_width;
+        name_line_width > line_width && name_at_continuation <= line_width
     };
 
-    let mut did_wrap_name = false;
+    // Only need space after modifiers if they didn't end with newline
+    let mut need_space = false;
+    if let Some(modifiers) = method.modifiers {
+        let (modifier_items, ends_with_newline) = gen_modifiers(modifiers, context);
+        items.extend(modifier_items);
+        need_space = !ends_with_newline;
+    }
+    if let Some(type_parameters) = method.type_parameters {
+        if need_space {
+            items.space();
+        }
+        items.extend(gen_type_parameters(type_parameters, context));
+        need_space = true;
+    }
+    // Type-use annotations between the type parameters and the return type,
+    // `<T> @Nullable T find()`, are children of the declaration itself.
+    let mut cursor = method.node.walk();
+    for annotation in method
+        .node
+        .children(&mut cursor)
+        .filter(|c| matches!(c.kind(), "annotation" | "marker_annotation"))
+    {
+        if need_space {
+            items.space();
+        }
+        items.extend(gen_node(annotation, context));
+        need_space = true;
+    }
+    if let Some(return_type) = method.return_type {
+        if need_space {
+            items.space();
+        }
+        context.start_type_args_wrap_tracking();
+        items.extend(gen_node(return_type, context));
+        if context.finish_type_args_wrap_tracking() {
+            wrap_before_name = true;
+        }
+        need_space = true;
+    }
 
-    for child in node.children(&mut cursor) {
-        match child.kind() {
-            "modifiers" => {
-                let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
-                items.extend(modifier_items);
-                // Only need space if modifiers didn't end with newline
-                need_space = !ends_with_newline;
-            }
-            "type_parameters" => {
-                if need_space {
-                    items.space();
-                }
-                items.extend(gen_type_parameters(child, context));
-                need_space = true;
-            }
-            // Return type: various type nodes
-            kind if is_type_node(kind) => {
-                if need_space {
-                    items.space();
-                }
-                context.start_type_args_wrap_tracking();
-                items.extend(gen_node(child, context));
-                if context.finish_type_args_wrap_tracking() {
-                    wrap_before_name = true;
-                }
-                need_space = true;
-            }
-            "identifier" => {
-                if wrap_before_name {
-                    // Wrap: put method name on continuation-indent line
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    did_wrap_name = true;
-                    // Tell formal_parameters the effective prefix is just the method name
-                    let name_text = &context.source[child.start_byte()..child.end_byte()];
-                    context.set_override_prefix_width(Some(name_text.len()));
-                } else if need_space {
-                    items.space();
-                }
-                items.extend(gen_node_text(child, context.source));
-                need_space = false;
-            }
-            "formal_parameters" => {
-                items.extend(gen_formal_parameters(child, context));
-                need_space = true;
-            }
-            "throws" => {
-                if wrap_throws {
-                    if !did_wrap_name {
-                        items.start_indent();
-                        items.start_indent();
-                    }
-                    items.newline();
-                    items.extend(gen_throws(child, context));
-                    if !did_wrap_name {
-                        items.finish_indent();
-                        items.finish_indent();
-                    }
-                } else {
-                    items.space();
-                    items.extend(gen_throws(child, context));
-                }
-                need_space = true;
-            }
-            "block" => {
-                if did_wrap_name {
-                    items.finish_indent();
-                    items.finish_indent();
-                }
-                items.space();
-                items.extend(gen_node(child, context));
-                need_space = false;
-                did_wrap_name = false; // consumed
-            }
-            ";" => {
-                if did_wrap_name {
-                    items.finish_indent();
-                    items.finish_indent();
-                    did_wrap_name = false;
-                }
-                items.push_str(";");
-                need_space = false;
+    if wrap_before_name {
+        // Wrap: put method name on continuation-indent line
+        items.start_indent();
+        items.start_indent();
+        items.newline();
+        // Tell formal_parameters the effective prefix is just the method name
+        context.set_override_prefix_width(Some(name_width));
+    } else if need_space {
+        items.space();
+    }
+    items.extend(gen_node_text(method.name, context.source));
+    items.extend(gen_formal_parameters(method.parameters, context));
+    if let Some(dimensions) = method.dimensions {
+        items.extend(gen_node(dimensions, context));
+    }
+
+    if let Some(throws) = method.throws {
+        if wrap_throws {
+            if !wrap_before_name {
+                items.start_indent();
+                items.start_indent();
             }
-            "dimensions" => {
-                items.extend(gen_node_text(child, context.source));
-                need_space = true;
+            items.newline();
+            items.extend(gen_throws(throws, context));
+            if !wrap_before_name {
+                items.finish_indent();
+                items.finish_indent();
             }
-            _ => {}
+        } else {
+            items.space();
+            items.extend(gen_throws(throws, context));
         }
     }
 
-    if did_wrap_name {
+    if wrap_before_name {
         items.finish_indent();
         items.finish_indent();
     }
@@ -637,36 +622,80 @@ pub fn gen_method_declaration<'a>(
     items
 }
 
-/// Estimate the width of a method signature line (modifiers + return type + name + params + throws)
-/// from the source text. Only considers the "flat" width, ignoring existing line breaks.
-fn estimate_method_sig_width(node: tree_sitter::Node, source: &str) -> usize {
-    let mut cursor = node.walk();
-    let mut width = 0;
+/// Decide whether the `throws` clause of a method or constructor goes on its
+/// own continuation line.
+///
+/// PJF wraps throws when the line containing `) throws ... {` would exceed
+/// `line_width`. If the parameters fit inline that line is the whole flat
+/// signature; if they wrap, it is the continuation line holding the closing
+/// `)`, which carries either all parameters (when they pack onto one line)
+/// or just the last one.
+fn throws_on_new_line(
+    method: &MethodDecl,
+    indent_width: usize,
+    context: &FormattingContext,
+) -> bool {
+    let Some(throws_node) = method.throws else {
+        return false;
+    };
+    let node = method.node;
+    let line_width = context.config.line_width as usize;
+    // " {" after the throws clause, or ";" for an abstract method
+    let suffix_width = if method.body.is_some() { 2 } else { 1 };
+    let measurer = SignatureMeasurer::new(context.source, MeasureMode::Collapsed);
+    let sig_width = estimate_method_sig_width(node, context.source);
+    if indent_width + sig_width + suffix_width <= line_width {
+        return false;
+    }
 
-    for child in node.children(&mut cursor) {
-        match child.kind() {
-            "block" | "constructor_body" => break, // Stop at body
-            ";" => {
-                width += 1;
-                break;
-            }
-            _ => {
-                let text = &source[child.start_byte()..child.end_byte()];
-                // Use first line only (for multiline modifiers like annotations)
-                let first_line = text.lines().last().unwrap_or(text);
-                if width > 0
-                    && child.kind() != "formal_parameters"
-                    && child.kind() != "("
-                    && child.kind() != ")"
-                {
-                    width += 1; // space separator
-                }
-                width += first_line.trim().len();
-            }
+    // Width of the signature without the throws clause
+    let sig_no_throws = measurer.width_until(node, |c| c.kind() == "throws" || is_signature_end(c));
+    if indent_width + sig_no_throws <= line_width {
+        // Params on one line: throws wraps based on full sig width
+        return true;
+    }
+
+    // Params will wrap. Check if `) throws ... {` fits on the line with the `)`.
+    let throws_width =
+        collapse_whitespace_len(&context.source[throws_node.start_byte()..throws_node.end_byte()]);
+    let param_measurer = SignatureMeasurer::new(context.source, MeasureMode::Flat);
+    let mut param_widths = Vec::new();
+    let mut has_comments = false;
+    let mut pc = method.parameters.walk();
+    for p in method.parameters.children(&mut pc) {
+        match p.kind() {
+            "formal_parameter" | "spread_parameter" | "receiver_parameter" => {
+                param_widths.push(param_measurer.part_width(p));
+            }
+            _ if p.is_extra() => has_comments = true,
+            _ => {}
         }
     }
+    let continuation_col = indent_width + context.continuation_width();
+    // Mirrors the bin-packing check in `gen_formal_parameters`.
+    let packed_width =
+        param_widths.iter().sum::<usize>() + 2 * param_widths.len().saturating_sub(1);
+    let closing_line_params = if !has_comments && continuation_col + packed_width + 3 <= line_width
+    {
+        packed_width
+    } else {
+        param_widths.last().copied().unwrap_or(0)
+    };
+    // continuation + params + ") throws ... {"
+    continuation_col + closing_line_params + 2 + throws_width + suffix_width > line_width
+}
 
-    width
+/// Estimate the width of a method signature line (modifiers + return type + name + params + throws)
+/// from the source text, without the `;` or ` {` that ends it. Only considers the "flat" width,
+/// ignoring existing line breaks; modifiers count only their keywords, as annotations print on
+/// lines of their own.
+fn estimate_method_sig_width(node: tree_sitter::Node, source: &str) -> usize {
+    SignatureMeasurer::new(source, MeasureMode::Collapsed).width_until(node, is_signature_end)
+}
+
+/// Whether `node` ends a method or constructor signature: its body or `;`.
+fn is_signature_end(node: tree_sitter::Node) -> bool {
+    matches!(node.kind(), "block" | "constructor_body" | ";")
 }
 
 /// Estimate the prefix width before a `formal_parameters` or `argument_list` node.
@@ -678,21 +707,30 @@ fn estimate_method_sig_width(node: tree_sitter::Node, source: &str) -> usize {
 ///
 /// Uses the parent-to-node text as the base measurement, then walks up
 /// ancestors to account for keywords/LHS that share the same line.
-pub(super) fn estimate_prefix_width(
-    node: tree_sitter::Node,
-    source: &str,
-    assignment_wrapped: bool,
-) -> usize {
+pub(super) fn estimate_prefix_width(node: tree_sitter::Node, context: &FormattingContext) -> usize {
+    if context.is_over_work_budget() {
+        return 0;
+    }
+    let source = context.source;
+    let assignment_wrapped = context.is_assignment_wrapped();
     let Some(parent) = node.parent() else {
         return 0;
     };
 
     // Extract the text from the start of the parent to the start of this node
-    let prefix_text = &source[parent.start_byte()..node.start_byte()];
+    let prefix_text = declaration_line_text(parent, node.start_byte(), source);
 
-    // Only consider the last line to handle multiline modifiers/annotations
-    let last_line = prefix_text.lines().last().unwrap_or(prefix_text);
-    let mut width = last_line.trim_start().len();
+    // Only consider the last line to handle multiline modifiers/annotations,
+    // joined to the lines before it while it starts with the `.` of a call
+    // that a previous pass wrapped
+    let mut width = 0;
+    for line in prefix_text.lines().rev() {
+        let line = line.trim_start();
+        width += display_width(line);
+        if !line.starts_with('.') {
+            break;
+        }
+    }
 
     // Walk up ancestors to accumulate prefix from keywords/LHS that share the line.
     // Stop when we hit a node that may introduce a line break (e.g., variable_declarator
@@ -701,10 +739,24 @@ pub(super) fn estimate_prefix_width(
     let mut ancestor = parent.parent();
     let parent_start_row = parent.start_position().row;
     while let Some(anc) = ancestor {
+        // Finding a node's parent walks down from the root
+        context.charge_work(context.depth());
+        // `case A, B -> call(...)`: the labels and arrow share the body's line,
+        // even when a previous pass wrapped the labels onto rows of their own
+        if anc.kind() == "switch_rule" {
+            if prev.kind() != "switch_label" {
+                width += statements::switch_rule_label_width(anc, context);
+            }
+            break;
+        }
         // Only add prefix from ancestors that start on the same source line
         if anc.start_position().row != parent_start_row {
             break;
         }
+        // A wrapped chain segment starts its own line
+        if anc.kind() == "method_invocation" && context.is_chain_segment_on_new_line() {
+            break;
+        }
         match anc.kind() {
             "return_statement" => {
                 width += 7; // "return "
@@ -714,13 +766,17 @@ pub(super) fn estimate_prefix_width(
                 width += 6; // "throw "
                 break;
             }
+            "guard" => {
+                width += 5; // "when "
+                break;
+            }
             "assignment_expression" => {
                 // If the assignment is being wrapped at '=', the RHS starts on a new
                 // line at continuation indent — don't count LHS as prefix width.
                 if !assignment_wrapped {
                     let lhs_text = &source[anc.start_byte()..prev.start_byte()];
                     let lhs_last_line = lhs_text.lines().last().unwrap_or(lhs_text);
-                    width += lhs_last_line.trim_start().len();
+                    width += display_width(lhs_last_line.trim_start());
                 }
                 break;
             }
@@ -728,9 +784,16 @@ pub(super) fn estimate_prefix_width(
                 // If the assignment already wrapped at '=', the RHS starts on a new
                 // line at continuation indent — don't count LHS as prefix width.
                 if !assignment_wrapped {
-                    let lhs_text = &source[anc.start_byte()..prev.start_byte()];
-                    let lhs_last_line = lhs_text.lines().last().unwrap_or(lhs_text);
-                    width += lhs_last_line.trim_start().len();
+                    let lhs_text = match first_declarator_on_new_line(anc, context) {
+                        // After wrapped type arguments, the declarators start
+                        // their own line
+                        Some(first) => {
+                            Cow::Borrowed(&source[first.start_byte()..prev.start_byte()])
+                        }
+                        None => declaration_line_text(anc, prev.start_byte(), source),
+                    };
+                    let lhs_last_line = lhs_text.lines().last().unwrap_or(&lhs_text);
+                    width += display_width(lhs_last_line.trim_start());
                 }
                 // Continue walking up if there's a containing declaration
                 prev = anc;
@@ -748,48 +811,35 @@ pub(super) fn estimate_prefix_width(
     width
 }
 
-/// Estimate the width of a class/interface/enum/record declaration line
-/// (modifiers + keyword + name + `type_parameters` + extends/implements + body start)
-/// from the source text. Only considers the "flat" width, ignoring existing line breaks.
-fn estimate_class_decl_width(node: tree_sitter::Node, source: &str) -> usize {
-    let mut cursor = node.walk();
-    let mut width = 0;
-
-    for child in node.children(&mut cursor) {
-        match child.kind() {
-            "class_body" | "interface_body" | "enum_body" => break, // Stop at body
-            "modifiers" => {
-                let text = &source[child.start_byte()..child.end_byte()];
-                // Use last line only (for multiline modifiers like annotations)
-                let last_line = text.lines().last().unwrap_or(text);
-                width += last_line.trim().len();
-            }
-            _ => {
-                let text = &source[child.start_byte()..child.end_byte()];
-                // Use collapsed width for all non-modifier nodes to avoid
-                // instability when the source text has been wrapped from a
-                // previous formatting pass.
-                let flat_len = collapse_whitespace_len(text);
-                if width > 0
-                    && child.kind() != "formal_parameters"
-                    && child.kind() != "("
-                    && child.kind() != ")"
-                {
-                    width += 1; // space separator
-                }
-                width += flat_len;
-            }
-        }
+/// The first declarator of `declaration` when the declarators print on a
+/// continuation line of their own, after type arguments that wrapped.
+fn first_declarator_on_new_line<'a>(
+    declaration: tree_sitter::Node<'a>,
+    context: &FormattingContext,
+) -> Option<tree_sitter::Node<'a>> {
+    if !context.is_declarator_on_new_line()
+        || !matches!(
+            declaration.kind(),
+            "local_variable_declaration" | "field_declaration"
+        )
+    {
+        return None;
     }
+    declaration.child_by_field_name("declarator")
+}
 
-    width
+/// Estimate the width of a class/interface/enum/record declaration line
+/// (modifiers + keyword + name + `type_parameters` + extends/implements)
+/// from the source text. Only considers the "flat" width, ignoring existing line breaks;
+/// modifiers count only their keywords, as annotations print on lines of their own.
+fn estimate_class_decl_width(node: tree_sitter::Node, source: &str) -> usize {
+    SignatureMeasurer::new(source, MeasureMode::Collapsed).width_until(node, |c| {
+        matches!(c.kind(), "class_body" | "interface_body" | "enum_body")
+    })
 }
 
-/// Format a constructor declaration.
-///
-/// Handles wrapping of the throws clause onto a continuation line when the
-/// constructor signature would exceed `line_width`.
-pub fn gen_constructor_declaration<'a>(
+/// Format a compact canonical constructor of a record: `public Point { ... }`
+pub fn gen_compact_constructor_declaration<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
@@ -797,124 +847,22 @@ pub fn gen_constructor_declaration<'a>(
     let mut cursor = node.walk();
     let mut need_space = false;
 
-    // Pre-calculate: estimate constructor signature line width to decide throws wrapping.
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
-    let sig_width = estimate_method_sig_width(node, context.source);
-    let line_width = context.config.line_width as usize;
-    // +2 for the trailing " {" that follows the throws clause
-    let full_too_wide = indent_width + sig_width + 2 > line_width;
-    let wrap_throws = if full_too_wide {
-        // Check if params fit inline (without wrapping)
-        let sig_no_throws: usize = {
-            let mut w = 0;
-            let mut c2 = node.walk();
-            for ch in node.children(&mut c2) {
-                match ch.kind() {
-                    "block" | "constructor_body" | ";" | "throws" => break,
-                    _ => {
-                        let text = &context.source[ch.start_byte()..ch.end_byte()];
-                        let last_line = text.lines().last().unwrap_or(text);
-                        if w > 0
-                            && ch.kind() != "formal_parameters"
-                            && ch.kind() != "("
-                            && ch.kind() != ")"
-                        {
-                            w += 1;
-                        }
-                        w += last_line.trim().len();
-                    }
-                }
-            }
-            w
-        };
-        if indent_width + sig_no_throws <= line_width {
-            // Params fit inline: wrap throws based on full sig width
-            true
-        } else {
-            // Params will wrap. Check last param line + throws.
-            let mut c = node.walk();
-            let children_vec: Vec<_> = node.children(&mut c).collect();
-            let throws_width: usize =
-                children_vec
-                    .iter()
-                    .find(|ch| ch.kind() == "throws")
-                    .map_or(0, |throws_node| {
-                        let text =
-                            &context.source[throws_node.start_byte()..throws_node.end_byte()];
-                        collapse_whitespace_len(text)
-                    });
-            if throws_width == 0 {
-                false
-            } else {
-                let last_param_width = children_vec
-                    .iter()
-                    .find(|ch| ch.kind() == "formal_parameters")
-                    .and_then(|params| {
-                        let mut pc = params.walk();
-                        params
-                            .children(&mut pc)
-                            .filter(|p| {
-                                p.kind() == "formal_parameter" || p.kind() == "spread_parameter"
-                            })
-                            .last()
-                            .map(|p| {
-                                let text = &context.source[p.start_byte()..p.end_byte()];
-                                collapse_whitespace_len(text)
-                            })
-                    })
-                    .unwrap_or(0);
-                let continuation_col = indent_width + 2 * context.config.indent_width as usize;
-                continuation_col + last_param_width + 2 + throws_width + 2 > line_width
-            }
-        }
-    } else {
-        false
-    };
-
     for child in node.children(&mut cursor) {
         match child.kind() {
             "modifiers" => {
                 let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
                 items.extend(modifier_items);
-                // Only need space if modifiers didn't end with newline
                 need_space = !ends_with_newline;
             }
-            "type_parameters" => {
-                if need_space {
-                    items.space();
-                }
-                items.extend(gen_type_parameters(child, context));
-                need_space = true;
-            }
             "identifier" => {
                 if need_space {
                     items.space();
                 }
                 items.extend(gen_node_text(child, context.source));
-                need_space = false;
-            }
-            "formal_parameters" => {
-                items.extend(gen_formal_parameters(child, context));
-                need_space = true;
             }
-            "throws" => {
-                if wrap_throws {
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    items.extend(gen_throws(child, context));
-                    items.finish_indent();
-                    items.finish_indent();
-                } else {
-                    items.space();
-                    items.extend(gen_throws(child, context));
-                }
-                need_space = true;
-            }
-            "constructor_body" => {
-                items.space();
+            "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_node(child, context));
-                need_space = false;
             }
             _ => {}
         }
@@ -923,7 +871,28 @@ pub fn gen_constructor_declaration<'a>(
     items
 }
 
+/// Format a constructor declaration.
+///
+/// The signature is laid out by `gen_callable_signature`, exactly as for methods.
+pub fn gen_constructor_declaration<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let Some(constructor) = MethodDecl::cast(node) else {
+        return gen_verbatim_text(node, context.source);
+    };
+    let mut items = gen_callable_signature(&constructor, context);
+    if let Some(body) = constructor.body {
+        items.extend(context.brace_separator());
+        items.extend(gen_node(body, context));
+    }
+    items
+}
+
 /// Format a field declaration: `private String name;`
+///
+/// Wraps like a local variable declaration, including every declarator
+/// moving to one continuation line after wrapped type arguments.
 pub fn gen_field_declaration<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -932,6 +901,7 @@ pub fn gen_field_declaration<'a>(
     let mut cursor = node.walk();
     let mut need_space = false;
     let mut type_args_wrapped = false;
+    let mut declarators_continued = false;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -953,19 +923,18 @@ pub fn gen_field_declaration<'a>(
             }
             "variable_declarator" => {
                 if type_args_wrapped {
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    context.indent();
-                    context.indent();
-                    context.set_declarator_on_new_line(true);
-                    items.extend(gen_variable_declarator(child, context));
-                    context.set_declarator_on_new_line(false);
-                    context.dedent();
-                    context.dedent();
-                    items.finish_indent();
-                    items.finish_indent();
-                    type_args_wrapped = false;
+                    if declarators_continued {
+                        items.space();
+                    } else {
+                        items.start_indent();
+                        items.start_indent();
+                        items.newline();
+                        declarators_continued = true;
+                    }
+                    let mut indented = context.indented(CONTINUATION_INDENT_LEVELS);
+                    let mut on_new_line =
+                        indented.with_flag(ContextFlag::DeclaratorOnNewLine, true);
+                    items.extend(gen_variable_declarator(child, &mut on_new_line));
                 } else {
                     if need_space {
                         items.space();
@@ -985,6 +954,64 @@ pub fn gen_field_declaration<'a>(
             _ => {}
         }
     }
+    if declarators_continued {
+        items.finish_indent();
+        items.finish_indent();
+    }
+
+    items
+}
+
+/// Format an annotation interface element: `String name() default "x";`
+///
+/// Only the keywords inside `modifiers` are reordered; the `default` that
+/// introduces the element's default value is a separate token and always stays
+/// after the parentheses.
+pub fn gen_annotation_type_element_declaration<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+    let mut need_space = false;
+    let value = node.child_by_field_name("value");
+
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "modifiers" => {
+                let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
+                items.extend(modifier_items);
+                need_space = !ends_with_newline;
+            }
+            "(" => items.push_str("("),
+            ")" => items.push_str(")"),
+            "default" => {
+                items.space();
+                items.push_str("default");
+                items.space();
+            }
+            ";" => items.push_str(";"),
+            _ if value.is_some_and(|v| v.id() == child.id()) => {
+                items.extend(gen_node(child, context));
+            }
+            "identifier" => {
+                items.space();
+                items.extend(gen_node_text(child, context.source));
+            }
+            "dimensions" => items.extend(gen_node(child, context)),
+            kind if is_type_node(kind) => {
+                if need_space {
+                    items.space();
+                }
+                items.extend(gen_node(child, context));
+            }
+            _ if child.is_extra() => {
+                items.space();
+                items.extend(gen_node(child, context));
+            }
+            _ => {}
+        }
+    }
 
     items
 }
@@ -1009,9 +1036,26 @@ const JLS_MODIFIER_ORDER: &[&str] = &[
     "non-sealed",
 ];
 
+/// Sort keyword modifiers into JLS order. A comment between the keywords
+/// pins them to their source order, since moving the comment past a keyword
+/// would change what it annotates.
+fn sort_modifier_keywords(keywords: &mut [&tree_sitter::Node], source: &str) {
+    if keywords.iter().any(|kw| kw.is_extra()) {
+        return;
+    }
+    keywords.sort_by_key(|kw| {
+        let text = &source[kw.start_byte()..kw.end_byte()];
+        JLS_MODIFIER_ORDER
+            .iter()
+            .position(|m| *m == text)
+            .unwrap_or(usize::MAX)
+    });
+}
+
 /// Format modifiers (public, static, final, abstract, etc.)
 ///
-/// Annotations are placed on their own line before keyword modifiers.
+/// Annotations written before the keyword modifiers are placed on their own
+/// lines; annotations written after them stay after them on the same line.
 /// Keyword modifiers are reordered to JLS canonical order.
 ///
 /// Returns (items, `ends_with_newline`) where `ends_with_newline` is true
@@ -1024,33 +1068,28 @@ pub fn gen_modifiers<'a>(
     let mut cursor = node.walk();
     let children: Vec<_> = node.children(&mut cursor).collect();
 
-    // Separate annotations from keyword modifiers
-    let annotations: Vec<_> = children
+    // Annotations written before the keywords go on their own lines; those
+    // written after the keywords stay after them, as in gen_inline_modifiers.
+    let is_annotation =
+        |n: &tree_sitter::Node| n.kind() == "marker_annotation" || n.kind() == "annotation";
+    let first_keyword = children
         .iter()
-        .filter(|c| c.kind() == "marker_annotation" || c.kind() == "annotation")
-        .collect();
-    let mut keywords: Vec<_> = children
-        .iter()
-        .filter(|c| c.kind() != "marker_annotation" && c.kind() != "annotation")
-        .collect();
+        .position(|c| !is_annotation(c))
+        .unwrap_or(children.len());
+    let annotations = &children[..first_keyword];
+    let mut keywords: Vec<_> = children.iter().filter(|c| !is_annotation(c)).collect();
 
     // Sort keyword modifiers by JLS canonical order
-    keywords.sort_by_key(|kw| {
-        let text = &context.source[kw.start_byte()..kw.end_byte()];
-        JLS_MODIFIER_ORDER
-            .iter()
-            .position(|m| *m == text)
-            .unwrap_or(usize::MAX)
-    });
+    sort_modifier_keywords(&mut keywords, context.source);
 
     // Emit annotations, each on their own line
-    for ann in &annotations {
-        items.extend(gen_node(**ann, context));
+    for ann in annotations {
+        items.extend(gen_node(*ann, context));
         // Always add newline after each annotation
         items.newline();
     }
 
-    // Emit keyword modifiers on a single line
+    // Emit keyword modifiers and any later annotations on a single line
     let mut first = true;
     for kw in &keywords {
         if !first {
@@ -1059,12 +1098,65 @@ pub fn gen_modifiers<'a>(
         items.extend(gen_node_text(**kw, context.source));
         first = false;
     }
+    for ann in children[first_keyword..]
+        .iter()
+        .filter(|c| is_annotation(c))
+    {
+        items.space();
+        items.extend(gen_node(*ann, context));
+    }
 
     // Return true if we ended with a newline (annotations but no keywords)
     let ends_with_newline = !annotations.is_empty() && keywords.is_empty();
     (items, ends_with_newline)
 }
 
+/// Format modifiers that stay on the same line as what follows them, such as
+/// on catch parameters: `@Ann final Exception e`.
+///
+/// Keywords are put in JLS order with single spaces. Annotations written before
+/// the keywords stay in front of them; annotations written after the keywords
+/// stay after them, since those usually annotate the type.
+pub fn gen_inline_modifiers<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.children(&mut cursor).collect();
+
+    let is_annotation =
+        |n: &tree_sitter::Node| n.kind() == "marker_annotation" || n.kind() == "annotation";
+    let first_keyword = children
+        .iter()
+        .position(|c| !is_annotation(c))
+        .unwrap_or(children.len());
+    let mut keywords: Vec<_> = children.iter().filter(|c| !is_annotation(c)).collect();
+    sort_modifier_keywords(&mut keywords, context.source);
+
+    let mut parts: Vec<PrintItems> = Vec::new();
+    for ann in &children[..first_keyword] {
+        parts.push(gen_node(*ann, context));
+    }
+    for kw in keywords {
+        parts.push(gen_node_text(*kw, context.source));
+    }
+    for ann in children[first_keyword..]
+        .iter()
+        .filter(|c| is_annotation(c))
+    {
+        parts.push(gen_node(*ann, context));
+    }
+
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            items.space();
+        }
+        items.extend(part);
+    }
+    items
+}
+
 /// Format type parameters: `<T, U extends Comparable<U>>`
 fn gen_type_parameters<'a>(
     node: tree_sitter::Node<'a>,
@@ -1072,19 +1164,23 @@ fn gen_type_parameters<'a>(
 ) -> PrintItems {
     let mut items = PrintItems::new();
     let mut cursor = node.walk();
+    let inner: Vec<_> = node
+        .children(&mut cursor)
+        .filter(|c| c.kind() != "<" && c.kind() != ">")
+        .collect();
+    // A line comment breaks the list; continue on a continuation line.
+    let has_line_comment = inner.iter().any(|c| c.kind() == "line_comment");
 
-    for child in node.children(&mut cursor) {
-        match child.kind() {
-            "<" => items.push_str("<"),
-            ">" => items.push_str(">"),
-            "," => {
-                items.push_str(",");
-                items.space();
-            }
-            _ => {
-                items.extend(gen_node(child, context));
-            }
-        }
+    items.push_str("<");
+    if has_line_comment {
+        items.start_indent();
+        items.start_indent();
+    }
+    items.extend(gen_generic_list(&inner, false, context));
+    items.push_str(">");
+    if has_line_comment {
+        items.finish_indent();
+        items.finish_indent();
     }
 
     items
@@ -1174,6 +1270,144 @@ fn gen_extends_interfaces<'a>(
     items
 }
 
+/// Format `permits Subclass1, Subclass2` (for sealed classes and interfaces).
+///
+/// A clause with comments puts each permitted type on its own line, with its
+/// comments kept beside it. Returns the items and whether they end with a
+/// newline (a trailing line comment), in which case the body's `{` must not
+/// be preceded by a space.
+fn gen_permits<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> (PrintItems, bool) {
+    let mut items = PrintItems::new();
+    items.push_str("permits");
+
+    if !has_comments(node) {
+        let mut cursor = node.walk();
+        if let Some(type_list) = node.children(&mut cursor).find(|c| c.kind() == "type_list") {
+            items.space();
+            items.extend(gen_type_list(type_list, context));
+        }
+        return (items, false);
+    }
+
+    let permitted = permitted_types(node);
+    let mut ends_with_newline = false;
+    items.start_indent();
+    items.start_indent();
+    for (i, permitted_type) in permitted.iter().enumerate() {
+        if !ends_with_newline {
+            items.newline();
+        }
+        for comment in &permitted_type.leading {
+            items.extend(gen_node(*comment, context));
+            if comment.kind() != "line_comment" {
+                if comment.end_position().row == permitted_type.ty.start_position().row {
+                    items.space();
+                } else {
+                    items.newline();
+                }
+            }
+        }
+        items.extend(gen_node(permitted_type.ty, context));
+        if i + 1 < permitted.len() {
+            items.push_str(",");
+        }
+        for comment in &permitted_type.trailing {
+            items.space();
+            items.extend(gen_node(*comment, context));
+        }
+        ends_with_newline = permitted_type
+            .trailing
+            .last()
+            .is_some_and(|c| c.kind() == "line_comment");
+    }
+    items.finish_indent();
+    items.finish_indent();
+
+    (items, ends_with_newline)
+}
+
+/// A type in a `permits` clause with the comments that belong to it.
+struct PermittedType<'a> {
+    leading: Vec<tree_sitter::Node<'a>>,
+    ty: tree_sitter::Node<'a>,
+    trailing: Vec<tree_sitter::Node<'a>>,
+}
+
+/// The comments of a `permits` clause: those around its types, and those
+/// between the clause and the body, which the parser puts after the clause.
+fn permits_comments(node: tree_sitter::Node) -> impl Iterator<Item = tree_sitter::Node> {
+    let mut cursor = node.walk();
+    let inner: Vec<_> = node
+        .children(&mut cursor)
+        .flat_map(|child| {
+            let mut cursor = child.walk();
+            if child.kind() == "type_list" {
+                child.children(&mut cursor).collect()
+            } else {
+                vec![child]
+            }
+        })
+        .filter(tree_sitter::Node::is_extra)
+        .collect();
+    let after = std::iter::successors(node.next_sibling(), tree_sitter::Node::next_sibling)
+        .take_while(tree_sitter::Node::is_extra);
+    inner.into_iter().chain(after)
+}
+
+fn has_comments(permits: tree_sitter::Node) -> bool {
+    permits_comments(permits).next().is_some()
+}
+
+/// Assign each comment of a `permits` clause to a permitted type: a comment
+/// on the row where the previous type ends trails that type, any other
+/// comment leads the next type, and comments after the last type trail it.
+fn permitted_types(node: tree_sitter::Node) -> Vec<PermittedType> {
+    let mut cursor = node.walk();
+    let types: Vec<_> = node
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "type_list")
+        .flat_map(|type_list| {
+            let mut cursor = type_list.walk();
+            type_list
+                .named_children(&mut cursor)
+                .filter(|c| !c.is_extra())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let mut permitted: Vec<PermittedType> = types
+        .into_iter()
+        .map(|ty| PermittedType {
+            leading: Vec::new(),
+            ty,
+            trailing: Vec::new(),
+        })
+        .collect();
+    for comment in permits_comments(node) {
+        let next = permitted
+            .iter()
+            .position(|p| p.ty.start_byte() >= comment.end_byte());
+        let previous = match next {
+            Some(0) => None,
+            Some(i) => Some(i - 1),
+            None => permitted.len().checked_sub(1),
+        };
+        let trails_previous = previous.is_some_and(|i| {
+            next.is_none()
+                || comment.start_position().row == permitted[i].ty.end_position().row
+                    && permitted[i + 1].leading.is_empty()
+        });
+        match (previous, next) {
+            (Some(i), _) if trails_previous => permitted[i].trailing.push(comment),
+            (_, Some(i)) => permitted[i].leading.push(comment),
+            _ => {}
+        }
+    }
+    permitted
+}
+
 /// Format a type list (comma-separated types).
 fn gen_type_list<'a>(
     node: tree_sitter::Node<'a>,
@@ -1203,7 +1437,94 @@ pub fn gen_class_body<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
-    gen_body_with_members(node, context)
+    if let Some(initializer) = double_brace_initializer(node)
+        && context.config.tight_double_brace_initialization
+    {
+        // `new HashMap<>() {{ put(..); }}`: keep the braces together and
+        // indent the statements once, as if the initializer were the body.
+        let mut items = PrintItems::new();
+        items.push_str("{");
+        items.extend(gen_node(initializer, context));
+        items.push_str("}");
+        return items;
+    }
+    if let Some(method) = single_line_anonymous_method(node, context) {
+        // `new Comparator<>() { public int compare(..) { return a - b; } }`
+        let mut items = PrintItems::new();
+        items.push_str("{ ");
+        items.extend(gen_node(method, context));
+        items.push_str(" }");
+        return items;
+    }
+    gen_body_with_members(node, None, context)
+}
+
+/// The only member of an anonymous class body that stays on one line: with
+/// `keepSimpleAnonymousClassesOnOneLine`, when the source has the body on
+/// one line, its member is a method without annotations whose block holds a
+/// single return or expression statement, and the line fits.
+pub(super) fn single_line_anonymous_method<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &FormattingContext,
+) -> Option<tree_sitter::Node<'a>> {
+    if !context.config.keep_simple_anonymous_classes_on_one_line
+        || node.parent()?.kind() != "object_creation_expression"
+        || node.start_position().row != node.end_position().row
+    {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let mut members = node.named_children(&mut cursor);
+    let method = MethodDecl::cast(members.next()?)?;
+    if members.next().is_some() || method.node.has_error() {
+        return None;
+    }
+    let mut cursor = method.node.walk();
+    let annotated = method.node.children(&mut cursor).any(|child| {
+        let mut cursor = child.walk();
+        matches!(child.kind(), "annotation" | "marker_annotation")
+            || (child.kind() == "modifiers"
+                && child
+                    .named_children(&mut cursor)
+                    .any(|m| matches!(m.kind(), "annotation" | "marker_annotation")))
+    });
+    let body = method.body?;
+    let mut cursor = body.walk();
+    let [stmt] = body.named_children(&mut cursor).collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    if annotated
+        || stmt.is_extra()
+        || !matches!(stmt.kind(), "return_statement" | "expression_statement")
+        || statements::has_nested_body(stmt)
+    {
+        return None;
+    }
+
+    // The body stays on the line the source has it on, so that line is
+    // what must fit
+    let row_start = context.source[..node.start_byte()]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let row_end = context.source[node.end_byte()..]
+        .find('\n')
+        .map_or(context.source.len(), |i| node.end_byte() + i);
+    let line = context.source[row_start..row_end].trim();
+    let indent = context.effective_indent_level() * context.indent_width();
+    (indent + collapse_whitespace_len(line) <= context.config.line_width as usize)
+        .then_some(method.node)
+}
+
+/// The instance initializer of a double-brace initialization: an anonymous
+/// class body whose only member is an initializer block, with no comments.
+pub(super) fn double_brace_initializer(node: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    if node.parent()?.kind() != "object_creation_expression" {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let mut members = node.named_children(&mut cursor);
+    let initializer = members.next().filter(|m| m.kind() == "block")?;
+    members.next().is_none().then_some(initializer)
 }
 
 /// Format an interface body.
@@ -1211,7 +1532,7 @@ pub fn gen_interface_body<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
-    gen_body_with_members(node, context)
+    gen_body_with_members(node, None, context)
 }
 
 /// Format an annotation type body.
@@ -1219,7 +1540,7 @@ pub fn gen_annotation_type_body<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
-    gen_body_with_members(node, context)
+    gen_body_with_members(node, None, context)
 }
 
 /// Format an enum body: `{ CONSTANT1, CONSTANT2; methods... }`
@@ -1274,28 +1595,48 @@ fn gen_enum_body<'a>(
         .find(|c| c.kind() == "{")
         .map(|c| c.end_position().row);
     let mut enum_prev_end_row: Option<usize> = enum_open_brace_row;
+    // A line comment already ends with a newline
+    let mut prev_was_line_comment = false;
 
     for child in &members {
         // Handle comments (extra nodes) without disrupting enum constant state
         if child.is_extra() {
-            items.newline();
-            // Preserve source blank lines before comments in enum body
-            if enum_prev_end_row.is_some_and(|r| child.start_position().row > r + 1) {
-                items.newline();
+            if context.comment_map().is_trailing(**child) && !prev_was_line_comment {
+                items.space();
+            } else {
+                if !prev_was_line_comment {
+                    items.newline();
+                }
+                // Preserve source blank lines before comments in enum body
+                items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+                    context,
+                    enum_prev_end_row,
+                    child.start_position().row,
+                )));
             }
             items.extend(gen_node(**child, context));
+            prev_was_line_comment = child.kind() == "line_comment";
             enum_prev_end_row = Some(child.end_position().row);
             continue;
         }
 
+        let after_line_comment = std::mem::take(&mut prev_was_line_comment);
         match child.kind() {
             "enum_constant" => {
-                items.newline();
-                // Preserve source blank lines before enum constants
-                if enum_prev_end_row.is_some_and(|r| child.start_position().row > r + 1) {
+                if !after_line_comment {
                     items.newline();
                 }
-                items.extend(gen_enum_constant(**child, context));
+                // Preserve source blank lines before enum constants
+                items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+                    context,
+                    enum_prev_end_row,
+                    child.start_position().row,
+                )));
+                if context.is_suppressed(**child) {
+                    items.extend(gen_verbatim_text(**child, context.source));
+                } else {
+                    items.extend(gen_enum_constant(**child, context));
+                }
                 constant_idx += 1;
                 let is_last = constant_idx == enum_constants.len();
                 if !is_last {
@@ -1310,11 +1651,15 @@ fn gen_enum_body<'a>(
             }
             "," => {
                 // Tree-sitter may emit commas as anonymous tokens; skip
-                // since we handle commas ourselves above.
+                // since we handle commas ourselves above. A comma on the
+                // line after a trailing comment still ends the constant, and
+                // the comment's newline still separates the next one.
+                prev_was_line_comment = after_line_comment;
+                enum_prev_end_row = Some(child.end_position().row);
             }
             ";" => {
                 // PJF puts the semicolon on its own line after the last constant
-                if prev_was_constant {
+                if prev_was_constant && !after_line_comment {
                     items.newline();
                 }
                 items.push_str(";");
@@ -1331,7 +1676,7 @@ fn gen_enum_body<'a>(
                 for decl_child in &decl_children {
                     if decl_child.kind() == ";" {
                         // PJF puts the semicolon on its own line when there's a trailing comma
-                        if prev_was_constant && has_trailing_comma {
+                        if prev_was_constant && has_trailing_comma && !after_line_comment {
                             items.newline();
                         }
                         items.push_str(";");
@@ -1340,14 +1685,21 @@ fn gen_enum_body<'a>(
                         continue;
                     }
                     if decl_child.is_extra() {
-                        if !decl_prev_was_line_comment {
-                            items.newline();
-                        }
-                        // Preserve source blank lines between comments
-                        if let Some(prev_row) = decl_prev_end_row
-                            && decl_child.start_position().row > prev_row + 1
+                        if context.comment_map().is_trailing(*decl_child)
+                            && !decl_prev_was_line_comment
                         {
-                            items.newline();
+                            // `LAST; // comment` keeps the comment after the `;`
+                            items.space();
+                        } else {
+                            if !decl_prev_was_line_comment {
+                                items.newline();
+                            }
+                            // Preserve source blank lines between comments
+                            items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+                                context,
+                                decl_prev_end_row,
+                                decl_child.start_position().row,
+                            )));
                         }
                         items.extend(gen_node(*decl_child, context));
                         decl_prev_was_line_comment = decl_child.kind() == "line_comment";
@@ -1359,15 +1711,16 @@ fn gen_enum_body<'a>(
                             items.newline();
                         }
                         // Blank line from source or from block member adjacency
-                        let source_blank = decl_prev_end_row
-                            .is_some_and(|prev| decl_child.start_position().row > prev + 1);
                         let block_blank = match decl_prev_was_block {
                             None => false,
                             Some(prev_b) => prev_b || is_block_member(decl_child),
                         };
-                        if source_blank || block_blank {
-                            items.newline();
-                        }
+                        items.extend(blank_lines::gen_blank_lines(blank_lines::between_members(
+                            context,
+                            decl_prev_end_row,
+                            decl_child.start_position().row,
+                            block_blank,
+                        )));
                         items.extend(gen_node(*decl_child, context));
                         decl_prev_was_line_comment = false;
                         decl_prev_was_block = Some(is_block_member(decl_child));
@@ -1394,13 +1747,21 @@ fn gen_enum_body<'a>(
     let _ = has_body_decls;
 
     items.finish_indent();
-    items.newline();
+    if !prev_was_line_comment {
+        items.newline();
+    }
+    items.extend(blank_lines::gen_blank_lines(blank_lines::before_class_end(
+        context,
+    )));
     items.push_str("}");
 
     items
 }
 
 /// Format a single enum constant.
+///
+/// Its annotations go on their own lines or before the name depending on
+/// `enumConstantAnnotations`; comments between them and the name are kept.
 fn gen_enum_constant<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -1410,12 +1771,26 @@ fn gen_enum_constant<'a>(
 
     for child in node.children(&mut cursor) {
         match child.kind() {
-            "modifiers" => {
-                let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
-                items.extend(modifier_items);
-                if !ends_with_newline {
+            "modifiers" => match context.config.enum_constant_annotations {
+                AnnotationPlacement::OwnLine => {
+                    let (modifier_items, ends_with_newline) = gen_modifiers(child, context);
+                    items.extend(modifier_items);
+                    if !ends_with_newline {
+                        items.space();
+                    }
+                }
+                AnnotationPlacement::Inline => {
+                    items.extend(gen_inline_modifiers(child, context));
                     items.space();
                 }
+            },
+            "line_comment" => {
+                // Ends with its own newline
+                items.extend(gen_node(child, context));
+            }
+            "block_comment" => {
+                items.extend(gen_node(child, context));
+                items.space();
             }
             "identifier" => {
                 items.extend(gen_node_text(child, context.source));
@@ -1491,46 +1866,92 @@ pub fn gen_formal_parameters<'a>(
         .enumerate()
         .map(|(i, p)| {
             let text = &context.source[p.start_byte()..p.end_byte()];
-            let flat: usize = text.lines().map(|l| l.trim().len()).sum();
+            let flat: usize = text.lines().map(|l| display_width(l.trim())).sum();
             flat + if i < params.len() - 1 { 2 } else { 0 }
         })
         .sum();
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    // A lambda's parameters can sit in a wrapped argument list or chain, so
+    // they start at the continuation indent those add.
+    let lambda = node.parent().filter(|p| p.kind() == "lambda_expression");
+    let indent_level = if lambda.is_some() {
+        context.effective_indent_level()
+    } else {
+        context.indent_level()
+    };
+    let indent_width = indent_level * context.indent_width();
 
     // Account for the prefix width (method name, return type, etc.) on the same line.
     // If the method name was wrapped to a continuation line, use the override prefix width.
-    let prefix_width = context.take_override_prefix_width().unwrap_or_else(|| {
-        estimate_prefix_width(node, context.source, context.is_assignment_wrapped())
-    });
-
-    // Suffix after closing paren: ") {" for methods/constructors with body (+4 for "(" + ") {"),
-    // ");" for abstract methods (+3 for "(" + ");"), default +4 for safety.
-    let suffix_width = match node.parent().map(|p| p.kind()) {
-        Some("method_declaration" | "constructor_declaration") => {
+    let prefix_width = context
+        .take_override_prefix_width()
+        .unwrap_or_else(|| estimate_prefix_width(node, context));
+
+    // Text after the last parameter: ") {" for methods/constructors with body,
+    // ");" for abstract methods, ") -> {" for lambdas with a block body and
+    // ") -> " and a lambda's expression body, which ends the line unless
+    // wrapping its arguments after the first `(` would shorten it, plus the
+    // `);` or `),` usually closing the call the lambda is passed to.
+    let close_width = match node.parent() {
+        Some(parent)
+            if matches!(
+                parent.kind(),
+                "method_declaration" | "constructor_declaration"
+            ) =>
+        {
             // Check if the method has a body (block) or throws clause following params.
             // If it has a throws clause, that adds more but wraps separately.
             // Just account for the `) {` or `);` suffix.
-            let parent = node.parent().unwrap();
             let has_body = parent.child_by_field_name("body").is_some();
-            if has_body { 4 } else { 3 } // "() {" vs "();"
+            if has_body { 3 } else { 2 }
+        }
+        Some(parent) if parent.kind() == "lambda_expression" => {
+            match parent.child_by_field_name("body") {
+                Some(body) if body.kind() == "block" => 6,
+                Some(body) => {
+                    let text = &context.source[body.byte_range()];
+                    let body_width = collapse_whitespace_len(text);
+                    let head_width = text
+                        .find('(')
+                        .map_or(body_width, |i| collapse_whitespace_len(&text[..=i]));
+                    let wraps = body_width - head_width > context.continuation_width();
+                    if wraps {
+                        5 + head_width
+                    } else {
+                        7 + body_width
+                    }
+                }
+                None => 4,
+            }
         }
-        _ => 2, // Just "()" for other contexts
+        _ => 1, // Just ")" for other contexts
     };
+    let suffix_width = 1 + close_width; // "(" + the close
 
-    let should_wrap = has_interleaved_comments
-        || indent_width + prefix_width + param_text_width + suffix_width
-            > context.config.line_width as usize;
+    let inline_width = indent_width + prefix_width + param_text_width + suffix_width;
+    let should_wrap = has_interleaved_comments || inline_width > context.config.line_width as usize;
 
     items.push_str("(");
+    if should_wrap {
+        items.extend(context.wrap_reason("params", || {
+            if has_interleaved_comments {
+                "comments".to_string()
+            } else {
+                format!("width={inline_width}>{}", context.config.line_width)
+            }
+        }));
+    }
 
     if should_wrap {
         // PJF bin-packing: first try putting ALL params on one continuation line.
         // If they fit, use single-line continuation. If not, fall back to one-per-line.
-        let continuation_col = indent_width + 2 * (context.config.indent_width as usize);
-        // Account for suffix after ): typically " {" for methods/constructors = 3 chars (") {")
-        // PJF allows lines up to exactly line_width (120), so use <= not <
+        let continuation_col = indent_width + context.continuation_width();
+        // Account for suffix after ): typically " {" for methods/constructors = 3 chars (") {"),
+        // and the arrow for lambdas.
+        // PJF allows lines up to exactly line_width, so use <= not <
+        let continuation_close_width = if lambda.is_some() { close_width } else { 3 };
         let all_fit_continuation = !has_interleaved_comments
-            && continuation_col + param_text_width + 3 <= context.config.line_width as usize;
+            && continuation_col + param_text_width + continuation_close_width
+                <= context.config.line_width as usize;
 
         // 2x StartIndent for 8-space continuation indent
         items.start_indent();
@@ -1548,7 +1969,7 @@ pub fn gen_formal_parameters<'a>(
             }
         } else {
             // One-per-line (too long even at continuation indent)
-            let continuation_col = indent_width + 2 * (context.config.indent_width as usize);
+            let continuation_col = indent_width + context.continuation_width();
             for (i, param) in params.iter().enumerate() {
                 // Emit any comments that precede this parameter
                 let has_preceding_comment = comments_before_param.contains_key(&param.start_byte());
@@ -1566,7 +1987,8 @@ pub fn gen_formal_parameters<'a>(
                 // Check if this param exceeds line_width at continuation indent.
                 // If so, split after annotations: put type+name on next line at +8.
                 let param_text = &context.source[param.start_byte()..param.end_byte()];
-                let param_flat_width: usize = param_text.lines().map(|l| l.trim().len()).sum();
+                let param_flat_width: usize =
+                    param_text.lines().map(|l| display_width(l.trim())).sum();
                 let suffix = usize::from(i < params.len() - 1); // comma
                 if continuation_col + param_flat_width + suffix > context.config.line_width as usize
                 {
@@ -1664,13 +2086,13 @@ fn gen_throws<'a>(node: tree_sitter::Node<'a>, context: &mut FormattingContext<'
         .enumerate()
         .map(|(i, t)| {
             let text = &context.source[t.start_byte()..t.end_byte()];
-            text.len() + if i < types.len() - 1 { 2 } else { 0 } // ", "
+            display_width(text) + if i < types.len() - 1 { 2 } else { 0 } // ", "
         })
         .sum();
 
     // Use effective indent level to account for continuation indent when throws
     // is on a wrapped line. Add "throws " (7) prefix and " {" (2) suffix.
-    let indent_width = context.effective_indent_level() * context.config.indent_width as usize;
+    let indent_width = context.effective_indent_level() * context.indent_width();
     let line_width = context.config.line_width as usize;
 
     // Check if the full throws clause fits on the current line.
@@ -1682,11 +2104,11 @@ fn gen_throws<'a>(node: tree_sitter::Node<'a>, context: &mut FormattingContext<'
 
     if needs_wrap && types.len() > 1 {
         // Bin-pack exceptions: fill up the current line, then wrap remaining
-        let continuation_col = indent_width + 2 * (context.config.indent_width as usize);
+        let continuation_col = indent_width + context.continuation_width();
         let mut current_line_width = indent_width + 7; // "throws "
         for (i, typ) in types.iter().enumerate() {
             let text = &context.source[typ.start_byte()..typ.end_byte()];
-            let type_width = text.len();
+            let type_width = display_width(text);
 
             if i > 0 && current_line_width + type_width + 2 > line_width {
                 // +2 for suffix (" {" or ", "). Wrap to continuation line.
@@ -1780,10 +2202,8 @@ pub fn gen_variable_declarator<'a>(
             let val_text = &context.source[val.start_byte()..val.end_byte()];
             let rhs_flat_width = collapse_whitespace_len(val_text);
 
-            let indent_unit = context.config.indent_width as usize;
-            let indent_col = context.indent_level() * indent_unit;
-            // Continuation indent: current indent + 2 indent units (double indent for wrapping)
-            let continuation_indent = indent_col + indent_unit * 2;
+            let indent_col = context.indent_level() * context.indent_width();
+            let continuation_indent = indent_col + context.continuation_width();
             let line_width = context.config.line_width as usize;
 
             // Compute LHS width: type + variable name (everything before the `=` sign).
@@ -1823,13 +2243,19 @@ pub fn gen_variable_declarator<'a>(
                         break;
                     }
 
-                    // Accumulate width from type, modifiers, etc. before variable_declarator
+                    // Accumulate width from type, modifiers, etc. before variable_declarator;
+                    // annotations print on lines of their own
                     if c.is_named() {
-                        let text = &context.source[c.start_byte()..c.end_byte()];
-                        if w > 0 {
+                        let text = if c.kind() == "modifiers" {
+                            declaration_line_text(parent, c.end_byte(), context.source)
+                        } else {
+                            Cow::Borrowed(&context.source[c.start_byte()..c.end_byte()])
+                        };
+                        let text_width = collapse_whitespace_len(&text);
+                        if w > 0 && text_width > 0 {
                             w += 1;
                         } // space between tokens
-                        w += collapse_whitespace_len(text);
+                        w += text_width;
                     }
                 }
                 w
@@ -1850,7 +2276,7 @@ pub fn gen_variable_declarator<'a>(
             };
 
             // PJF-style chain assignment: prefer wrapping at '=' over wrapping the chain.
-            // Use flatten_chain to get the TRUE chain root and first segment.
+            // Flatten the chain to get the TRUE chain root and first segment.
             let is_chain = val.kind() == "method_invocation" && expressions::chain_depth(*val) >= 1;
 
             if is_chain {
@@ -1878,8 +2304,7 @@ pub fn gen_variable_declarator<'a>(
                     } else {
                         // Chain would wrap at current position. Check if it fits
                         // inline at continuation indent — if so, wrap at '='.
-                        let continuation_col =
-                            indent_col + 2 * (context.config.indent_width as usize);
+                        let continuation_col = indent_col + context.continuation_width();
                         expressions::chain_fits_inline_at(
                             *val,
                             continuation_col,
@@ -1926,8 +2351,9 @@ pub fn gen_variable_declarator<'a>(
                             // `LHS = opening(` inline also exceeds line_width.
                             // If so, we must wrap at `=` to avoid >line_width lines.
                             let rhs_text = &context.source[val.start_byte()..val.end_byte()];
-                            let rhs_opening_width =
-                                rhs_text.find('(').map_or(rhs_flat_width, |p| p + 1);
+                            let rhs_opening_width = rhs_text
+                                .find('(')
+                                .map_or(rhs_flat_width, |p| display_width(&rhs_text[..=p]));
                             let opening_line_width = indent_col + lhs_width + 3 + rhs_opening_width;
                             opening_line_width > line_width
                         } else {
@@ -1945,9 +2371,12 @@ pub fn gen_variable_declarator<'a>(
     let mut cursor2 = node.walk();
     for child in node.children(&mut cursor2) {
         match child.kind() {
-            "identifier" | "dimensions" => {
+            "identifier" => {
                 items.extend(gen_node_text(child, context.source));
             }
+            "dimensions" => {
+                items.extend(gen_node(child, context));
+            }
             "=" => {
                 items.space();
                 items.push_str("=");
@@ -1964,11 +2393,10 @@ pub fn gen_variable_declarator<'a>(
                 // If we wrapped at '=', tell downstream that the assignment is
                 // on a different line (prefix width should not include LHS)
                 if wrap_value && saw_eq {
-                    context.set_assignment_wrapped(true);
-                }
-                items.extend(gen_node(child, context));
-                if wrap_value && saw_eq {
-                    context.set_assignment_wrapped(false);
+                    let mut wrapped = context.with_flag(ContextFlag::AssignmentWrapped, true);
+                    items.extend(gen_node(child, &mut wrapped.with_continuation_indent()));
+                } else {
+                    items.extend(gen_node(child, context));
                 }
             }
             _ => {}
@@ -1983,6 +2411,19 @@ pub fn gen_variable_declarator<'a>(
     items
 }
 
+/// Which check wrapped an argument list after its `(`, for `debug_wrap_reasons`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgListWrap {
+    /// The arguments do not fit on the line.
+    Width,
+    /// Comments between the arguments.
+    Comments,
+    /// A chain in the arguments has a dot past `methodChainThreshold`.
+    ChainDot,
+    /// A chain broken for its number of calls sits beside other arguments.
+    ChainCalls,
+}
+
 /// Format an argument list: `(arg1, arg2, arg3)`
 ///
 /// Wraps with 8-space continuation indent when the argument list would
@@ -1991,6 +2432,8 @@ pub fn gen_variable_declarator<'a>(
 ///
 /// When wrapping, uses PJF-style "bin-packing": tries to fit all args on one
 /// continuation line first, only putting each arg on its own line if they don't fit.
+/// Arguments that are all plain names and literals leave the choice between
+/// the first two layouts to the printer, which measures from the real column.
 #[allow(clippy::too_many_lines)]
 pub fn gen_argument_list<'a>(
     node: tree_sitter::Node<'a>,
@@ -2005,27 +2448,7 @@ pub fn gen_argument_list<'a>(
         .filter(|c| c.is_named() && !c.is_extra())
         .collect();
 
-    // Collect comment (extra) nodes between arguments, keyed by the byte offset
-    // of the NEXT named arg they precede. Comments before the first arg are keyed
-    // by the first arg's start_byte.
-    let mut comments_before_arg: std::collections::HashMap<usize, Vec<tree_sitter::Node>> =
-        std::collections::HashMap::new();
-    {
-        let mut pending_comments: Vec<tree_sitter::Node> = Vec::new();
-        for child in &children {
-            if child.is_extra() {
-                pending_comments.push(*child);
-            } else if child.is_named() && !pending_comments.is_empty() {
-                comments_before_arg.insert(child.start_byte(), pending_comments.clone());
-                pending_comments.clear();
-            }
-        }
-        // Comments after the last arg (before ')') — attach to a sentinel key
-        if !pending_comments.is_empty() {
-            comments_before_arg.insert(usize::MAX, pending_comments);
-        }
-    }
-    let has_interleaved_comments = !comments_before_arg.is_empty();
+    let has_interleaved_comments = children.iter().any(tree_sitter::Node::is_extra);
 
     // Estimate the "flat" width of arguments (stripping embedded newlines).
     // For lambda expressions with block bodies, only count the header (params -> {)
@@ -2051,17 +2474,19 @@ pub fn gen_argument_list<'a>(
                         if child.kind() == "->" {
                             header_width += 4; // " -> "
                         } else {
-                            header_width += text.len();
+                            header_width += display_width(text);
                         }
                     }
                     header_width
                 } else {
                     let text = &context.source[a.start_byte()..a.end_byte()];
-                    text.lines().map(|l| l.trim().len()).sum()
+                    text.lines().map(|l| display_width(l.trim())).sum()
                 }
+            } else if a.kind() == "method_reference" {
+                expressions::method_reference_width(**a, context.source)
             } else {
                 let text = &context.source[a.start_byte()..a.end_byte()];
-                text.lines().map(|l| l.trim().len()).sum()
+                text.lines().map(|l| display_width(l.trim())).sum()
             };
             width + if i < args.len() - 1 { 2 } else { 0 }
         })
@@ -2077,13 +2502,14 @@ pub fn gen_argument_list<'a>(
                 .child_by_field_name("object")
                 .is_some_and(|obj| obj.kind() == "method_invocation")
                 || p.parent()
-                    .is_some_and(|gp| gp.kind() == "method_invocation"))
+                    .is_some_and(|gp| gp.kind() == "method_invocation")
+                || expressions::is_rethrown_creation(p))
     });
 
     // Use effective indent level (including continuation indent from wrapped chains
     // and wrapped argument lists) to get the true column position.
     let indent_level = context.effective_indent_level();
-    let indent_width = indent_level * context.config.indent_width as usize;
+    let indent_width = indent_level * context.indent_width();
     let prefix_width = if is_in_chain {
         // Inside a chain, the chain wrapper handles overall layout.
         // Use only the immediate method/constructor name as prefix, not the full chain text.
@@ -2092,7 +2518,7 @@ pub fn gen_argument_list<'a>(
             .and_then(|p| p.child_by_field_name("name"))
             .map_or(0, |n| {
                 let text = &context.source[n.start_byte()..n.end_byte()];
-                text.len()
+                display_width(text)
             });
         let type_args_width = parent_node
             .and_then(|p| p.child_by_field_name("type_arguments"))
@@ -2104,14 +2530,16 @@ pub fn gen_argument_list<'a>(
     } else {
         // Check if the caller (e.g., an outer gen_argument_list) set an override
         // to communicate the true column position for nested calls.
-        context.take_override_prefix_width().unwrap_or_else(|| {
-            estimate_prefix_width(node, context.source, context.is_assignment_wrapped())
-        })
+        context
+            .take_override_prefix_width()
+            .unwrap_or_else(|| estimate_prefix_width(node, context))
     };
 
     // For single-arg calls where the arg is itself a call expression,
     // compute the "head width" (up to the inner call's opening paren).
     // PJF keeps `outer(inner(` on one line and lets the inner call wrap.
+    // A lambda with typed parameters likewise keeps `outer((` and wraps its
+    // parameters.
     let single_arg_head_width: Option<usize> = if args.len() == 1
         && matches!(
             args[0].kind(),
@@ -2121,6 +2549,11 @@ pub fn gen_argument_list<'a>(
             let head_text = &context.source[args[0].start_byte()..arg_args.start_byte()];
             collapse_whitespace_len(head_text) + 1 // +1 for "("
         })
+    } else if args.len() == 1 && args[0].kind() == "lambda_expression" {
+        args[0]
+            .child_by_field_name("parameters")
+            .filter(|params| params.kind() == "formal_parameters")
+            .map(|_| 1)
     } else {
         None
     };
@@ -2135,7 +2568,7 @@ pub fn gen_argument_list<'a>(
         // Single-arg method/constructor: PJF's approach —
         // 1. If the full arg fits on a continuation line, wrap at outer level (normal)
         // 2. If it doesn't fit, keep outer(inner( inline and let inner wrap
-        let continuation_indent = indent_width + (2 * context.config.indent_width as usize);
+        let continuation_indent = indent_width + context.continuation_width();
         let arg_fits_on_continuation =
             continuation_indent + args_flat_width + 1 < context.config.line_width as usize;
         if arg_fits_on_continuation {
@@ -2153,23 +2586,27 @@ pub fn gen_argument_list<'a>(
         indent_width + prefix_width + args_flat_width + 2 < context.config.line_width as usize
     };
 
+    // Which check forced the wrap, for `debug_wrap_reasons`.
+    let mut wrap_cause = ArgListWrap::Width;
+
     // Comments between arguments force one-per-line wrapping
     if has_interleaved_comments {
         fits_on_one_line = false;
+        wrap_cause = ArgListWrap::Comments;
     }
 
     // PJF's preferBreakingLastInnerLevel: if any arg contains a method chain whose
-    // last dot would exceed METHOD_CHAIN_COLUMN_LIMIT (80), force wrapping.
+    // last dot would exceed `method_chain_threshold` (PJF's METHOD_CHAIN_COLUMN_LIMIT), force wrapping.
     // Check at both inline and continuation positions.
-    let chain_threshold = context.config.method_chain_threshold as usize;
+    let chain_threshold = context.config.chain_threshold();
 
     // Helper: check if any arg's chain dot exceeds threshold at given base column
     let exceeds_chain_limit = |base_col: usize| -> bool {
         let mut col = base_col;
         for arg in &args {
             let text = &context.source[arg.start_byte()..arg.end_byte()];
-            let arg_width: usize = text.lines().map(|l| l.trim().len()).sum();
-            let dot_pos = super::expressions::rightmost_chain_dot(**arg, context.source, col);
+            let arg_width: usize = text.lines().map(|l| display_width(l.trim())).sum();
+            let dot_pos = super::expressions::rightmost_chain_dot(**arg, context, col);
             if dot_pos > chain_threshold {
                 return true;
             }
@@ -2178,7 +2615,7 @@ pub fn gen_argument_list<'a>(
         false
     };
 
-    // Check at inline position: if chain dots exceed 80, break after "("
+    // Check at inline position: if chain dots exceed the threshold, break after "("
     // Skip for single-arg long chains (depth >= 3) — they will wrap at their
     // own dots, so forcing arg-list wrapping is unnecessary. Short chains
     // (depth 1-2) might stay inline, so the chain limit check still applies.
@@ -2191,19 +2628,32 @@ pub fn gen_argument_list<'a>(
         && exceeds_chain_limit(indent_width + prefix_width)
     {
         fits_on_one_line = false;
+        wrap_cause = ArgListWrap::ChainDot;
+    }
+
+    // A chain broken whatever its width, for its number of calls or as a
+    // stream pipeline, spans several lines, so the arguments beside it go one
+    // per line
+    let has_call_count_chain = args.len() > 1
+        && args.iter().any(|arg| {
+            expressions::breaks_regardless_of_width(**arg, context.source, context.config)
+        });
+    if fits_on_one_line && has_call_count_chain {
+        fits_on_one_line = false;
+        wrap_cause = ArgListWrap::ChainCalls;
     }
 
     // If not, check if args fit on ONE continuation line (8-space indent = 2 levels of indent_width)
-    let continuation_indent = indent_width + (2 * context.config.indent_width as usize);
-    let mut fits_on_continuation_line =
-        continuation_indent + args_flat_width + 1 < context.config.line_width as usize;
+    let continuation_indent = indent_width + context.continuation_width();
+    let mut fits_on_continuation_line = !has_call_count_chain
+        && continuation_indent + args_flat_width + 1 < context.config.line_width as usize;
 
     // Comments between arguments force one-per-line (can't bin-pack with comments)
     if has_interleaved_comments {
         fits_on_continuation_line = false;
     }
 
-    // Also check at continuation position: if chain dots still exceed 80, force one-per-line
+    // Also check at continuation position: if chain dots still exceed the threshold, force one-per-line
     if !fits_on_one_line
         && fits_on_continuation_line
         && args.len() > 1
@@ -2212,7 +2662,53 @@ pub fn gen_argument_list<'a>(
         fits_on_continuation_line = false;
     }
 
+    // Names and literals print as written wherever they go, so whether they
+    // stay on the line of `(` or move to one continuation line is left to
+    // the printer, which knows the column `(` ends up at
+    let printer_decides = !is_in_chain
+        && !has_interleaved_comments
+        && wrap_cause == ArgListWrap::Width
+        && fits_on_continuation_line
+        && args.iter().all(|arg| is_plain_arg(**arg, context.source));
+
     items.push_str("(");
+    if printer_decides {
+        let mut flat = gen_args_on_one_line(&args, context);
+        flat.push_str(")");
+        let mut expanded =
+            context.wrap_reason("argList", || format!("width>{}", context.config.line_width));
+        expanded.start_indent();
+        expanded.start_indent();
+        expanded.newline();
+        expanded.extend(gen_args_on_one_line(
+            &args,
+            &mut context.with_continuation_indent(),
+        ));
+        expanded.push_str(")");
+        expanded.finish_indent();
+        expanded.finish_indent();
+        // The arguments and `)` end short of `line_width`, as the estimate
+        // for the other layouts requires
+        items.extend(gen_if_fits(
+            flat,
+            args_flat_width + 2,
+            expanded,
+            context.config.line_width,
+        ));
+        return items;
+    }
+    if !fits_on_one_line {
+        items.extend(context.wrap_reason("argList", || match wrap_cause {
+            ArgListWrap::Comments => "comments".to_string(),
+            ArgListWrap::ChainCalls => "chainCalls".to_string(),
+            ArgListWrap::ChainDot => format!("chainDot>{chain_threshold}"),
+            ArgListWrap::Width => format!(
+                "width={}>{}",
+                indent_width + prefix_width + args_flat_width + 2,
+                context.config.line_width
+            ),
+        }));
+    }
 
     if fits_on_one_line {
         // Keep all args on the same line as the opening paren.
@@ -2221,10 +2717,14 @@ pub fn gen_argument_list<'a>(
         // call knows its true column position for wrapping decisions.
         // Don't set override in chain context — chains handle their own layout.
         if !is_in_chain && let Some(head_width) = single_arg_head_width {
-            let continuation_indent = indent_width + (2 * context.config.indent_width as usize);
+            let continuation_indent = indent_width + context.continuation_width();
             let arg_fits_on_continuation =
                 continuation_indent + args_flat_width + 1 < context.config.line_width as usize;
             if !arg_fits_on_continuation {
+                // A chain measures its own head from where it starts
+                let is_chain = args[0].kind() == "method_invocation"
+                    && super::expressions::chain_depth(*args[0]) >= 1;
+                let head_width = if is_chain { 1 } else { head_width };
                 context.set_override_prefix_width(Some(prefix_width + head_width));
             }
         }
@@ -2244,7 +2744,8 @@ pub fn gen_argument_list<'a>(
         items.start_indent();
         items.start_indent();
         items.newline();
-        context.add_continuation_indent(2);
+        let mut continued = context.with_continuation_indent();
+        let context = &mut *continued;
         for (i, arg) in args.iter().enumerate() {
             items.extend(gen_node(**arg, context));
             if i < args.len() - 1 {
@@ -2252,7 +2753,32 @@ pub fn gen_argument_list<'a>(
                 items.space();
             }
         }
-        context.remove_continuation_indent(2);
+        items.push_str(")");
+        items.finish_indent();
+        items.finish_indent();
+    } else if let Some(line_counts) = balanced_arg_lines(&args, continuation_indent, context)
+        .filter(|_| !has_interleaved_comments && !exceeds_chain_limit(continuation_indent))
+    {
+        // argumentPacking: "balanced" — spread the args over the fewest
+        // continuation lines, with break points chosen to even out line lengths.
+        items.start_indent();
+        items.start_indent();
+        let mut continued = context.with_continuation_indent();
+        let context = &mut *continued;
+        let mut remaining = args.iter();
+        for (line, count) in line_counts.iter().enumerate() {
+            items.newline();
+            for (i, arg) in remaining.by_ref().take(*count).enumerate() {
+                items.extend(gen_node(**arg, context));
+                if i < count - 1 {
+                    items.push_str(",");
+                    items.space();
+                }
+            }
+            if line < line_counts.len() - 1 {
+                items.push_str(",");
+            }
+        }
         items.push_str(")");
         items.finish_indent();
         items.finish_indent();
@@ -2260,29 +2786,46 @@ pub fn gen_argument_list<'a>(
         // Args don't fit on one continuation line, put each arg on its own line
         items.start_indent();
         items.start_indent();
-        context.add_continuation_indent(2);
+        let mut continued = context.with_continuation_indent();
+        let context = &mut *continued;
+        // A line comment ends its own line, so whatever follows it needs no newline
+        let comment_map = context.comment_map();
+        let mut line_ended = false;
+        for comment in comment_map.after_open(node) {
+            items.space();
+            items.extend(gen_node(*comment, context));
+            line_ended = comment.kind() == "line_comment";
+        }
         for (i, arg) in args.iter().enumerate() {
-            // Emit any comments that precede this arg
-            if let Some(comments) = comments_before_arg.get(&arg.start_byte()) {
-                for comment in comments {
+            for comment in comment_map.leading(**arg) {
+                if !line_ended {
                     items.newline();
-                    items.extend(gen_node(*comment, context));
                 }
+                items.extend(gen_node(*comment, context));
+                line_ended = comment.kind() == "line_comment";
+            }
+            if !line_ended {
+                items.newline();
             }
-            items.newline();
             items.extend(gen_node(**arg, context));
+            line_ended = false;
             if i < args.len() - 1 {
                 items.push_str(",");
             }
+            for comment in comment_map.trailing(**arg) {
+                items.space();
+                items.extend(gen_node(*comment, context));
+                line_ended = comment.kind() == "line_comment";
+            }
         }
-        // Emit any trailing comments (after last arg, before ')')
-        if let Some(comments) = comments_before_arg.get(&usize::MAX) {
-            for comment in comments {
+        // Comments after the last arg, before `)`
+        for comment in comment_map.dangling(node) {
+            if !line_ended {
                 items.newline();
-                items.extend(gen_node(*comment, context));
             }
+            items.extend(gen_node(*comment, context));
+            line_ended = comment.kind() == "line_comment";
         }
-        context.remove_continuation_indent(2);
         items.push_str(")");
         items.finish_indent();
         items.finish_indent();
@@ -2291,6 +2834,71 @@ pub fn gen_argument_list<'a>(
     items
 }
 
+/// Whether `arg` is a name or a literal on one line, which prints as written.
+fn is_plain_arg(arg: tree_sitter::Node, source: &str) -> bool {
+    matches!(
+        arg.kind(),
+        "identifier"
+            | "this"
+            | "null_literal"
+            | "true"
+            | "false"
+            | "character_literal"
+            | "string_literal"
+            | "decimal_integer_literal"
+            | "hex_integer_literal"
+            | "octal_integer_literal"
+            | "binary_integer_literal"
+            | "decimal_floating_point_literal"
+            | "hex_floating_point_literal"
+    ) && !source[arg.byte_range()].contains('\n')
+}
+
+/// `args` separated by `, ` on one line.
+fn gen_args_on_one_line<'a>(
+    args: &[&tree_sitter::Node<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    for (i, arg) in args.iter().enumerate() {
+        items.extend(gen_node(**arg, context));
+        if i < args.len() - 1 {
+            items.push_str(",");
+            items.space();
+        }
+    }
+    items
+}
+
+/// Line layout for `argumentPacking: "balanced"`: how many arguments go on each
+/// continuation line. Returns `None` when the option is off or when an argument
+/// cannot be treated as a single flat token (lambdas, bodies in braces, or args
+/// too wide for a line), in which case the caller falls back to one per line.
+fn balanced_arg_lines(
+    args: &[&tree_sitter::Node],
+    continuation_indent: usize,
+    context: &FormattingContext,
+) -> Option<Vec<usize>> {
+    if context.config.argument_packing != ArgumentPacking::Balanced || args.len() < 3 {
+        return None;
+    }
+    let mut widths = Vec::with_capacity(args.len());
+    for arg in args {
+        let text = &context.source[arg.start_byte()..arg.end_byte()];
+        if arg.kind() == "lambda_expression" || text.contains('{') {
+            return None;
+        }
+        widths.push(if arg.kind() == "method_reference" {
+            expressions::method_reference_width(**arg, context.source)
+        } else {
+            collapse_whitespace_len(text)
+        });
+    }
+    // Every line ends in "," or ")" and must stay strictly under line_width.
+    let capacity = (context.config.line_width as usize).checked_sub(continuation_indent + 2)?;
+    balance_lines(&widths, 2, capacity)
+}
+
 /// Generic handler for bodies with member declarations (`class_body`, `interface_body`, etc.)
 ///
 /// Uses dprint-core's StartIndent/FinishIndent signals so that `NewLine`
@@ -2323,68 +2931,65 @@ fn is_block_member(node: &tree_sitter::Node) -> bool {
 
 fn gen_body_with_members<'a>(
     node: tree_sitter::Node<'a>,
+    policy: Option<MemberPolicy>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
     let mut items = PrintItems::new();
     items.push_str("{");
 
+    let comment_map = context.comment_map();
     let mut cursor = node.walk();
-    let children: Vec<_> = node.children(&mut cursor).collect();
-
-    // Include both named members and extra (comment) nodes, excluding braces
-    let members: Vec<_> = children
-        .iter()
-        .filter(|c| c.kind() != "{" && c.kind() != "}" && (c.is_named() || c.is_extra()))
+    let members: Vec<_> = node
+        .named_children(&mut cursor)
+        .filter(|c| !c.is_extra())
         .collect();
+    let after_open = comment_map.after_open(node);
+    let dangling = comment_map.dangling(node);
 
-    if members.is_empty() {
+    if members.is_empty() && after_open.is_empty() && dangling.is_empty() {
         items.push_str("}");
         return items;
     }
 
     items.start_indent();
-    context.indent();
+    let mut indented = context.indented(1);
+    let context = &mut *indented;
 
     let mut prev_was_line_comment = false;
     // Track whether previous member was a block member (has body ending with })
     let mut prev_was_block: Option<bool> = None; // None = first member after {
+    let mut prev_was_field = false;
     // Track whether there was a comment between the previous member and current
     let mut had_comment_since_last_member = false;
-    // Initialize to opening `{` row so we can detect source blank lines before first member
-    let open_brace_row = children
-        .iter()
-        .find(|c| c.kind() == "{")
+    // Initialize to opening `{` row so we can detect source blank lines before
+    // first member. A double-brace initializer sits right under its `{`.
+    let open_brace_row = node
+        .child(0)
+        .filter(|c| c.kind() == "{")
+        .filter(|_| double_brace_initializer(node).is_none())
         .map(|c| c.end_position().row);
     let mut prev_end_row: Option<usize> = open_brace_row;
 
-    for member in members.iter() {
-        if member.is_extra() {
-            let is_trailing = comments::is_trailing_comment(**member);
-            if is_trailing {
-                // Trailing comment: append on same line
-                items.space();
-                items.extend(gen_node(**member, context));
-                prev_was_line_comment = member.kind() == "line_comment";
-            } else {
-                // Leading/standalone comment within body
-                if !prev_was_line_comment {
-                    items.newline();
-                }
-                // Add blank line before comment only if source has one.
-                // PJF does NOT automatically add blanks before comments (javadoc etc.)
-                // between block members — that blank is added before the actual member, not
-                // before its leading comment.
-                let source_has_blank =
-                    prev_end_row.is_some_and(|prev_row| member.start_position().row > prev_row + 1);
-                if source_has_blank {
-                    items.newline();
-                }
-                items.extend(gen_node(**member, context));
-                prev_was_line_comment = member.kind() == "line_comment";
-                prev_end_row = Some(member.end_position().row);
-                had_comment_since_last_member = true;
-            }
-            continue;
+    for comment in after_open {
+        items.space();
+        items.extend(gen_node(*comment, context));
+        prev_was_line_comment = comment.kind() == "line_comment";
+    }
+
+    for member in &members {
+        for comment in comment_map.leading(*member) {
+            // PJF does NOT automatically add blanks before comments (javadoc
+            // etc.) between block members — that blank is added before the
+            // actual member, not before its leading comment.
+            items.extend(comments::gen_own_line_comment(
+                *comment,
+                prev_was_line_comment,
+                prev_end_row,
+                context,
+            ));
+            prev_was_line_comment = comment.kind() == "line_comment";
+            prev_end_row = Some(comment.end_position().row);
+            had_comment_since_last_member = true;
         }
 
         if !prev_was_line_comment {
@@ -2395,37 +3000,70 @@ fn gen_body_with_members<'a>(
         // - Between block members (prev or cur has body ending with }), but ONLY if no
         //   comment intervened — PJF treats javadoc+method as one unit and doesn't add
         //   blank between end of javadoc and the method's annotation/modifiers.
-        let source_has_blank =
-            prev_end_row.is_some_and(|prev_row| member.start_position().row > prev_row + 1);
-        let block_blank = if had_comment_since_last_member {
-            false // comment between members: no automatic blank
-        } else {
-            match prev_was_block {
-                None => false,
-                Some(prev_block) => {
-                    let cur_is_block = is_block_member(member);
-                    prev_block || cur_is_block
+        // `annotationPolicies` can turn off the automatic blank between block
+        // members, or the source blank between two fields.
+        let compact_fields = policy == Some(MemberPolicy::CompactFields)
+            && member.kind() == "field_declaration"
+            && prev_was_field
+            && !had_comment_since_last_member;
+        let block_blank =
+            if had_comment_since_last_member || policy == Some(MemberPolicy::SourceBlankLines) {
+                false // comment between members or a member policy: no automatic blank
+            } else {
+                match prev_was_block {
+                    None => false,
+                    Some(prev_block) => {
+                        let cur_is_block = is_block_member(member);
+                        prev_block || cur_is_block
+                    }
                 }
-            }
+            };
+        let blank_lines = if compact_fields {
+            0
+        } else {
+            blank_lines::between_members(
+                context,
+                prev_end_row,
+                member.start_position().row,
+                block_blank,
+            )
         };
-        if source_has_blank || block_blank {
-            items.newline();
-        }
-        items.extend(gen_node(**member, context));
+        items.extend(blank_lines::gen_blank_lines(blank_lines));
+        items.extend(gen_node(*member, context));
 
         prev_was_line_comment = false;
         prev_was_block = Some(is_block_member(member));
+        prev_was_field = member.kind() == "field_declaration";
         prev_end_row = Some(member.end_position().row);
         had_comment_since_last_member = false;
+
+        for comment in comment_map.trailing(*member) {
+            items.space();
+            items.extend(gen_node(*comment, context));
+            prev_was_line_comment = comment.kind() == "line_comment";
+        }
+    }
+
+    for comment in dangling {
+        items.extend(comments::gen_own_line_comment(
+            *comment,
+            prev_was_line_comment,
+            prev_end_row,
+            context,
+        ));
+        prev_was_line_comment = comment.kind() == "line_comment";
+        prev_end_row = Some(comment.end_position().row);
     }
 
     items.finish_indent();
-    context.dedent();
     if !prev_was_line_comment {
         items.newline();
     }
     // PJF removes source blank lines before closing `}` in class bodies.
     // (Statement blocks preserve them — handled separately in statements.rs.)
+    items.extend(blank_lines::gen_blank_lines(blank_lines::before_class_end(
+        context,
+    )));
     items.push_str("}");
 
     items
@@ -1,20 +1,16 @@
 use dprint_core::formatting::PrintItems;
 
+use crate::ast::{ChainSegment, MethodChain, trailing_line_comment};
+use crate::configuration::MethodChainWrapStyle;
+
 use super::comments::{gen_block_comment, gen_line_comment};
-use super::context::FormattingContext;
+use super::context::{ContextFlag, FormattingContext};
 use super::declarations;
-use super::generate::gen_node;
-use super::helpers::{PrintItemsExt, collapse_whitespace_len, gen_node_text};
-
-/// A segment of a flattened method invocation chain.
-///
-/// Represents one `.method(args)` call in a chain like `a.b().c().d()`.
-pub(super) struct ChainSegment<'a> {
-    pub name: tree_sitter::Node<'a>,
-    pub type_args: Option<tree_sitter::Node<'a>>,
-    pub arg_list: Option<tree_sitter::Node<'a>>,
-    pub trailing_comment: Option<tree_sitter::Node<'a>>,
-}
+use super::generate::{self, gen_node};
+use super::helpers::{
+    PrintItemsExt, collapse_whitespace_len, display_width, gen_node_text, is_type_node,
+};
+use super::statements;
 
 /// Check if a binary expression's `+` operator is being used for string concatenation.
 /// Returns true if at least one operand is a `string_literal` or is itself a string concatenation.
@@ -62,6 +58,7 @@ fn is_wrappable_op(op: Option<&str>, node: tree_sitter::Node, source: &str) -> b
 /// throw new IllegalStateException("First part of message. "
 ///         + "Second part of message.");
 /// ```
+#[allow(clippy::too_many_lines)]
 pub fn gen_binary_expression<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -100,41 +97,64 @@ pub fn gen_binary_expression<'a>(
         };
 
         if !is_nested_in_chain {
-            let (operands, operators) = flatten_wrappable_chain(node, context.source);
+            let (operands, operators) = flatten_wrappable_chain(node, context.source, &|group| {
+                pattern_guard_fits(group, context)
+            });
+            if operators.is_empty() {
+                return gen_node_text(node, context.source);
+            }
 
-            let should_wrap = {
-                let start_col = node.start_position().column;
+            let wrap_width = {
                 let expr_text = &context.source[node.start_byte()..node.end_byte()];
-                let expr_flat_width: usize =
-                    expr_text.lines().map(|l| l.trim().len()).sum::<usize>()
-                        + expr_text.lines().count().saturating_sub(1);
-
-                // For conditions inside if/while/for, account for trailing `) {`
-                let is_condition = node
+                let expr_flat_width: usize = expr_text
+                    .lines()
+                    .map(|l| display_width(l.trim()))
+                    .sum::<usize>()
+                    + expr_text.lines().count().saturating_sub(1);
+
+                // For conditions inside if/while, the line starts with `if (` and
+                // ends with `) {`
+                let condition_keyword = node
                     .parent()
-                    .and_then(|p| {
-                        if p.kind() == "parenthesized_expression" {
-                            p.parent()
-                        } else {
-                            None
-                        }
-                    })
-                    .is_some_and(|gp| {
-                        matches!(
-                            gp.kind(),
-                            "if_statement" | "while_statement" | "for_statement"
-                        )
+                    .filter(|p| p.kind() == "parenthesized_expression")
+                    .and_then(|p| p.parent())
+                    .and_then(|gp| match gp.kind() {
+                        "if_statement" => Some("if"),
+                        "while_statement" => Some("while"),
+                        _ => None,
                     });
 
-                let suffix_width = if is_condition { 3 } else { 0 }; // `) {`
+                // Measure from the formatted indent, not the source column, so the
+                // decision is the same on every pass.
+                let indent_col = context.effective_indent_level() * context.indent_width();
+                let prefix_width = if let Some(keyword) = condition_keyword {
+                    keyword.len() + 2 // `if (`
+                } else if context.is_assignment_wrapped() {
+                    0
+                } else {
+                    compute_expression_prefix_width(node, context)
+                };
+                let suffix_width = match node.parent().map(|p| p.kind()) {
+                    _ if condition_keyword.is_some() => 3, // `) {`
+                    Some("return_statement" | "throw_statement" | "variable_declarator") => 1, // `;`
+                    Some("guard") => node
+                        .parent()
+                        .and_then(|guard| guard.parent())
+                        .map_or(0, statements::switch_label_suffix_width),
+                    _ => 0,
+                };
 
-                start_col + expr_flat_width + suffix_width > context.config.line_width as usize
+                let width = indent_col + prefix_width + expr_flat_width + suffix_width;
+                (width > context.config.line_width as usize).then_some(width)
             };
 
-            if should_wrap {
+            if let Some(width) = wrap_width {
                 let mut items = PrintItems::new();
 
-                items.extend(gen_node(operands[0], context));
+                items.extend(gen_chain_operand(operands[0], context));
+                items.extend(context.wrap_reason("binary", || {
+                    format!("width={width}>{}", context.config.line_width)
+                }));
                 items.start_indent();
                 items.start_indent();
 
@@ -142,7 +162,7 @@ pub fn gen_binary_expression<'a>(
                     items.newline();
                     items.push_str(op);
                     items.space();
-                    items.extend(gen_node(operands[i + 1], context));
+                    items.extend(gen_chain_operand(operands[i + 1], context));
                 }
 
                 items.finish_indent();
@@ -171,11 +191,69 @@ pub fn gen_binary_expression<'a>(
     items
 }
 
+/// Generate one operand of a wrapped chain. An `&&` group that
+/// [`flatten_wrappable_chain`] kept whole is printed on one line.
+fn gen_chain_operand<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let operator = node
+        .child_by_field_name("operator")
+        .map(|op| &context.source[op.start_byte()..op.end_byte()]);
+    if node.kind() != "binary_expression" || operator != Some("&&") {
+        return gen_node(node, context);
+    }
+
+    let (operands, operators) = flatten_wrappable_chain(node, context.source, &|_| false);
+    if operators.is_empty() {
+        return gen_node_text(node, context.source);
+    }
+    let mut items = gen_node(operands[0], context);
+    for (op, operand) in operators.iter().zip(&operands[1..]) {
+        items.space();
+        items.push_str(op);
+        items.space();
+        items.extend(gen_node(*operand, context));
+    }
+    items
+}
+
+/// Whether an `&&` group of an `||` chain should stay on one line: it starts
+/// with an `instanceof` that binds a pattern, as in
+/// `o instanceof Foo f && f.isReady()`, and fits on a continuation line.
+fn pattern_guard_fits(group: tree_sitter::Node, context: &FormattingContext) -> bool {
+    let mut first = group;
+    while first.kind() == "binary_expression" {
+        match first.child_by_field_name("left") {
+            Some(left) => first = left,
+            None => return false,
+        }
+    }
+    let binds_pattern = first.kind() == "instanceof_expression"
+        && (first.child_by_field_name("name").is_some()
+            || first
+                .named_children(&mut first.walk())
+                .any(|c| c.kind() == "record_pattern"));
+    if !binds_pattern {
+        return false;
+    }
+
+    let text = &context.source[group.start_byte()..group.end_byte()];
+    let flat_width = text.lines().map(|l| display_width(l.trim())).sum::<usize>()
+        + text.lines().count().saturating_sub(1);
+    let indent_col = context.effective_indent_level() * context.indent_width();
+    indent_col + context.continuation_width() + "|| ".len() + flat_width
+        <= context.config.line_width as usize
+}
+
 /// Flatten a chain of binary expressions with wrappable operators (&&, ||, string +).
 /// Returns (operands, operators) where operands[i] op operators[i] = operands[i+1].
+///
+/// An `&&` operand of an `||` is kept whole when `keep_group` accepts it.
 fn flatten_wrappable_chain<'a>(
     node: tree_sitter::Node<'a>,
     source: &str,
+    keep_group: &dyn Fn(tree_sitter::Node<'a>) -> bool,
 ) -> (Vec<tree_sitter::Node<'a>>, Vec<String>) {
     let mut operands = Vec::new();
     let mut operators = Vec::new();
@@ -184,6 +262,8 @@ fn flatten_wrappable_chain<'a>(
     fn collect<'a>(
         node: tree_sitter::Node<'a>,
         source: &str,
+        parent_op: Option<&str>,
+        keep_group: &dyn Fn(tree_sitter::Node<'a>) -> bool,
         operands: &mut Vec<tree_sitter::Node<'a>>,
         operators: &mut Vec<String>,
     ) {
@@ -206,20 +286,36 @@ fn flatten_wrappable_chain<'a>(
             Some("+") => is_string_concat(node, source),
             _ => false,
         };
-        if !is_wrappable {
+        if !is_wrappable || (op_str == Some("&&") && parent_op == Some("||") && keep_group(node)) {
             operands.push(node);
             return;
         }
 
-        let left = children.iter().find(|c| c.is_named()).unwrap();
-        let right = children.iter().rev().find(|c| c.is_named()).unwrap();
+        // A tree with a missing side, as error recovery can leave, stays whole
+        let left = children.iter().find(|c| c.is_named());
+        let right = children.iter().rev().find(|c| c.is_named());
+        let (Some(left), Some(right), Some(op)) = (left, right, op.clone()) else {
+            operands.push(node);
+            return;
+        };
+        if left.id() == right.id() {
+            operands.push(node);
+            return;
+        }
 
-        collect(*left, source, operands, operators);
-        operators.push(op.unwrap());
-        collect(*right, source, operands, operators);
+        collect(*left, source, op_str, keep_group, operands, operators);
+        operators.push(op);
+        collect(*right, source, op_str, keep_group, operands, operators);
     }
 
-    collect(node, source, &mut operands, &mut operators);
+    collect(
+        node,
+        source,
+        None,
+        keep_group,
+        &mut operands,
+        &mut operators,
+    );
     (operands, operators)
 }
 
@@ -265,8 +361,15 @@ pub fn gen_update_expression<'a>(
 ///
 /// For chains of 2+ method calls (e.g., `a.b().c().d()`), this flattens the
 /// chain and uses PJF-style column-position wrapping: if the column where the
-/// first `.` would appear exceeds `method_chain_threshold` (default 80), ALL
+/// first `.` would appear exceeds `method_chain_threshold` (default `line_width - 40`), ALL
 /// segments wrap onto new lines with 8-space continuation indent.
+///
+/// The chain always decides first; argument lists are then laid out at the
+/// column their segment ended up on, so only the last segments' arguments wrap
+/// (e.g. a long `describedAs(...)` message in an AssertJ chain). A line comment
+/// after the root or any inner segment forces the chain to wrap, and so does a
+/// chain of at least `method_chain_min_calls_to_break` calls or, with
+/// `break_stream_pipelines`, a stream pipeline.
 #[allow(
     clippy::too_many_lines,
     clippy::bool_to_int_with_if,
@@ -277,74 +380,62 @@ pub fn gen_method_invocation<'a>(
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
     let depth = chain_depth(node);
-    if depth < 1 {
+    let rethrown = is_rethrown_creation(node);
+    if depth < 1 && !rethrown {
         return gen_method_invocation_simple(node, context);
     }
 
-    // Flatten the chain into (root, [ChainSegment, ...])
-    let mut segments: Vec<ChainSegment<'a>> = Vec::new();
-    let root = flatten_chain(node, &mut segments);
+    let MethodChain { root, segments } = MethodChain::cast(node);
+
+    if !context.config.format_chains {
+        return gen_chain_as_written(root, &segments, context);
+    }
 
     // PJF-style chain wrapping: compute chain "prefix width" — the width of the chain
     // up to (but excluding) lambda block bodies. PJF measures where the chain DOTs fall,
     // not the total content including multi-line lambda bodies.
-    let root_text = &context.source[root.start_byte()..root.end_byte()];
-    let root_width = collapse_whitespace_len(root_text);
+    let root_width = chain_root_width(root, context.source);
 
     // When the assignment/variable_declarator has already wrapped at '=',
     // the chain starts at continuation indent with NO prefix on the same line.
     // Adjust indent_col and prefix_width accordingly.
-    let indent_width = context.config.indent_width as usize;
+    let indent_width = context.indent_width();
     let (indent_col, prefix_width) = if context.is_assignment_wrapped() {
         // Assignment wrapped: chain is at continuation indent, already tracked
-        // in effective_indent_level via add_continuation_indent(2)
+        // in effective_indent_level via with_continuation_indent
         let cont_col = context.effective_indent_level() * indent_width;
         (cont_col, 0)
     } else {
         // Use effective_indent_level to include continuation indent from
         // outer chain wrapping and argument list wrapping.
+        // An argument list that keeps this chain on its line says how much
+        // of the line precedes it, e.g. `throw new ServiceException(`.
         let col = context.effective_indent_level() * indent_width;
-        let prefix = compute_chain_prefix_width(node, context);
+        let prefix = context
+            .take_override_prefix_width()
+            .unwrap_or_else(|| compute_expression_prefix_width(node, context));
         (col, prefix)
     };
 
     // Sum up each segment: . + name + type_args + arg_list (with lambda body excluded)
-    let mut segments_width = 0;
-    for seg in &segments {
-        segments_width += 1; // for the '.'
-        let name_text = &context.source[seg.name.start_byte()..seg.name.end_byte()];
-        segments_width += name_text.len();
-
-        if let Some(ta) = seg.type_args {
-            let ta_text = &context.source[ta.start_byte()..ta.end_byte()];
-            segments_width += collapse_whitespace_len(ta_text);
-        }
-
-        if let Some(al) = seg.arg_list {
-            // If the argument list contains a lambda with a block body, only count
-            // the "header" width up to the opening '{', not the full body content.
-            // This matches PJF which measures chain prefix position, not total content.
-            segments_width += estimate_arg_list_width(al, context.source);
-        }
-
-        if let Some(tc) = seg.trailing_comment {
-            let tc_text = &context.source[tc.start_byte()..tc.end_byte()];
-            segments_width += 1 + tc_text.len(); // space + comment
-        }
-    }
+    let segments_width: usize = segments
+        .iter()
+        .map(|seg| chain_segment_width(seg, context.source))
+        .sum();
 
     let chain_flat_width = root_width + segments_width;
 
-    // PJF's METHOD_CHAIN_COLUMN_LIMIT: check if ANY dot's column position exceeds 80.
+    // PJF's METHOD_CHAIN_COLUMN_LIMIT: check if ANY dot's column position exceeds
+    // `method_chain_threshold` (PJF's fixed 80 at line width 120).
     // Walk through segments accumulating position. If any dot exceeds the threshold, wrap.
     // Exception: single-invocation chains (root + 1 method) use line_width as threshold
     // per PJF's LastLevelBreakability.ACCEPT_INLINE_CHAIN_IF_SIMPLE optimization.
     let line_width = context.config.line_width as usize;
-    let chain_threshold = context.config.method_chain_threshold as usize;
+    let chain_threshold = context.config.chain_threshold();
     let effective_chain_threshold = if segments.len() == 1 {
-        line_width // Single-method chains only wrap at line_width (120)
+        line_width // Single-method chains only wrap at line_width
     } else {
-        chain_threshold // Multi-method chains wrap at column 80
+        chain_threshold // Multi-method chains wrap at the chain threshold
     };
 
     let mut any_dot_exceeds = false;
@@ -359,39 +450,44 @@ pub fn gen_method_invocation<'a>(
                 first_exceeding_segment = Some(i);
             }
         }
-        // Add this segment's width to cumulative
-        cumulative += 1; // '.'
-        let name_text = &context.source[seg.name.start_byte()..seg.name.end_byte()];
-        cumulative += name_text.len();
-        if let Some(ta) = seg.type_args {
-            let ta_text = &context.source[ta.start_byte()..ta.end_byte()];
-            cumulative += collapse_whitespace_len(ta_text);
-        }
-        if let Some(al) = seg.arg_list {
-            cumulative += estimate_arg_list_width(al, context.source);
-        }
-        if let Some(tc) = seg.trailing_comment {
-            let tc_text = &context.source[tc.start_byte()..tc.end_byte()];
-            cumulative += 1 + tc_text.len();
-        }
+        cumulative += chain_segment_width(seg, context.source);
     }
 
     // Also check total line width (indent + prefix + chain) against line_width
     // Use >= (not >) to match PJF's strict behavior (line_width is exclusive)
     let effective_position = indent_col + prefix_width + chain_flat_width;
-    let should_wrap = any_dot_exceeds || effective_position >= line_width;
+    // A line comment after the root (`assertThat(x) // why`) or after an inner
+    // segment ends the line, so the chain cannot stay on one line.
+    let root_trailing_comment = trailing_line_comment(root);
+    let has_inner_comment = root_trailing_comment.is_some()
+        || segments
+            .iter()
+            .rev()
+            .skip(1)
+            .any(|seg| seg.trailing_comment.is_some());
+    let min_calls_reached = reaches_min_calls_to_break(&segments, context.config);
+    let stream_pipeline = is_broken_stream_pipeline(&segments, context.source, context.config);
+    let should_wrap = has_inner_comment
+        || any_dot_exceeds
+        || effective_position >= line_width
+        || min_calls_reached
+        || stream_pipeline;
 
     let mut items = PrintItems::new();
     items.extend(gen_node(root, context));
+    if let Some(tc) = root_trailing_comment {
+        items.space();
+        items.extend(gen_node(tc, context));
+    }
 
     if should_wrap {
         // PJF chain prefix detection:
         // Determine how many initial segments form the "prefix" (stay inline with root).
         //
         // Two rules (derived from PJF source analysis):
-        // 1. If any dot exceeds METHOD_CHAIN_COLUMN_LIMIT (80): everything before that
+        // 1. If any dot exceeds METHOD_CHAIN_COLUMN_LIMIT: everything before that
         //    dot stays inline as prefix, everything from that dot wraps.
-        // 2. If no dot exceeds 80 but total exceeds line_width: use zero-arg prefix
+        // 2. If no dot exceeds the limit but total exceeds line_width: use zero-arg prefix
         //    (consecutive zero-arg methods from start stay inline).
         // 3. Class-ref roots: always at least 1 prefix (root + first method).
         let root_is_class_ref = {
@@ -403,32 +499,35 @@ pub fn gen_method_invocation<'a>(
                 .is_some_and(|c| c.is_ascii_uppercase())
         };
 
-        // Helper: check if a segment is zero-arg (no args or empty parens)
+        // Helper: check if a segment is zero-arg (no args or empty parens,
+        // however they are spaced)
         let is_seg_zero_arg = |seg: &ChainSegment| -> bool {
-            match seg.arg_list {
-                None => true,
-                Some(al) => {
-                    let al_text = &context.source[al.start_byte()..al.end_byte()];
-                    al_text.trim() == "()"
-                }
-            }
+            seg.arg_list.is_none_or(|al| al.named_child_count() == 0)
         };
 
         // PJF prefix rules (verified by testing against PJF 2.50):
         // 1. Class-ref roots: always prefix = 1 (e.g., SDK.builder())
         // 2. Method invocation roots: prefix = 0 (root IS the first call)
         // 3. Identifier/field_access/new expression roots:
-        //    PJF uses root text length <= 8 as threshold (matches continuation indent).
+        //    PJF keeps the first segment inline when the root is no wider than the
+        //    continuation indent (8 columns at indent width 4).
         //    Short roots (e.g., sdk, obj, client) keep first segment inline;
         //    long roots (e.g., contextRunner, sdkConfiguration) wrap from root.
         // 4. Stream/parallelStream extends prefix beyond initial count
+        // 5. Parenthesized casts (`((Foo) bar).baz()`) wrap from the root however
+        //    short they are, like long roots.
+        // 6. Rethrown exceptions (`throw new ServiceException(msg, e).withContext(..)`)
+        //    keep the creation and its arguments on the `throw` line and wrap
+        //    every call after it.
         let root_text_len = root.end_byte() - root.start_byte();
 
-        let mut prefix_count = if root_is_class_ref {
+        let mut prefix_count = if is_parenthesized_cast(root) || rethrown {
+            0
+        } else if root_is_class_ref {
             1
         } else if root.kind() == "method_invocation" {
             0
-        } else if root_text_len <= 8 {
+        } else if root_text_len <= context.continuation_width() {
             // Short root → keep first segment inline with root
             1
         } else {
@@ -463,93 +562,252 @@ pub fn gen_method_invocation<'a>(
             }
         }
 
+        if root_trailing_comment.is_some() {
+            prefix_count = 0;
+        }
+
         // Emit prefix segments inline, then wrap the rest
-        for (i, seg) in segments.iter().enumerate() {
-            if i < prefix_count {
-                // Inline with root (prefix)
-                items.push_str(".");
-                if let Some(ta) = seg.type_args {
-                    items.extend(gen_node(ta, context));
-                }
-                items.extend(gen_node_text(seg.name, context.source));
-                if let Some(al) = seg.arg_list {
-                    items.extend(gen_node(al, context));
-                }
-                if let Some(tc) = seg.trailing_comment {
-                    items.space();
-                    items.extend(gen_node(tc, context));
-                }
-            } else if i == prefix_count {
-                // First wrapping segment — start indent block
-                items.start_indent();
-                items.start_indent();
-                context.add_continuation_indent(2);
-                // Check if previous prefix segment had a trailing comment
-                let prev_had_comment = if i > 0 {
-                    segments[i - 1].trailing_comment.is_some()
+        for seg in segments.iter().take(prefix_count) {
+            items.extend(gen_chain_segment(seg, context));
+        }
+        if prefix_count < segments.len() {
+            items.extend(context.wrap_reason("chain", || {
+                if has_inner_comment {
+                    "comment".to_string()
+                } else if any_dot_exceeds {
+                    format!("dot>{effective_chain_threshold}")
+                } else if effective_position >= line_width {
+                    format!("width={effective_position}>={line_width}")
+                } else if min_calls_reached {
+                    format!("calls={}", segments.len())
                 } else {
-                    false
-                };
-                if !prev_had_comment {
-                    items.newline();
-                }
-                items.push_str(".");
-                if let Some(ta) = seg.type_args {
-                    items.extend(gen_node(ta, context));
-                }
-                items.extend(gen_node_text(seg.name, context.source));
-                if let Some(al) = seg.arg_list {
-                    items.extend(gen_node(al, context));
-                }
-                if let Some(tc) = seg.trailing_comment {
-                    items.space();
-                    items.extend(gen_node(tc, context));
+                    "stream".to_string()
                 }
+            }));
+            // A line comment on the root or the last inline call already ended the line
+            let after_comment = match prefix_count {
+                0 => root_trailing_comment.is_some(),
+                n => segments[n - 1].trailing_comment.is_some(),
+            };
+            let root_col = indent_col + prefix_width;
+            if let Some(dot_col) =
+                aligned_dot_column(root, &segments, prefix_count, root_col, context)
+            {
+                let mut aligned = context.with_flag(ContextFlag::ChainSegmentOnNewLine, true);
+                let attach_last = segments.len() - prefix_count > 1
+                    && keeps_terminal_call_attached(&segments, dot_col, &aligned);
+                let padding = " ".repeat(dot_col - indent_col);
+                items.extend(gen_wrapped_segments(
+                    &segments[prefix_count..],
+                    after_comment,
+                    &padding,
+                    attach_last,
+                    &mut aligned,
+                ));
             } else {
-                // Subsequent wrapping segments
-                let prev_had_comment = segments[i - 1].trailing_comment.is_some();
-                if !prev_had_comment {
-                    items.newline();
-                }
-                items.push_str(".");
-                if let Some(ta) = seg.type_args {
-                    items.extend(gen_node(ta, context));
-                }
-                items.extend(gen_node_text(seg.name, context.source));
-                if let Some(al) = seg.arg_list {
-                    items.extend(gen_node(al, context));
-                }
-                if let Some(tc) = seg.trailing_comment {
-                    items.space();
-                    items.extend(gen_node(tc, context));
-                }
+                items.start_indent();
+                items.start_indent();
+                let mut continued = context.with_continuation_indent();
+                let mut continued = continued.with_flag(ContextFlag::ChainSegmentOnNewLine, true);
+                let segment_col = continued.effective_indent_level() * indent_width;
+                let attach_last = segments.len() - prefix_count > 1
+                    && keeps_terminal_call_attached(&segments, segment_col, &continued);
+                items.extend(gen_wrapped_segments(
+                    &segments[prefix_count..],
+                    after_comment,
+                    "",
+                    attach_last,
+                    &mut continued,
+                ));
+                items.finish_indent();
+                items.finish_indent();
             }
         }
-        // Close indent block if any segments were wrapped
-        if prefix_count < segments.len() {
-            context.remove_continuation_indent(2);
-            items.finish_indent();
-            items.finish_indent();
-        }
     } else {
         // Keep on one line
-        for seg in segments {
-            items.push_str(".");
-            if let Some(ta) = seg.type_args {
-                items.extend(gen_node(ta, context));
+        for seg in &segments {
+            items.extend(gen_chain_segment(seg, context));
+        }
+    }
+
+    items
+}
+
+/// Emit the wrapped calls of a chain, each on a new line after `padding`,
+/// except the last when `attach_last` keeps it on the line before. A line
+/// comment after a call ends that line itself, as `after_comment` says one
+/// did before the first.
+fn gen_wrapped_segments<'a>(
+    wrapped: &[ChainSegment<'a>],
+    after_comment: bool,
+    padding: &str,
+    attach_last: bool,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    for (i, seg) in wrapped.iter().enumerate() {
+        if !(attach_last && i == wrapped.len() - 1) {
+            let prev_had_comment = match i {
+                0 => after_comment,
+                _ => wrapped[i - 1].trailing_comment.is_some(),
+            };
+            if !prev_had_comment {
+                items.newline();
             }
-            items.extend(gen_node_text(seg.name, context.source));
-            if let Some(al) = seg.arg_list {
-                items.extend(gen_node(al, context));
+            if !padding.is_empty() {
+                items.push_str(padding);
             }
-            // Emit trailing comment if present
-            if let Some(tc) = seg.trailing_comment {
-                items.space();
-                items.extend(gen_node(tc, context));
+        }
+        items.extend(gen_chain_segment(seg, context));
+    }
+    items
+}
+
+/// With `methodChainWrapStyle: alignDots`, the column of the first `.` on the
+/// line a wrapped chain starts on, where the chain's wrapped calls line up.
+/// The root starts at `root_col`. `None` keeps the continuation indent: when
+/// the style is off, when no call stays on the root's line, when the text
+/// before the first wrapped call spans lines, or when a wrapped call opens a
+/// block or would not fit at that column.
+fn aligned_dot_column(
+    root: tree_sitter::Node,
+    segments: &[ChainSegment],
+    prefix_count: usize,
+    root_col: usize,
+    context: &FormattingContext,
+) -> Option<usize> {
+    if context.config.method_chain_wrap_style != MethodChainWrapStyle::AlignDots
+        || prefix_count == 0
+    {
+        return None;
+    }
+    // The dots align after the root and the calls printed inline with it,
+    // so those must print on one line whatever their source layout
+    let inline = &segments[..prefix_count];
+    let prints_inline = prints_on_one_line(root, context.source)
+        && inline.iter().all(|seg| {
+            seg.trailing_comment.is_none()
+                && seg
+                    .arg_list
+                    .is_none_or(|args| prints_on_one_line(args, context.source))
+        });
+    let dot_col = root_col + chain_root_width(root, context.source);
+    let inline_width: usize = inline
+        .iter()
+        .map(|seg| chain_segment_width(seg, context.source))
+        .sum();
+    let line_width = context.config.line_width as usize;
+    if !prints_inline || dot_col + inline_width > line_width {
+        return None;
+    }
+    segments[prefix_count..]
+        .iter()
+        .all(|seg| {
+            !seg.arg_list.is_some_and(has_block_argument)
+                && dot_col + chain_segment_width(seg, context.source) < line_width
+        })
+        .then_some(dot_col)
+}
+
+/// Whether `node` prints on one line at any width: it holds no body, text
+/// block or line comment, which always span lines.
+fn prints_on_one_line(node: tree_sitter::Node, source: &str) -> bool {
+    let spans_lines = match node.kind() {
+        "block" | "class_body" | "switch_block" | "line_comment" => true,
+        "string_literal" => source[node.byte_range()].starts_with("\"\"\""),
+        _ => false,
+    };
+    let mut cursor = node.walk();
+    !spans_lines
+        && node
+            .children(&mut cursor)
+            .all(|child| prints_on_one_line(child, source))
+}
+
+/// Whether an argument opens a block, a lambda's body or an anonymous
+/// class's, whose lines are indented from the statement rather than the call.
+fn has_block_argument(arg_list: tree_sitter::Node) -> bool {
+    let mut cursor = arg_list.walk();
+    arg_list.named_children(&mut cursor).any(|arg| {
+        let body = match arg.kind() {
+            "lambda_expression" => arg.child_by_field_name("body"),
+            "object_creation_expression" => arg.named_children(&mut arg.walk()).last(),
+            _ => None,
+        };
+        body.is_some_and(|b| matches!(b.kind(), "block" | "class_body"))
+    })
+}
+
+/// Emit a chain with `formatChains` off: a segment starts a continuation
+/// line where its `.` does in the source, and stays inline otherwise.
+fn gen_chain_as_written<'a>(
+    root: tree_sitter::Node<'a>,
+    segments: &[ChainSegment<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = gen_node(root, context);
+    let root_trailing_comment = trailing_line_comment(root);
+    if let Some(tc) = root_trailing_comment {
+        items.space();
+        items.extend(gen_node(tc, context));
+    }
+
+    let mut prev_end = root_trailing_comment.unwrap_or(root).end_byte();
+    let mut prev_had_comment = root_trailing_comment.is_some();
+    let mut wrapped = false;
+    for seg in segments {
+        if context.source[prev_end..seg.name.start_byte()].contains('\n') {
+            if !wrapped {
+                items.start_indent();
+                items.start_indent();
+                wrapped = true;
+            }
+            if !prev_had_comment {
+                items.newline();
             }
         }
+        if wrapped {
+            items.extend(gen_chain_segment(
+                seg,
+                &mut context.with_continuation_indent(),
+            ));
+        } else {
+            items.extend(gen_chain_segment(seg, context));
+        }
+        prev_end = [Some(seg.name), seg.arg_list, seg.trailing_comment]
+            .into_iter()
+            .flatten()
+            .map(|n| n.end_byte())
+            .max()
+            .unwrap_or(prev_end);
+        prev_had_comment = seg.trailing_comment.is_some();
     }
+    if wrapped {
+        items.finish_indent();
+        items.finish_indent();
+    }
+
+    items
+}
 
+/// Emit one chain segment: `.<T>name(args)` plus its trailing comment, if any.
+fn gen_chain_segment<'a>(
+    seg: &ChainSegment<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    items.push_str(".");
+    if let Some(ta) = seg.type_args {
+        items.extend(gen_node(ta, context));
+    }
+    items.extend(gen_node_text(seg.name, context.source));
+    if let Some(al) = seg.arg_list {
+        items.extend(gen_node(al, context));
+    }
+    if let Some(tc) = seg.trailing_comment {
+        items.space();
+        items.extend(gen_node(tc, context));
+    }
     items
 }
 
@@ -593,6 +851,23 @@ fn gen_method_invocation_simple<'a>(
     items
 }
 
+/// The flat width of a chain segment, `.<T>name(args)` plus a space and its
+/// trailing comment. An argument with a lambda block body counts only up to
+/// the `{`, as PJF measures where the chain's dots fall, not its content.
+fn chain_segment_width(seg: &ChainSegment, source: &str) -> usize {
+    let mut width = 1 + display_width(&source[seg.name.byte_range()]);
+    if let Some(ta) = seg.type_args {
+        width += collapse_whitespace_len(&source[ta.byte_range()]);
+    }
+    if let Some(al) = seg.arg_list {
+        width += estimate_arg_list_width(al, source);
+    }
+    if let Some(tc) = seg.trailing_comment {
+        width += 1 + display_width(&source[tc.byte_range()]);
+    }
+    width
+}
+
 /// Check if any argument list in a chain segment contains a lambda with a block body.
 /// This is used to force chain wrapping when lambdas with block bodies are present,
 /// since the multi-line block content would produce incorrect indentation on a single line.
@@ -635,6 +910,72 @@ fn estimate_arg_list_width(arg_list: tree_sitter::Node, source: &str) -> usize {
     }
 }
 
+/// Whether the last of `segments` is one of `chain_terminal_calls` and stays
+/// on the line of the segment before it, which starts at column `col`.
+fn keeps_terminal_call_attached(
+    segments: &[ChainSegment],
+    col: usize,
+    context: &FormattingContext,
+) -> bool {
+    let [.., prev, last] = segments else {
+        return false;
+    };
+    let name = &context.source[last.name.byte_range()];
+    context
+        .config
+        .chain_terminal_calls
+        .iter()
+        .any(|n| n == name)
+        && last.arg_list.is_none_or(|al| al.named_child_count() == 0)
+        && prev.trailing_comment.is_none()
+        && col
+            + chain_segment_width(prev, context.source)
+            + chain_segment_width(last, context.source)
+            < context.config.line_width as usize
+}
+
+/// Whether a chain with these segments has as many calls as
+/// `method_chain_min_calls_to_break`, so it wraps whatever its width. A single
+/// call is not a chain, so it never counts.
+fn reaches_min_calls_to_break(
+    segments: &[ChainSegment],
+    config: &crate::configuration::Configuration,
+) -> bool {
+    let min_calls = config.method_chain_min_calls_to_break as usize;
+    min_calls > 0 && segments.len() >= min_calls.max(2)
+}
+
+/// Whether a chain with these segments is a stream pipeline that
+/// `break_stream_pipelines` wraps: it calls `stream()` or `parallelStream()`
+/// and then at least one more method.
+fn is_broken_stream_pipeline(
+    segments: &[ChainSegment],
+    source: &str,
+    config: &crate::configuration::Configuration,
+) -> bool {
+    config.break_stream_pipelines
+        && segments
+            .iter()
+            .rev()
+            .skip(1)
+            .any(|seg| matches!(&source[seg.name.byte_range()], "stream" | "parallelStream"))
+}
+
+/// Whether `node` is a method chain that `method_chain_min_calls_to_break` or
+/// `break_stream_pipelines` wraps however short it is.
+pub fn breaks_regardless_of_width(
+    node: tree_sitter::Node,
+    source: &str,
+    config: &crate::configuration::Configuration,
+) -> bool {
+    if !config.format_chains || node.kind() != "method_invocation" {
+        return false;
+    }
+    let segments = MethodChain::cast(node).segments;
+    reaches_min_calls_to_break(&segments, config)
+        || is_broken_stream_pipeline(&segments, source, config)
+}
+
 /// Check if a method chain would fit inline (without wrapping) at a given column position.
 /// Used by `gen_variable_declarator` to determine if wrapping at '=' allows the chain to stay inline.
 pub fn chain_fits_inline_at(
@@ -643,13 +984,14 @@ pub fn chain_fits_inline_at(
     source: &str,
     config: &crate::configuration::Configuration,
 ) -> bool {
-    let mut segments: Vec<ChainSegment> = Vec::new();
-    let root = flatten_chain(node, &mut segments);
+    if breaks_regardless_of_width(node, source, config) {
+        return false;
+    }
+    let MethodChain { root, segments } = MethodChain::cast(node);
 
-    let root_text = &source[root.start_byte()..root.end_byte()];
-    let root_width = collapse_whitespace_len(root_text);
+    let root_width = chain_root_width(root, source);
 
-    let chain_threshold = config.method_chain_threshold as usize;
+    let chain_threshold = config.chain_threshold();
     let line_width = config.line_width as usize;
 
     // Check per-dot positions — if ANY dot exceeds chain threshold, chain needs wrapping
@@ -659,31 +1001,19 @@ pub fn chain_fits_inline_at(
         if dot_position > chain_threshold {
             return false;
         }
-        total_width += 1; // '.'
-        let name_text = &source[seg.name.start_byte()..seg.name.end_byte()];
-        total_width += name_text.len();
-        if let Some(ta) = seg.type_args {
-            let ta_text = &source[ta.start_byte()..ta.end_byte()];
-            total_width += collapse_whitespace_len(ta_text);
-        }
-        if let Some(al) = seg.arg_list {
-            total_width += estimate_arg_list_width(al, source);
-        }
-        if let Some(tc) = seg.trailing_comment {
-            let tc_text = &source[tc.start_byte()..tc.end_byte()];
-            total_width += 1 + tc_text.len();
-        }
+        total_width += chain_segment_width(seg, source);
     }
 
     // Total line position must fit within line_width (strict less-than, matching PJF)
     (col + total_width) < line_width
 }
 
-/// Compute the width of content that precedes a chain on the same line.
+/// Compute the width of content that precedes an expression (a chain or a
+/// wrappable binary expression) on the same line.
 /// For `this.field = chain.method()`, returns width of "this.field = " (prefix before chain).
 /// For `return chain.method()`, returns 7 (for "return ").
-/// This lets the chain wrapping decision account for the full line width, not just indent + chain.
-fn compute_chain_prefix_width(node: tree_sitter::Node, context: &FormattingContext) -> usize {
+/// This lets wrapping decisions account for the full line width, not just indent + expression.
+fn compute_expression_prefix_width(node: tree_sitter::Node, context: &FormattingContext) -> usize {
     let parent = node.parent();
     match parent.map(|p| p.kind()) {
         Some("assignment_expression") => {
@@ -719,11 +1049,18 @@ fn compute_chain_prefix_width(node: tree_sitter::Node, context: &FormattingConte
                 // Add variable name width
                 if let Some(name) = p.child_by_field_name("name") {
                     let name_text = &context.source[name.start_byte()..name.end_byte()];
-                    return type_width + 1 + name_text.len() + 3; // " name = "
+                    return type_width + 1 + display_width(name_text) + 3; // " name = "
                 }
             }
             0
         }
+        Some("expression_statement") => {
+            // `case A -> expr;` — the labels and arrow precede the expression
+            parent
+                .and_then(|p| p.parent())
+                .map_or(0, |rule| statements::switch_rule_label_width(rule, context))
+        }
+        Some("guard") => 5,            // "when "
         Some("return_statement") => 7, // "return "
         Some("throw_statement") => 6,  // "throw "
         Some("argument_list") => {
@@ -742,7 +1079,7 @@ fn compute_chain_prefix_width(node: tree_sitter::Node, context: &FormattingConte
                         .is_some_and(|ggp| ggp.kind() == "method_invocation");
                 if in_chain && let Some(name) = gp.child_by_field_name("name") {
                     let name_text = &context.source[name.start_byte()..name.end_byte()];
-                    return 1 + name_text.len() + 1; // ".name("
+                    return 1 + display_width(name_text) + 1; // ".name("
                 }
             }
             0
@@ -754,31 +1091,27 @@ fn compute_chain_prefix_width(node: tree_sitter::Node, context: &FormattingConte
 /// Count how deep a method invocation chain is (number of nested `method_invocations`).
 /// `a.b()` = 0, `a.b().c()` = 1, `a.b().c().d()` = 2, etc.
 pub(super) fn chain_depth(node: tree_sitter::Node) -> usize {
-    let mut depth = 0;
-    let mut current = node;
-    loop {
-        let mut cursor = current.walk();
-        let object = current
-            .children(&mut cursor)
-            .find(|c| c.is_named() && c.kind() != "argument_list" && c.kind() != "type_arguments");
-        match object {
-            Some(obj) if obj.kind() == "method_invocation" => {
-                depth += 1;
-                current = obj;
-            }
-            _ => break,
-        }
-    }
-    depth
+    let MethodChain { root, segments } = MethodChain::cast(node);
+    // A bare first call is the root, but still one of the chain's calls
+    (segments.len() + usize::from(root.kind() == "method_invocation")).saturating_sub(1)
 }
 
 /// Find the rightmost "last dot" position within any method chain in the expression.
 /// Returns the column position relative to `base_col` where the last `.method(...)` segment
 /// starts. For nested expressions, this walks into arguments to find deeply nested chains.
 /// Returns 0 if no chain dots are found.
-pub(super) fn rightmost_chain_dot(node: tree_sitter::Node, source: &str, base_col: usize) -> usize {
+pub(super) fn rightmost_chain_dot(
+    node: tree_sitter::Node,
+    context: &FormattingContext,
+    base_col: usize,
+) -> usize {
+    if context.is_over_work_budget() {
+        return 0;
+    }
+    let source = context.source;
     let text = &source[node.start_byte()..node.end_byte()];
-    let flat_width: usize = text.lines().map(|l| l.trim().len()).sum();
+    context.charge_work(text.len());
+    let flat_width: usize = text.lines().map(|l| display_width(l.trim())).sum();
 
     if node.kind() == "method_invocation" && chain_depth(node) >= 1 {
         // This is a chain. Find the last dot position.
@@ -787,7 +1120,7 @@ pub(super) fn rightmost_chain_dot(node: tree_sitter::Node, source: &str, base_co
             .map_or(0, |n| n.end_byte() - n.start_byte());
         let args_w = node.child_by_field_name("arguments").map_or(0, |a| {
             let t = &source[a.start_byte()..a.end_byte()];
-            t.lines().map(|l| l.trim().len()).sum::<usize>()
+            t.lines().map(|l| display_width(l.trim())).sum::<usize>()
         });
         let last_seg_width = 1 + name_w + args_w; // ".name(args)"
         base_col + flat_width.saturating_sub(last_seg_width)
@@ -801,9 +1134,10 @@ pub(super) fn rightmost_chain_dot(node: tree_sitter::Node, source: &str, base_co
                 if child.is_named() {
                     let child_offset: usize = {
                         let before = &source[node.start_byte()..child.start_byte()];
-                        before.lines().map(|l| l.trim().len()).sum()
+                        context.charge_work(before.len());
+                        before.lines().map(|l| display_width(l.trim())).sum()
                     };
-                    let dot_pos = rightmost_chain_dot(child, source, base_col + child_offset);
+                    let dot_pos = rightmost_chain_dot(child, context, base_col + child_offset);
                     max_dot = max_dot.max(dot_pos);
                 }
             }
@@ -818,10 +1152,13 @@ pub(super) fn rightmost_chain_dot(node: tree_sitter::Node, source: &str, base_co
         let mut col = base_col;
         for child in node.children(&mut cursor) {
             if child.is_named() {
-                let dot_pos = rightmost_chain_dot(child, source, col);
+                let dot_pos = rightmost_chain_dot(child, context, col);
                 max_dot = max_dot.max(dot_pos);
                 let child_text = &source[child.start_byte()..child.end_byte()];
-                col += child_text.lines().map(|l| l.trim().len()).sum::<usize>();
+                col += child_text
+                    .lines()
+                    .map(|l| display_width(l.trim()))
+                    .sum::<usize>();
             } else {
                 // Operator like "+", "&&", etc.
                 let op_text = &source[child.start_byte()..child.end_byte()];
@@ -839,16 +1176,14 @@ pub(super) fn rightmost_chain_dot(node: tree_sitter::Node, source: &str, base_co
 /// (`root_width="AuthResponse`", `first_seg_width=".builder()`") so the caller can check
 /// if `LHS = AuthResponse.builder()` fits on one line.
 pub fn chain_root_first_seg_width(node: tree_sitter::Node, source: &str) -> (usize, usize) {
-    let mut segments = Vec::new();
-    let root = flatten_chain(node, &mut segments);
+    let MethodChain { root, segments } = MethodChain::cast(node);
 
-    let root_text = &source[root.start_byte()..root.end_byte()];
-    let root_width = collapse_whitespace_len(root_text);
+    let root_width = chain_root_width(root, source);
 
     let first_seg_width = if let Some(seg) = segments.first() {
         let mut w = 1; // '.'
         let name_text = &source[seg.name.start_byte()..seg.name.end_byte()];
-        w += name_text.len();
+        w += display_width(name_text);
         if let Some(ta) = seg.type_args {
             let ta_text = &source[ta.start_byte()..ta.end_byte()];
             w += collapse_whitespace_len(ta_text);
@@ -865,84 +1200,43 @@ pub fn chain_root_first_seg_width(node: tree_sitter::Node, source: &str) -> (usi
     (root_width, first_seg_width)
 }
 
-/// Flatten a nested `method_invocation` chain into segments.
-/// Returns the root object node (the non-method-invocation at the bottom).
-/// Segments are collected in call order (first call first).
-/// Each segment is (`invocation_node`, `name_node`, `type_args`, `arg_list`).
-/// Extract trailing line comment that appears on the same line as the given node
-fn extract_trailing_line_comment(node: tree_sitter::Node<'_>) -> Option<tree_sitter::Node<'_>> {
-    let node_end_row = node.end_position().row;
-
-    // Look for a line_comment sibling that starts on the same row
-    let mut next = node.next_sibling();
-    while let Some(sibling) = next {
-        if sibling.kind() == "line_comment" {
-            if sibling.start_position().row == node_end_row {
-                return Some(sibling);
-            }
-            return None; // Comment on different line
+/// Width of a chain root as formatted. Parenthesized casts are measured
+/// through their parentheses, so `( (Foo)  bar )` counts as `((Foo) bar)`.
+fn chain_root_width(root: tree_sitter::Node, source: &str) -> usize {
+    match root.kind() {
+        "parenthesized_expression" => root
+            .named_child(0)
+            .map_or(2, |inner| 2 + chain_root_width(inner, source)),
+        "cast_expression" => {
+            let mut cursor = root.walk();
+            let types: Vec<_> = root.children_by_field_name("type", &mut cursor).collect();
+            let types_width: usize = types
+                .iter()
+                .map(|t| collapse_whitespace_len(&source[t.start_byte()..t.end_byte()]))
+                .sum();
+            let value_width = root
+                .child_by_field_name("value")
+                .map_or(0, |value| chain_root_width(value, source));
+            // "(" + types joined by " & " + ") "
+            3 + types_width + 3 * types.len().saturating_sub(1) + value_width
         }
-        if !sibling.is_extra() {
-            return None; // Non-comment node in the way
-        }
-        next = sibling.next_sibling();
+        _ => collapse_whitespace_len(&source[root.start_byte()..root.end_byte()]),
     }
-    None
 }
 
-fn flatten_chain<'a>(
-    node: tree_sitter::Node<'a>,
-    segments: &mut Vec<ChainSegment<'a>>,
-) -> tree_sitter::Node<'a> {
-    // Collect the chain in reverse (innermost first), then reverse at the end.
-    let mut chain = Vec::new();
-    let mut current = node;
-
-    loop {
-        // tree-sitter method_invocation has named fields: "object", "name", "arguments"
-        let object = current.child_by_field_name("object");
-        let name = current.child_by_field_name("name");
-        let type_args = {
-            let mut cursor = current.walk();
-            current
-                .children(&mut cursor)
-                .find(|c| c.kind() == "type_arguments")
-        };
-        let arg_list = current.child_by_field_name("arguments");
-
-        // Check for trailing line comment on this segment
-        let trailing_comment = extract_trailing_line_comment(current);
-
-        if let Some(name_node) = name {
-            chain.push(ChainSegment {
-                name: name_node,
-                type_args,
-                arg_list,
-                trailing_comment,
-            });
-        }
+/// Check if `node` is a chain of calls on an exception a `throw` creates:
+/// `throw new ServiceException(msg, e).withContext("path", path)`.
+pub(super) fn is_rethrown_creation(node: tree_sitter::Node) -> bool {
+    MethodChain::cast(node).root.kind() == "object_creation_expression"
+        && node.parent().is_some_and(|p| p.kind() == "throw_statement")
+}
 
-        match object {
-            Some(obj) if obj.kind() == "method_invocation" => {
-                current = obj;
-            }
-            Some(obj) => {
-                // Root object (e.g., field_access, identifier, etc.)
-                chain.reverse();
-                segments.extend(chain);
-                return obj;
-            }
-            None => {
-                // No object — bare method call at the root of the chain.
-                // Pop the root entry from chain; the caller's gen_node(root)
-                // will format the bare call via gen_method_invocation_simple.
-                chain.pop();
-                chain.reverse();
-                segments.extend(chain);
-                return current;
-            }
-        }
-    }
+/// Check if a chain root is a parenthesized cast: `((Foo) bar)`.
+fn is_parenthesized_cast(root: tree_sitter::Node) -> bool {
+    root.kind() == "parenthesized_expression"
+        && root
+            .named_child(0)
+            .is_some_and(|inner| inner.kind() == "cast_expression")
 }
 
 /// Format a field access: `obj.field`
@@ -950,6 +1244,13 @@ pub fn gen_field_access<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
+    if context.config.wrap_field_access_chains
+        && context.config.format_chains
+        && let Some(items) = gen_field_access_chain(node, context)
+    {
+        return items;
+    }
+
     let mut items = PrintItems::new();
     let mut cursor = node.walk();
 
@@ -971,6 +1272,123 @@ pub fn gen_field_access<'a>(
     items
 }
 
+/// Format a chain of two or more field accesses, like
+/// `config.server.http.port`, wrapping it at its dots when it overflows the
+/// line. The wrapped layout is the one [`gen_method_invocation`] gives method
+/// chains; `methodChainThreshold` does not apply, as it is about calls:
+/// ```java
+/// int port = someConfiguration
+///         .server
+///         .http
+///         .port;
+/// ```
+///
+/// Returns `None` when `node` is part of a longer chain, is the object of a
+/// method call, or is not a chain of plain field accesses, and when the
+/// chain stays on one line.
+fn gen_field_access_chain<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> Option<PrintItems> {
+    let is_object = node.parent().is_some_and(|parent| {
+        matches!(parent.kind(), "field_access" | "method_invocation")
+            && parent.child_by_field_name("object") == Some(node)
+    });
+    if is_object {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+    let mut current = node;
+    while current.kind() == "field_access" {
+        // `Outer.super.field` and comments between the dots keep the inline layout
+        if current.child_count() != 3 {
+            return None;
+        }
+        fields.push(current.child_by_field_name("field")?);
+        current = current.child_by_field_name("object")?;
+    }
+    if fields.len() < 2 {
+        return None;
+    }
+    fields.reverse();
+    let root = current;
+
+    // A value wrapped after `=` starts at the continuation indent, which
+    // `effective_indent_level` already counts
+    let indent_col = context.effective_indent_level() * context.indent_width();
+    let prefix_width = if context.is_assignment_wrapped() {
+        0
+    } else {
+        compute_expression_prefix_width(node, context)
+    };
+    let line_width = context.config.line_width as usize;
+    let position = indent_col
+        + prefix_width
+        + chain_root_width(root, context.source)
+        + fields
+            .iter()
+            .map(|field| 1 + display_width(&context.source[field.byte_range()]))
+            .sum::<usize>()
+        + field_access_chain_suffix_width(node);
+    if position <= line_width {
+        return None;
+    }
+
+    // Like method chains, a class or short root keeps the first field inline
+    let root_text = &context.source[root.start_byte()..root.end_byte()];
+    let root_is_class_ref = root_text
+        .rsplit('.')
+        .next()
+        .and_then(|last| last.chars().next())
+        .is_some_and(|c| c.is_ascii_uppercase());
+    let prefix_count = if is_parenthesized_cast(root) || root.kind() == "method_invocation" {
+        0
+    } else {
+        usize::from(root_is_class_ref || display_width(root_text) <= context.continuation_width())
+    };
+
+    let mut items = gen_node(root, context);
+    for field in fields.iter().take(prefix_count) {
+        items.push_str(".");
+        items.extend(gen_node_text(*field, context.source));
+    }
+    items.extend(context.wrap_reason("fields", || format!("width={position}>={line_width}")));
+    items.start_indent();
+    items.start_indent();
+    for field in fields.iter().skip(prefix_count) {
+        items.newline();
+        items.push_str(".");
+        items.extend(gen_node_text(*field, context.source));
+    }
+    items.finish_indent();
+    items.finish_indent();
+    Some(items)
+}
+
+/// Width of the punctuation that follows a field access chain on its line:
+/// the `;` ending its statement, or the `,` or `)` after it in an argument
+/// list.
+fn field_access_chain_suffix_width(node: tree_sitter::Node) -> usize {
+    let Some(parent) = node.parent() else {
+        return 0;
+    };
+    match parent.kind() {
+        // `,` or `)` in an argument list, `;` after a statement
+        "argument_list"
+        | "variable_declarator"
+        | "return_statement"
+        | "throw_statement"
+        | "expression_statement" => 1,
+        "assignment_expression" => usize::from(
+            parent
+                .parent()
+                .is_some_and(|p| p.kind() == "expression_statement"),
+        ),
+        _ => 0,
+    }
+}
+
 /// Format a lambda expression: `x -> x + 1` or `(x, y) -> { body }`
 pub fn gen_lambda_expression<'a>(
     node: tree_sitter::Node<'a>,
@@ -1017,16 +1435,15 @@ pub fn gen_ternary_expression<'a>(
 ) -> PrintItems {
     // Estimate the "flat" width of the entire ternary expression (as if on one line).
     let ternary_text = &context.source[node.start_byte()..node.end_byte()];
-    let ternary_flat_width: usize = ternary_text.lines().map(|l| l.trim().len()).sum::<usize>()
+    let ternary_flat_width: usize = ternary_text
+        .lines()
+        .map(|l| display_width(l.trim()))
+        .sum::<usize>()
         + ternary_text.lines().count().saturating_sub(1); // spaces between joined lines
 
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    let indent_width = context.indent_level() * context.indent_width();
     // Account for prefix on the same line (e.g., "return " or "variable = ")
-    let prefix_width = super::declarations::estimate_prefix_width(
-        node,
-        context.source,
-        context.is_assignment_wrapped(),
-    );
+    let prefix_width = super::declarations::estimate_prefix_width(node, context);
     let should_wrap =
         indent_width + prefix_width + ternary_flat_width > context.config.line_width as usize;
 
@@ -1144,7 +1561,7 @@ pub fn gen_array_creation_expression<'a>(
                 items.extend(gen_node(child, context));
             }
             "dimensions" => {
-                items.extend(gen_node_text(child, context.source));
+                items.extend(gen_node(child, context));
                 // Add space after dimensions if array_initializer follows
                 if has_initializer {
                     items.space();
@@ -1163,6 +1580,12 @@ pub fn gen_array_creation_expression<'a>(
     items
 }
 
+/// Annotation arrays of at most this many elements, none wider than
+/// [`SHORT_ANNOTATION_ELEMENT_WIDTH`], stay on one line when the annotation's
+/// arguments are wrapped.
+const SMALL_ANNOTATION_ARRAY_LEN: usize = 2;
+const SHORT_ANNOTATION_ELEMENT_WIDTH: usize = 20;
+
 /// Format an array initializer: `{1, 2, 3}`
 ///
 /// When the initializer contains comments (`is_extra()` children), expands to
@@ -1170,7 +1593,11 @@ pub fn gen_array_creation_expression<'a>(
 ///
 /// When the parent is an annotation context (`element_value_pair` or
 /// `annotation_argument_list`) and there are multiple elements, forces
-/// one-element-per-line format with trailing comma, matching PJF behavior.
+/// one-element-per-line format when the annotation doesn't fit on its line,
+/// matching PJF behavior. The decision depends only on the annotation, not
+/// on whether the member it annotates wraps. A small array such as
+/// `{"admin", "user"}` stays inline when the annotation's arguments already
+/// went on lines of their own.
 #[allow(clippy::too_many_lines)]
 pub fn gen_array_initializer<'a>(
     node: tree_sitter::Node<'a>,
@@ -1200,28 +1627,23 @@ pub fn gen_array_initializer<'a>(
 
     // Force expanded format in annotation context with multiple elements,
     // but only if the annotation wouldn't fit on one line
-    let force_expand = if in_annotation && element_count > 1 {
-        // Find the annotation node to check the full width
-        let mut current = node;
-        let mut should_expand = true; // Default to expanding if annotation not found
-        while let Some(parent) = current.parent() {
-            if parent.kind() == "marker_annotation"
-                || parent.kind() == "annotation"
-                || parent.kind() == "normal_annotation"
-            {
-                // Compute flat width of the entire annotation
-                let ann_text = &context.source[parent.start_byte()..parent.end_byte()];
-                let flat_width = collapse_whitespace_len(ann_text);
-                let indent_col =
-                    context.effective_indent_level() * context.config.indent_width as usize;
-                should_expand = indent_col + flat_width > context.config.line_width as usize;
-                break;
-            }
-            current = parent;
-        }
-        should_expand
-    } else {
-        false
+    let force_expand = in_annotation && element_count > 1 && {
+        let annotation = node
+            .parent()
+            .and_then(|p| {
+                if p.kind() == "element_value_pair" {
+                    p.parent()
+                } else {
+                    Some(p)
+                }
+            })
+            .filter(|args| args.kind() == "annotation_argument_list")
+            .and_then(|args| args.parent().map(|annotation| (args, annotation)));
+        // Default to expanding if annotation not found
+        annotation.is_none_or(|(args, annotation)| {
+            generate::annotation_exceeds_line(annotation, context)
+                && !(args.named_child_count() > 1 && is_small_array(node, context.source))
+        })
     };
 
     // Reset cursor for iteration
@@ -1311,6 +1733,20 @@ pub fn gen_array_initializer<'a>(
     items
 }
 
+/// Whether `node` has at most [`SMALL_ANNOTATION_ARRAY_LEN`] elements, none
+/// wider than [`SHORT_ANNOTATION_ELEMENT_WIDTH`].
+fn is_small_array(node: tree_sitter::Node, source: &str) -> bool {
+    let mut cursor = node.walk();
+    let elements: Vec<_> = node
+        .named_children(&mut cursor)
+        .filter(|c| !c.is_extra())
+        .collect();
+    elements.len() <= SMALL_ANNOTATION_ARRAY_LEN
+        && elements.iter().all(|e| {
+            collapse_whitespace_len(&source[e.byte_range()]) <= SHORT_ANNOTATION_ELEMENT_WIDTH
+        })
+}
+
 /// Format an array access: `arr[i]`
 pub fn gen_array_access<'a>(
     node: tree_sitter::Node<'a>,
@@ -1331,7 +1767,7 @@ pub fn gen_array_access<'a>(
     items
 }
 
-/// Format a cast expression: `(Type) expr`
+/// Format a cast expression: `(Type) expr` or `(A & B) expr`
 pub fn gen_cast_expression<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -1348,6 +1784,11 @@ pub fn gen_cast_expression<'a>(
                 items.space();
                 after_type = true;
             }
+            "&" => {
+                items.space();
+                items.push_str("&");
+                items.space();
+            }
             _ if child.is_named() && !after_type => {
                 // The type inside the cast
                 items.extend(gen_node(child, context));
@@ -1363,13 +1804,15 @@ pub fn gen_cast_expression<'a>(
     items
 }
 
-/// Format an instanceof expression: `expr instanceof Type`
+/// Format an instanceof expression: `expr instanceof Type`, `expr instanceof
+/// final Type name` or `expr instanceof Point(int x, int y)`
 pub fn gen_instanceof_expression<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
     let mut items = PrintItems::new();
     let mut cursor = node.walk();
+    let binding = node.child_by_field_name("name");
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -1378,6 +1821,14 @@ pub fn gen_instanceof_expression<'a>(
                 items.push_str("instanceof");
                 items.space();
             }
+            "final" => {
+                items.push_str("final");
+                items.space();
+            }
+            _ if Some(child) == binding => {
+                items.space();
+                items.extend(gen_node(child, context));
+            }
             _ if child.is_named() => {
                 items.extend(gen_node(child, context));
             }
@@ -1388,6 +1839,104 @@ pub fn gen_instanceof_expression<'a>(
     items
 }
 
+/// Format a pattern in a switch label or after `instanceof`: a type pattern
+/// like `String s`, or a record pattern like `Line(Point(var x, var y), Point p)`.
+///
+/// Type patterns and record components are single-spaced; record pattern
+/// components are separated by `, `.
+pub fn gen_pattern<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+
+    match node.kind() {
+        "record_pattern" => {
+            for child in node.named_children(&mut cursor) {
+                items.extend(gen_node(child, context));
+            }
+        }
+        "record_pattern_body" => {
+            items.push_str("(");
+            for (i, child) in node.named_children(&mut cursor).enumerate() {
+                if i > 0 {
+                    items.push_str(",");
+                    items.space();
+                }
+                items.extend(gen_node(child, context));
+            }
+            items.push_str(")");
+        }
+        // `pattern`, `type_pattern`, `record_pattern_component`
+        _ => {
+            for (i, child) in node.named_children(&mut cursor).enumerate() {
+                if i > 0 {
+                    items.space();
+                }
+                if child.kind() == "modifiers" {
+                    items.extend(declarations::gen_inline_modifiers(child, context));
+                } else {
+                    items.extend(gen_node(child, context));
+                }
+            }
+        }
+    }
+
+    items
+}
+
+/// Format a string literal. Text blocks keep their content: the lines are
+/// re-based onto the current indent, and their indentation relative to the
+/// incidental indentation (the least indented line, counting the closing
+/// `"""` when it is on its own line) is preserved.
+///
+/// ```java
+/// String json = """
+///     {"a": 1}
+///     """;
+/// ```
+pub fn gen_string_literal<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let text = &context.source[node.start_byte()..node.end_byte()];
+    if !text.starts_with("\"\"\"") {
+        return gen_node_text(node, context.source);
+    }
+
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let is_blank = |line: &str| line.trim().is_empty();
+    let leading_whitespace = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+    // The last line is only `"""` after whitespace when the delimiter stands alone,
+    // in which case it takes part in the incidental indentation.
+    let incidental = lines[1..]
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| !is_blank(line) || *i == lines.len() - 2)
+        .map(|(_, line)| leading_whitespace(line))
+        .min()
+        .unwrap_or(0);
+
+    let mut items = PrintItems::new();
+    items.push_str(lines[0].trim_end());
+    for line in &lines[1..] {
+        items.newline();
+        // Trailing whitespace is stripped from text block lines by the compiler
+        let stripped = line
+            .char_indices()
+            .nth(incidental)
+            .map_or("", |(start, _)| line[start..].trim_end());
+        if !stripped.is_empty() {
+            items.push_str(stripped);
+        }
+    }
+    items
+}
+
 /// Format a parenthesized expression: `(expr)`
 pub fn gen_parenthesized_expression<'a>(
     node: tree_sitter::Node<'a>,
@@ -1429,6 +1978,24 @@ pub fn gen_method_reference<'a>(
     items
 }
 
+/// Width of a method reference as it will be formatted. A type or name
+/// receiver loses all its inner spaces (`Foo :: < T > bar` becomes
+/// `Foo::<T>bar`) except one after each comma in type arguments.
+pub(super) fn method_reference_width(node: tree_sitter::Node, source: &str) -> usize {
+    let text = &source[node.start_byte()..node.end_byte()];
+    let simple_receiver = node.named_child(0).is_some_and(|receiver| {
+        is_type_node(receiver.kind())
+            || matches!(
+                receiver.kind(),
+                "identifier" | "field_access" | "this" | "super" | "scoped_type_identifier"
+            )
+    });
+    if !simple_receiver || text.contains("//") || text.contains("/*") {
+        return collapse_whitespace_len(text);
+    }
+    text.chars().filter(|c| !c.is_whitespace()).count() + text.matches(',').count()
+}
+
 /// Format an assignment expression: `x = y`, `x += y`
 ///
 /// PJF wraps at `=` when the RHS is a chain that would fit at continuation indent,
@@ -1451,8 +2018,7 @@ pub fn gen_assignment_expression<'a>(
         let is_chain = rhs_node.kind() == "method_invocation" && chain_depth(rhs_node) >= 1;
 
         if is_chain {
-            let indent_unit = context.config.indent_width as usize;
-            let indent_col = context.effective_indent_level() * indent_unit;
+            let indent_col = context.effective_indent_level() * context.indent_width();
             let lhs_text = &context.source[lhs_node.start_byte()..lhs_node.end_byte()];
             let lhs_width = collapse_whitespace_len(lhs_text);
 
@@ -1465,7 +2031,7 @@ pub fn gen_assignment_expression<'a>(
                 false
             } else {
                 // Chain would wrap. Check if wrapping at '=' lets the chain stay inline.
-                let continuation_col = indent_col + 2 * indent_unit;
+                let continuation_col = indent_col + context.continuation_width();
                 chain_fits_inline_at(rhs_node, continuation_col, context.source, context.config)
             }
         } else {
@@ -1479,13 +2045,10 @@ pub fn gen_assignment_expression<'a>(
     for child in &children {
         if child.is_named() {
             if wrap_at_eq && saw_eq {
-                context.set_assignment_wrapped(true);
-                context.add_continuation_indent(2);
-            }
-            items.extend(gen_node(*child, context));
-            if wrap_at_eq && saw_eq {
-                context.remove_continuation_indent(2);
-                context.set_assignment_wrapped(false);
+                let mut wrapped = context.with_flag(ContextFlag::AssignmentWrapped, true);
+                items.extend(gen_node(*child, &mut wrapped.with_continuation_indent()));
+            } else {
+                items.extend(gen_node(*child, context));
             }
         } else {
             let op = &context.source[child.start_byte()..child.end_byte()];
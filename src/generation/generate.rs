@@ -1,20 +1,156 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::rc::Rc;
+
 use dprint_core::formatting::PrintItems;
 
-use crate::configuration::Configuration;
+use crate::configuration::{Configuration, StaticImportPosition};
+use crate::error;
+use crate::source_map::SourceMap;
 
+use super::blank_lines;
+use super::comment_map::CommentMap;
 use super::comments;
-use super::context::FormattingContext;
+use super::context::{CONTINUATION_INDENT_LEVELS, FormattingContext};
 use super::declarations;
 use super::expressions;
-use super::helpers::{PrintItemsExt, collapse_whitespace_len, gen_node_text, is_type_node};
+use super::helpers::{
+    PrintItemsExt, collapse_whitespace_len, declaration_line_text, display_width, gen_node_text,
+    gen_reindented_text, gen_verbatim_text, is_type_node,
+};
+use super::member_cache::{self, MemberCache};
+use super::modules;
 use super::statements;
+use super::work_budget;
 
 /// Generate dprint `PrintItems` IR from a tree-sitter parse tree.
 #[must_use]
 pub fn generate(source: &str, tree: &tree_sitter::Tree, config: &Configuration) -> PrintItems {
+    generate_inner(source, tree, config, None, None)
+}
+
+/// Generate `PrintItems` like [`generate`], with markers that fill in
+/// `source_map` as the items are printed.
+#[must_use]
+pub fn generate_with_source_map(
+    source: &str,
+    tree: &tree_sitter::Tree,
+    config: &Configuration,
+    source_map: Rc<RefCell<SourceMap>>,
+) -> PrintItems {
+    generate_inner(source, tree, config, Some(source_map), None)
+}
+
+/// Generate `PrintItems` like [`generate`], reusing the output `member_cache`
+/// holds for members of top-level types and recording the output of the
+/// others into it as the items are printed.
+#[must_use]
+pub(crate) fn generate_with_member_cache(
+    source: &str,
+    tree: &tree_sitter::Tree,
+    config: &Configuration,
+    member_cache: Rc<RefCell<MemberCache>>,
+) -> PrintItems {
+    generate_inner(source, tree, config, None, Some(member_cache))
+}
+
+fn generate_inner(
+    source: &str,
+    tree: &tree_sitter::Tree,
+    config: &Configuration,
+    source_map: Option<Rc<RefCell<SourceMap>>>,
+    member_cache: Option<Rc<RefCell<MemberCache>>>,
+) -> PrintItems {
+    let mut context = new_context(source, tree, config);
+    if let Some(source_map) = source_map {
+        context.set_source_map(source_map);
+    }
+    if let Some(member_cache) = member_cache {
+        context.set_member_cache(member_cache);
+    }
+    gen_node(tree.root_node(), &mut context)
+}
+
+/// The parts of a tree [`generate`] prints from their source text instead
+/// of formatting them.
+#[derive(Debug, Default)]
+pub(crate) struct Fallbacks {
+    /// Byte ranges of the members kept as written because formatting them
+    /// needs more work than one member is allowed.
+    pub(crate) skipped_members: Vec<Range<usize>>,
+    /// The kind and byte range of each node with children that no handler
+    /// formats, in source order.
+    pub(crate) unhandled_nodes: Vec<(&'static str, Range<usize>)>,
+}
+
+/// Generate `tree` like [`generate`] and collect what it prints from its
+/// source text.
+#[must_use]
+pub(crate) fn fallbacks(
+    source: &str,
+    tree: &tree_sitter::Tree,
+    config: &Configuration,
+) -> Fallbacks {
+    let mut context = new_context(source, tree, config);
+    gen_node(tree.root_node(), &mut context);
+    let mut unhandled_nodes: Vec<_> = context
+        .unhandled_nodes()
+        .iter()
+        .map(|node| (node.kind(), node.byte_range()))
+        .collect();
+    // Imports are generated after sorting
+    unhandled_nodes.sort_by_key(|(_, range)| range.start);
+    Fallbacks {
+        skipped_members: context.skipped_members().to_vec(),
+        unhandled_nodes,
+    }
+}
+
+/// A context for generating all of `tree`.
+fn new_context<'a>(
+    source: &'a str,
+    tree: &'a tree_sitter::Tree,
+    config: &'a Configuration,
+) -> FormattingContext<'a> {
     let mut context = FormattingContext::new(source, config);
     let root = tree.root_node();
-    gen_node(root, &mut context)
+    let mut suppressed = comments::suppressed_ranges(root, source);
+    // With `formatWithSyntaxErrors`, the code around each error is kept as written
+    suppressed.extend(error::error_nodes(root).into_iter().map(syntax_error_range));
+    context.set_suppressed_ranges(suppressed);
+    context.set_comment_map(CommentMap::new(root));
+    context
+}
+
+/// Node kinds that format each of their children through `gen_node`, so one
+/// child kept as written leaves its siblings formatted.
+const NODE_LIST_KINDS: &[&str] = &[
+    "program",
+    "class_body",
+    "interface_body",
+    "enum_body_declarations",
+    "annotation_type_body",
+    "block",
+    "constructor_body",
+];
+
+/// The byte range kept as written around a syntax error: the statement,
+/// member or other node containing it that its parent formats on its own.
+fn syntax_error_range(error: tree_sitter::Node) -> Range<usize> {
+    // A `MISSING` token has no source text, so its parent is kept instead
+    let mut node = if error.is_missing() {
+        error.parent().unwrap_or(error)
+    } else {
+        error
+    };
+    while let Some(parent) = node.parent() {
+        if NODE_LIST_KINDS.contains(&parent.kind()) {
+            break;
+        }
+        node = parent;
+    }
+    node.byte_range()
 }
 
 /// Generate `PrintItems` for a tree-sitter node.
@@ -26,13 +162,43 @@ pub fn gen_node<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
+    if !node.is_extra() && context.is_suppressed(node) {
+        return context.map_source(node, gen_verbatim_text(node, context.source));
+    }
+    // The member is kept as written, so the rest of it is not generated
+    if context.is_over_work_budget() {
+        return PrintItems::new();
+    }
+    if let Some(items) = member_cache::reuse(node, context) {
+        return items;
+    }
     context.push_parent(node.kind());
-    let items = match node.kind() {
+    let items = if member_cache::is_type_member(node) {
+        let mut member = context.member_scope();
+        let mut budget = member.with_work_budget(work_budget::MEMBER_WORK_BUDGET);
+        let items = gen_node_kind(node, &mut budget);
+        work_budget::finish(node, items, &mut budget)
+    } else {
+        gen_node_kind(node, context)
+    };
+    context.pop_parent();
+    let items = member_cache::record(node, items, context);
+    context.map_source(node, items)
+}
+
+/// Generate `node` with the handler for its kind.
+#[allow(clippy::too_many_lines)]
+fn gen_node_kind<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    match node.kind() {
         "program" => gen_program(node, context),
 
         // --- Declarations ---
         "package_declaration" => declarations::gen_package_declaration(node, context),
         "import_declaration" => declarations::gen_import_declaration(node, context),
+        "module_declaration" => modules::gen_module_declaration(node, context),
         "class_declaration" => declarations::gen_class_declaration(node, context),
         "interface_declaration" => declarations::gen_interface_declaration(node, context),
         "enum_declaration" => declarations::gen_enum_declaration(node, context),
@@ -40,8 +206,14 @@ pub fn gen_node<'a>(
         "annotation_type_declaration" => {
             declarations::gen_annotation_type_declaration(node, context)
         }
+        "annotation_type_element_declaration" => {
+            declarations::gen_annotation_type_element_declaration(node, context)
+        }
         "method_declaration" => declarations::gen_method_declaration(node, context),
         "constructor_declaration" => declarations::gen_constructor_declaration(node, context),
+        "compact_constructor_declaration" => {
+            declarations::gen_compact_constructor_declaration(node, context)
+        }
         "field_declaration" | "constant_declaration" => {
             declarations::gen_field_declaration(node, context)
         }
@@ -75,6 +247,10 @@ pub fn gen_node<'a>(
         // --- Types ---
         "generic_type" => gen_generic_type(node, context),
         "array_type" => gen_array_type(node, context),
+        "annotated_type" => gen_annotated_type(node, context),
+        "scoped_type_identifier" => gen_scoped_type_identifier(node, context),
+        "dimensions" => gen_dimensions(node, context),
+        "type_arguments" => gen_type_arguments(node, context),
         kind if is_type_node(kind) => gen_node_text(node, context.source),
         "type_parameter" => gen_type_parameter(node, context),
         "wildcard" => gen_wildcard(node, context),
@@ -109,6 +285,12 @@ pub fn gen_node<'a>(
         "array_access" => expressions::gen_array_access(node, context),
         "cast_expression" => expressions::gen_cast_expression(node, context),
         "instanceof_expression" => expressions::gen_instanceof_expression(node, context),
+        "pattern"
+        | "type_pattern"
+        | "record_pattern"
+        | "record_pattern_body"
+        | "record_pattern_component" => expressions::gen_pattern(node, context),
+        "string_literal" => expressions::gen_string_literal(node, context),
         "parenthesized_expression" => expressions::gen_parenthesized_expression(node, context),
         "method_reference" => expressions::gen_method_reference(node, context),
         "assignment_expression" => expressions::gen_assignment_expression(node, context),
@@ -123,7 +305,7 @@ pub fn gen_node<'a>(
             items.push_str("static");
             for child in node.children(&mut node.walk()) {
                 if child.kind() == "block" {
-                    items.space();
+                    items.extend(context.brace_separator());
                     items.extend(statements::gen_block(child, context));
                 }
             }
@@ -131,10 +313,14 @@ pub fn gen_node<'a>(
         }
 
         // --- Fallback: emit source text unchanged ---
-        _ => gen_node_text(node, context.source),
-    };
-    context.pop_parent();
-    items
+        _ => {
+            // Tokens and other leaves need no handler of their own
+            if node.named_child_count() > 0 {
+                context.record_unhandled_node(node);
+            }
+            gen_node_text(node, context.source)
+        }
+    }
 }
 
 /// Generate a program node (the root of the parse tree).
@@ -144,20 +330,64 @@ fn gen_program<'a>(node: tree_sitter::Node<'a>, context: &mut FormattingContext<
 
     let mut cursor = node.walk();
     let children: Vec<_> = node.children(&mut cursor).collect();
+    // An empty or whitespace-only file stays empty, without a newline
+    if children.is_empty() {
+        return items;
+    }
 
     // First pass: collect and categorize imports
     let mut static_imports: Vec<tree_sitter::Node> = vec![];
     let mut regular_imports: Vec<tree_sitter::Node> = vec![];
     let mut non_import_children: Vec<tree_sitter::Node> = vec![];
 
+    let format_imports = context.config.format_imports;
+    let sort_imports = sorts_imports(context.config);
+    let used_names = (format_imports && context.config.remove_unused_imports)
+        .then(|| used_simple_names(node, context.source));
+
+    // Unused imports are removed together with their comments, except the
+    // comments above the first line of code, which head the file
+    let comment_map = context.comment_map();
+    let first_code = children
+        .iter()
+        .find(|c| !c.is_extra())
+        .map(tree_sitter::Node::id);
+    let mut removed = HashSet::new();
     for child in &children {
+        if child.kind() == "import_declaration"
+            && !is_static_import(*child)
+            && !context.is_suppressed(*child)
+            && let Some(used_names) = &used_names
+            && is_unused_import(*child, used_names, context.source)
+        {
+            removed.insert(child.id());
+            if first_code != Some(child.id()) {
+                removed.extend(
+                    comment_map
+                        .leading(*child)
+                        .iter()
+                        .map(tree_sitter::Node::id),
+                );
+            }
+            removed.extend(
+                comment_map
+                    .trailing(*child)
+                    .iter()
+                    .map(tree_sitter::Node::id),
+            );
+        }
+    }
+
+    for child in &children {
+        if removed.contains(&child.id()) {
+            continue;
+        }
         if child.kind() == "import_declaration" {
-            let is_static = {
-                let mut c = child.walk();
-                child.children(&mut c).any(|ch| ch.kind() == "static")
-            };
+            let is_static = is_static_import(*child);
 
-            if is_static {
+            // Without sorting, imports keep their source order in one block
+            let mixed = context.config.static_import_position == StaticImportPosition::Mixed;
+            if is_static && sort_imports && !mixed {
                 static_imports.push(*child);
             } else {
                 regular_imports.push(*child);
@@ -167,24 +397,29 @@ fn gen_program<'a>(node: tree_sitter::Node<'a>, context: &mut FormattingContext<
         }
     }
 
-    // Sort imports alphabetically by their full path
-    static_imports.sort_by(|a, b| {
-        let path_a = extract_import_path(*a, context.source);
-        let path_b = extract_import_path(*b, context.source);
-        path_a.cmp(&path_b)
-    });
-
-    regular_imports.sort_by(|a, b| {
-        let path_a = extract_import_path(*a, context.source);
-        let path_b = extract_import_path(*b, context.source);
-        path_a.cmp(&path_b)
-    });
+    // Sort imports alphabetically by their full path, with regular imports
+    // first ordered by their `importOrder` group
+    if sort_imports {
+        static_imports.sort_by_cached_key(|node| extract_import_path(*node, context.source));
+        regular_imports.sort_by_cached_key(|node| {
+            let path = extract_import_path(*node, context.source);
+            (import_group(&path, &context.config.import_order), path)
+        });
+    }
 
     // Second pass: emit nodes in order
     let mut prev_kind: Option<&str> = None;
-    let mut prev_was_comment = false;
     let mut prev_end_row: Option<usize> = None;
     let mut emitted_imports = false;
+    // Whether the output is at the start of a line: after a line comment,
+    // which prints its own newline, or after the imports
+    let mut line_ended = false;
+
+    // Comments after the first import follow the imports
+    let first_import_start = children
+        .iter()
+        .find(|c| c.kind() == "import_declaration")
+        .map(tree_sitter::Node::start_byte);
 
     // Check if we have a package declaration
     let has_package = non_import_children
@@ -194,151 +429,267 @@ fn gen_program<'a>(node: tree_sitter::Node<'a>, context: &mut FormattingContext<
     for (i, child) in non_import_children.iter().enumerate() {
         // Emit imports:
         // - After package declaration (if present), OR
-        // - Before first non-extra node (if no package declaration)
+        // - Before first non-extra node or comment after the first import
+        //   (if no package declaration)
         let should_emit_imports = !emitted_imports
             && (!static_imports.is_empty() || !regular_imports.is_empty())
             && ((has_package && prev_kind == Some("package_declaration"))
-                || (!has_package && !child.is_extra()));
+                || (!has_package
+                    && (!child.is_extra() || first_import_start < Some(child.start_byte()))));
 
         if should_emit_imports {
-            // Add blank line after package declaration
-            if prev_kind == Some("package_declaration") {
+            // A header block comment does not end its line
+            if prev_kind.is_some() && !line_ended {
                 items.newline();
             }
-
-            // Emit static imports
-            for import_node in &static_imports {
-                items.extend(gen_node(*import_node, context));
-                items.newline();
-            }
-
-            // Blank line between static and regular imports
-            if !static_imports.is_empty() && !regular_imports.is_empty() {
-                items.newline();
-            }
-
-            // Emit regular imports
-            for import_node in &regular_imports {
-                items.extend(gen_node(*import_node, context));
-                items.newline();
+            // Add blank lines after package declaration
+            if prev_kind == Some("package_declaration") {
+                items.extend(blank_lines::gen_blank_lines(blank_lines::after_package(
+                    context,
+                )));
             }
+            items.extend(gen_imports(&static_imports, &regular_imports, context));
 
             prev_kind = Some("import_declaration");
-            prev_was_comment = false;
             emitted_imports = true;
+            line_ended = true;
         }
 
-        if child.is_extra() {
-            // Handle comment nodes
-            let is_trailing = comments::is_trailing_comment(*child);
-
-            if is_trailing {
-                // Trailing comment: append on same line
-                items.space();
-                items.extend(gen_node(*child, context));
-            } else {
-                // Leading/standalone comment: emit on its own line
-                if prev_kind.is_some() || prev_was_comment {
-                    // Determine if we need a blank line before this comment
-                    let prev_is_different_section =
-                        prev_kind.is_some_and(|pk| pk != "line_comment" && pk != "block_comment");
-                    let is_block_comment = child.kind() == "block_comment";
-
-                    if prev_is_different_section && !prev_was_comment {
-                        // Add blank line before comment (previous statement's newline + this newline = blank line)
-                        // Exception: after imports, we only add ONE blank line total (not two)
-                        if (prev_kind == Some("import_declaration")
-                            || prev_kind == Some("package_declaration"))
-                            && is_block_comment
-                        {
-                            // Add one newline to create the blank line (import already has its newline)
-                            items.newline();
-                        } else {
-                            items.newline();
-                            // For block comments (not line comments), add an extra newline
-                            if is_block_comment {
-                                items.newline();
-                            }
-                        }
-                    } else if prev_was_comment && child.kind() != "line_comment" {
-                        // Separate consecutive block comments with blank line.
-                        // Consecutive line comments stay tightly grouped.
-                        items.newline();
-                    } else if prev_was_comment && child.kind() == "line_comment" {
-                        if prev_kind == Some("block_comment") {
-                            // Block comments don't emit trailing newlines, so we always
-                            // need at least one newline before the next line comment.
-                            items.newline();
-                        }
-                        if prev_end_row.is_some_and(|r| child.start_position().row > r + 1) {
-                            // Source had a blank line between consecutive line comments — preserve it.
-                            items.newline();
-                        }
-                    }
-                    // Don't add newline here - the previous statement already ended with one
-                }
-                items.extend(gen_node(*child, context));
-                prev_kind = Some(child.kind());
-                prev_was_comment = true;
-                prev_end_row = Some(child.end_position().row);
-            }
+        // A comment on the line a node ends on stays there
+        if child.is_extra() && context.comment_map().is_trailing(*child) {
+            items.space();
+            items.extend(gen_node(*child, context));
+            line_ended = child.kind() == "line_comment";
             continue;
         }
 
-        // Do not preserve blank lines between a header comment and package declaration.
-        // palantir-java-format always removes that extra blank line.
-
-        // Add blank lines between different top-level sections
-        // But skip this if the current child is a comment (comments handle their own spacing)
-        // Also skip if previous was a line comment (line comments are transparent for spacing)
-        // Block comments still need blank lines after them
-        // Special case: after imports, we only add ONE blank line (not two)
-        if let Some(pk) = prev_kind
-            && !child.is_extra()
-        {
-            if pk == "line_comment" {
-                // After line comment: the comment already emitted a trailing newline.
-                // Only add a blank if source has one.
-                if prev_end_row.is_some_and(|r| child.start_position().row > r + 1) {
-                    items.newline();
-                }
-            } else if pk == "block_comment" {
-                // After block comment: block comments don't emit trailing newlines,
-                // so we always need at least one newline. Add an extra if source has a blank.
+        // Everything else starts its own line, after the blank lines that
+        // separate it from what came before
+        if let Some(pk) = prev_kind {
+            if !line_ended {
                 items.newline();
-                if prev_end_row.is_some_and(|r| child.start_position().row > r + 1) {
-                    items.newline();
+            }
+            let blank_lines = match pk {
+                // Comments stay with what they describe
+                "line_comment" | "block_comment" => {
+                    blank_lines::kept(context, prev_end_row, child.start_position().row)
                 }
-            } else {
-                let needs_double_newline = (pk == "package_declaration")
-                    || pk != "import_declaration"
-                    || child.kind() != "import_declaration";
-
-                if needs_double_newline {
-                    items.newline();
+                "package_declaration" => blank_lines::after_package(context),
+                // Comments after the last declaration keep at most its
+                // separation from the source
+                _ if non_import_children[i..]
+                    .iter()
+                    .all(tree_sitter::Node::is_extra) =>
+                {
+                    blank_lines::kept(context, prev_end_row, child.start_position().row)
+                        .min(blank_lines::after_declaration())
                 }
-            }
+                _ => blank_lines::after_declaration(),
+            };
+            items.extend(blank_lines::gen_blank_lines(blank_lines));
         }
 
         items.extend(gen_node(*child, context));
         prev_kind = Some(child.kind());
-        prev_was_comment = false;
         prev_end_row = Some(child.end_position().row);
+        line_ended = child.kind() == "line_comment";
+    }
 
-        // Add newline after each top-level declaration
-        if i < non_import_children.len() - 1
-            && non_import_children[i + 1..].iter().any(|c| !c.is_extra())
-        {
+    // Imports with no declaration after them, as in a file of only a
+    // package and imports, still print
+    if !emitted_imports && (!static_imports.is_empty() || !regular_imports.is_empty()) {
+        if prev_kind.is_some() && !line_ended {
             items.newline();
         }
+        if prev_kind == Some("package_declaration") {
+            items.extend(blank_lines::gen_blank_lines(blank_lines::after_package(
+                context,
+            )));
+        }
+        items.extend(gen_imports(&static_imports, &regular_imports, context));
+        line_ended = true;
+    }
+
+    // End the file with exactly one newline, however many the source has
+    if !line_ended {
+        items.newline();
+    }
+
+    items
+}
+
+/// Emit the static and regular imports in their configured order, with a
+/// blank line between the two kinds.
+fn gen_imports<'a>(
+    static_imports: &[tree_sitter::Node<'a>],
+    regular_imports: &[tree_sitter::Node<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let static_items = gen_static_imports(static_imports, context);
+    let regular_items = gen_import_groups(regular_imports, context);
+    let (first, second) = if context.config.static_import_position == StaticImportPosition::Bottom {
+        (regular_items, static_items)
+    } else {
+        (static_items, regular_items)
+    };
+    items.extend(first);
+    if !static_imports.is_empty() && !regular_imports.is_empty() {
+        items.newline();
     }
+    items.extend(second);
+    items
+}
 
-    // Ensure file ends with a newline
-    items.newline();
+/// Emit static imports one per line.
+fn gen_static_imports<'a>(
+    imports: &[tree_sitter::Node<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    for import_node in imports {
+        items.extend(gen_node(*import_node, context));
+        items.newline();
+    }
+    items
+}
 
+/// Emit regular imports one per line, with a blank line wherever the
+/// `importOrder` group changes, or with `sortImports` off, wherever the
+/// source has one or more blank lines.
+fn gen_import_groups<'a>(
+    imports: &[tree_sitter::Node<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut prev: Option<(usize, tree_sitter::Node)> = None;
+    for import_node in imports {
+        let path = extract_import_path(*import_node, context.source);
+        let group = import_group(&path, &context.config.import_order);
+        let separated = prev.is_some_and(|(prev_group, prev_node)| {
+            if sorts_imports(context.config) {
+                prev_group != group
+            } else {
+                import_node.start_position().row > prev_node.end_position().row + 1
+            }
+        });
+        if separated {
+            items.newline();
+        }
+        prev = Some((group, *import_node));
+        items.extend(gen_node(*import_node, context));
+        items.newline();
+    }
     items
 }
 
+/// Whether imports are reordered: by `sortImports`, unless `formatImports`
+/// leaves the import block as written.
+fn sorts_imports(config: &Configuration) -> bool {
+    config.format_imports && config.sort_imports
+}
+
+/// The index in `import_order` of the group an import `path` belongs to.
+///
+/// The longest prefix that matches whole package segments wins, so
+/// `com.example` takes `com.example.Foo` over `com`. An import no prefix
+/// matches goes to the `*` entry, or after every group if there is none.
+fn import_group(path: &str, import_order: &[String]) -> usize {
+    let matches = |prefix: &str| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    };
+    import_order
+        .iter()
+        .enumerate()
+        .filter(|(_, prefix)| prefix.as_str() != "*" && matches(prefix))
+        .max_by_key(|(_, prefix)| prefix.len())
+        .or_else(|| {
+            import_order
+                .iter()
+                .enumerate()
+                .find(|(_, prefix)| prefix.as_str() == "*")
+        })
+        .map_or(import_order.len(), |(i, _)| i)
+}
+
+/// Javadoc tags whose first argument can name an imported type.
+const JAVADOC_REFERENCE_TAGS: &[&str] =
+    &["link", "linkplain", "see", "throws", "exception", "value"];
+
+/// Simple names that may refer to an import: identifiers outside `package`
+/// and `import` declarations, and types named by Javadoc tags such as
+/// `{@link Foo#bar}`. Like PJF, this is purely syntactic, so a local
+/// variable that shares an import's name keeps the import.
+fn used_simple_names<'a>(root: tree_sitter::Node, source: &'a str) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            "import_declaration" => continue,
+            "package_declaration" => {
+                // Only the annotations of a `package-info.java` package
+                // refer to imports
+                let mut cursor = node.walk();
+                stack.extend(
+                    node.children(&mut cursor)
+                        .filter(|c| matches!(c.kind(), "annotation" | "marker_annotation")),
+                );
+                continue;
+            }
+            "identifier" | "type_identifier" => {
+                names.insert(&source[node.byte_range()]);
+            }
+            "block_comment" | "line_comment" => {
+                let text = &source[node.byte_range()];
+                if text.starts_with("/**") || text.starts_with("///") {
+                    names.extend(javadoc_references(text));
+                }
+            }
+            _ => {}
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    names
+}
+
+/// The leading identifier of each reference in a doc comment, e.g. `Map`
+/// for `@see Map.Entry` or `{@link Map#get(Object)}`.
+fn javadoc_references(text: &str) -> impl Iterator<Item = &str> {
+    text.match_indices('@').filter_map(|(at, _)| {
+        let rest = &text[at + 1..];
+        let tag_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        if !JAVADOC_REFERENCE_TAGS.contains(&&rest[..tag_len]) {
+            return None;
+        }
+        let reference = rest[tag_len..].trim_start();
+        let name_len = reference
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(reference.len());
+        (name_len > 0).then(|| &reference[..name_len])
+    })
+}
+
+/// Whether `node` is a `static` import.
+fn is_static_import(node: tree_sitter::Node) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).any(|c| c.kind() == "static")
+}
+
+/// Whether a single-type import names a type that `used_names` never
+/// mentions. Wildcard imports can't be proven unused and are kept.
+fn is_unused_import(node: tree_sitter::Node, used_names: &HashSet<&str>, source: &str) -> bool {
+    let path = extract_import_path(node, source);
+    if path.is_empty() || path.ends_with('*') {
+        return false;
+    }
+    let simple_name = path.rsplit('.').next().unwrap_or(&path);
+    !used_names.contains(simple_name)
+}
+
 /// Extract the import path from an `import_declaration` node.
 fn extract_import_path(node: tree_sitter::Node, source: &str) -> String {
     let mut cursor = node.walk();
@@ -359,6 +710,55 @@ fn extract_import_path(node: tree_sitter::Node, source: &str) -> String {
     String::new()
 }
 
+/// Format a type with type-use annotations: `@NonNull String`. The
+/// annotations stay on the same line as the type they annotate.
+fn gen_annotated_type<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+    let mut first = true;
+
+    for child in node.named_children(&mut cursor) {
+        if !first {
+            items.space();
+        }
+        items.extend(gen_node(child, context));
+        first = false;
+    }
+
+    items
+}
+
+/// Format a qualified type name: `java.lang.@NonNull String`. A type-use
+/// annotation is followed by a space; the dots get none.
+fn gen_scoped_type_identifier<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let text = &context.source[node.start_byte()..node.end_byte()];
+    if text.contains("//") || text.contains("/*") {
+        return gen_node_text(node, context.source);
+    }
+
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "." => items.push_str("."),
+            "annotation" | "marker_annotation" => {
+                items.extend(gen_node(child, context));
+                items.space();
+            }
+            _ if child.is_named() => items.extend(gen_node(child, context)),
+            _ => {}
+        }
+    }
+
+    items
+}
+
 /// Format a generic type: `List<String>`, `Map<K, V>`
 fn gen_generic_type<'a>(
     node: tree_sitter::Node<'a>,
@@ -384,14 +784,15 @@ fn gen_generic_type<'a>(
 
 /// Estimate the prefix width before a type arguments node, including
 /// declaration modifiers or `new` where applicable. Uses collapsed
-/// whitespace on the source's last line to keep estimates stable.
+/// whitespace on the last line of the text as printed, with annotations on
+/// lines of their own, to keep estimates stable.
 fn estimate_type_args_prefix_width(node: tree_sitter::Node, source: &str) -> usize {
     let Some(parent) = node.parent() else {
         return 0;
     };
 
-    let prefix_text = &source[parent.start_byte()..node.start_byte()];
-    let last_line = prefix_text.lines().last().unwrap_or(prefix_text);
+    let prefix_text = declaration_line_text(parent, node.start_byte(), source);
+    let last_line = prefix_text.lines().last().unwrap_or(&prefix_text);
     let mut width = collapse_prefix_len(last_line);
 
     let mut prev = parent;
@@ -405,8 +806,8 @@ fn estimate_type_args_prefix_width(node: tree_sitter::Node, source: &str) -> usi
             | "object_creation_expression"
             | "method_invocation"
             | "constructor_declaration" => {
-                let text = &source[anc.start_byte()..prev.start_byte()];
-                let last = text.lines().last().unwrap_or(text);
+                let text = declaration_line_text(anc, prev.start_byte(), source);
+                let last = text.lines().last().unwrap_or(&text);
                 width += collapse_prefix_len(last);
                 break;
             }
@@ -460,8 +861,17 @@ fn gen_type_arguments<'a>(
     let mut cursor = node.walk();
     let children: Vec<_> = node.children(&mut cursor).collect();
 
-    // Collect type argument nodes
-    let type_args: Vec<_> = children.iter().filter(|c| c.is_named()).collect();
+    // Collect type argument nodes (comments are named too, but are not arguments)
+    let type_args: Vec<_> = children
+        .iter()
+        .filter(|c| c.is_named() && !c.is_extra())
+        .collect();
+    let inner: Vec<_> = children
+        .iter()
+        .copied()
+        .filter(|c| c.kind() != "<" && c.kind() != ">")
+        .collect();
+    let has_line_comment = inner.iter().any(|c| c.kind() == "line_comment");
 
     // Estimate flat width of the entire type_arguments including angle brackets
     let args_flat_width: usize = type_args
@@ -472,7 +882,12 @@ fn gen_type_arguments<'a>(
             let flat = collapse_whitespace_len(text);
             flat + if i < type_args.len() - 1 { 2 } else { 0 } // ", " between args
         })
-        .sum();
+        .sum::<usize>()
+        + inner
+            .iter()
+            .filter(|c| c.kind() == "block_comment")
+            .map(|c| collapse_whitespace_len(&context.source[c.start_byte()..c.end_byte()]) + 1)
+            .sum::<usize>();
 
     // Estimate prefix width: everything on the current line before the `<`.
     // Walk up the tree to find the full prefix including keywords like `implements`.
@@ -501,7 +916,7 @@ fn gen_type_arguments<'a>(
             }
             let prefix_text = &context.source[line_start.start_byte()..node.start_byte()];
             let last_line = prefix_text.lines().last().unwrap_or(prefix_text);
-            (last_line.trim_start().len(), found_clause)
+            (display_width(last_line.trim_start()), found_clause)
         } else {
             (0, false)
         }
@@ -514,22 +929,26 @@ fn gen_type_arguments<'a>(
         base_prefix_width.max(expanded)
     };
 
-    let indent_width = context.effective_indent_level() * context.config.indent_width as usize;
+    let indent_width = context.effective_indent_level() * context.indent_width();
     let line_width = context.config.line_width as usize;
 
     // Check if type args fit inline: prefix + <args> must fit on line.
     // Add 2 for trailing " {" when in extends/implements context.
     let trailing = if in_class_decl { 2 } else { 0 };
     let total_inline = indent_width + prefix_width + 1 + args_flat_width + 1 + trailing; // <args> [+ " {"]
-    let should_wrap = total_inline > line_width;
+    let should_wrap = has_line_comment || total_inline > line_width;
 
     if should_wrap {
         context.mark_type_args_wrapped();
         // PJF uses double continuation indent (+16 = 4 indent levels) for type args
         // in local variable declarations, but single continuation (+8 = 2 indent levels)
         // in class declaration contexts (extends/implements clauses).
-        let indent_levels = if in_class_decl { 2 } else { 4 };
-        let continuation_col = indent_width + indent_levels * context.config.indent_width as usize;
+        let indent_levels = if in_class_decl {
+            CONTINUATION_INDENT_LEVELS
+        } else {
+            2 * CONTINUATION_INDENT_LEVELS
+        };
+        let continuation_col = indent_width + indent_levels * context.indent_width();
         let all_fit_continuation = continuation_col + args_flat_width + 1 + trailing <= line_width; // args + ">" [+ " {"]
 
         items.push_str("<");
@@ -537,44 +956,117 @@ fn gen_type_arguments<'a>(
             items.start_indent();
         }
 
-        if all_fit_continuation {
-            // All type args on one continuation line
+        // All type args on one continuation line, or one per line
+        let one_per_line = has_line_comment || !all_fit_continuation;
+        if !one_per_line {
             items.newline();
-            for (i, arg) in type_args.iter().enumerate() {
-                items.extend(gen_node(**arg, context));
-                if i < type_args.len() - 1 {
-                    items.push_str(",");
-                    items.space();
-                }
-            }
-        } else {
-            // One per line
-            for (i, arg) in type_args.iter().enumerate() {
-                items.newline();
-                items.extend(gen_node(**arg, context));
-                if i < type_args.len() - 1 {
-                    items.push_str(",");
-                }
-            }
         }
+        items.extend(gen_generic_list(&inner, one_per_line, context));
         items.push_str(">");
         for _ in 0..indent_levels {
             items.finish_indent();
         }
     } else {
-        for child in &children {
-            match child.kind() {
-                "<" => items.push_str("<"),
-                ">" => items.push_str(">"),
-                "," => {
-                    items.push_str(",");
+        items.push_str("<");
+        items.extend(gen_generic_list(&inner, false, context));
+        items.push_str(">");
+    }
+
+    items
+}
+
+/// Emit the contents of a `type_arguments` or `type_parameters` list (without
+/// the angle brackets), keeping any comments between the entries.
+///
+/// Block comments stay inline, attached to the entry that follows them on the
+/// same line or else trailing the previous one. Line comments trail the
+/// previous token and end the line. With `one_per_line`, each entry starts on
+/// a new line; otherwise entries are separated by `, `.
+pub(super) fn gen_generic_list<'a>(
+    inner: &[tree_sitter::Node<'a>],
+    one_per_line: bool,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    // Whether the next entry needs a separator (newline or space) before it.
+    let mut pending_separator = one_per_line;
+    let separate = |items: &mut PrintItems| {
+        if one_per_line {
+            items.newline();
+        } else {
+            items.space();
+        }
+    };
+
+    for (i, child) in inner.iter().enumerate() {
+        match child.kind() {
+            "," => items.push_str(","),
+            "line_comment" => {
+                if context.comment_map().is_trailing(*child) {
                     items.space();
+                } else if pending_separator {
+                    separate(&mut items);
                 }
-                _ if child.is_named() => {
+                // The comment emits its own newline
+                items.extend(gen_node(*child, context));
+                pending_separator = false;
+            }
+            "block_comment" => {
+                let comment_row = child.end_position().row;
+                let leads_next = inner[i + 1..]
+                    .iter()
+                    .find(|n| !n.is_extra())
+                    .is_some_and(|n| n.is_named() && n.start_position().row == comment_row);
+                if leads_next {
+                    if pending_separator {
+                        separate(&mut items);
+                    }
+                    items.extend(gen_node(*child, context));
+                    items.space();
+                    pending_separator = false;
+                } else {
+                    items.space();
                     items.extend(gen_node(*child, context));
                 }
-                _ => {}
             }
+            _ if child.is_named() => {
+                if pending_separator {
+                    separate(&mut items);
+                }
+                items.extend(gen_node(*child, context));
+                pending_separator = true;
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+/// Format array dimensions without inner spaces: `[ ] []` becomes `[][]`.
+/// Type annotations on a dimension keep a space on both sides:
+/// `String @NonNull [] @Nullable []`.
+fn gen_dimensions<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "[" => items.push_str("["),
+            "]" => items.push_str("]"),
+            "annotation" | "marker_annotation" => {
+                items.space();
+                items.extend(gen_node(child, context));
+                items.space();
+            }
+            _ if child.is_extra() => {
+                items.extend(gen_node(child, context));
+                items.space();
+            }
+            _ => {}
         }
     }
 
@@ -591,7 +1083,6 @@ fn gen_array_type<'a>(
 
     for child in node.children(&mut cursor) {
         match child.kind() {
-            "dimensions" => items.extend(gen_node_text(child, context.source)),
             _ if child.is_named() => items.extend(gen_node(child, context)),
             _ => {}
         }
@@ -600,7 +1091,7 @@ fn gen_array_type<'a>(
     items
 }
 
-/// Format a type parameter: `T`, `T extends Comparable<T>`
+/// Format a type parameter: `T`, `T extends Comparable<T>`, `@NonNull T`
 fn gen_type_parameter<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -610,6 +1101,10 @@ fn gen_type_parameter<'a>(
 
     for child in node.children(&mut cursor) {
         match child.kind() {
+            "annotation" | "marker_annotation" => {
+                items.extend(gen_node(child, context));
+                items.space();
+            }
             "identifier" | "type_identifier" => {
                 items.extend(gen_node_text(child, context.source));
             }
@@ -735,7 +1230,7 @@ fn gen_formal_parameter<'a>(
                 need_space = false;
             }
             "dimensions" => {
-                items.extend(gen_node_text(child, context.source));
+                items.extend(gen_node(child, context));
             }
             _ => {}
         }
@@ -773,6 +1268,9 @@ fn gen_annotation<'a>(
             "identifier" | "scoped_identifier" => {
                 items.extend(gen_node_text(child, context.source));
             }
+            "annotation_argument_list" if !context.config.format_strings_in_annotations => {
+                items.extend(gen_reindented_text(child, context.source));
+            }
             "annotation_argument_list" => {
                 items.extend(gen_node(child, context));
             }
@@ -825,21 +1323,9 @@ fn gen_annotation_argument_list<'a>(
     // Reset cursor
     cursor = node.walk();
 
-    // Compute flat width of the entire annotation argument list
-    let text = &context.source[node.start_byte()..node.end_byte()];
-    let flat_width = collapse_whitespace_len(text);
-
-    // Also need the annotation name width (go up to parent annotation node)
-    let annotation_prefix_width = if let Some(parent) = node.parent() {
-        let prefix = &context.source[parent.start_byte()..node.start_byte()];
-        prefix.len() // e.g., "@Target" = 7 chars
-    } else {
-        0
-    };
-
-    let indent_col = context.indent_level() * context.config.indent_width as usize;
-    let annotation_total_width = indent_col + annotation_prefix_width + flat_width;
-    let exceeds_line_width = annotation_total_width > context.config.line_width as usize;
+    let exceeds_line_width = node
+        .parent()
+        .is_some_and(|annotation| annotation_exceeds_line(annotation, context));
 
     // Force multi-line when:
     // 1. Annotation has multi-element arrays (PJF always wraps these), OR
@@ -906,6 +1392,17 @@ fn gen_annotation_argument_list<'a>(
     items
 }
 
+/// Whether `annotation` is too wide for a line of its own at the current
+/// indent, measured flat.
+pub(super) fn annotation_exceeds_line(
+    annotation: tree_sitter::Node,
+    context: &FormattingContext,
+) -> bool {
+    let indent_col = context.effective_indent_level() * context.indent_width();
+    let flat_width = collapse_whitespace_len(&context.source[annotation.byte_range()]);
+    indent_col + flat_width > context.config.line_width as usize
+}
+
 /// Format element value pair: `key = value`
 fn gen_element_value_pair<'a>(
     node: tree_sitter::Node<'a>,
@@ -1,5 +1,10 @@
+use std::borrow::Cow;
+use std::rc::Rc;
+
 use dprint_core::formatting::PrintItems;
 use dprint_core::formatting::Signal;
+use dprint_core::formatting::{Condition, ConditionProperties, LineNumber};
+use unicode_width::UnicodeWidthStr;
 
 /// Extension trait for `PrintItems` that reduces boilerplate.
 ///
@@ -41,6 +46,63 @@ impl PrintItemsExt for PrintItems {
     }
 }
 
+/// Print `flat` when its `flat_width` columns still fit within `line_width`
+/// at the column the printer has reached, and `expanded` otherwise.
+///
+/// The printer makes the choice against the output, so it holds however
+/// the text before it on the line was laid out; only `flat` itself is
+/// measured ahead of time. Simple method bodies and argument lists of plain
+/// names and literals use it; other argument lists, chains and parameters
+/// still estimate the text before them.
+pub fn gen_if_fits(
+    flat: PrintItems,
+    flat_width: usize,
+    expanded: PrintItems,
+    line_width: u32,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    items.push_condition(Condition::new(
+        "fitsOnLine",
+        ConditionProperties {
+            condition: Rc::new(move |context| {
+                Some(context.writer_info.column_number as usize + flat_width <= line_width as usize)
+            }),
+            true_path: Some(flat),
+            false_path: Some(expanded),
+        },
+    ));
+    items
+}
+
+/// Print `flat` like [`gen_if_fits`], and only while the printer is still on
+/// the line `start` marks, so nothing between the two wrapped.
+pub fn gen_if_fits_on_line(
+    start: LineNumber,
+    flat: PrintItems,
+    flat_width: usize,
+    expanded: PrintItems,
+    line_width: u32,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    items.push_condition(Condition::new(
+        "fitsOnStartLine",
+        ConditionProperties {
+            condition: Rc::new(move |context| {
+                let on_start_line =
+                    context.resolved_line_number(start)? == context.writer_info.line_number;
+                Some(
+                    on_start_line
+                        && context.writer_info.column_number as usize + flat_width
+                            <= line_width as usize,
+                )
+            }),
+            true_path: Some(flat),
+            false_path: Some(expanded),
+        },
+    ));
+    items
+}
+
 /// Check if a tree-sitter node kind is a Java type node.
 ///
 /// Used to deduplicate the repeated type-kind match patterns
@@ -57,31 +119,225 @@ pub fn is_type_node(kind: &str) -> bool {
             | "scoped_type_identifier"
             | "generic_type"
             | "array_type"
+            | "annotated_type"
     )
 }
 
+/// The columns `s` takes up when printed: wide East Asian characters and
+/// most emoji take two, combining marks none.
+///
+/// Every width compared against `line_width` is measured with this rather
+/// than `str::len`, which counts bytes.
+#[must_use]
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
 /// Estimate the "flat" width of a code fragment as if formatted on one line.
 ///
 /// Collapses newlines and runs of whitespace into single spaces, then
-/// returns the length. Avoids `String` allocation.
+/// returns the display width. Whitespace inside an empty `( )` or `< >` is
+/// dropped, as the generators print them `()` and `<>`. Avoids `String`
+/// allocation.
 pub fn collapse_whitespace_len(s: &str) -> usize {
-    let s = s.trim();
     let mut len = 0;
-    let mut prev_was_space = false;
-    for c in s.chars() {
-        if c.is_whitespace() {
-            if !prev_was_space {
-                len += 1;
-                prev_was_space = true;
-            }
-        } else {
+    let mut prev: Option<&str> = None;
+    for word in s.split_whitespace() {
+        let empty_list = prev.is_some_and(|p| {
+            (p.ends_with('(') && word.starts_with(')'))
+                || (p.ends_with('<') && word.starts_with('>'))
+        });
+        if prev.is_some() && !empty_list {
             len += 1;
-            prev_was_space = false;
         }
+        len += display_width(word);
+        prev = Some(word);
     }
     len
 }
 
+/// Declarations whose modifiers `gen_modifiers` prints: each annotation
+/// written before the keywords on a line of its own, then the keywords and any
+/// later annotations on the declaration's line.
+const OWN_LINE_ANNOTATION_DECLARATIONS: &[&str] = &[
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+    "method_declaration",
+    "constructor_declaration",
+    "compact_constructor_declaration",
+    "field_declaration",
+    "constant_declaration",
+    "annotation_type_element_declaration",
+    "local_variable_declaration",
+];
+
+/// The source text of `declaration` up to byte `end`, with its modifiers as
+/// they print on the declaration's line.
+///
+/// For declarations that put annotations on lines of their own, the modifiers
+/// become everything from their first keyword on, joined by single spaces,
+/// which is the last line of the printed modifiers however the source laid
+/// the annotations out. Other
+/// text is returned as written.
+#[must_use]
+pub fn declaration_line_text<'a>(
+    declaration: tree_sitter::Node,
+    end: usize,
+    source: &'a str,
+) -> Cow<'a, str> {
+    let text = &source[declaration.start_byte()..end];
+    let modifiers = declaration
+        .child(0)
+        .filter(|c| c.kind() == "modifiers" && c.end_byte() <= end);
+    let Some(modifiers) = modifiers else {
+        return Cow::Borrowed(text);
+    };
+    if !OWN_LINE_ANNOTATION_DECLARATIONS.contains(&declaration.kind()) {
+        return Cow::Borrowed(text);
+    }
+    let mut cursor = modifiers.walk();
+    let keywords: Vec<&str> = modifiers
+        .children(&mut cursor)
+        .filter(|c| !c.is_extra())
+        .skip_while(|c| matches!(c.kind(), "annotation" | "marker_annotation"))
+        .map(|c| &source[c.byte_range()])
+        .collect();
+    let mut line = keywords.join(" ");
+    line.push_str(&source[modifiers.end_byte()..end]);
+    Cow::Owned(line)
+}
+
+/// How [`SignatureMeasurer`] measures a part that spans several source lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureMode {
+    /// Trim each line and join them with nothing between, like the
+    /// per-parameter widths `gen_formal_parameters` packs by.
+    Flat,
+    /// Collapse every whitespace run to one space, so that a list wrapped by
+    /// a previous pass measures the same as the original one-line list.
+    Collapsed,
+}
+
+/// Measures the one-line width of a declaration header, such as a class
+/// declaration or a method signature, from its source text.
+///
+/// Modifiers count only what prints on the declaration's line, as
+/// [`declaration_line_text`] gives it: leading annotations sit on lines of
+/// their own. Every other part is measured in the measurer's mode. Parts are
+/// joined by a space, except before a parameter list.
+#[derive(Debug, Clone, Copy)]
+pub struct SignatureMeasurer<'a> {
+    source: &'a str,
+    mode: MeasureMode,
+}
+
+impl<'a> SignatureMeasurer<'a> {
+    #[must_use]
+    pub fn new(source: &'a str, mode: MeasureMode) -> Self {
+        Self { source, mode }
+    }
+
+    /// Width of the text of one part of a header.
+    #[must_use]
+    pub fn part_width(&self, node: tree_sitter::Node) -> usize {
+        let text = &self.source[node.byte_range()];
+        if node.kind() == "modifiers" {
+            let line = node.parent().map_or(Cow::Borrowed(text), |declaration| {
+                declaration_line_text(declaration, node.end_byte(), self.source)
+            });
+            return display_width(line.lines().last().unwrap_or_default().trim());
+        }
+        if node.kind() == "type_parameters" {
+            return type_parameters_width(node, self.source);
+        }
+        match self.mode {
+            MeasureMode::Flat => text.lines().map(|line| display_width(line.trim())).sum(),
+            MeasureMode::Collapsed => collapse_whitespace_len(text),
+        }
+    }
+
+    /// Width of the children of `node` up to, not including, the first one
+    /// `stop` accepts.
+    #[must_use]
+    pub fn width_until(
+        &self,
+        node: tree_sitter::Node,
+        stop: impl Fn(tree_sitter::Node) -> bool,
+    ) -> usize {
+        let mut cursor = node.walk();
+        let mut width = 0;
+        for child in node.children(&mut cursor) {
+            if stop(child) {
+                break;
+            }
+            if width > 0 && !matches!(child.kind(), "formal_parameters" | "(" | ")") {
+                width += 1; // space separator
+            }
+            width += self.part_width(child);
+        }
+        width
+    }
+}
+
+/// Width of `<@NonNull T, U extends V>` as `gen_type_parameters` prints it:
+/// single spaces between the annotations, name and bound of a parameter and
+/// none inside the brackets, however the source spaces them.
+fn type_parameters_width(node: tree_sitter::Node, source: &str) -> usize {
+    let mut cursor = node.walk();
+    let widths: Vec<usize> = node
+        .named_children(&mut cursor)
+        .map(|child| collapse_whitespace_len(&source[child.byte_range()]))
+        .collect();
+    "<>".len() + widths.iter().sum::<usize>() + ", ".len() * widths.len().saturating_sub(1)
+}
+
+/// Split items of the given widths into consecutive lines, minimizing first the
+/// number of lines and then the raggedness (sum of squared unused columns).
+///
+/// Items on the same line are joined by `separator_width` columns and each line
+/// must fit within `capacity`. Returns the number of items on each line, or
+/// `None` if some item is wider than `capacity` on its own.
+pub fn balance_lines(
+    widths: &[usize],
+    separator_width: usize,
+    capacity: usize,
+) -> Option<Vec<usize>> {
+    let n = widths.len();
+    // best[j] = (lines, raggedness, start of last line) for the first j items
+    let mut best: Vec<Option<(usize, usize, usize)>> = vec![None; n + 1];
+    best[0] = Some((0, 0, 0));
+    for end in 1..=n {
+        let mut line_width = 0;
+        for start in (0..end).rev() {
+            line_width += widths[start] + if start + 1 < end { separator_width } else { 0 };
+            if line_width > capacity {
+                break;
+            }
+            let Some((lines, cost, _)) = best[start] else {
+                continue;
+            };
+            let slack = capacity - line_width;
+            let candidate = (lines + 1, cost + slack * slack, start);
+            if best[end].is_none_or(|(l, c, _)| (candidate.0, candidate.1) < (l, c)) {
+                best[end] = Some(candidate);
+            }
+        }
+    }
+
+    let mut counts = Vec::new();
+    let mut end = n;
+    while end > 0 {
+        let (_, _, start) = best[end]?;
+        counts.push(end - start);
+        end = start;
+    }
+    counts.reverse();
+    Some(counts)
+}
+
 /// Extract the source text for a tree-sitter node.
 ///
 /// Properly handles newlines by emitting them as `Signal::NewLine`
@@ -110,18 +366,79 @@ pub fn gen_node_text(node: tree_sitter::Node, source: &str) -> PrintItems {
     items
 }
 
+/// Emit a node's source text with its line breaks, for constructs whose
+/// layout the configuration leaves alone. Later lines move with the node's
+/// first line, so they keep their indentation relative to it.
+pub fn gen_reindented_text(node: tree_sitter::Node, source: &str) -> PrintItems {
+    let line_start = source[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    let base_indent = source[line_start..]
+        .bytes()
+        .take_while(|b| *b == b' ' || *b == b'\t')
+        .count();
+    let mut items = PrintItems::new();
+
+    for (i, line) in source[node.byte_range()].split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let content = if i == 0 {
+            line
+        } else {
+            items.newline();
+            let indent = line
+                .bytes()
+                .take(base_indent)
+                .take_while(|b| *b == b' ' || *b == b'\t')
+                .count();
+            &line[indent..]
+        };
+        if !content.is_empty() {
+            items.push_str(content);
+        }
+    }
+
+    items
+}
+
+/// Emit a node's source text exactly as written, for regions the user has
+/// excluded from formatting. Lines after the first keep their original
+/// leading whitespace instead of taking the current indentation.
+pub fn gen_verbatim_text(node: tree_sitter::Node, source: &str) -> PrintItems {
+    let text = &source[node.start_byte()..node.end_byte()];
+    let mut items = PrintItems::new();
+
+    for (i, line) in text.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if i == 0 {
+            items.push_str(line);
+            continue;
+        }
+        items.push_signal(Signal::StartIgnoringIndent);
+        items.newline();
+        if !line.is_empty() {
+            items.push_str(line);
+        }
+        items.push_signal(Signal::FinishIgnoringIndent);
+    }
+
+    items
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::parse;
+
+    #[test]
+    fn test_balance_lines_evens_out_lines() {
+        // Greedy filling would give [3, 1]; balanced splits 2 + 2.
+        assert_eq!(balance_lines(&[4, 4, 4, 4], 2, 16), Some(vec![2, 2]));
+        assert_eq!(balance_lines(&[3, 3, 3], 2, 20), Some(vec![3]));
+        assert_eq!(balance_lines(&[5, 30], 2, 20), None);
+    }
 
     #[test]
     fn test_gen_node_text() {
         let source = "public class Hello {}";
-        let mut parser = tree_sitter::Parser::new();
-        parser
-            .set_language(&tree_sitter_java::LANGUAGE.into())
-            .unwrap();
-        let tree = parser.parse(source, None).unwrap();
+        let tree = parse(source);
         let root = tree.root_node();
 
         let items = gen_node_text(root, source);
@@ -135,6 +452,28 @@ mod tests {
         assert_eq!(collapse_whitespace_len(""), 0);
         assert_eq!(collapse_whitespace_len("foo bar"), 7);
         assert_eq!(collapse_whitespace_len("single"), 6);
+        assert_eq!(collapse_whitespace_len("名前 =\n    \"🎉\""), 11);
+        assert_eq!(collapse_whitespace_len("new ArrayList< >( )"), 17);
+        assert_eq!(collapse_whitespace_len("a < b ? ( c ) : d"), 17);
+    }
+
+    #[test]
+    fn test_signature_measurer_modes() {
+        let source = "class A {\n    @Override\n    public Map<String,\n            Integer> get(int a,\n            int b) throws IOException {}\n}";
+        let tree = parse(source);
+        let class_body = tree.root_node().child(0).unwrap().child(2).unwrap();
+        let method = class_body.named_child(0).unwrap();
+        assert_eq!(method.kind(), "method_declaration");
+
+        let collapsed = SignatureMeasurer::new(source, MeasureMode::Collapsed);
+        let flat = SignatureMeasurer::new(source, MeasureMode::Flat);
+        let to_body = |c: tree_sitter::Node| c.kind() == "block";
+        let to_throws = |c: tree_sitter::Node| c.kind() == "throws";
+        // "public Map<String, Integer> get(int a, int b) throws IOException"
+        assert_eq!(collapsed.width_until(method, to_body), 64);
+        assert_eq!(collapsed.width_until(method, to_throws), 45);
+        // The same without the spaces that stood for line breaks
+        assert_eq!(flat.width_until(method, to_body), 62);
     }
 
     #[test]
@@ -144,6 +483,7 @@ mod tests {
         assert!(is_type_node("scoped_type_identifier"));
         assert!(is_type_node("generic_type"));
         assert!(is_type_node("array_type"));
+        assert!(is_type_node("annotated_type"));
         assert!(!is_type_node("identifier"));
         assert!(!is_type_node("block"));
     }
@@ -0,0 +1,878 @@
+use dprint_core::formatting::PrintItems;
+
+use super::context::FormattingContext;
+use super::helpers::{PrintItemsExt, display_width};
+
+/// HTML tags that start a new line in Javadoc prose instead of flowing into
+/// the line before them.
+const BLOCK_TAGS: &[&str] = &[
+    "<p>",
+    "<p ",
+    "<ul",
+    "</ul>",
+    "<ol",
+    "</ol>",
+    "<li",
+    "<dl",
+    "</dl>",
+    "<dt",
+    "<dd",
+    "<table",
+    "</table>",
+    "<tr",
+    "<blockquote",
+    "</blockquote>",
+    "<h1",
+    "<h2",
+    "<h3",
+    "<h4",
+    "<h5",
+    "<h6",
+];
+
+/// Indent of the wrapped lines of a block tag description when aligning them
+/// under the description start would leave too little room.
+const TAG_FALLBACK_INDENT: usize = 4;
+
+/// Block tags in the order they are written, by name. Tags not listed here
+/// go after these.
+const TAG_ORDER: &[&[&str]] = &[
+    &["@param"],
+    &["@return"],
+    &["@throws", "@exception"],
+    &["@see"],
+];
+
+/// A parsed piece of a Javadoc comment.
+#[derive(Debug, PartialEq)]
+enum JavadocSegment {
+    /// A paragraph of prose, re-wrapped to the line width.
+    Text(String),
+    /// A block tag like `@param`, `@return` or `@throws`.
+    Tag {
+        name: String,
+        args: Option<String>,
+        desc: String,
+    },
+    /// Lines kept exactly as written: a `<pre>` block or a `{@code ...}`
+    /// spanning several lines.
+    Verbatim(Vec<String>),
+    /// A paragraph break.
+    BlankLine,
+}
+
+/// A parsed block of a Markdown documentation comment.
+#[derive(Debug, PartialEq)]
+enum MarkdownBlock {
+    /// A paragraph or list item, re-wrapped to the line width. `marker` is
+    /// the list item marker, such as `- ` or `1. `, or empty.
+    Paragraph { marker: String, text: String },
+    /// A block tag like `@param`, `@return` or `@throws`.
+    Tag {
+        name: String,
+        args: Option<String>,
+        desc: String,
+    },
+    /// Lines kept exactly as written: code blocks, headings, tables, block
+    /// quotes and thematic breaks.
+    Verbatim(Vec<String>),
+    /// A paragraph break.
+    BlankLine,
+}
+
+/// Format a Javadoc comment.
+///
+/// Prose is re-wrapped to the line width with paragraph breaks kept, every
+/// line gets a ` * ` gutter, and `<pre>` blocks and multi-line `{@code ...}`
+/// are kept verbatim. Block tags are put in `@param`, `@return`, `@throws`,
+/// `@see` order and wrap under the start of their description. A comment
+/// holding a single short paragraph stays on one line: `/** Text. */`.
+pub fn gen_javadoc(node: tree_sitter::Node, context: &FormattingContext) -> PrintItems {
+    let text = &context.source[node.start_byte()..node.end_byte()];
+    let mut segments = parse_javadoc_segments(&extract_javadoc_content(text));
+    sort_tags(&mut segments, |segment| match segment {
+        JavadocSegment::Tag { name, .. } => Some(name),
+        _ => None,
+    });
+
+    let line_width = context.config.line_width as usize;
+    let indent_width = context.effective_indent_level() * context.indent_width();
+    let max_content_width = content_width(context, " * ");
+
+    let mut items = PrintItems::new();
+    match segments.as_slice() {
+        [] => {
+            items.push_str("/** */");
+            return items;
+        }
+        [JavadocSegment::Text(text)]
+            if indent_width + "/** ".len() + display_width(text) + " */".len() <= line_width =>
+        {
+            items.push_str(&format!("/** {text} */"));
+            return items;
+        }
+        _ => {}
+    }
+
+    items.push_str("/**");
+    for segment in &segments {
+        match segment {
+            JavadocSegment::Text(text) => {
+                for line in wrap_text(text, max_content_width) {
+                    push_gutter_line(&mut items, &line);
+                }
+            }
+            JavadocSegment::Tag { name, args, desc } => {
+                for line in wrap_tag(name, args.as_ref(), desc, max_content_width) {
+                    push_gutter_line(&mut items, &line);
+                }
+            }
+            JavadocSegment::Verbatim(lines) => {
+                for line in lines {
+                    push_gutter_line(&mut items, line.trim_end());
+                }
+            }
+            JavadocSegment::BlankLine => push_gutter_line(&mut items, ""),
+        }
+    }
+    items.newline();
+    items.push_str(" */");
+
+    items
+}
+
+/// Format a Markdown documentation comment (JEP 467) from the text after
+/// the `///` of each of its lines.
+///
+/// Paragraphs, list items and block tags are re-wrapped to the line width
+/// and block tags are ordered as in Javadoc comments; fenced and indented
+/// code, headings, tables and block quotes are kept as written. Runs of
+/// blank lines collapse to one.
+pub fn gen_markdown_doc(lines: &[&str], context: &FormattingContext) -> PrintItems {
+    let max_content_width = content_width(context, "/// ");
+
+    let mut blocks = parse_markdown_blocks(lines);
+    sort_tags(&mut blocks, |block| match block {
+        MarkdownBlock::Tag { name, .. } => Some(name),
+        _ => None,
+    });
+
+    let mut output = Vec::new();
+    for block in blocks {
+        match block {
+            MarkdownBlock::Paragraph { marker, text } => {
+                let padding = " ".repeat(marker.len());
+                let width = max_content_width.saturating_sub(marker.len());
+                for (i, line) in wrap_text(&text, width).into_iter().enumerate() {
+                    let prefix = if i == 0 { &marker } else { &padding };
+                    output.push(format!("{prefix}{line}"));
+                }
+            }
+            MarkdownBlock::Tag { name, args, desc } => {
+                output.extend(wrap_tag(&name, args.as_ref(), &desc, max_content_width));
+            }
+            MarkdownBlock::Verbatim(lines) => output.extend(lines),
+            MarkdownBlock::BlankLine => output.push(String::new()),
+        }
+    }
+    if output.is_empty() {
+        output.push(String::new());
+    }
+
+    let mut items = PrintItems::new();
+    for line in output {
+        if line.is_empty() {
+            items.push_str("///");
+        } else {
+            items.push_str(&format!("/// {line}"));
+        }
+        items.newline();
+    }
+    items
+}
+
+/// Parse the lines of a Markdown documentation comment into blocks.
+///
+/// The indentation shared by all lines is removed first, so each line is
+/// measured from the column where the comment text starts.
+fn parse_markdown_blocks(lines: &[&str]) -> Vec<MarkdownBlock> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect();
+
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.is_empty() {
+            if blocks
+                .last()
+                .is_some_and(|b| *b != MarkdownBlock::BlankLine)
+            {
+                blocks.push(MarkdownBlock::BlankLine);
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some(fence) = code_fence(line) {
+            let end = (i + 1..lines.len())
+                .find(|&j| lines[j].trim_start().starts_with(fence))
+                .unwrap_or(lines.len() - 1);
+            blocks.push(verbatim(&lines[i..=end]));
+            i = end + 1;
+            continue;
+        }
+
+        // Indented code: a paragraph would have taken the line as a
+        // continuation, so it starts after a blank line or another block
+        if indentation(line) >= 4 {
+            let end = (i..lines.len())
+                .take_while(|&j| indentation(lines[j]) >= 4)
+                .last()
+                .unwrap_or(i);
+            blocks.push(verbatim(&lines[i..=end]));
+            i = end + 1;
+            continue;
+        }
+
+        if is_markdown_line_block(line) {
+            blocks.push(verbatim(&lines[i..=i]));
+            i += 1;
+            continue;
+        }
+
+        let continuation = |i: usize, lines: &[&str]| {
+            let line = lines[i];
+            !lines[i - 1].ends_with('\\')
+                && !line.is_empty()
+                && code_fence(line).is_none()
+                && !is_markdown_line_block(line)
+                && !line.trim_start().starts_with('@')
+                && list_marker_len(line).is_none()
+        };
+
+        if line.starts_with('@') {
+            let (name, args, mut desc) = parse_tag_line(line);
+            i += 1;
+            while i < lines.len() && continuation(i, &lines) {
+                desc.push(' ');
+                desc.push_str(lines[i].trim());
+                i += 1;
+            }
+            blocks.push(MarkdownBlock::Tag { name, args, desc });
+            continue;
+        }
+
+        let marker_len = list_marker_len(line).unwrap_or(0);
+        let (marker, first) = line.split_at(marker_len);
+        let mut text = first.trim().to_string();
+        i += 1;
+        while i < lines.len() && continuation(i, &lines) {
+            text.push(' ');
+            text.push_str(lines[i].trim());
+            i += 1;
+        }
+        blocks.push(MarkdownBlock::Paragraph {
+            marker: marker.to_string(),
+            text,
+        });
+    }
+
+    if blocks.last() == Some(&MarkdownBlock::BlankLine) {
+        blocks.pop();
+    }
+    blocks
+}
+
+fn verbatim(lines: &[&str]) -> MarkdownBlock {
+    MarkdownBlock::Verbatim(lines.iter().map(|line| (*line).to_string()).collect())
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The fence that opens a fenced code block on `line`, if it opens one.
+fn code_fence(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    ["```", "~~~"]
+        .into_iter()
+        .find(|fence| trimmed.starts_with(fence))
+}
+
+/// Whether `line` is a Markdown block that is always a single line: a
+/// heading, table row, block quote, or thematic break or setext underline.
+fn is_markdown_line_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let is_rule = |c: char| trimmed.len() >= 3 && trimmed.chars().all(|t| t == c || t == ' ');
+    trimmed.starts_with('#')
+        || trimmed.starts_with('|')
+        || trimmed.starts_with('>')
+        || ['-', '*', '_', '='].into_iter().any(is_rule)
+}
+
+/// Length of the list item marker that starts `line`, including the space
+/// after it and any indentation before it: `- `, `* `, `+ `, `1. ` or `1) `.
+fn list_marker_len(line: &str) -> Option<usize> {
+    let indent = indentation(line);
+    let rest = &line[indent..];
+    let marker = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+    rest[marker..]
+        .starts_with(' ')
+        .then_some(indent + marker + 1)
+}
+
+/// Width left for comment text after the indent and `gutter` of each line.
+fn content_width(context: &FormattingContext, gutter: &str) -> usize {
+    let line_width = context.config.line_width as usize;
+    let indent_width = context.effective_indent_level() * context.indent_width();
+    let gutter_width = indent_width + gutter.len();
+    if line_width > gutter_width + 10 {
+        line_width - gutter_width
+    } else {
+        60 // reasonable fallback
+    }
+}
+
+/// Start a new comment line: ` * text`, or ` *` for an empty line.
+fn push_gutter_line(items: &mut PrintItems, line: &str) {
+    items.newline();
+    if line.is_empty() {
+        items.push_str(" *");
+    } else {
+        items.push_str(&format!(" * {line}"));
+    }
+}
+
+/// Extract the inner text content from a Javadoc comment.
+///
+/// Strips the `/**` prefix and `*/` suffix, and normalizes each
+/// continuation line by removing the leading ` * ` prefix.
+fn extract_javadoc_content(text: &str) -> String {
+    let inner = text
+        .strip_prefix("/**")
+        .unwrap_or(text)
+        .strip_suffix("*/")
+        .unwrap_or(text);
+
+    let mut lines = Vec::new();
+    for (i, line) in inner.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if i == 0 {
+            // First line (after /**) — just trim whitespace
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                lines.push(trimmed.to_string());
+            }
+        } else {
+            // Continuation lines: strip leading whitespace and optional `*`
+            let trimmed = line.trim_start();
+            if let Some(rest) = trimmed.strip_prefix('*') {
+                // Strip one leading space after * if present
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                lines.push(rest.trim_end().to_string());
+            } else {
+                lines.push(trimmed.trim_end().to_string());
+            }
+        }
+    }
+
+    // Remove trailing empty lines
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    lines.join("\n")
+}
+
+/// Parse Javadoc inner content into structured segments.
+///
+/// Runs of blank lines become a single paragraph break, and blank lines at
+/// the start are dropped.
+fn parse_javadoc_segments(content: &str) -> Vec<JavadocSegment> {
+    let mut segments = Vec::new();
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+
+        if trimmed.is_empty() {
+            if segments
+                .last()
+                .is_some_and(|s| *s != JavadocSegment::BlankLine)
+            {
+                segments.push(JavadocSegment::BlankLine);
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some(end) = verbatim_block_end(&lines, i) {
+            let mut block = vec![trimmed.to_string()];
+            block.extend(lines[i + 1..=end].iter().map(|l| (*l).to_string()));
+            segments.push(JavadocSegment::Verbatim(block));
+            i = end + 1;
+            continue;
+        }
+
+        // Tag line: the description runs until a blank line or the next block
+        if trimmed.starts_with('@') {
+            let (name, args, mut desc) = parse_tag_line(trimmed);
+            i += 1;
+            while i < lines.len() && continues_paragraph(&lines, i) {
+                desc.push(' ');
+                desc.push_str(lines[i].trim());
+                i += 1;
+            }
+            segments.push(JavadocSegment::Tag { name, args, desc });
+            continue;
+        }
+
+        // Prose: consecutive lines up to a blank line, tag, verbatim block
+        // or block-level HTML tag
+        let mut text_parts = vec![trimmed];
+        i += 1;
+        while i < lines.len() && continues_paragraph(&lines, i) && !starts_html_block(lines[i]) {
+            text_parts.push(lines[i].trim());
+            i += 1;
+        }
+        segments.push(JavadocSegment::Text(text_parts.join(" ")));
+    }
+
+    segments
+}
+
+/// Whether line `i` flows into the paragraph or tag above it.
+fn continues_paragraph(lines: &[&str], i: usize) -> bool {
+    let trimmed = lines[i].trim();
+    !trimmed.is_empty() && !trimmed.starts_with('@') && verbatim_block_end(lines, i).is_none()
+}
+
+/// Whether a line starts with a block-level HTML tag such as `<p>` or `<li>`.
+fn starts_html_block(line: &str) -> bool {
+    let trimmed = line.trim_start().to_ascii_lowercase();
+    BLOCK_TAGS.iter().any(|tag| trimmed.starts_with(tag))
+}
+
+/// If line `start` opens a block kept verbatim, the index of its last line.
+///
+/// A `<pre>` block runs to the line holding `</pre>`; a `{@code` whose
+/// braces don't close on its first line runs until they balance. An
+/// unterminated block runs to the end of the comment.
+fn verbatim_block_end(lines: &[&str], start: usize) -> Option<usize> {
+    let trimmed = lines[start].trim_start();
+    if trimmed.starts_with("<pre>") || trimmed.starts_with("<pre ") {
+        let end = (start..lines.len())
+            .find(|&i| lines[i].contains("</pre>"))
+            .unwrap_or(lines.len() - 1);
+        return Some(end);
+    }
+    if trimmed.starts_with("{@code") {
+        let mut depth = 0_i32;
+        for (i, line) in lines.iter().enumerate().skip(start) {
+            for c in line.chars() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            if depth <= 0 {
+                return (i > start).then_some(i);
+            }
+        }
+        return Some(lines.len() - 1);
+    }
+    None
+}
+
+/// Parse a single Javadoc tag line into (name, `optional_arg`, description).
+///
+/// Examples:
+/// - `@param name the name of the thing` -> ("@param", Some("name"), "the name of the thing")
+/// - `@return the result` -> ("@return", None, "the result")
+/// - `@throws IOException if I/O fails` -> ("@throws", Some("IOException"), "if I/O fails")
+fn parse_tag_line(line: &str) -> (String, Option<String>, String) {
+    let parts: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
+    let tag_name = parts[0].to_string();
+    let rest = if parts.len() > 1 { parts[1].trim() } else { "" };
+
+    // Tags that take an argument (parameter name, exception type)
+    match tag_name.as_str() {
+        "@param" | "@throws" | "@exception" | "@serialField" => {
+            let rest_parts: Vec<&str> = rest.splitn(2, char::is_whitespace).collect();
+            let arg = rest_parts[0].to_string();
+            let desc = if rest_parts.len() > 1 {
+                rest_parts[1].trim().to_string()
+            } else {
+                String::new()
+            };
+            (tag_name, Some(arg), desc)
+        }
+        _ => (tag_name, None, rest.to_string()),
+    }
+}
+
+/// Put each run of consecutive block tags in [`TAG_ORDER`], keeping the
+/// written order of tags with the same name. `tag_name` gives the name of
+/// the tag an item holds, or `None` if it is not a tag.
+fn sort_tags<T>(items: &mut [T], tag_name: impl Fn(&T) -> Option<&str>) {
+    let rank = |item: &T| {
+        tag_name(item).map_or(0, |name| {
+            TAG_ORDER
+                .iter()
+                .position(|names| names.contains(&name))
+                .unwrap_or(TAG_ORDER.len())
+        })
+    };
+    for run in items.chunk_by_mut(|a, b| tag_name(a).is_some() && tag_name(b).is_some()) {
+        run.sort_by_key(rank);
+    }
+}
+
+/// Wrap a block tag, aligning continuation lines under the start of the
+/// description:
+///
+/// ```text
+/// @param name the name of the thing, which
+///             wraps under "the"
+/// ```
+///
+/// When that column would leave less than half the width for the
+/// description, continuation lines use a four-space indent instead.
+fn wrap_tag(name: &str, args: Option<&String>, desc: &str, max_width: usize) -> Vec<String> {
+    let head = format_tag_line(name, args, "");
+    if desc.is_empty() {
+        return vec![head];
+    }
+
+    let hang = display_width(&head) + 1;
+    if hang * 2 <= max_width {
+        let padding = " ".repeat(hang);
+        return wrap_text(desc, max_width - hang)
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                if i == 0 {
+                    format!("{head} {line}")
+                } else {
+                    format!("{padding}{line}")
+                }
+            })
+            .collect();
+    }
+
+    let lines = wrap_text(&format_tag_line(name, args, desc), max_width);
+    let (first, rest) = lines.split_first().expect("wrap_text returns a line");
+    let mut result = vec![first.clone()];
+    let rest = rest.join(" ");
+    if !rest.is_empty() {
+        let padding = " ".repeat(TAG_FALLBACK_INDENT);
+        let width = max_width.saturating_sub(TAG_FALLBACK_INDENT);
+        result.extend(
+            wrap_text(&rest, width)
+                .into_iter()
+                .map(|line| format!("{padding}{line}")),
+        );
+    }
+    result
+}
+
+/// Format a tag line for output.
+fn format_tag_line(name: &str, args: Option<&String>, desc: &str) -> String {
+    let mut result = name.to_string();
+    if let Some(arg) = args {
+        result.push(' ');
+        result.push_str(arg);
+    }
+    if !desc.is_empty() {
+        result.push(' ');
+        result.push_str(desc);
+    }
+    result
+}
+
+/// Word-wrap text to the given maximum width.
+///
+/// Preserves inline `{@code ...}` constructs as atomic units.
+/// Returns a vector of lines.
+fn wrap_text(text: &str, max_width: usize) -> Vec<String> {
+    if text.is_empty() {
+        return vec![String::new()];
+    }
+
+    let words = split_preserving_inline_tags(text);
+    let mut lines = Vec::new();
+    let mut current_line = String::new();
+
+    for word in &words {
+        if current_line.is_empty() {
+            current_line.clone_from(word);
+        } else if display_width(&current_line) + 1 + display_width(word) <= max_width {
+            current_line.push(' ');
+            current_line.push_str(word);
+        } else {
+            lines.push(std::mem::take(&mut current_line));
+            current_line.clone_from(word);
+        }
+    }
+
+    if !current_line.is_empty() {
+        lines.push(current_line);
+    }
+
+    if lines.is_empty() {
+        vec![String::new()]
+    } else {
+        lines
+    }
+}
+
+/// Split text into words, preserving `{@code ...}` and similar inline tags
+/// as single tokens.
+fn split_preserving_inline_tags(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    let mut current_word = String::new();
+
+    while i < chars.len() {
+        if chars[i] == '{' && i + 1 < chars.len() && chars[i + 1] == '@' {
+            // Start of inline tag — collect until matching '}'
+            if !current_word.is_empty() {
+                // Flush the word accumulated before the tag
+                for w in current_word.split_whitespace() {
+                    tokens.push(w.to_string());
+                }
+                current_word.clear();
+            }
+            let mut tag = String::new();
+            let mut depth = 0;
+            while i < chars.len() {
+                tag.push(chars[i]);
+                if chars[i] == '{' {
+                    depth += 1;
+                } else if chars[i] == '}' {
+                    depth -= 1;
+                    if depth == 0 {
+                        i += 1;
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            tokens.push(tag);
+        } else {
+            current_word.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    if !current_word.is_empty() {
+        for w in current_word.split_whitespace() {
+            tokens.push(w.to_string());
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_javadoc_content() {
+        let text = "/**\n * Hello world.\n * @param name the name\n */";
+        let content = extract_javadoc_content(text);
+        assert!(content.contains("Hello world."));
+        assert!(content.contains("@param name the name"));
+    }
+
+    #[test]
+    fn test_parse_tag_line_param() {
+        let (name, args, desc) = parse_tag_line("@param name the name of the thing");
+        assert_eq!(name, "@param");
+        assert_eq!(args, Some("name".to_string()));
+        assert_eq!(desc, "the name of the thing");
+    }
+
+    #[test]
+    fn test_parse_tag_line_return() {
+        let (name, args, desc) = parse_tag_line("@return the result");
+        assert_eq!(name, "@return");
+        assert_eq!(args, None);
+        assert_eq!(desc, "the result");
+    }
+
+    #[test]
+    fn test_parse_segments_keeps_paragraphs_and_blocks() {
+        let content = "\nFirst line\ncontinues.\n\n\n<ul>\n<li>one\n</ul>\n<pre>\n  code();\n</pre>\n{@code\n  more();\n}";
+        let segments = parse_javadoc_segments(content);
+        assert_eq!(
+            segments,
+            vec![
+                JavadocSegment::Text("First line continues.".to_string()),
+                JavadocSegment::BlankLine,
+                JavadocSegment::Text("<ul>".to_string()),
+                JavadocSegment::Text("<li>one".to_string()),
+                JavadocSegment::Text("</ul>".to_string()),
+                JavadocSegment::Verbatim(vec![
+                    "<pre>".to_string(),
+                    "  code();".to_string(),
+                    "</pre>".to_string()
+                ]),
+                JavadocSegment::Verbatim(vec![
+                    "{@code".to_string(),
+                    "  more();".to_string(),
+                    "}".to_string()
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_tags_within_runs() {
+        let tag = |name: &str| JavadocSegment::Tag {
+            name: name.to_string(),
+            args: None,
+            desc: String::new(),
+        };
+        let mut segments = vec![
+            tag("@see"),
+            tag("@return"),
+            JavadocSegment::BlankLine,
+            tag("@throws"),
+            tag("@since"),
+            tag("@param"),
+        ];
+        sort_tags(&mut segments, |segment| match segment {
+            JavadocSegment::Tag { name, .. } => Some(name),
+            _ => None,
+        });
+        assert_eq!(
+            segments,
+            vec![
+                tag("@return"),
+                tag("@see"),
+                JavadocSegment::BlankLine,
+                tag("@param"),
+                tag("@throws"),
+                tag("@since"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_tag_aligns_under_description() {
+        let lines = wrap_tag(
+            "@param",
+            Some(&"name".to_string()),
+            "the name of the thing to look up",
+            30,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                "@param name the name of the",
+                "            thing to look up"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_markdown_blocks() {
+        let lines = [
+            " First line",
+            " continues.",
+            "",
+            "",
+            " - item one",
+            "   wraps",
+            " 2. item two",
+            " ```",
+            "   code();",
+            " ```",
+            " # Heading",
+            " @param name the name",
+            "",
+        ];
+        assert_eq!(
+            parse_markdown_blocks(&lines),
+            vec![
+                MarkdownBlock::Paragraph {
+                    marker: String::new(),
+                    text: "First line continues.".to_string()
+                },
+                MarkdownBlock::BlankLine,
+                MarkdownBlock::Paragraph {
+                    marker: "- ".to_string(),
+                    text: "item one wraps".to_string()
+                },
+                MarkdownBlock::Paragraph {
+                    marker: "2. ".to_string(),
+                    text: "item two".to_string()
+                },
+                MarkdownBlock::Verbatim(vec![
+                    "```".to_string(),
+                    "  code();".to_string(),
+                    "```".to_string()
+                ]),
+                MarkdownBlock::Verbatim(vec!["# Heading".to_string()]),
+                MarkdownBlock::Tag {
+                    name: "@param".to_string(),
+                    args: Some("name".to_string()),
+                    desc: "the name".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_text_short() {
+        let lines = wrap_text("hello world", 80);
+        assert_eq!(lines, vec!["hello world"]);
+    }
+
+    #[test]
+    fn test_wrap_text_long() {
+        let long = "this is a really long line that should definitely be wrapped because it exceeds the maximum width";
+        let lines = wrap_text(long, 40);
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= 40 || line.split_whitespace().count() == 1);
+        }
+    }
+
+    #[test]
+    fn test_wrap_preserves_inline_code() {
+        let text = "See {@code SomeClass} for details";
+        let lines = wrap_text(text, 80);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("{@code SomeClass}"));
+    }
+
+    #[test]
+    fn test_split_preserving_inline_tags() {
+        let tokens = split_preserving_inline_tags("See {@code SomeClass} for details");
+        assert_eq!(tokens, vec!["See", "{@code SomeClass}", "for", "details"]);
+    }
+}
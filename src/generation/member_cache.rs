@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use dprint_core::formatting::{Condition, ConditionProperties, PrintItems, Signal};
+
+use super::context::FormattingContext;
+use super::helpers::PrintItemsExt;
+
+/// Bodies whose members are cached, when they belong to a top-level type.
+pub(super) const BODY_KINDS: &[&str] = &[
+    "class_body",
+    "interface_body",
+    "enum_body_declarations",
+    "annotation_type_body",
+];
+
+/// Member kinds whose output is cached.
+pub(super) const MEMBER_KINDS: &[&str] = &[
+    "field_declaration",
+    "constant_declaration",
+    "method_declaration",
+    "constructor_declaration",
+    "compact_constructor_declaration",
+    "annotation_type_element_declaration",
+    "static_initializer",
+    "block",
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+];
+
+/// Whether `node` is a member of a type body, of a top-level type or not.
+pub(super) fn is_type_member(node: tree_sitter::Node) -> bool {
+    MEMBER_KINDS.contains(&node.kind())
+        && node
+            .parent()
+            .is_some_and(|parent| BODY_KINDS.contains(&parent.kind()))
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// The member's output lines, with their indentation.
+    lines: Rc<[String]>,
+    /// Whether the entry was reused or added since the last `retain_used`.
+    used: bool,
+}
+
+/// A member generated in the current pass, and the output lines its first
+/// and last lines were printed on.
+#[derive(Debug)]
+struct Pending {
+    key: String,
+    first_line: Option<usize>,
+    last_line: Option<usize>,
+}
+
+/// The formatted output of the members of top-level types, keyed by their
+/// source text, so incremental formatting can reuse it for members an edit
+/// did not touch.
+///
+/// A member's output depends only on its own text, the configuration and
+/// the kind of type it is in, as long as no `@formatter:off` region or
+/// syntax error reaches into it and no comment shares its first or last
+/// line. Members that fail any of these are always generated.
+#[derive(Debug, Default)]
+pub(crate) struct MemberCache {
+    entries: HashMap<String, Entry>,
+    pending: Vec<Pending>,
+    /// Members reused since the last `retain_used`.
+    reused: usize,
+}
+
+impl MemberCache {
+    /// Store the output of the members generated by the pass that printed
+    /// `output`.
+    pub(crate) fn finish_pass(&mut self, output: &str) {
+        let lines: Vec<&str> = output.lines().collect();
+        for pending in self.pending.drain(..) {
+            let (Some(first), Some(last)) = (pending.first_line, pending.last_line) else {
+                continue;
+            };
+            let Some(member_lines) = lines.get(first..=last) else {
+                continue;
+            };
+            let entry = Entry {
+                lines: member_lines
+                    .iter()
+                    .map(|line| (*line).to_string())
+                    .collect(),
+                used: true,
+            };
+            self.entries.insert(pending.key, entry);
+        }
+    }
+
+    /// Drop the entries that were neither reused nor added since the last
+    /// call, and return how many members were reused.
+    pub(crate) fn retain_used(&mut self) -> usize {
+        self.pending.clear();
+        self.entries
+            .retain(|_, entry| std::mem::take(&mut entry.used));
+        std::mem::take(&mut self.reused)
+    }
+}
+
+/// The cache key for `node` if it is a member whose output can be cached.
+fn member_key(node: tree_sitter::Node, context: &FormattingContext) -> Option<String> {
+    if !MEMBER_KINDS.contains(&node.kind()) || node.has_error() {
+        return None;
+    }
+    let body = node.parent().filter(|p| BODY_KINDS.contains(&p.kind()))?;
+    let enum_body = body.parent().filter(|p| p.kind() == "enum_body");
+    let declaration = enum_body.unwrap_or(body).parent()?;
+    if declaration.parent()?.kind() != "program" || context.overlaps_suppressed(node) {
+        return None;
+    }
+
+    // A comment sharing the member's first or last line is printed on that
+    // line by the body, outside the member's own output
+    let shares_line = |sibling: Option<tree_sitter::Node>, row: usize| {
+        sibling.is_some_and(|s| {
+            s.is_extra() && (s.end_position().row == row || s.start_position().row == row)
+        })
+    };
+    if shares_line(node.prev_sibling(), node.start_position().row)
+        || shares_line(node.next_sibling(), node.end_position().row)
+    {
+        return None;
+    }
+
+    let text = &context.source[node.byte_range()];
+    Some(format!("{}\n{}", declaration.kind(), text))
+}
+
+/// The cached output of `node`, if it is a member the cache has seen.
+pub(crate) fn reuse(node: tree_sitter::Node, context: &FormattingContext) -> Option<PrintItems> {
+    let cache = context.member_cache()?;
+    let key = member_key(node, context)?;
+    let lines = {
+        let mut cache = cache.borrow_mut();
+        let entry = cache.entries.get_mut(&key)?;
+        entry.used = true;
+        let lines = Rc::clone(&entry.lines);
+        cache.reused += 1;
+        lines
+    };
+
+    // The first line starts at the current indent; later lines keep the
+    // indentation they were printed with
+    let mut items = PrintItems::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i == 0 {
+            items.push_str(line.trim_start());
+            continue;
+        }
+        items.push_signal(Signal::StartIgnoringIndent);
+        items.newline();
+        if !line.is_empty() {
+            items.push_str(line);
+        }
+        items.push_signal(Signal::FinishIgnoringIndent);
+    }
+    Some(items)
+}
+
+/// Wrap the items generated for `node` with markers that record the output
+/// lines of its first and last line, if it is a member the cache can keep.
+pub(crate) fn record(
+    node: tree_sitter::Node,
+    items: PrintItems,
+    context: &FormattingContext,
+) -> PrintItems {
+    let (Some(cache), Some(key)) = (context.member_cache(), member_key(node, context)) else {
+        return items;
+    };
+    let index = {
+        let mut cache = cache.borrow_mut();
+        cache.pending.push(Pending {
+            key,
+            first_line: None,
+            last_line: None,
+        });
+        cache.pending.len() - 1
+    };
+
+    let mut recorded = PrintItems::new();
+    recorded.push_condition(line_marker(cache, index, true));
+    recorded.extend(items);
+    recorded.push_condition(line_marker(cache, index, false));
+    recorded
+}
+
+/// A print item that records the output line the printer is on as the first
+/// or last line of pending member `index`. It prints nothing.
+fn line_marker(cache: &Rc<RefCell<MemberCache>>, index: usize, first: bool) -> Condition {
+    let cache = Rc::clone(cache);
+    Condition::new(
+        "memberCacheMarker",
+        ConditionProperties {
+            condition: Rc::new(move |context| {
+                let line = Some(context.writer_info.line_number as usize);
+                let pending = &mut cache.borrow_mut().pending[index];
+                if first {
+                    pending.first_line = line;
+                } else {
+                    pending.last_line = line;
+                }
+                Some(false)
+            }),
+            true_path: None,
+            false_path: None,
+        },
+    )
+}
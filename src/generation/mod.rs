@@ -1,10 +1,20 @@
+mod blank_lines;
+mod comment_map;
 mod comments;
 mod context;
 mod declarations;
 mod expressions;
 mod generate;
 mod helpers;
+mod javadoc;
+mod member_cache;
+mod modules;
 mod statements;
+mod work_budget;
 
+pub use comment_map::{CommentMap, CommentPosition};
+pub(crate) use context::CONTINUATION_INDENT_LEVELS;
 pub use context::FormattingContext;
-pub use generate::generate;
+pub(crate) use generate::{fallbacks, generate_with_member_cache};
+pub use generate::{generate, generate_with_source_map};
+pub(crate) use member_cache::MemberCache;
@@ -0,0 +1,308 @@
+use dprint_core::formatting::PrintItems;
+
+use super::context::FormattingContext;
+use super::generate::gen_node;
+use super::helpers::{PrintItemsExt, collapse_whitespace_len, display_width, gen_node_text};
+
+/// Order of directive groups in a module body; directives are sorted by name
+/// within a group and groups are separated by a blank line.
+const DIRECTIVE_ORDER: &[&str] = &[
+    "requires_module_directive",
+    "exports_module_directive",
+    "opens_module_directive",
+    "uses_module_directive",
+    "provides_module_directive",
+];
+
+/// A directive with the comments that belong to it: the ones on the lines
+/// above, and one trailing it on the same line.
+struct Directive<'a> {
+    node: tree_sitter::Node<'a>,
+    leading: Vec<tree_sitter::Node<'a>>,
+    trailing: Option<tree_sitter::Node<'a>>,
+}
+
+/// Format a module declaration (`module-info.java`):
+/// `@Ann open module com.example { directives }`
+pub fn gen_module_declaration<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "annotation" | "marker_annotation" => {
+                items.extend(gen_node(child, context));
+                items.newline();
+            }
+            "open" => {
+                items.push_str("open");
+                items.space();
+            }
+            "module" => items.push_str("module"),
+            "identifier" | "scoped_identifier" => {
+                items.space();
+                items.extend(gen_node_text(child, context.source));
+            }
+            "module_body" => {
+                items.extend(context.brace_separator());
+                items.extend(gen_module_body(child, context));
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+/// Format a module body. Directives are grouped as `requires`, `exports`,
+/// `opens`, `uses` and `provides`, each group sorted by module, package or
+/// type name. Comments move with the directive they precede or trail.
+fn gen_module_body<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    items.push_str("{");
+
+    let mut directives: Vec<Directive> = Vec::new();
+    let mut pending_comments = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if child.is_extra() {
+            match directives.last_mut() {
+                Some(prev)
+                    if pending_comments.is_empty()
+                        && prev.trailing.is_none()
+                        && prev.node.end_position().row == child.start_position().row =>
+                {
+                    prev.trailing = Some(child);
+                }
+                _ => pending_comments.push(child),
+            }
+        } else {
+            directives.push(Directive {
+                node: child,
+                leading: std::mem::take(&mut pending_comments),
+                trailing: None,
+            });
+        }
+    }
+
+    if directives.is_empty() && pending_comments.is_empty() {
+        items.push_str("}");
+        return items;
+    }
+
+    directives.sort_by_cached_key(|d| {
+        let group = DIRECTIVE_ORDER
+            .iter()
+            .position(|kind| *kind == d.node.kind())
+            .unwrap_or(DIRECTIVE_ORDER.len());
+        (group, directive_name(d.node, context.source))
+    });
+
+    items.start_indent();
+    let mut indented = context.indented(1);
+    let context = &mut *indented;
+
+    let mut prev_kind: Option<&str> = None;
+    let mut prev_was_line_comment = false;
+    for directive in &directives {
+        if !prev_was_line_comment {
+            items.newline();
+        }
+        if prev_kind.is_some_and(|kind| kind != directive.node.kind()) {
+            items.newline();
+        }
+        for comment in &directive.leading {
+            items.extend(gen_node(*comment, context));
+            if comment.kind() == "block_comment" {
+                items.newline();
+            }
+        }
+        items.extend(gen_module_directive(directive.node, context));
+        prev_was_line_comment = false;
+        if let Some(comment) = directive.trailing {
+            items.space();
+            items.extend(gen_node(comment, context));
+            prev_was_line_comment = comment.kind() == "line_comment";
+        }
+        prev_kind = Some(directive.node.kind());
+    }
+
+    // Comments after the last directive stay at the end of the body
+    for comment in &pending_comments {
+        if !prev_was_line_comment {
+            items.newline();
+        }
+        items.extend(gen_node(*comment, context));
+        prev_was_line_comment = comment.kind() == "line_comment";
+    }
+
+    items.finish_indent();
+    if !prev_was_line_comment {
+        items.newline();
+    }
+    items.push_str("}");
+
+    items
+}
+
+/// The name a directive is sorted by: the required module, the exported or
+/// opened package, or the used or provided type.
+fn directive_name<'a>(node: tree_sitter::Node, source: &'a str) -> &'a str {
+    let field = match node.kind() {
+        "requires_module_directive" => "module",
+        "exports_module_directive" | "opens_module_directive" => "package",
+        "uses_module_directive" => "type",
+        _ => "provided",
+    };
+    node.child_by_field_name(field)
+        .map_or("", |name| &source[name.start_byte()..name.end_byte()])
+}
+
+/// Format a single module directive, e.g. `requires transitive java.sql;`.
+///
+/// A `with` list that doesn't fit on the line puts each name on its own
+/// continuation line:
+/// ```java
+/// provides com.example.spi.Plugin with
+///         com.example.impl.FirstPlugin,
+///         com.example.impl.SecondPlugin;
+/// ```
+///
+/// The target modules of a qualified `exports` or `opens` are packed onto
+/// continuation lines instead, like the types of a `throws` clause, with `to`
+/// kept on the directive's first line. They go one per line when comments
+/// sit between them or a module is too wide for a continuation line.
+fn gen_module_directive<'a>(
+    node: tree_sitter::Node<'a>,
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let mut items = PrintItems::new();
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.children(&mut cursor).collect();
+
+    let list: Vec<_> = children
+        .iter()
+        .skip_while(|c| !matches!(c.kind(), "to" | "with"))
+        .skip(1)
+        .filter(|c| c.kind() != "," && c.kind() != ";")
+        .collect();
+    let has_comments = list.iter().any(|c| c.is_extra());
+    let names: Vec<_> = list.iter().filter(|c| !c.is_extra()).copied().collect();
+
+    let indent_width = context.effective_indent_level() * context.indent_width();
+    let flat_width = collapse_whitespace_len(&context.source[node.start_byte()..node.end_byte()]);
+    let wrap_list = has_comments || indent_width + flat_width > context.config.line_width as usize;
+    let packed = if wrap_list && !has_comments && node.child_by_field_name("modules").is_some() {
+        packed_lines(&names, indent_width + context.continuation_width(), context)
+    } else {
+        None
+    };
+    // Whether each name of the list starts a line
+    let mut starts_line = vec![wrap_list; names.len()];
+    if let Some(lines) = packed {
+        starts_line.fill(false);
+        let mut first = 0;
+        for count in lines {
+            starts_line[first] = true;
+            first += count;
+        }
+    }
+
+    let mut in_list = false;
+    let mut name_index = 0;
+    let mut after_line_comment = false;
+    for child in &children {
+        match child.kind() {
+            "requires" | "exports" | "opens" | "uses" | "provides" => {
+                items.push_str(child.kind());
+            }
+            "to" | "with" => {
+                items.space();
+                items.push_str(child.kind());
+                in_list = true;
+                if wrap_list {
+                    items.start_indent();
+                    items.start_indent();
+                }
+            }
+            "," => items.push_str(","),
+            ";" => {
+                if in_list && wrap_list {
+                    items.finish_indent();
+                    items.finish_indent();
+                }
+                items.push_str(";");
+            }
+            "requires_modifier" => {
+                items.space();
+                items.extend(gen_node_text(*child, context.source));
+            }
+            "line_comment" | "block_comment" => {
+                items.space();
+                items.extend(gen_node(*child, context));
+                after_line_comment = child.kind() == "line_comment";
+            }
+            "identifier" | "scoped_identifier" => {
+                if in_list {
+                    // A line comment ends with its own newline
+                    if !after_line_comment {
+                        if starts_line[name_index] {
+                            items.newline();
+                        } else {
+                            items.space();
+                        }
+                    }
+                    name_index += 1;
+                } else {
+                    items.space();
+                }
+                after_line_comment = false;
+                items.extend(gen_node_text(*child, context.source));
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+/// How many of `names` go on each continuation line starting at column
+/// `continuation_col`, filling each line before starting the next, or `None`
+/// if a name does not fit on a line of its own.
+fn packed_lines(
+    names: &[&tree_sitter::Node],
+    continuation_col: usize,
+    context: &FormattingContext,
+) -> Option<Vec<usize>> {
+    // Leave room for the `,` or `;` ending each line
+    let capacity = (context.config.line_width as usize).saturating_sub(1);
+    let mut lines = Vec::new();
+    let mut current = 0;
+    let mut width = 0;
+    for name in names {
+        let name_width = display_width(&context.source[name.byte_range()]);
+        if continuation_col + name_width > capacity {
+            return None;
+        }
+        if current > 0 && width + 2 + name_width <= capacity {
+            width += 2 + name_width;
+            current += 1;
+        } else {
+            if current > 0 {
+                lines.push(current);
+            }
+            width = continuation_col + name_width;
+            current = 1;
+        }
+    }
+    if current > 0 {
+        lines.push(current);
+    }
+    Some(lines)
+}
@@ -1,16 +1,20 @@
-use dprint_core::formatting::PrintItems;
+use dprint_core::formatting::{LineNumber, PrintItems};
 
+use super::blank_lines;
 use super::comments;
-use super::context::FormattingContext;
+use super::context::{CONTINUATION_INDENT_LEVELS, ContextFlag, FormattingContext};
 use super::declarations;
 use super::generate::gen_node;
-use super::helpers::{PrintItemsExt, gen_node_text, is_type_node};
+use super::helpers::{
+    PrintItemsExt, collapse_whitespace_len, display_width, gen_if_fits, gen_if_fits_on_line,
+    gen_node_text, is_type_node,
+};
 
 /// Format a block: `{ statement1; statement2; }`
 ///
-/// Handles comment (extra) nodes that appear within the block,
-/// emitting trailing comments on the same line and leading comments
-/// on their own lines.
+/// Comments are placed from the comment map: trailing comments stay on the
+/// line of the code before them, and leading and dangling comments go on
+/// their own lines.
 pub fn gen_block<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -18,73 +22,127 @@ pub fn gen_block<'a>(
     let mut items = PrintItems::new();
     items.push_str("{");
 
+    let comment_map = context.comment_map();
     let mut cursor = node.walk();
-    let children: Vec<_> = node.children(&mut cursor).collect();
-
-    // Include both named statements and extra (comment) nodes
-    let stmts: Vec<_> = children
-        .iter()
-        .filter(|c| c.kind() != "{" && c.kind() != "}" && (c.is_named() || c.is_extra()))
+    let stmts: Vec<_> = node
+        .named_children(&mut cursor)
+        .filter(|c| !c.is_extra())
         .collect();
 
-    if stmts.is_empty() {
+    if stmts.is_empty()
+        && comment_map.after_open(node).is_empty()
+        && comment_map.dangling(node).is_empty()
+    {
         items.push_str("}");
         return items;
     }
 
+    if keeps_simple_method_on_one_line(node, &stmts, context) {
+        let stmt = stmts[0];
+        let mut flat = PrintItems::new();
+        flat.space();
+        flat.extend(gen_node(stmt, context));
+        flat.space();
+        flat.push_str("}");
+        // The anonymous class measured the whole line already
+        if in_single_line_anonymous_class(node, context) {
+            items.extend(flat);
+            return items;
+        }
+        let stmt_width = collapse_whitespace_len(&context.source[stmt.byte_range()]);
+        let expanded = gen_block_statements(node, &stmts, context);
+        let line_width = context.config.line_width;
+        items.extend(gen_if_fits(
+            flat,
+            " ".len() + stmt_width + " }".len(),
+            expanded,
+            line_width,
+        ));
+        return items;
+    }
+
+    items.extend(gen_block_statements(node, &stmts, context));
+    items
+}
+
+/// The statements of a non-empty block, each on its own line, and its `}`.
+fn gen_block_statements<'a>(
+    node: tree_sitter::Node<'a>,
+    stmts: &[tree_sitter::Node<'a>],
+    context: &mut FormattingContext<'a>,
+) -> PrintItems {
+    let comment_map = context.comment_map();
+    let mut items = PrintItems::new();
     items.start_indent();
-    context.indent();
+    let mut indented = context.indented(1);
+    let context = &mut *indented;
 
     let mut prev_was_line_comment = false;
-    // Initialize to opening brace's row to preserve blank lines after `{`
-    let open_brace_row = children
-        .iter()
-        .find(|c| c.kind() == "{")
+    // Initialize to opening brace's row to preserve blank lines after `{`,
+    // except in a double-brace initializer, which stays tight at both ends
+    let double_brace = node
+        .parent()
+        .and_then(declarations::double_brace_initializer)
+        .is_some();
+    let open_brace_row = node
+        .child(0)
+        .filter(|c| c.kind() == "{")
+        .filter(|_| !double_brace)
         .map(|c| c.end_position().row);
     let mut prev_end_row: Option<usize> = open_brace_row;
-    for stmt in &stmts {
-        if stmt.is_extra() {
-            let is_trailing = comments::is_trailing_comment(**stmt);
-            if is_trailing {
-                // Trailing comment: append on same line
-                items.space();
-                items.extend(gen_node(**stmt, context));
-                prev_was_line_comment = stmt.kind() == "line_comment";
-                prev_end_row = Some(stmt.end_position().row);
-            } else {
-                // Leading/standalone comment
-                if !prev_was_line_comment {
-                    items.newline();
-                }
-                // Preserve blank line from source before this comment
-                if let Some(prev_row) = prev_end_row
-                    && stmt.start_position().row > prev_row + 1
-                {
-                    items.newline();
-                }
-                items.extend(gen_node(**stmt, context));
-                prev_was_line_comment = stmt.kind() == "line_comment";
-                prev_end_row = Some(stmt.end_position().row);
-            }
-            continue;
+
+    for comment in comment_map.after_open(node) {
+        items.space();
+        items.extend(gen_node(*comment, context));
+        prev_was_line_comment = comment.kind() == "line_comment";
+        prev_end_row = Some(comment.end_position().row);
+    }
+
+    for stmt in stmts {
+        for comment in comment_map.leading(*stmt) {
+            items.extend(comments::gen_own_line_comment(
+                *comment,
+                prev_was_line_comment,
+                prev_end_row,
+                context,
+            ));
+            prev_was_line_comment = comment.kind() == "line_comment";
+            prev_end_row = Some(comment.end_position().row);
         }
 
         if !prev_was_line_comment {
             items.newline();
         }
-        // Preserve blank line from source between statements
-        if let Some(prev_row) = prev_end_row
-            && stmt.start_position().row > prev_row + 1
-        {
-            items.newline();
-        }
-        items.extend(gen_node(**stmt, context));
+        // Preserve blank lines from source between statements
+        items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+            context,
+            prev_end_row,
+            stmt.start_position().row,
+        )));
+        items.extend(gen_node(*stmt, context));
         prev_was_line_comment = false;
         prev_end_row = Some(stmt.end_position().row);
+
+        for comment in comment_map.trailing(*stmt) {
+            items.space();
+            items.extend(gen_node(*comment, context));
+            prev_was_line_comment = comment.kind() == "line_comment";
+            prev_end_row = Some(comment.end_position().row);
+        }
+    }
+
+    for comment in comment_map.dangling(node) {
+        items.extend(comments::gen_own_line_comment(
+            *comment,
+            prev_was_line_comment,
+            prev_end_row,
+            context,
+        ));
+        prev_was_line_comment = comment.kind() == "line_comment";
+        prev_end_row = Some(comment.end_position().row);
     }
 
     items.finish_indent();
-    context.dedent();
     // Don't emit extra newline if last item was a line comment (which already
     // includes a trailing newline), to avoid blank line before `}`.
     if !prev_was_line_comment {
@@ -93,21 +151,20 @@ pub fn gen_block<'a>(
     // PJF strips blank lines before closing `}` in method/constructor bodies
     // but preserves them in other blocks (try, if, for, etc.)
     let parent_kind = node.parent().map_or("", |p| p.kind());
-    let strip_trailing_blank = matches!(
-        parent_kind,
-        "method_declaration" | "constructor_declaration" | "static_initializer"
-    );
-    if !strip_trailing_blank && let Some(prev_row) = prev_end_row {
-        let close_brace_row = children
-            .iter()
-            .rev()
-            .find(|c| c.kind() == "}")
-            .map(|c| c.start_position().row);
-        if let Some(close_row) = close_brace_row
-            && close_row > prev_row + 1
-        {
-            items.newline();
-        }
+    let strip_trailing_blank = double_brace
+        || matches!(
+            parent_kind,
+            "method_declaration" | "constructor_declaration" | "static_initializer"
+        );
+    let close_brace = node
+        .child(node.child_count().saturating_sub(1))
+        .filter(|c| c.kind() == "}");
+    if !strip_trailing_blank && let Some(close_brace) = close_brace {
+        items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+            context,
+            prev_end_row,
+            close_brace.start_position().row,
+        )));
     }
     items.push_str("}");
 
@@ -115,6 +172,12 @@ pub fn gen_block<'a>(
 }
 
 /// Format a local variable declaration: `int x = 5;`
+///
+/// Three wraps can apply, in this order: annotations go on lines of their
+/// own, type arguments too wide for the line wrap one per line, and a value
+/// too wide for its line wraps after `=`. When the type arguments wrap,
+/// every declarator moves to one continuation line after the type, and a
+/// value that wraps after `=` is indented from that line.
 pub fn gen_local_variable_declaration<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -123,6 +186,7 @@ pub fn gen_local_variable_declaration<'a>(
     let mut cursor = node.walk();
     let mut need_space = false;
     let mut type_args_wrapped = false;
+    let mut declarators_continued = false;
 
     for child in node.children(&mut cursor) {
         match child.kind() {
@@ -145,19 +209,18 @@ pub fn gen_local_variable_declaration<'a>(
             }
             "variable_declarator" => {
                 if type_args_wrapped {
-                    items.start_indent();
-                    items.start_indent();
-                    items.newline();
-                    context.indent();
-                    context.indent();
-                    context.set_declarator_on_new_line(true);
-                    items.extend(gen_node(child, context));
-                    context.set_declarator_on_new_line(false);
-                    context.dedent();
-                    context.dedent();
-                    items.finish_indent();
-                    items.finish_indent();
-                    type_args_wrapped = false;
+                    if declarators_continued {
+                        items.space();
+                    } else {
+                        items.start_indent();
+                        items.start_indent();
+                        items.newline();
+                        declarators_continued = true;
+                    }
+                    let mut indented = context.indented(CONTINUATION_INDENT_LEVELS);
+                    let mut on_new_line =
+                        indented.with_flag(ContextFlag::DeclaratorOnNewLine, true);
+                    items.extend(gen_node(child, &mut on_new_line));
                 } else {
                     if need_space {
                         items.space();
@@ -177,6 +240,10 @@ pub fn gen_local_variable_declaration<'a>(
             _ => {}
         }
     }
+    if declarators_continued {
+        items.finish_indent();
+        items.finish_indent();
+    }
 
     items
 }
@@ -209,41 +276,72 @@ pub fn gen_if_statement<'a>(
     let mut cursor = node.walk();
     let children: Vec<_> = node.children(&mut cursor).collect();
 
+    // A body kept on the line of its `if` or `else` needs everything before
+    // it on that line, the condition flat
+    let mut start = LineNumber::new("ifStart");
     let mut i = 0;
     let mut prev_was_block = false;
     while i < children.len() {
         let child = children[i];
         match child.kind() {
             "if" => {
+                items.push_info(start);
                 items.push_str("if");
                 items.space();
             }
             "parenthesized_expression" | "condition" => {
                 items.extend(gen_node(child, context));
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
                 prev_was_block = true;
             }
             "else" => {
                 if prev_was_block {
-                    // After block: `} else` on same line
-                    items.space();
+                    // After block: `} else` on same line, or `else` on the next one
+                    items.extend(context.keyword_separator(context.config.else_on_new_line));
                 } else {
                     // After brace-less statement: `else` on new line
                     items.newline();
                 }
+                start = LineNumber::new("elseStart");
+                items.push_info(start);
                 items.push_str("else");
-                items.space();
                 prev_was_block = false;
             }
             "if_statement" => {
                 // else if: recursively format
+                items.space();
                 items.extend(gen_if_statement(child, context));
             }
+            _ if child.is_named() && !child.is_extra() => {
+                // Non-block body (single statement)
+                let mut expanded = PrintItems::new();
+                expanded.start_indent();
+                expanded.newline();
+                expanded.extend(gen_node(child, &mut context.indented(1)));
+                expanded.finish_indent();
+                if keeps_single_line_if_body(child, context) {
+                    let mut flat = PrintItems::new();
+                    flat.space();
+                    flat.extend(gen_node(child, context));
+                    let body_width = collapse_whitespace_len(&context.source[child.byte_range()]);
+                    let line_width = context.config.line_width;
+                    items.extend(gen_if_fits_on_line(
+                        start,
+                        flat,
+                        " ".len() + body_width,
+                        expanded,
+                        line_width,
+                    ));
+                } else {
+                    items.extend(expanded);
+                }
+                prev_was_block = false;
+            }
             _ if child.is_named() => {
-                // Non-block consequence (single statement)
+                items.space();
                 items.extend(gen_node(child, context));
                 prev_was_block = false;
             }
@@ -255,6 +353,77 @@ pub fn gen_if_statement<'a>(
     items
 }
 
+/// Whether the `body` of a method or constructor may stay on the line of its
+/// header as `{ return x; }`: always in an anonymous class kept on one line,
+/// and otherwise only with `keepSimpleMethodsOnOneLine`, when the source
+/// already has it there on one line and when it holds a single return or
+/// expression statement with no nested block. Whether the line fits is left
+/// to the printer.
+fn keeps_simple_method_on_one_line(
+    body: tree_sitter::Node,
+    stmts: &[tree_sitter::Node],
+    context: &FormattingContext,
+) -> bool {
+    if in_single_line_anonymous_class(body, context) {
+        return true;
+    }
+    if !context.config.keep_simple_methods_on_one_line
+        || !body
+            .parent()
+            .is_some_and(|p| matches!(p.kind(), "method_declaration" | "constructor_declaration"))
+    {
+        return false;
+    }
+    let [stmt] = stmts else {
+        return false;
+    };
+    // A comment in the block puts it on lines of its own
+    let comment_map = context.comment_map();
+    let has_comments = !comment_map.after_open(body).is_empty()
+        || !comment_map.leading(*stmt).is_empty()
+        || !comment_map.trailing(*stmt).is_empty()
+        || !comment_map.dangling(body).is_empty();
+    if has_comments
+        || !matches!(stmt.kind(), "return_statement" | "expression_statement")
+        || has_nested_body(*stmt)
+    {
+        return false;
+    }
+    body.prev_sibling().is_some_and(|header_end| {
+        header_end.end_position().row == body.start_position().row
+            && body.start_position().row == body.end_position().row
+    })
+}
+
+/// Whether `body` is the block of the only method of an anonymous class that
+/// stays on one line.
+fn in_single_line_anonymous_class(body: tree_sitter::Node, context: &FormattingContext) -> bool {
+    body.parent()
+        .and_then(|method| method.parent())
+        .is_some_and(|class_body| {
+            declarations::single_line_anonymous_method(class_body, context).is_some()
+        })
+}
+
+/// Whether `node` contains a block or class body, which always spans lines.
+pub(super) fn has_nested_body(node: tree_sitter::Node) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).any(|child| {
+        matches!(child.kind(), "block" | "class_body" | "switch_block") || has_nested_body(child)
+    })
+}
+
+/// Whether the brace-less `body` of an `if` or `else` may stay on the line
+/// of its keyword: only with `allowSingleLineIf` and when the source already
+/// has it there on one line. Whether the line fits is left to the printer.
+fn keeps_single_line_if_body(body: tree_sitter::Node, context: &FormattingContext) -> bool {
+    context.config.allow_single_line_if
+        && body.prev_sibling().is_some_and(|keyword_end| {
+            keyword_end.end_position().row == body.start_position().row
+                && body.start_position().row == body.end_position().row
+        })
+}
+
 /// Format a for statement: `for (init; cond; update) { }`
 pub fn gen_for_statement<'a>(
     node: tree_sitter::Node<'a>,
@@ -285,7 +454,11 @@ pub fn gen_for_statement<'a>(
     items.push_str(")");
 
     if let Some(body) = node.child_by_field_name("body") {
-        items.space();
+        if body.kind() == "block" {
+            items.extend(context.brace_separator());
+        } else {
+            items.space();
+        }
         items.extend(gen_node(body, context));
     }
 
@@ -335,7 +508,7 @@ pub fn gen_enhanced_for_statement<'a>(
             }
             "block" => {
                 items.push_str(")");
-                items.space();
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
                 return items;
             }
@@ -370,12 +543,13 @@ pub fn gen_while_statement<'a>(
             }
             "parenthesized_expression" | "condition" => {
                 items.extend(gen_node(child, context));
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             _ if child.is_named() => {
+                items.space();
                 items.extend(gen_node(child, context));
             }
             _ => {}
@@ -397,13 +571,13 @@ pub fn gen_do_statement<'a>(
         match child.kind() {
             "do" => {
                 items.push_str("do");
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             "while" => {
-                items.space();
+                items.extend(context.keyword_separator(context.config.while_on_new_line));
                 items.push_str("while");
                 items.space();
             }
@@ -436,9 +610,9 @@ pub fn gen_switch_expression<'a>(
             }
             "parenthesized_expression" => {
                 items.extend(gen_node(child, context));
-                items.space();
             }
             "switch_block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_switch_block(child, context));
             }
             _ => {}
@@ -467,19 +641,28 @@ fn gen_switch_block<'a>(
     }
 
     items.start_indent();
+    let mut indented = context.indented(1);
+    let context = &mut *indented;
 
     let mut prev_case_end_row: Option<usize> = children
         .iter()
         .find(|c| c.kind() == "{")
         .map(|c| c.end_position().row);
     for case in &cases {
+        // A comment after the last statement of a case stays on its line
+        if case.is_extra() && context.comment_map().is_trailing(**case) {
+            items.space();
+            items.extend(gen_switch_case(**case, context));
+            prev_case_end_row = Some(case.end_position().row);
+            continue;
+        }
         items.newline();
         // Preserve source blank lines between switch cases
-        if let Some(prev_row) = prev_case_end_row
-            && case.start_position().row > prev_row + 1
-        {
-            items.newline();
-        }
+        items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+            context,
+            prev_case_end_row,
+            case.start_position().row,
+        )));
         items.extend(gen_switch_case(**case, context));
         prev_case_end_row = Some(case.end_position().row);
     }
@@ -518,12 +701,17 @@ fn gen_switch_case<'a>(
             let is_single_block = body_stmts.len() == 1 && body_stmts[0].kind() == "block";
 
             let mut prev_stmt_end_row: Option<usize> = None;
+            // A line comment already ends with a newline
+            let mut prev_was_line_comment = false;
             for child in &children {
                 if child.kind() == "switch_label" {
                     if label_done {
                         items.newline();
                     }
-                    items.extend(gen_switch_label(*child, context));
+                    // `:` follows the last label, and ` {` a block body
+                    let suffix_width = ":".len() + if is_single_block { " {".len() } else { 0 };
+                    let wrap = switch_labels_wrap(*child, suffix_width, context);
+                    items.extend(gen_switch_label(*child, wrap, context));
                     label_done = true;
                 } else if child.kind() == ":" {
                     // Colon is a child of switch_block_statement_group, not switch_label
@@ -534,21 +722,33 @@ fn gen_switch_case<'a>(
                     }
                     prev_stmt_end_row = Some(child.end_position().row);
                 } else if child.is_named() {
-                    if !is_single_block {
+                    let trailing = child.is_extra()
+                        && in_body
+                        && !prev_was_line_comment
+                        && context.comment_map().is_trailing(*child);
+                    if trailing {
+                        items.space();
+                    } else if !is_single_block {
                         // Multiple statements or non-block: indent and place on new lines
                         if !in_body {
                             items.start_indent();
                             in_body = true;
                         }
-                        items.newline();
-                        // Preserve source blank lines between statements in case body
-                        if let Some(prev_row) = prev_stmt_end_row
-                            && child.start_position().row > prev_row + 1
-                        {
+                        if !prev_was_line_comment {
                             items.newline();
                         }
+                        // Preserve source blank lines between statements in case body
+                        items.extend(blank_lines::gen_blank_lines(blank_lines::kept(
+                            context,
+                            prev_stmt_end_row,
+                            child.start_position().row,
+                        )));
                     }
-                    items.extend(gen_node(*child, context));
+                    items.extend(gen_node(
+                        *child,
+                        &mut context.indented(usize::from(in_body)),
+                    ));
+                    prev_was_line_comment = child.kind() == "line_comment";
                     prev_stmt_end_row = Some(child.end_position().row);
                 }
             }
@@ -558,12 +758,19 @@ fn gen_switch_case<'a>(
         }
         "switch_rule" => {
             // Arrow case: `case X -> expr;` or `case X -> { block }`
+            let wrap_labels = switch_rule_labels_wrap(node, context);
             for child in &children {
                 match child.kind() {
                     "switch_label" => {
-                        items.extend(gen_switch_label(*child, context));
+                        items.extend(gen_switch_label(*child, wrap_labels, context));
                     }
                     "->" => {
+                        if let Some(aligned) = aligned_label_width(node, context)
+                            && let Some(width) =
+                                switch_rule_label(node).and_then(|l| simple_label_width(l, context))
+                        {
+                            items.push_str(&" ".repeat(aligned - width));
+                        }
                         items.space();
                         items.push_str("->");
                         items.space();
@@ -583,9 +790,126 @@ fn gen_switch_case<'a>(
     items
 }
 
+/// Whether a switch rule's comma-separated labels are too long for one line
+/// and go one per continuation line instead.
+fn switch_rule_labels_wrap(rule: tree_sitter::Node, context: &FormattingContext) -> bool {
+    switch_rule_label(rule).is_some_and(|label| switch_labels_wrap(label, " -> ".len(), context))
+}
+
+/// Whether the comma-separated labels of `label` are too long for one line
+/// once followed by `suffix_width` columns of `->` or `:`, and go one per
+/// continuation line instead.
+fn switch_labels_wrap(
+    label: tree_sitter::Node,
+    suffix_width: usize,
+    context: &FormattingContext,
+) -> bool {
+    let mut cursor = label.walk();
+    let has_multiple = label.children(&mut cursor).any(|c| c.kind() == ",");
+    let indent_width = context.effective_indent_level() * context.indent_width();
+    has_multiple
+        && indent_width
+            + collapse_whitespace_len(&context.source[label.start_byte()..label.end_byte()])
+            + suffix_width
+            > context.config.line_width as usize
+}
+
+/// Width of the line text before a switch rule's body: `case A, B -> `, or
+/// the continuation indent plus `B -> ` when the labels wrap. Zero for
+/// anything but a `switch_rule`.
+pub(super) fn switch_rule_label_width(
+    rule: tree_sitter::Node,
+    context: &FormattingContext,
+) -> usize {
+    if rule.kind() != "switch_rule" {
+        return 0;
+    }
+    let Some(label) = switch_rule_label(rule) else {
+        return 0;
+    };
+    if let Some(aligned) = aligned_label_width(rule, context) {
+        return aligned + " -> ".len();
+    }
+    let text = &context.source[label.start_byte()..label.end_byte()];
+    if switch_rule_labels_wrap(rule, context) {
+        let last = text.rsplit(',').next().unwrap_or(text);
+        context.continuation_width() + collapse_whitespace_len(last.trim()) + " -> ".len()
+    } else {
+        collapse_whitespace_len(text) + " -> ".len()
+    }
+}
+
+/// With `alignSwitchArrows`, the width every label in the rule's switch is
+/// padded to so their `->` tokens line up. `None` when alignment is off, or
+/// when any label in the switch has a pattern, a guard, a comment or wraps.
+fn aligned_label_width(rule: tree_sitter::Node, context: &FormattingContext) -> Option<usize> {
+    if !context.config.align_switch_arrows || rule.kind() != "switch_rule" {
+        return None;
+    }
+    let block = rule.parent()?;
+    let mut cursor = block.walk();
+    block
+        .named_children(&mut cursor)
+        .filter(|c| !c.is_extra())
+        .map(|rule| {
+            if rule.kind() != "switch_rule" || switch_rule_labels_wrap(rule, context) {
+                return None;
+            }
+            switch_rule_label(rule).and_then(|label| simple_label_width(label, context))
+        })
+        .try_fold(0, |max, width| Some(max.max(width?)))
+}
+
+/// Width of a label made of constants and `default`, such as `case A, B`, whose
+/// formatted text is its tokens separated by `, `. `None` for labels with
+/// patterns, guards or comments, or with whitespace inside a constant.
+fn simple_label_width(label: tree_sitter::Node, context: &FormattingContext) -> Option<usize> {
+    let mut cursor = label.walk();
+    let mut width = 0;
+    for child in label.children(&mut cursor) {
+        let text = &context.source[child.start_byte()..child.end_byte()];
+        match child.kind() {
+            "case" => width += "case ".len(),
+            "," => width += ", ".len(),
+            "default" => width += display_width(text),
+            "pattern" | "guard" | "line_comment" | "block_comment" => return None,
+            _ if text.contains(char::is_whitespace) || text.starts_with("\"\"\"") => {
+                return None;
+            }
+            _ => width += display_width(text),
+        }
+    }
+    Some(width)
+}
+
+fn switch_rule_label(rule: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    let mut cursor = rule.walk();
+    rule.children(&mut cursor)
+        .find(|c| c.kind() == "switch_label")
+}
+
+/// Width of what follows a switch label on its line: ` ->` in a switch rule,
+/// `:` in a statement group.
+pub(super) fn switch_label_suffix_width(label: tree_sitter::Node) -> usize {
+    if label.parent().is_some_and(|p| p.kind() == "switch_rule") {
+        " ->".len()
+    } else {
+        ":".len()
+    }
+}
+
 /// Format a switch label: `case X:` or `default:`
+///
+/// With `wrap`, each label after the first goes on its own continuation line,
+/// and the `->` or `:` stays after the last one:
+/// ```java
+/// case STATUS_CONTINUE,
+///         STATUS_SWITCHING_PROTOCOLS,
+///         STATUS_PROCESSING -> handleInformational(response);
+/// ```
 fn gen_switch_label<'a>(
     node: tree_sitter::Node<'a>,
+    wrap: bool,
     context: &mut FormattingContext<'a>,
 ) -> PrintItems {
     let mut items = PrintItems::new();
@@ -596,6 +920,10 @@ fn gen_switch_label<'a>(
             "case" => {
                 items.push_str("case");
                 items.space();
+                if wrap {
+                    items.start_indent();
+                    items.start_indent();
+                }
             }
             "default" => {
                 items.push_str("default");
@@ -605,7 +933,40 @@ fn gen_switch_label<'a>(
             }
             "," => {
                 items.push_str(",");
-                items.space();
+                if wrap {
+                    items.newline();
+                } else {
+                    items.space();
+                }
+            }
+            "guard" => {
+                // `case Type t when cond`; a label too long for its line puts
+                // the guard on a continuation line
+                let indent_width = context.effective_indent_level() * context.indent_width();
+                let wrap_guard = indent_width
+                    + collapse_whitespace_len(&context.source[node.start_byte()..node.end_byte()])
+                    + switch_label_suffix_width(node)
+                    > context.config.line_width as usize;
+                if wrap_guard {
+                    items.start_indent();
+                    items.start_indent();
+                    items.newline();
+                } else {
+                    items.space();
+                }
+                items.push_str("when");
+                if let Some(condition) = child.named_child(0) {
+                    items.space();
+                    if wrap_guard {
+                        items.extend(gen_node(condition, &mut context.with_continuation_indent()));
+                    } else {
+                        items.extend(gen_node(condition, context));
+                    }
+                }
+                if wrap_guard {
+                    items.finish_indent();
+                    items.finish_indent();
+                }
             }
             _ if child.is_named() => {
                 items.extend(gen_node(child, context));
@@ -613,6 +974,10 @@ fn gen_switch_label<'a>(
             _ => {}
         }
     }
+    if wrap {
+        items.finish_indent();
+        items.finish_indent();
+    }
 
     items
 }
@@ -629,17 +994,17 @@ pub fn gen_try_statement<'a>(
         match child.kind() {
             "try" => {
                 items.push_str("try");
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             "catch_clause" => {
-                items.space();
+                items.extend(context.keyword_separator(context.config.catch_on_new_line));
                 items.extend(gen_catch_clause(child, context));
             }
             "finally_clause" => {
-                items.space();
+                items.extend(context.keyword_separator(context.config.finally_on_new_line));
                 items.extend(gen_finally_clause(child, context));
             }
             _ => {}
@@ -661,21 +1026,21 @@ pub fn gen_try_with_resources_statement<'a>(
         match child.kind() {
             "try" => {
                 items.push_str("try");
-                items.space();
             }
             "resource_specification" => {
-                items.extend(gen_resource_specification(child, context));
                 items.space();
+                items.extend(gen_resource_specification(child, context));
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             "catch_clause" => {
-                items.space();
+                items.extend(context.keyword_separator(context.config.catch_on_new_line));
                 items.extend(gen_catch_clause(child, context));
             }
             "finally_clause" => {
-                items.space();
+                items.extend(context.keyword_separator(context.config.finally_on_new_line));
                 items.extend(gen_finally_clause(child, context));
             }
             _ => {}
@@ -694,10 +1059,9 @@ fn estimate_catch_clause_width(node: tree_sitter::Node, source: &str) -> usize {
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
         if child.kind() == "catch_formal_parameter" {
+            // Flat width, including modifiers, with whitespace collapsed
             let text = &source[child.start_byte()..child.end_byte()];
-            // Collapse all whitespace to single spaces for flat width
-            let flat_text: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
-            width += flat_text.len();
+            width += collapse_whitespace_len(text);
         }
     }
 
@@ -714,7 +1078,7 @@ fn gen_catch_clause<'a>(
     let mut cursor = node.walk();
 
     // Pre-calculate: estimate catch clause line width to decide multi-exception wrapping
-    let indent_width = context.indent_level() * context.config.indent_width as usize;
+    let indent_width = context.indent_level() * context.indent_width();
     let catch_width = estimate_catch_clause_width(node, context.source);
     let should_wrap_catch = indent_width + catch_width > context.config.line_width as usize;
 
@@ -732,9 +1096,9 @@ fn gen_catch_clause<'a>(
                     should_wrap_catch,
                 ));
                 items.push_str(")");
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             _ => {}
@@ -744,7 +1108,10 @@ fn gen_catch_clause<'a>(
     items
 }
 
-/// Format a catch formal parameter: `Exception | RuntimeException e`
+/// Format a catch formal parameter: `final Exception | RuntimeException e`
+///
+/// Modifiers are normalized inline (single spaces, JLS keyword order) rather
+/// than placed on their own lines like declaration annotations.
 fn gen_catch_formal_parameter<'a>(
     node: tree_sitter::Node<'a>,
     context: &mut FormattingContext<'a>,
@@ -757,7 +1124,7 @@ fn gen_catch_formal_parameter<'a>(
     for child in node.children(&mut cursor) {
         match child.kind() {
             "modifiers" => {
-                items.extend(gen_node(child, context));
+                items.extend(declarations::gen_inline_modifiers(child, context));
                 need_space = true;
             }
             "catch_type" => {
@@ -850,9 +1217,9 @@ fn gen_finally_clause<'a>(
         match child.kind() {
             "finally" => {
                 items.push_str("finally");
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             _ => {}
@@ -1029,9 +1396,9 @@ pub fn gen_synchronized_statement<'a>(
             }
             "parenthesized_expression" => {
                 items.extend(gen_node(child, context));
-                items.space();
             }
             "block" => {
+                items.extend(context.brace_separator());
                 items.extend(gen_block(child, context));
             }
             _ => {}
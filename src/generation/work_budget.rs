@@ -0,0 +1,24 @@
+use dprint_core::formatting::PrintItems;
+
+use super::context::WorkBudgetGuard;
+use super::helpers::gen_verbatim_text;
+
+/// Work, in bytes of source measured and nodes walked, the wrap estimators
+/// may do while generating one member before it is kept as written instead.
+/// Hand-written members stay far below it; machine-generated calls nested
+/// hundreds deep, whose estimates grow with the cube of their depth, do not.
+pub(crate) const MEMBER_WORK_BUDGET: usize = 5_000_000;
+
+/// Keep the member `node` as written in place of `items` if the budget
+/// `budget` started for it ran out.
+pub(crate) fn finish(
+    node: tree_sitter::Node,
+    items: PrintItems,
+    budget: &mut WorkBudgetGuard,
+) -> PrintItems {
+    if !budget.is_exceeded() {
+        return items;
+    }
+    budget.record_skipped_member(node.byte_range());
+    gen_verbatim_text(node, budget.source)
+}
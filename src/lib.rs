@@ -1,8 +1,31 @@
+mod ast;
 pub mod configuration;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod corpus;
+mod diff;
+pub mod error;
 pub mod format_text;
 pub mod generation;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod source_map;
+#[cfg(test)]
+mod test_support;
+mod verify;
 
-pub use format_text::format_text;
+#[cfg(not(target_arch = "wasm32"))]
+pub use corpus::{CorpusDiff, diff_versions};
+#[cfg(feature = "rayon")]
+pub use format_text::format_files;
+pub use format_text::{
+    ChangeLimitExceeded, FormatSnapshot, PrinterOptions, SkipReason, UnhandledNode, format_text,
+    format_text_checked, format_text_incremental, format_text_single_pass,
+    format_text_with_coverage, format_text_with_diagnostics, format_text_with_printer_options,
+    format_text_with_report, format_text_with_source_map, format_to_diff, is_formatted,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use format_text::{FormatOutcome, format_named_stdin, format_stream};
+pub use source_map::SourceMap;
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 mod wasm_shims;
@@ -0,0 +1,409 @@
+//! A minimal language server for editors without a dprint integration, such
+//! as Neovim through `lspconfig`.
+//!
+//! It keeps the text of open documents, synced in full, and answers
+//! `textDocument/formatting` and `textDocument/rangeFormatting` with
+//! [`format_text`]. Plugin options come from the client's
+//! `initializationOptions`, e.g. `{ "lineWidth": 100 }`; the tab size and
+//! spaces setting of a formatting request are ignored, so the output is the
+//! same as dprint's.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue, GlobalConfiguration};
+use serde_json::{Value, json};
+
+use crate::configuration::{Configuration, resolve_config, validate_resolved};
+use crate::diff;
+use crate::format_text::format_text;
+
+/// `TextDocumentSyncKind.Full`: every change sends the whole document.
+const SYNC_FULL: u8 = 1;
+
+/// JSON-RPC error codes.
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+const INVALID_REQUEST: i64 = -32600;
+const REQUEST_FAILED: i64 = -32803;
+
+/// Serve the language server protocol on `input` and `output`, usually
+/// stdin and stdout, until the client sends `exit`.
+///
+/// Returns whether the client asked for a `shutdown` before exiting, which
+/// the protocol maps to exit code 0.
+///
+/// # Errors
+///
+/// Returns an error if a message cannot be read or written, or is not
+/// framed as the protocol requires.
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut output: W) -> Result<bool> {
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        if method == "exit" {
+            return Ok(server.shut_down);
+        }
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(id) = message.get("id").cloned() else {
+            server.notify(method, &params);
+            continue;
+        };
+        let response = match server.request(method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(server.shut_down)
+}
+
+#[derive(Default)]
+struct Server {
+    /// `None` until `initialize`.
+    config: Option<Configuration>,
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+    shut_down: bool,
+}
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if method == "initialize" {
+            let options = params.get("initializationOptions").unwrap_or(&Value::Null);
+            self.config = Some(resolve(options).map_err(|e| (INVALID_PARAMS, e))?);
+            return Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "documentFormattingProvider": true,
+                    "documentRangeFormattingProvider": true,
+                },
+                "serverInfo": {
+                    "name": "dprint-java-lsp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }));
+        }
+        let Some(config) = &self.config else {
+            return Err((SERVER_NOT_INITIALIZED, "Server not initialized".to_string()));
+        };
+        if self.shut_down {
+            return Err((INVALID_REQUEST, "Server is shutting down".to_string()));
+        }
+        match method {
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/formatting" | "textDocument/rangeFormatting" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let Some(text) = self.documents.get(uri) else {
+                    return Err((INVALID_PARAMS, format!("Unknown document {uri}")));
+                };
+                let lines = match params.get("range") {
+                    Some(range) => Some(
+                        line_range(range)
+                            .ok_or_else(|| (INVALID_PARAMS, "Invalid range".to_string()))?,
+                    ),
+                    None => None,
+                };
+                let edits = format_edits(uri, text, config, lines.as_ref())
+                    .map_err(|e| (REQUEST_FAILED, format!("{e:#}")))?;
+                Ok(Value::Array(edits))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method {method}"))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) {
+        let document = &params["textDocument"];
+        let Some(uri) = document["uri"].as_str() else {
+            return;
+        };
+        match method {
+            "textDocument/didOpen" => {
+                if let Some(text) = document["text"].as_str() {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The configuration `initializationOptions` set, or why it is invalid.
+fn resolve(options: &Value) -> Result<Configuration, String> {
+    let config = match options {
+        Value::Object(options) => options
+            .iter()
+            .map(|(key, value)| (key.clone(), config_value(value)))
+            .collect(),
+        _ => ConfigKeyMap::new(),
+    };
+    let result = validate_resolved(resolve_config(config, &GlobalConfiguration::default()));
+    match result.diagnostics.first() {
+        Some(d) => Err(format!("{}: {}", d.property_name, d.message)),
+        None => Ok(result.config),
+    }
+}
+
+/// A JSON option value as the dprint config file would hold it.
+fn config_value(value: &Value) -> ConfigKeyValue {
+    match value {
+        Value::Null => ConfigKeyValue::Null,
+        Value::Bool(value) => ConfigKeyValue::from_bool(*value),
+        Value::Number(number) => number
+            .as_i64()
+            .and_then(|number| i32::try_from(number).ok())
+            .map_or_else(
+                || ConfigKeyValue::from_str(&number.to_string()),
+                ConfigKeyValue::from_i32,
+            ),
+        Value::String(value) => ConfigKeyValue::from_str(value),
+        Value::Array(values) => ConfigKeyValue::Array(values.iter().map(config_value).collect()),
+        Value::Object(entries) => ConfigKeyValue::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), config_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// The lines `range` covers, from the line of its start to the line of its
+/// end, leaving out the end's line when the range stops at its start.
+fn line_range(range: &Value) -> Option<Range<usize>> {
+    let line = |position: &Value| {
+        let line = usize::try_from(position.get("line")?.as_u64()?).ok()?;
+        let character = position.get("character")?.as_u64()?;
+        Some((line, character))
+    };
+    let (start, _) = line(range.get("start")?)?;
+    let (end, end_character) = line(range.get("end")?)?;
+    if end > start && end_character == 0 {
+        Some(start..end)
+    } else {
+        Some(start..end + 1)
+    }
+}
+
+/// The `TextEdit`s that format `text`, each replacing whole lines. With
+/// `lines`, only the changes that touch those lines are returned.
+fn format_edits(
+    uri: &str,
+    text: &str,
+    config: &Configuration,
+    lines: Option<&Range<usize>>,
+) -> Result<Vec<Value>> {
+    let Some(formatted) = format_text(Path::new(uri), text, config)? else {
+        return Ok(Vec::new());
+    };
+    let old_lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = formatted.split_inclusive('\n').collect();
+    let edits = diff::hunks(&old_lines, &new_lines)
+        .into_iter()
+        .filter(|hunk| {
+            lines.is_none_or(|lines| {
+                hunk.old.start < lines.end
+                    && (hunk.old.end > lines.start || hunk.old.start >= lines.start)
+            })
+        })
+        .map(|hunk| {
+            json!({
+                "range": {
+                    "start": position(&old_lines, hunk.old.start),
+                    "end": position(&old_lines, hunk.old.end),
+                },
+                "newText": new_lines[hunk.new].concat(),
+            })
+        })
+        .collect();
+    Ok(edits)
+}
+
+/// The position of the start of line `line` of a text split into `lines`,
+/// or of its end when `line` is past its last line.
+fn position(lines: &[&str], line: usize) -> Value {
+    match lines.last() {
+        // A last line without a newline ends where its text does
+        Some(last) if line == lines.len() && !last.ends_with('\n') => json!({
+            "line": line - 1,
+            "character": last.encode_utf16().count(),
+        }),
+        _ => json!({ "line": line, "character": 0 }),
+    }
+}
+
+/// Read one message, or `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            if content_length.is_none() {
+                return Ok(None);
+            }
+            bail!("Unexpected end of input in message header");
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context("Invalid Content-Length header")?,
+            );
+        }
+    }
+    let Some(length) = content_length else {
+        bail!("Message without a Content-Length header");
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(
+        serde_json::from_slice(&body).context("Invalid JSON message")?,
+    ))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(messages: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        input
+    }
+
+    /// Run a session and return the responses, in order.
+    fn session(messages: &[Value]) -> (bool, Vec<Value>) {
+        let mut output = Vec::new();
+        let shut_down = serve(frame(messages).as_slice(), &mut output).unwrap();
+        let mut output = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(response) = read_message(&mut output).unwrap() {
+            responses.push(response);
+        }
+        (shut_down, responses)
+    }
+
+    fn open(text: &str) -> [Value; 3] {
+        [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                "initializationOptions": { "indentWidth": 2 },
+            }}),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": { "uri": "file:///A.java", "languageId": "java", "version": 1, "text": text },
+            }}),
+        ]
+    }
+
+    #[test]
+    fn formats_open_documents() {
+        let mut messages = open("class A {\nint x=1;\n}\n").to_vec();
+        messages.extend([
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+                "textDocument": { "uri": "file:///A.java" },
+                "options": { "tabSize": 8, "insertSpaces": true },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+        let (shut_down, responses) = session(&messages);
+        assert!(shut_down);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["documentRangeFormattingProvider"],
+            true
+        );
+        assert_eq!(
+            responses[1]["result"],
+            json!([{
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 2, "character": 0 },
+                },
+                "newText": "  int x = 1;\n",
+            }])
+        );
+        assert_eq!(responses[2]["result"], Value::Null);
+    }
+
+    #[test]
+    fn range_formatting_keeps_other_lines() {
+        let mut messages = open("class A {\nint x=1;\n\nint y=2;\n}").to_vec();
+        messages.push(json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/rangeFormatting", "params": {
+            "textDocument": { "uri": "file:///A.java" },
+            "range": { "start": { "line": 3, "character": 0 }, "end": { "line": 3, "character": 8 } },
+        }}));
+        let (shut_down, responses) = session(&messages);
+        assert!(!shut_down);
+        assert_eq!(
+            responses[1]["result"],
+            json!([{
+                "range": {
+                    "start": { "line": 3, "character": 0 },
+                    "end": { "line": 4, "character": 1 },
+                },
+                "newText": "  int y = 2;\n}\n",
+            }])
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors_and_unknown_methods() {
+        let mut messages = open("class A {").to_vec();
+        messages.extend([
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+                "textDocument": { "uri": "file:///A.java" },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {} }),
+        ]);
+        let (_, responses) = session(&messages);
+        assert_eq!(responses[1]["error"]["code"], REQUEST_FAILED);
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let (_, responses) = session(&[json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "initializationOptions": { "lineWidth": 0 } },
+        })]);
+        assert_eq!(responses[0]["error"]["code"], INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+/// Maps each line of formatted output back to the input bytes it came from.
+///
+/// Built by [`format_text_with_source_map`](crate::format_text::format_text_with_source_map)
+/// so that tools which lint the formatted text can report diagnostics
+/// against the original file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Input byte span for each output line, indexed by 0-based line number.
+    lines: Vec<Option<Range<usize>>>,
+}
+
+impl SourceMap {
+    /// The input byte span that produced `output_line` (0-based).
+    ///
+    /// Returns `None` for blank lines, for lines past the end of the output,
+    /// and for lines inside text that is copied through unchanged, such as
+    /// the later lines of a block comment or a `@formatter:off` region.
+    #[must_use]
+    pub fn input_range(&self, output_line: usize) -> Option<Range<usize>> {
+        self.lines.get(output_line).cloned().flatten()
+    }
+
+    /// The input byte spans of all output lines, indexed by 0-based line number.
+    #[must_use]
+    pub fn lines(&self) -> &[Option<Range<usize>>] {
+        &self.lines
+    }
+
+    /// Record that input byte offset `byte` was reached while printing
+    /// `output_line`.
+    pub(crate) fn record(&mut self, output_line: usize, byte: usize) {
+        if self.lines.len() <= output_line {
+            self.lines.resize(output_line + 1, None);
+        }
+        let span = &mut self.lines[output_line];
+        *span = Some(match span.take() {
+            Some(range) => range.start.min(byte)..range.end.max(byte),
+            None => byte..byte,
+        });
+    }
+
+    /// Drop lines past the `line_count` lines of the output and trim the
+    /// whitespace of `source` from both ends of each span.
+    pub(crate) fn finish(&mut self, source: &str, line_count: usize) {
+        self.lines.resize(line_count, None);
+        for range in self.lines.iter_mut().flatten() {
+            let text = &source[range.clone()];
+            let start = range.start + (text.len() - text.trim_start().len());
+            let end = range.end - (text.len() - text.trim_end().len());
+            *range = start..end.max(start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_widens_span() {
+        let mut map = SourceMap::default();
+        map.record(1, 10);
+        map.record(1, 4);
+        map.record(1, 7);
+        assert_eq!(map.input_range(0), None);
+        assert_eq!(map.input_range(1), Some(4..10));
+        assert_eq!(map.input_range(2), None);
+    }
+
+    #[test]
+    fn test_finish_trims_whitespace_and_extra_lines() {
+        let mut map = SourceMap::default();
+        map.record(0, 0);
+        map.record(0, 6);
+        map.record(2, 6);
+        map.finish("  ab  ", 1);
+        assert_eq!(map.lines(), &[Some(2..4)]);
+    }
+}
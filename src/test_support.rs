@@ -0,0 +1,10 @@
+//! Helpers shared by the unit tests.
+
+/// Parse `source` as Java, keeping any syntax errors in the tree.
+pub(crate) fn parse(source: &str) -> tree_sitter::Tree {
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_java::LANGUAGE.into())
+        .unwrap();
+    parser.parse(source, None).unwrap()
+}
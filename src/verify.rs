@@ -0,0 +1,182 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use crate::error::TokenMismatch;
+
+/// A token of the code, with whitespace and comments left out.
+#[derive(Debug)]
+struct Token<'t> {
+    text: Cow<'t, str>,
+    /// Byte offset in the text the token was read from.
+    start: usize,
+}
+
+/// The tokens of a file, in the form `verifyTokens` compares them.
+///
+/// Changes the formatter makes on purpose are normalized away: the keywords
+/// and annotations of a `modifiers` list and the directives of a module are
+/// compared in any order, imports are compared as a set, and the lines of a
+/// text block are compared without their indentation.
+#[derive(Debug)]
+pub(crate) struct Tokens<'t> {
+    code: Vec<Token<'t>>,
+    imports: BTreeSet<String>,
+}
+
+impl<'t> Tokens<'t> {
+    pub(crate) fn collect(tree: &tree_sitter::Tree, source: &'t str) -> Self {
+        let mut tokens = Self {
+            code: Vec::new(),
+            imports: BTreeSet::new(),
+        };
+        tokens.visit(tree.root_node(), source);
+        tokens
+    }
+
+    fn visit(&mut self, node: tree_sitter::Node, source: &'t str) {
+        match node.kind() {
+            "line_comment" | "block_comment" => {}
+            "import_declaration" => {
+                let mut parts = Vec::new();
+                leaf_texts(node, source, &mut parts);
+                self.imports.insert(parts.join(" "));
+            }
+            "modifiers" | "module_body" => {
+                let mut cursor = node.walk();
+                let mut parts: Vec<String> = node
+                    .children(&mut cursor)
+                    .filter(|child| !is_comment(*child))
+                    .map(|child| {
+                        let mut texts = Vec::new();
+                        leaf_texts(child, source, &mut texts);
+                        texts.join(" ")
+                    })
+                    .collect();
+                parts.sort();
+                self.push(Cow::Owned(parts.join(" ")), node.start_byte());
+            }
+            "string_literal" if source[node.byte_range()].starts_with("\"\"\"") => {
+                let lines: Vec<&str> = source[node.byte_range()].lines().map(str::trim).collect();
+                self.push(Cow::Owned(lines.join("\n")), node.start_byte());
+            }
+            "string_literal" | "character_literal" => {
+                self.push(Cow::Borrowed(&source[node.byte_range()]), node.start_byte());
+            }
+            _ if node.child_count() == 0 => {
+                self.push(Cow::Borrowed(&source[node.byte_range()]), node.start_byte());
+            }
+            _ => {
+                let mut cursor = node.walk();
+                for child in node.children(&mut cursor) {
+                    self.visit(child, source);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, text: Cow<'t, str>, start: usize) {
+        // `MISSING` nodes are empty and print nothing
+        if !text.is_empty() {
+            self.code.push(Token { text, start });
+        }
+    }
+}
+
+fn is_comment(node: tree_sitter::Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+/// The text of each leaf under `node`, in order, without comments.
+fn leaf_texts(node: tree_sitter::Node, source: &str, texts: &mut Vec<String>) {
+    if is_comment(node) {
+        return;
+    }
+    if node.child_count() == 0 {
+        texts.push(source[node.byte_range()].to_string());
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        leaf_texts(child, source, texts);
+    }
+}
+
+/// Check that `output` has the tokens of `input`, the text they were read
+/// from. Imports may only be dropped when `remove_unused_imports` is on.
+pub(crate) fn check(
+    input: &Tokens,
+    output: &Tokens,
+    source: &str,
+    remove_unused_imports: bool,
+) -> Result<(), TokenMismatch> {
+    let first_difference = input
+        .code
+        .iter()
+        .zip(&output.code)
+        .position(|(a, b)| a.text != b.text)
+        .or_else(|| {
+            (input.code.len() != output.code.len()).then(|| input.code.len().min(output.code.len()))
+        });
+    if let Some(i) = first_difference {
+        let message = match (input.code.get(i), output.code.get(i)) {
+            (Some(a), Some(b)) => format!("`{}` became `{}`", a.text, b.text),
+            (Some(a), None) => format!("`{}` was dropped", a.text),
+            (None, Some(b)) => format!("`{}` was added", b.text),
+            (None, None) => unreachable!("a difference is within one of the token lists"),
+        };
+        let (start, len) = input
+            .code
+            .get(i)
+            .map_or((source.len(), 0), |a| (a.start, a.text.len()));
+        return Err(TokenMismatch::new(message, source, start, len));
+    }
+
+    let added = output.imports.difference(&input.imports).next();
+    let dropped = input.imports.difference(&output.imports).next();
+    let message = match (added, dropped) {
+        (Some(import), _) => format!("`{import}` was added"),
+        (None, Some(import)) if !remove_unused_imports => format!("`{import}` was dropped"),
+        _ => return Ok(()),
+    };
+    Err(TokenMismatch::new(message, source, 0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::parse;
+
+    fn check_texts(input: &str, output: &str) -> Result<(), TokenMismatch> {
+        let (input_tree, output_tree) = (parse(input), parse(output));
+        check(
+            &Tokens::collect(&input_tree, input),
+            &Tokens::collect(&output_tree, output),
+            input,
+            false,
+        )
+    }
+
+    #[test]
+    fn ignores_whitespace_comments_and_modifier_order() {
+        let input = "import b.B;\nimport a.A;\nclass X {\n  final static int x = 1; // one\n}\n";
+        let output = "import a.A;\nimport b.B;\n\nclass X {\n    // one\n    static final int x =\n            1;\n}\n";
+        assert_eq!(check_texts(input, output), Ok(()));
+    }
+
+    #[test]
+    fn reports_first_changed_token() {
+        let input = "class X {\n    int x = 1;\n    int y = 2;\n}\n";
+        let output = "class X {\n    int x = 1;\n    int y = 3;\n}\n";
+        let mismatch = check_texts(input, output).unwrap_err();
+        assert_eq!(mismatch.message, "`2` became `3`");
+        assert_eq!((mismatch.line, mismatch.column), (3, 13));
+    }
+
+    #[test]
+    fn reports_dropped_import() {
+        let input = "import a.A;\nclass X {}\n";
+        let output = "class X {}\n";
+        let mismatch = check_texts(input, output).unwrap_err();
+        assert_eq!(mismatch.message, "`import a . A ;` was dropped");
+    }
+}
@@ -1,3 +1,5 @@
+use std::io::Write as _;
+
 use anyhow::Result;
 use dprint_core::configuration::ConfigKeyMap;
 use dprint_core::configuration::GlobalConfiguration;
@@ -11,9 +13,15 @@ use dprint_core::plugins::PluginResolveConfigurationResult;
 use dprint_core::plugins::SyncFormatRequest;
 use dprint_core::plugins::SyncHostFormatRequest;
 use dprint_core::plugins::SyncPluginHandler;
+use dprint_core::plugins::wasm::WasiPrintFd;
 
 use crate::configuration::Configuration;
 use crate::configuration::resolve_config;
+use crate::configuration::validate_resolved;
+use crate::error::FormatError;
+
+/// The WASI file descriptor of the host's standard error.
+const STDERR_FD: i32 = 2;
 
 struct JavaPluginHandler;
 
@@ -39,7 +47,7 @@ impl SyncPluginHandler<Configuration> for JavaPluginHandler {
         config: ConfigKeyMap,
         global_config: &GlobalConfiguration,
     ) -> PluginResolveConfigurationResult<Configuration> {
-        let result = resolve_config(config, global_config);
+        let result = validate_resolved(resolve_config(config, global_config));
         PluginResolveConfigurationResult {
             config: result.config,
             diagnostics: result.diagnostics,
@@ -63,8 +71,22 @@ impl SyncPluginHandler<Configuration> for JavaPluginHandler {
         _format_with_host: impl FnMut(SyncHostFormatRequest) -> FormatResult,
     ) -> FormatResult {
         let file_text = String::from_utf8(request.file_bytes)?;
-        crate::format_text(request.file_path, &file_text, request.config)
-            .map(|maybe| maybe.map(|s| s.into_bytes()))
+        // Catch unstable output here, where the error can say which line
+        // changes, rather than leave it to the host's check
+        match crate::format_text_checked(request.file_path, &file_text, request.config) {
+            Ok(formatted) => Ok(formatted.map(String::into_bytes)),
+            // With verifyTokens on, keep the file as written and say why on
+            // the host's stderr, as the other entry points report it
+            Err(FormatError::TokenMismatch(mismatch)) => {
+                let _ = writeln!(
+                    WasiPrintFd(STDERR_FD),
+                    "{}: {mismatch}",
+                    request.file_path.display()
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 }
 
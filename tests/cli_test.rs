@@ -0,0 +1,94 @@
+// Runs the `dprint-java-fmt` binary, which only exists with the `cli` feature:
+//
+//     cargo test --features cli --test cli_test
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const UNFORMATTED: &str = "class A {\n  int x=1;\n}\n";
+const FORMATTED: &str = "class A {\n    int x = 1;\n}\n";
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dprint-java-fmt"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn formats_stdin_to_stdout() {
+    let output = run(&[], UNFORMATTED);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), FORMATTED);
+}
+
+#[test]
+fn check_reports_unformatted_stdin() {
+    assert_eq!(run(&["--check"], UNFORMATTED).status.code(), Some(1));
+    assert_eq!(run(&["--check"], FORMATTED).status.code(), Some(0));
+}
+
+#[test]
+fn check_skips_files_over_the_change_limit() {
+    let output = run(
+        &["--check", "--config", "maxChangedLinesPercent=10"],
+        UNFORMATTED,
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("Stdin.java: skipped: formatting would change 1 of 3 lines")
+    );
+}
+
+#[test]
+fn config_options_apply() {
+    let output = run(&["--config", "indentWidth=2"], UNFORMATTED);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "class A {\n  int x = 1;\n}\n"
+    );
+}
+
+#[test]
+fn write_rewrites_files_and_check_lists_them() {
+    let dir = std::env::temp_dir().join(format!("dprint-java-fmt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("A.java");
+    std::fs::write(&path, UNFORMATTED).unwrap();
+    let path_arg = path.to_str().unwrap();
+
+    let check = run(&["--check", path_arg], "");
+    assert_eq!(check.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(check.stdout).unwrap(),
+        format!("{path_arg}\n")
+    );
+
+    assert_eq!(run(&["--write", path_arg], "").status.code(), Some(0));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), FORMATTED);
+    assert_eq!(run(&["--check", path_arg], "").status.code(), Some(0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_arguments_exit_with_2() {
+    assert_eq!(run(&["--write"], "").status.code(), Some(2));
+    assert_eq!(
+        run(&["--check", "--write", "A.java"], "").status.code(),
+        Some(2)
+    );
+    assert_eq!(run(&["--config", "lineWidth"], "").status.code(), Some(2));
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests.
+
+use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue};
+
+/// Parse `key = value` options, such as the header lines of a spec file or
+/// the options of a quarantine manifest entry, into the map a dprint config
+/// file would give. `true` and `false` become booleans and integers become
+/// numbers; anything else is a string. Blank items and `#` comments are
+/// skipped.
+///
+/// Returns the first item without an `=` as the error.
+pub fn parse_options<'a>(
+    items: impl IntoIterator<Item = &'a str>,
+) -> Result<ConfigKeyMap, &'a str> {
+    let mut options = ConfigKeyMap::new();
+    for item in items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty() && !item.starts_with('#'))
+    {
+        let (key, value) = item.split_once('=').ok_or(item)?;
+        let value = value.trim();
+        let value = match value {
+            "true" | "false" => ConfigKeyValue::from_bool(value == "true"),
+            _ => value.parse().map_or_else(
+                |_| ConfigKeyValue::from_str(value),
+                ConfigKeyValue::from_i32,
+            ),
+        };
+        options.insert(key.trim().to_string(), value);
+    }
+    Ok(options)
+}
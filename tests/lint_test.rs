@@ -0,0 +1,35 @@
+// Source-level checks on the generation code.
+
+use std::fs;
+
+const GENERATION_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/generation");
+
+/// Wrap decisions must not read a node's source column: it is exactly what
+/// changes between formatting passes, so any decision based on it can flip
+/// on the second pass. Use `FormattingContext::effective_indent_level` plus
+/// an estimated prefix width instead.
+#[test]
+fn generation_does_not_use_source_columns() {
+    let mut offenders = Vec::new();
+    let mut scanned = 0;
+    for entry in fs::read_dir(GENERATION_DIR).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        scanned += 1;
+        for (i, line) in source.lines().enumerate() {
+            if line.contains("_position().column") {
+                let name = path.file_name().unwrap().to_string_lossy();
+                offenders.push(format!("{}:{}: {}", name, i + 1, line.trim()));
+            }
+        }
+    }
+    assert!(scanned > 0, "no sources found in {}", GENERATION_DIR);
+    assert!(
+        offenders.is_empty(),
+        "source columns used in generation code:\n  {}",
+        offenders.join("\n  ")
+    );
+}
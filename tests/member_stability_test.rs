@@ -0,0 +1,171 @@
+// Cross-member stability: a member's output is a function of its own source
+// and the configuration, so editing one member never reflows another.
+//
+// Each spec input is formatted, then formatted again with one member of a
+// top-level type removed at a time, and every other member must come out
+// exactly as before. State a member leaves behind in the formatting context
+// shows up here as a neighbour that changes when the member goes.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use dprint_core::configuration::GlobalConfiguration;
+use dprint_plugin_java::configuration::{Configuration, resolve_config};
+use dprint_plugin_java::format_text::format_text;
+use tree_sitter::Node;
+
+const SPECS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/specs");
+
+const TYPE_KINDS: &[&str] = &[
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+];
+
+/// A member of a top-level type in formatted text.
+struct Member {
+    /// Byte range of the lines the member is printed on.
+    lines: std::ops::Range<usize>,
+    /// Whether no other code or comment shares those lines.
+    own_lines: bool,
+    /// The member's output, with its indentation.
+    text: String,
+}
+
+fn format(source: &str, config: &Configuration) -> Option<String> {
+    let result = format_text(Path::new("Test.java"), source, config).ok()?;
+    Some(result.unwrap_or_else(|| source.to_string()))
+}
+
+/// The members of the top-level types in `source`, in order, or `None` when
+/// it does not parse cleanly.
+fn members(source: &str) -> Option<Vec<Member>> {
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_java::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(source, None)?;
+    if tree.root_node().has_error() {
+        return None;
+    }
+    let mut members = Vec::new();
+    let mut cursor = tree.root_node().walk();
+    for declaration in tree.root_node().named_children(&mut cursor) {
+        if !TYPE_KINDS.contains(&declaration.kind()) {
+            continue;
+        }
+        let Some(mut body) = declaration.child_by_field_name("body") else {
+            continue;
+        };
+        if let Some(declarations) = body
+            .named_children(&mut body.walk())
+            .find(|child| child.kind() == "enum_body_declarations")
+        {
+            body = declarations;
+        }
+        let mut body_cursor = body.walk();
+        for member in body.named_children(&mut body_cursor) {
+            if member.is_extra() || member.kind() == "enum_constant" {
+                continue;
+            }
+            members.push(member_at(member, source));
+        }
+    }
+    Some(members)
+}
+
+fn member_at(member: Node, source: &str) -> Member {
+    let start = source[..member.start_byte()]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let end = source[member.end_byte()..]
+        .find('\n')
+        .map_or(source.len(), |i| member.end_byte() + i + 1);
+    let own_lines = source[start..member.start_byte()].trim().is_empty()
+        && source[member.end_byte()..end].trim().is_empty();
+    Member {
+        lines: start..end,
+        own_lines,
+        text: source[start..member.end_byte()].to_string(),
+    }
+}
+
+/// The input of a spec file and the configuration its `key = value` header
+/// lines resolve to, as `spec_test` reads them.
+fn spec_case(content: &str) -> Option<(&str, Configuration)> {
+    let (header, rest) = content
+        .split_once("== input-output ==\n")
+        .or_else(|| content.split_once("== input ==\n"))?;
+    let input = rest.split("== output ==").next().unwrap_or(rest);
+    let options = common::parse_options(header.lines()).ok()?;
+    let config = resolve_config(options, &GlobalConfiguration::default()).config;
+    Some((input, config))
+}
+
+/// Check that removing each member of the formatted `input` leaves the
+/// others as they were, returning a description of the first that changed.
+fn check_source(input: &str, config: &Configuration) -> Result<(), String> {
+    // Regions kept as written are not generated, so not under test
+    if input.contains("@formatter:") || input.contains("dprint-ignore") {
+        return Ok(());
+    }
+    let Some(formatted) = format(input, config) else {
+        return Ok(());
+    };
+    let Some(before) = members(&formatted) else {
+        return Ok(());
+    };
+    for (removed, member) in before.iter().enumerate() {
+        if !member.own_lines {
+            continue;
+        }
+        let mut edited = formatted.clone();
+        edited.replace_range(member.lines.clone(), "");
+        let Some(after) = format(&edited, config).as_deref().and_then(members) else {
+            return Err(format!("removing member {removed} broke the file"));
+        };
+        if after.len() != before.len() - 1 {
+            return Err(format!(
+                "removing member {removed} changed the member count"
+            ));
+        }
+        let expected = before
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != removed)
+            .map(|(_, m)| m.text.as_str());
+        for (expected, actual) in expected.zip(after.iter().map(|m| m.text.as_str())) {
+            if expected != actual {
+                return Err(format!(
+                    "removing member {removed} changed another:\n--- before ---\n{expected}\n--- after ---\n{actual}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn members_format_independently_of_their_neighbours() {
+    let mut failures = Vec::new();
+    let entries = walkdir::WalkDir::new(SPECS_DIR).sort_by_file_name();
+    for entry in entries.into_iter().map(Result::unwrap) {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("txt") {
+            continue;
+        }
+        let content = fs::read_to_string(path).unwrap();
+        let Some((input, config)) = spec_case(&content) else {
+            continue;
+        };
+        if let Err(failure) = check_source(input, &config) {
+            let spec = path.strip_prefix(SPECS_DIR).unwrap().display();
+            failures.push(format!("{spec}: {failure}"));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
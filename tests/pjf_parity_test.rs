@@ -0,0 +1,99 @@
+// Report of where the formatter's output differs from palantir-java-format,
+// generated from notes in the headers of spec files:
+//
+//     # pjf-deviation: <what differs>   intended, and kept
+//     # pjf-gap: <what differs>         not yet matched; the spec records
+//                                       today's output
+//
+// `parity_report_is_current` fails when tests/pjf_parity.json no longer
+// matches the notes. Regenerate it with:
+//
+//     cargo test --test pjf_parity_test -- --ignored
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+const SPECS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/specs");
+const REPORT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/pjf_parity.json");
+
+/// A `# pjf-…:` note and the spec file it is in. Fields serialize in
+/// declaration order, so the report reads the same whatever map order
+/// `serde_json` is built with.
+#[derive(Serialize)]
+struct Note {
+    description: String,
+    spec: String,
+}
+
+/// The contents of tests/pjf_parity.json.
+#[derive(Serialize)]
+struct Report {
+    deviations: Vec<Note>,
+    gaps: Vec<Note>,
+}
+
+/// The notes of each kind in the spec files, ordered by spec path.
+fn collect_notes() -> (Vec<Note>, Vec<Note>) {
+    let mut deviations = Vec::new();
+    let mut gaps = Vec::new();
+    let entries = walkdir::WalkDir::new(SPECS_DIR).sort_by_file_name();
+    for entry in entries.into_iter().map(Result::unwrap) {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("txt") {
+            continue;
+        }
+        let content = fs::read_to_string(path).unwrap();
+        let spec = path
+            .strip_prefix(SPECS_DIR)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+        let header = content.lines().take_while(|line| !line.starts_with("=="));
+        for line in header.map(str::trim) {
+            let (notes, description) = if let Some(rest) = line.strip_prefix("# pjf-deviation:") {
+                (&mut deviations, rest)
+            } else if let Some(rest) = line.strip_prefix("# pjf-gap:") {
+                (&mut gaps, rest)
+            } else {
+                assert!(
+                    !line.starts_with("# pjf-"),
+                    "Unknown parity note in {}: {}",
+                    spec,
+                    line
+                );
+                continue;
+            };
+            notes.push(Note {
+                spec: spec.clone(),
+                description: description.trim().to_string(),
+            });
+        }
+    }
+    (deviations, gaps)
+}
+
+fn render_report() -> String {
+    let (deviations, gaps) = collect_notes();
+    let report = Report { deviations, gaps };
+    format!("{}\n", serde_json::to_string_pretty(&report).unwrap())
+}
+
+#[test]
+fn parity_report_is_current() {
+    let report = fs::read_to_string(REPORT_PATH).unwrap_or_default();
+    assert!(
+        report == render_report(),
+        "{} is out of date with the `# pjf-` notes in {}; regenerate it with \
+         `cargo test --test pjf_parity_test -- --ignored`",
+        Path::new(REPORT_PATH).display(),
+        SPECS_DIR
+    );
+}
+
+#[test]
+#[ignore]
+fn update_parity_report() {
+    fs::write(REPORT_PATH, render_report()).unwrap();
+}
@@ -0,0 +1,5 @@
+class AuthenticateOutputs {
+    public AuthenticateOutputs(String token) {
+        this.token = Optional.ofNullable(token).orElseThrow(() -> new IllegalArgumentException("token cannot be null"));
+    }
+}
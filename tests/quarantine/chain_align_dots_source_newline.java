@@ -0,0 +1,6 @@
+class A {
+    void f() {
+        Foo foo = Foo
+.builder().name("a").value(1).build();
+    }
+}
@@ -0,0 +1,5 @@
+class A {
+    void f() {
+        String s = "prefix" + call(aaaaaaaaaaaaaaaaaaaaaa, bbbb);
+    }
+}
@@ -0,0 +1,5 @@
+class Test {
+    void test() {
+        RequestOperation<OperationWithLeadingAndTrailingUnderscoresRequest, OperationWithLeadingAndTrailingUnderscoresResponse> operation = new OperationWithLeadingAndTrailingUnderscores.Sync(sdkConfiguration, headers);
+    }
+}
@@ -0,0 +1,6 @@
+public class Test {
+    void test() {
+        Utils.checkArgument(
+                response.isPresent() ^ error.isPresent(), "one and only one of response or error must be present");
+    }
+}
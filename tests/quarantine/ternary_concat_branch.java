@@ -0,0 +1,5 @@
+public class Test {
+    void test() {
+        String reason = e instanceof RetryableException ? "status " + ((RetryableException) e).response().statusCode() : e.getClass().getSimpleName();
+    }
+}
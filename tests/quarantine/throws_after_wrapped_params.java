@@ -0,0 +1,5 @@
+public class Connection {
+    public void connect(String hostName, int portNumber, Duration connectTimeout, boolean useTls, SocketFactory socketFactory, ProxySelector proxySelector) throws IOException, TimeoutException {
+        open();
+    }
+}
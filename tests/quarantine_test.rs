@@ -0,0 +1,125 @@
+// Ratchet over the quarantined corpus in tests/quarantine/.
+// See tests/quarantine/manifest.txt for the format and update rules.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use dprint_core::configuration::GlobalConfiguration;
+use dprint_plugin_java::configuration::{Configuration, resolve_config};
+use dprint_plugin_java::format_text::format_text_single_pass;
+
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/quarantine");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Unstable,
+}
+
+impl Status {
+    fn parse(s: &str) -> Self {
+        match s {
+            "ok" => Status::Ok,
+            "unstable" => Status::Unstable,
+            _ => panic!(
+                "Unknown quarantine status '{}' (expected ok or unstable)",
+                s
+            ),
+        }
+    }
+}
+
+struct Entry {
+    file: String,
+    status: Status,
+    config: Configuration,
+}
+
+fn parse_manifest(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let status = Status::parse(fields.next().unwrap());
+        let file = fields
+            .next()
+            .unwrap_or_else(|| panic!("Missing file name in manifest line: {}", line))
+            .to_string();
+        let options = common::parse_options(fields)
+            .unwrap_or_else(|option| panic!("Invalid option '{}' for {}", option, file));
+        let result = resolve_config(options, &GlobalConfiguration::default());
+        assert!(
+            result.diagnostics.is_empty(),
+            "Invalid options for {}: {:?}",
+            file,
+            result.diagnostics
+        );
+        entries.push(Entry {
+            file,
+            status,
+            config: result.config,
+        });
+    }
+    entries
+}
+
+/// Format `input` twice, one generation pass each, and report whether the
+/// second pass changed anything. `format_text` passes its output through
+/// again until it settles, which would hide a pass that is not stable.
+fn observe(input: &str, config: &Configuration) -> Status {
+    let path = Path::new("Test.java");
+    let pass1 = format_text_single_pass(path, input, config)
+        .unwrap()
+        .unwrap_or_else(|| input.to_string());
+    match format_text_single_pass(path, &pass1, config).unwrap() {
+        None => Status::Ok,
+        Some(_) => Status::Unstable,
+    }
+}
+
+#[test]
+fn quarantine_statuses_match_manifest() {
+    let manifest = fs::read_to_string(Path::new(CORPUS_DIR).join("manifest.txt")).unwrap();
+    let entries = parse_manifest(&manifest);
+
+    let mut problems = Vec::new();
+    for entry in &entries {
+        let input = fs::read_to_string(Path::new(CORPUS_DIR).join(&entry.file))
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", entry.file, e));
+        match (entry.status, observe(&input, &entry.config)) {
+            (Status::Unstable, Status::Ok) => problems.push(format!(
+                "{}: now stable, change its status to `ok` in manifest.txt",
+                entry.file
+            )),
+            (Status::Ok, Status::Unstable) => {
+                problems.push(format!(
+                    "{}: regressed, formatting is unstable again",
+                    entry.file
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    // Every corpus file must be tracked, so nothing sits in the directory unchecked.
+    for dir_entry in fs::read_dir(CORPUS_DIR).unwrap() {
+        let name = dir_entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .into_owned();
+        if name.ends_with(".java") && !entries.iter().any(|e| e.file == name) {
+            problems.push(format!("{}: missing from manifest.txt", name));
+        }
+    }
+
+    assert!(
+        problems.is_empty(),
+        "Quarantine status changed:\n  {}",
+        problems.join("\n  ")
+    );
+}
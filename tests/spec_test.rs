@@ -1,7 +1,13 @@
+mod common;
+
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use dprint_core::configuration::NewLineKind;
-use dprint_plugin_java::configuration::Configuration;
+use dprint_core::configuration::{GlobalConfiguration, NewLineKind};
+use dprint_plugin_java::configuration::{
+    AnnotationPlacement, ArgumentPacking, BraceStyle, Configuration, MethodChainWrapStyle,
+    StaticImportPosition, resolve_config,
+};
 use dprint_plugin_java::format_text::format_text;
 
 fn default_config() -> Configuration {
@@ -9,17 +15,56 @@ fn default_config() -> Configuration {
         line_width: 120,
         indent_width: 4,
         use_tabs: false,
+        tab_width: 4,
         new_line_kind: NewLineKind::LineFeed,
         format_javadoc: false,
         method_chain_threshold: 80,
+        method_chain_min_calls_to_break: 0,
+        break_stream_pipelines: false,
+        method_chain_wrap_style: MethodChainWrapStyle::ContinuationIndent,
         inline_lambdas: true,
+        argument_packing: ArgumentPacking::Greedy,
+        brace_style: BraceStyle::SameLine,
+        else_on_new_line: false,
+        catch_on_new_line: false,
+        finally_on_new_line: false,
+        while_on_new_line: false,
+        enum_constant_annotations: AnnotationPlacement::OwnLine,
+        align_switch_arrows: false,
+        import_order: Vec::new(),
+        static_import_position: StaticImportPosition::Top,
+        sort_imports: true,
+        remove_unused_imports: false,
+        allow_single_line_if: false,
+        keep_simple_methods_on_one_line: false,
+        keep_simple_anonymous_classes_on_one_line: false,
+        tight_double_brace_initialization: false,
+        annotation_policies: BTreeMap::new(),
+        format_chains: true,
+        wrap_field_access_chains: false,
+        chain_terminal_calls: Vec::new(),
+        format_imports: true,
+        format_strings_in_annotations: true,
+        format_with_syntax_errors: false,
+        verify_tokens: false,
+        strict: false,
+        max_changed_lines_percent: 100,
+        max_blank_lines: 1,
+        blank_lines_after_package: 1,
+        blank_lines_before_class_end: 0,
+        blank_lines_between_members: 1,
+        debug_wrap_reasons: false,
     }
 }
 
 /// Run a spec test: format `input` and assert it equals `expected`.
 fn run_spec(name: &str, input: &str, expected: &str) {
-    let config = default_config();
-    let result = format_text(Path::new("Test.java"), input, &config).unwrap();
+    run_spec_with_config(name, input, expected, &default_config());
+}
+
+/// Run a spec test with a specific configuration.
+fn run_spec_with_config(name: &str, input: &str, expected: &str, config: &Configuration) {
+    let result = format_text(Path::new("Test.java"), input, config).unwrap();
     let actual = result.unwrap_or_else(|| input.to_string());
     if actual != expected {
         panic!(
@@ -29,7 +74,7 @@ fn run_spec(name: &str, input: &str, expected: &str) {
     }
 
     // Idempotency check: formatting again should produce the same output
-    let result2 = format_text(Path::new("Test.java"), &actual, &config).unwrap();
+    let result2 = format_text(Path::new("Test.java"), &actual, config).unwrap();
     assert!(
         result2.is_none(),
         "Spec test '{}' is NOT idempotent! Second format changed the output.",
@@ -37,8 +82,17 @@ fn run_spec(name: &str, input: &str, expected: &str) {
     );
 }
 
-/// Parse a spec file with `== input ==` and `== output ==` sections.
+/// Marks the single section of a spec whose input is already formatted.
+const INPUT_OUTPUT_MARKER: &str = "== input-output ==";
+
+/// Parse a spec file with `== input ==` and `== output ==` sections, or a
+/// single `== input-output ==` section that must format to itself.
 fn parse_spec(content: &str) -> (&str, &str) {
+    if let Some(start) = content.find(INPUT_OUTPUT_MARKER) {
+        let text = content[start + INPUT_OUTPUT_MARKER.len()..].trim();
+        return (text, text);
+    }
+
     let input_marker = "== input ==";
     let output_marker = "== output ==";
 
@@ -57,6 +111,33 @@ fn parse_spec(content: &str) -> (&str, &str) {
     (input, output)
 }
 
+/// Build the configuration for a spec file.
+///
+/// Lines before `== input ==` (or `== input-output ==`) are `key = value`
+/// options resolved the same way as a dprint config file (e.g.
+/// `indentWidth = 2`), so derived defaults such as `methodChainThreshold`
+/// follow `lineWidth`. Lines starting with `#` are comments, such as the
+/// `# pjf-gap:` notes read by `pjf_parity_test`.
+fn spec_config(content: &str) -> Configuration {
+    let header_end = content
+        .find(INPUT_OUTPUT_MARKER)
+        .or_else(|| content.find("== input =="))
+        .unwrap_or(0);
+    let header = &content[..header_end];
+    let options = common::parse_options(header.lines())
+        .unwrap_or_else(|line| panic!("Invalid spec option line: {}", line));
+    if options.is_empty() {
+        return default_config();
+    }
+    let result = resolve_config(options, &GlobalConfiguration::default());
+    assert!(
+        result.diagnostics.is_empty(),
+        "Invalid spec options: {:?}",
+        result.diagnostics
+    );
+    result.config
+}
+
 fn run_spec_file(path: &str) {
     let content =
         std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
@@ -64,7 +145,12 @@ fn run_spec_file(path: &str) {
     // Add trailing newline to both since the formatter always ends with one
     let input_with_nl = format!("{}\n", input);
     let expected_with_nl = format!("{}\n", expected);
-    run_spec(path, &input_with_nl, &expected_with_nl);
+    run_spec_with_config(
+        path,
+        &input_with_nl,
+        &expected_with_nl,
+        &spec_config(&content),
+    );
 }
 
 // ======== Declaration specs ========
@@ -428,6 +514,54 @@ fn spec_file_enum_with_body() {
     ));
 }
 
+#[test]
+fn spec_file_enum_trailing_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/enum_trailing_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_enum_constant_annotations() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/enum_constant_annotations.txt"
+    ));
+}
+
+#[test]
+fn spec_file_enum_constant_annotations_inline() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/enum_constant_annotations_inline.txt"
+    ));
+}
+
+#[test]
+fn spec_file_interface_modifiers() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/interface_modifiers.txt"
+    ));
+}
+
+#[test]
+fn spec_file_type_use_annotations() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/type_use_annotations.txt"
+    ));
+}
+
+#[test]
+fn spec_file_type_use_annotations_after_modifiers() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/type_use_annotations_after_modifiers.txt"
+    ));
+}
+
 #[test]
 fn spec_file_method_basic() {
     run_spec_file(concat!(
@@ -436,6 +570,38 @@ fn spec_file_method_basic() {
     ));
 }
 
+#[test]
+fn spec_file_simple_methods_one_line() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/simple_methods_one_line.txt"
+    ));
+}
+
+#[test]
+fn spec_file_simple_bodies_after_wrapped_headers() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/simple_bodies_after_wrapped_headers.txt"
+    ));
+}
+
+#[test]
+fn spec_file_anonymous_class_one_line() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/anonymous_class_one_line.txt"
+    ));
+}
+
+#[test]
+fn spec_file_anonymous_class_member_context() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/anonymous_class_member_context.txt"
+    ));
+}
+
 #[test]
 fn spec_file_method_params() {
     run_spec_file(concat!(
@@ -476,6 +642,14 @@ fn spec_file_method_generic() {
     ));
 }
 
+#[test]
+fn spec_file_type_parameter_annotations() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/type_parameter_annotations.txt"
+    ));
+}
+
 #[test]
 fn spec_file_field_basic() {
     run_spec_file(concat!(
@@ -508,6 +682,14 @@ fn spec_file_constructor_throws() {
     ));
 }
 
+#[test]
+fn spec_file_callable_signature_throws() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/callable_signature_throws.txt"
+    ));
+}
+
 #[test]
 fn spec_file_record_basic() {
     run_spec_file(concat!(
@@ -516,6 +698,14 @@ fn spec_file_record_basic() {
     ));
 }
 
+#[test]
+fn spec_file_record_compact_constructor() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/record_compact_constructor.txt"
+    ));
+}
+
 #[test]
 fn spec_file_import_basic() {
     run_spec_file(concat!(
@@ -548,6 +738,70 @@ fn spec_file_import_sorting_single() {
     ));
 }
 
+#[test]
+fn spec_file_import_groups() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/import_groups.txt"
+    ));
+}
+
+#[test]
+fn spec_file_import_order_aosp() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/import_order_aosp.txt"
+    ));
+}
+
+#[test]
+fn spec_file_imports_unsorted() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/imports_unsorted.txt"
+    ));
+}
+
+#[test]
+fn spec_file_imports_static_bottom() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/imports_static_bottom.txt"
+    ));
+}
+
+#[test]
+fn spec_file_imports_static_mixed() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/imports_static_mixed.txt"
+    ));
+}
+
+#[test]
+fn spec_file_imports_unused() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/imports_unused.txt"
+    ));
+}
+
+#[test]
+fn spec_file_imports_unused_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/imports_unused_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_annotation_policies() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/annotation_policies.txt"
+    ));
+}
+
 #[test]
 fn spec_file_package_basic() {
     run_spec_file(concat!(
@@ -556,6 +810,14 @@ fn spec_file_package_basic() {
     ));
 }
 
+#[test]
+fn spec_file_package_annotations() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/package_annotations.txt"
+    ));
+}
+
 #[test]
 fn spec_file_package_header_blank_line() {
     run_spec_file(concat!(
@@ -609,6 +871,14 @@ fn spec_file_annotation_placement() {
     ));
 }
 
+#[test]
+fn spec_file_annotated_member_widths() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/annotated_member_widths.txt"
+    ));
+}
+
 #[test]
 fn spec_file_annotation_brace_spacing() {
     run_spec_file(concat!(
@@ -625,6 +895,14 @@ fn spec_file_annotation_arg_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_annotation_small_arrays() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/annotation_small_arrays.txt"
+    ));
+}
+
 #[test]
 fn spec_file_varargs() {
     run_spec_file(concat!(
@@ -641,6 +919,22 @@ fn spec_file_argument_list_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_argument_packing_balanced() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/argument_packing_balanced.txt"
+    ));
+}
+
+#[test]
+fn spec_file_brace_style_next_line() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/brace_style_next_line.txt"
+    ));
+}
+
 #[test]
 fn spec_file_argument_list_pjf_parity() {
     run_spec_file(concat!(
@@ -681,6 +975,46 @@ fn spec_file_class_extends_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_sealed_permits() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/sealed_permits.txt"
+    ));
+}
+
+#[test]
+fn spec_file_sealed_permits_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/sealed_permits_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_module_info() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/module_info.txt"
+    ));
+}
+
+#[test]
+fn spec_file_multiple_top_level_types() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/multiple_top_level_types.txt"
+    ));
+}
+
+#[test]
+fn spec_file_module_qualified_exports() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/module_qualified_exports.txt"
+    ));
+}
+
 #[test]
 fn spec_file_class_implements_wrapping() {
     run_spec_file(concat!(
@@ -713,6 +1047,14 @@ fn spec_file_enum_implements_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_enum_implements_constant_bodies() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/declarations/enum_implements_constant_bodies.txt"
+    ));
+}
+
 #[test]
 fn spec_file_record_implements_wrapping() {
     run_spec_file(concat!(
@@ -747,6 +1089,30 @@ fn spec_file_if_else_chain() {
     ));
 }
 
+#[test]
+fn spec_file_if_braceless_body() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/if_braceless_body.txt"
+    ));
+}
+
+#[test]
+fn spec_file_if_single_line() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/if_single_line.txt"
+    ));
+}
+
+#[test]
+fn spec_file_keywords_on_new_line() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/keywords_on_new_line.txt"
+    ));
+}
+
 #[test]
 fn spec_file_for_loop() {
     run_spec_file(concat!(
@@ -795,6 +1161,14 @@ fn spec_file_try_catch() {
     ));
 }
 
+#[test]
+fn spec_file_catch_modifiers() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/catch_modifiers.txt"
+    ));
+}
+
 #[test]
 fn spec_file_try_with_resources() {
     run_spec_file(concat!(
@@ -883,6 +1257,38 @@ fn spec_file_switch_mixed_blocks() {
     ));
 }
 
+#[test]
+fn spec_file_switch_arrow_cases() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/switch_arrow_cases.txt"
+    ));
+}
+
+#[test]
+fn spec_file_switch_patterns() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/switch_patterns.txt"
+    ));
+}
+
+#[test]
+fn spec_file_switch_label_wrapping() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/switch_label_wrapping.txt"
+    ));
+}
+
+#[test]
+fn spec_file_switch_align_arrows() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/statements/switch_align_arrows.txt"
+    ));
+}
+
 // ---- Expressions ----
 #[test]
 fn spec_file_binary_ops() {
@@ -892,6 +1298,14 @@ fn spec_file_binary_ops() {
     ));
 }
 
+#[test]
+fn spec_file_binary_return_wrapping() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/binary_return_wrapping.txt"
+    ));
+}
+
 #[test]
 fn spec_file_binary_wrapping() {
     run_spec_file(concat!(
@@ -900,6 +1314,14 @@ fn spec_file_binary_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_instanceof_guard_wrapping() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/instanceof_guard_wrapping.txt"
+    ));
+}
+
 #[test]
 fn spec_file_binary_if_condition_wrapping() {
     run_spec_file(concat!(
@@ -932,6 +1354,14 @@ fn spec_file_lambda_basic() {
     ));
 }
 
+#[test]
+fn spec_file_lambda_typed_params_wrapping() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/lambda_typed_params_wrapping.txt"
+    ));
+}
+
 #[test]
 fn spec_file_ternary() {
     run_spec_file(concat!(
@@ -956,6 +1386,22 @@ fn spec_file_object_creation() {
     ));
 }
 
+#[test]
+fn spec_file_double_brace_initialization() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/double_brace_initialization.txt"
+    ));
+}
+
+#[test]
+fn spec_file_double_brace_initialization_tight() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/double_brace_initialization_tight.txt"
+    ));
+}
+
 #[test]
 fn spec_file_array_ops() {
     run_spec_file(concat!(
@@ -1012,6 +1458,14 @@ fn spec_file_method_reference() {
     ));
 }
 
+#[test]
+fn spec_file_method_reference_generics() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/method_reference_generics.txt"
+    ));
+}
+
 #[test]
 fn spec_file_method_chain_breaking() {
     run_spec_file(concat!(
@@ -1020,6 +1474,46 @@ fn spec_file_method_chain_breaking() {
     ));
 }
 
+#[test]
+fn spec_file_chain_min_calls_to_break() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/chain_min_calls_to_break.txt"
+    ));
+}
+
+#[test]
+fn spec_file_chain_terminal_calls() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/chain_terminal_calls.txt"
+    ));
+}
+
+#[test]
+fn spec_file_stream_pipelines() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/stream_pipelines.txt"
+    ));
+}
+
+#[test]
+fn spec_file_chain_align_dots() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/chain_align_dots.txt"
+    ));
+}
+
+#[test]
+fn spec_file_field_access_chains() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/field_access_chains.txt"
+    ));
+}
+
 #[test]
 fn spec_file_method_chain_line_comment() {
     run_spec_file(concat!(
@@ -1044,6 +1538,14 @@ fn spec_file_lambda_chain_indent() {
     ));
 }
 
+#[test]
+fn spec_file_lambda_chain_getters() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/lambda_chain_getters.txt"
+    ));
+}
+
 #[test]
 fn spec_file_array_initializer_comments() {
     run_spec_file(concat!(
@@ -1076,6 +1578,14 @@ fn spec_file_chain_comments() {
     ));
 }
 
+#[test]
+fn spec_file_assertj_chains() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/assertj_chains.txt"
+    ));
+}
+
 #[test]
 fn spec_file_chain_inline_comments() {
     run_spec_file(concat!(
@@ -1084,6 +1594,22 @@ fn spec_file_chain_inline_comments() {
     ));
 }
 
+#[test]
+fn spec_file_chain_parenthesized_casts() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/chain_parenthesized_casts.txt"
+    ));
+}
+
+#[test]
+fn spec_file_rethrow_chains() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/rethrow_chains.txt"
+    ));
+}
+
 #[test]
 fn spec_file_assignment_expression_wrapping() {
     run_spec_file(concat!(
@@ -1092,6 +1618,14 @@ fn spec_file_assignment_expression_wrapping() {
     ));
 }
 
+#[test]
+fn spec_file_text_blocks() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/expressions/text_blocks.txt"
+    ));
+}
+
 // ---- Comments ----
 #[test]
 fn spec_file_trailing_whitespace() {
@@ -1101,6 +1635,78 @@ fn spec_file_trailing_whitespace() {
     ));
 }
 
+#[test]
+fn spec_file_generic_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/generic_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_formatter_off() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/formatter_off.txt"
+    ));
+}
+
+#[test]
+fn spec_file_enum_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/enum_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_argument_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/argument_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_dprint_ignore() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/dprint_ignore.txt"
+    ));
+}
+
+#[test]
+fn spec_file_switch_comments() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/switch_comments.txt"
+    ));
+}
+
+#[test]
+fn spec_file_javadoc_reflow() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/javadoc_reflow.txt"
+    ));
+}
+
+#[test]
+fn spec_file_javadoc_tags() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/javadoc_tags.txt"
+    ));
+}
+
+#[test]
+fn spec_file_markdown_doc() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/comments/markdown_doc.txt"
+    ));
+}
+
 // ---- Instability debugging ----
 
 /// Debug helper: format and check stability
@@ -1333,6 +1939,38 @@ fn spec_file_bad_formatting() {
     ));
 }
 
+#[test]
+fn spec_file_empty_list_spacing() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/mixed/empty_list_spacing.txt"
+    ));
+}
+
+#[test]
+fn spec_file_format_toggles_off() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/mixed/format_toggles_off.txt"
+    ));
+}
+
+#[test]
+fn spec_file_blank_lines() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/mixed/blank_lines.txt"
+    ));
+}
+
+#[test]
+fn spec_file_syntax_errors() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/mixed/syntax_errors.txt"
+    ));
+}
+
 // #[test]
 // fn spec_file_instance_initializer() {
 //     run_spec_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/specs/declarations/instance_initializer.txt"));
@@ -1434,6 +2072,14 @@ fn spec_file_chain_first_call_wrap() {
     ));
 }
 
+#[test]
+fn spec_file_java_lang_import() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/pjf_parity/java_lang_import.txt"
+    ));
+}
+
 #[test]
 fn spec_chain_wrapping_pjf_column_position() {
     // PJF wraps ALL segments when indent + root + first_seg > 80 (UNIFIED fill mode).
@@ -1497,3 +2143,91 @@ fn spec_chain_wrap_first_when_long_root() {
 "#,
     );
 }
+
+// ======== Indent width matrix ========
+// The same input formatted at several indent widths: continuation indent must
+// stay at two indent units and width heuristics must follow the configured unit.
+
+#[test]
+fn spec_file_indent_width_2() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/indent_width/indent_2.txt"
+    ));
+}
+
+#[test]
+fn spec_file_indent_width_3() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/indent_width/indent_3.txt"
+    ));
+}
+
+#[test]
+fn spec_file_indent_width_4() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/indent_width/indent_4.txt"
+    ));
+}
+
+#[test]
+fn spec_file_indent_width_8() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/indent_width/indent_8.txt"
+    ));
+}
+
+#[test]
+fn spec_file_indent_tabs_8() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/indent_width/tabs_8.txt"
+    ));
+}
+
+// ======== Line width matrix ========
+// The same input at the common line widths; `methodChainThreshold` is left to
+// its `lineWidth`-derived default.
+
+#[test]
+fn spec_file_line_width_80() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/line_width/line_width_80.txt"
+    ));
+}
+
+#[test]
+fn spec_file_line_width_100() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/line_width/line_width_100.txt"
+    ));
+}
+
+#[test]
+fn spec_file_line_width_120() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/line_width/line_width_120.txt"
+    ));
+}
+
+#[test]
+fn spec_file_line_width_wide_characters() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/line_width/wide_characters.txt"
+    ));
+}
+
+#[test]
+fn spec_file_plain_arguments_printer_fit() {
+    run_spec_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/specs/line_width/plain_arguments_printer_fit.txt"
+    ));
+}
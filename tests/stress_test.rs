@@ -0,0 +1,358 @@
+// Randomized stress test: generate synthetic but valid Java and check the
+// formatter's invariants on it.
+//
+// Each test covers a few hundred cases. Set STRESS_CASES to run more, and
+// STRESS_SEED to start from another seed, e.g. to reproduce a failure:
+//
+//     STRESS_CASES=1 STRESS_SEED=1234 cargo test --test stress_test -- --ignored
+
+use std::fmt::Write;
+use std::path::Path;
+
+use dprint_core::configuration::{ConfigKeyMap, ConfigKeyValue, GlobalConfiguration};
+use dprint_plugin_java::configuration::{Configuration, resolve_config};
+use dprint_plugin_java::format_text::format_text_single_pass;
+
+const DEFAULT_CASES: u64 = 300;
+
+/// SplitMix64: small, fast and reproducible from a single seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A number in `min..=max`.
+    fn between(&mut self, min: usize, max: usize) -> usize {
+        min + self.below(max - min + 1)
+    }
+
+    /// True with probability `percent` / 100.
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+/// Shape of the generated code.
+#[derive(Debug, Clone, Copy)]
+struct GenOptions {
+    /// Shortest and longest identifier.
+    identifier_len: (usize, usize),
+    /// Deepest nesting of statements and of expressions.
+    max_depth: usize,
+    /// Longest method chain.
+    max_chain_len: usize,
+}
+
+struct Generator {
+    rng: Rng,
+    options: GenOptions,
+}
+
+impl Generator {
+    fn identifier(&mut self) -> String {
+        const SYLLABLES: &[&str] = &["get", "value", "item", "count", "map", "to", "with", "on"];
+        let len = self
+            .rng
+            .between(self.options.identifier_len.0, self.options.identifier_len.1);
+        let mut name = String::new();
+        while name.len() < len {
+            let syllable = self.rng.pick(SYLLABLES);
+            if name.is_empty() {
+                name.push_str(syllable);
+            } else {
+                name.push_str(&syllable[..1].to_uppercase());
+                name.push_str(&syllable[1..]);
+            }
+        }
+        name
+    }
+
+    fn type_name(&mut self) -> String {
+        match self.rng.below(4) {
+            0 => self
+                .rng
+                .pick(&["int", "long", "boolean", "String"])
+                .to_string(),
+            1 => format!("List<{}>", self.rng.pick(&["String", "Integer", "Long"])),
+            2 => format!(
+                "Map<String, {}>",
+                self.rng.pick(&["Integer", "List<String>", "Object"])
+            ),
+            _ => self.class_name(),
+        }
+    }
+
+    fn class_name(&mut self) -> String {
+        let mut name = self.identifier();
+        name[..1].make_ascii_uppercase();
+        name
+    }
+
+    fn expression(&mut self, depth: usize) -> String {
+        if depth >= self.options.max_depth {
+            return self.atom();
+        }
+        match self.rng.below(8) {
+            0 | 1 => self.atom(),
+            2 => format!(
+                "{} {} {}",
+                self.expression(depth + 1),
+                self.rng.pick(&["+", "-", "*", "&&", "||", "==", "<"]),
+                self.expression(depth + 1)
+            ),
+            3 => self.call(depth),
+            4 => self.chain(depth),
+            5 => format!(
+                "{} ? {} : {}",
+                self.expression(depth + 1),
+                self.expression(depth + 1),
+                self.expression(depth + 1)
+            ),
+            6 => {
+                let class_name = self.class_name();
+                let type_arguments = if self.rng.chance(30) { "<>" } else { "" };
+                format!(
+                    "new {}{}({})",
+                    class_name,
+                    type_arguments,
+                    self.arguments(depth)
+                )
+            }
+            _ => format!("({})", self.expression(depth + 1)),
+        }
+    }
+
+    fn atom(&mut self) -> String {
+        match self.rng.below(4) {
+            0 => self.rng.below(10_000).to_string(),
+            1 => format!("\"{}\"", self.identifier()),
+            _ => self.identifier(),
+        }
+    }
+
+    fn arguments(&mut self, depth: usize) -> String {
+        let count = self.rng.below(4);
+        (0..count)
+            .map(|_| {
+                if self.rng.chance(20) {
+                    format!("{} -> {}", self.identifier(), self.expression(depth + 1))
+                } else {
+                    self.expression(depth + 1)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn call(&mut self, depth: usize) -> String {
+        format!("{}({})", self.identifier(), self.arguments(depth))
+    }
+
+    fn chain(&mut self, depth: usize) -> String {
+        let mut chain = self.identifier();
+        for _ in 0..self.rng.between(1, self.options.max_chain_len) {
+            chain.push('.');
+            chain.push_str(&self.call(depth + 1));
+        }
+        chain
+    }
+
+    fn statement(&mut self, out: &mut String, indent: usize, depth: usize) {
+        let pad = "  ".repeat(indent);
+        let nested = depth < self.options.max_depth;
+        match self.rng.below(if nested { 7 } else { 4 }) {
+            0 => {
+                let ty = self.type_name();
+                let name = self.identifier();
+                let value = self.expression(0);
+                writeln!(out, "{pad}{ty} {name} = {value};").unwrap();
+            }
+            1 => writeln!(out, "{pad}{};", self.chain(0)).unwrap(),
+            2 => writeln!(out, "{pad}{};", self.call(0)).unwrap(),
+            3 => writeln!(out, "{pad}{} = {};", self.identifier(), self.expression(0)).unwrap(),
+            4 => {
+                writeln!(out, "{pad}if ({}) {{", self.expression(0)).unwrap();
+                self.block(out, indent + 1, depth + 1);
+                if self.rng.chance(40) {
+                    writeln!(out, "{pad}}} else {{").unwrap();
+                    self.block(out, indent + 1, depth + 1);
+                }
+                writeln!(out, "{pad}}}").unwrap();
+            }
+            5 => {
+                let ty = self.type_name();
+                let name = self.identifier();
+                let items = self.expression(0);
+                writeln!(out, "{pad}for ({ty} {name} : {items}) {{").unwrap();
+                self.block(out, indent + 1, depth + 1);
+                writeln!(out, "{pad}}}").unwrap();
+            }
+            _ => {
+                writeln!(out, "{pad}while ({}) {{", self.expression(0)).unwrap();
+                self.block(out, indent + 1, depth + 1);
+                writeln!(out, "{pad}}}").unwrap();
+            }
+        }
+    }
+
+    fn block(&mut self, out: &mut String, indent: usize, depth: usize) {
+        for _ in 0..self.rng.between(1, 3) {
+            self.statement(out, indent, depth);
+        }
+    }
+
+    fn compilation_unit(&mut self) -> String {
+        let mut out = String::new();
+        let class_name = self.class_name();
+        writeln!(out, "class {class_name} {{").unwrap();
+        for _ in 0..self.rng.between(0, 2) {
+            let ty = self.type_name();
+            let name = self.identifier();
+            let value = self.expression(0);
+            writeln!(out, "  private {ty} {name} = {value};").unwrap();
+        }
+        for _ in 0..self.rng.between(1, 3) {
+            let ty = self.type_name();
+            let name = self.identifier();
+            let params = (0..self.rng.below(4))
+                .map(|_| format!("{} {}", self.type_name(), self.identifier()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(out, "  {ty} {name}({params}) {{").unwrap();
+            self.block(&mut out, 2, 0);
+            writeln!(out, "    return {};", self.expression(0)).unwrap();
+            writeln!(out, "  }}").unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+fn config(line_width: u32) -> Configuration {
+    let options = ConfigKeyMap::from([(
+        "lineWidth".to_string(),
+        ConfigKeyValue::from_i32(line_width as i32),
+    )]);
+    resolve_config(options, &GlobalConfiguration::default()).config
+}
+
+/// An invariant checked on the formatter's output for each generated file.
+type Invariant = fn(input: &str, output: &str, config: &Configuration) -> Option<String>;
+
+/// Formatting may only move whitespace in generated code, which has no
+/// comments, imports or modifiers to reorder.
+fn keeps_text(input: &str, output: &str, _config: &Configuration) -> Option<String> {
+    let without_whitespace =
+        |text: &str| -> String { text.chars().filter(|c| !c.is_whitespace()).collect() };
+    (without_whitespace(output) != without_whitespace(input))
+        .then(|| "output changed more than whitespace".to_string())
+}
+
+/// Formatting the output again changes nothing. Both are single generation
+/// passes: `format_text` formats its output again until it settles, which
+/// would hide a pass that is not stable.
+fn is_idempotent(_input: &str, output: &str, config: &Configuration) -> Option<String> {
+    match format_text_single_pass(Path::new("Stress.java"), output, config) {
+        Ok(None) => None,
+        Ok(Some(_)) => Some("second pass changed the output".to_string()),
+        Err(e) => Some(format!("output failed to format: {}", e)),
+    }
+}
+
+/// No line is wider than the line width, unless it holds a single token
+/// that cannot be broken.
+fn fits_line_width(_input: &str, output: &str, config: &Configuration) -> Option<String> {
+    let overflowing: Vec<usize> = output
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            line.len() > config.line_width as usize && line.split_whitespace().nth(1).is_some()
+        })
+        .map(|(i, _)| i + 1)
+        .collect();
+    (!overflowing.is_empty()).then(|| format!("lines {:?} are too wide", overflowing))
+}
+
+/// Format `cases` generated files starting at `STRESS_SEED` and check each
+/// output against `invariants`.
+fn run_stress(default_cases: u64, invariants: &[Invariant]) {
+    let env_number = |name: &str, default: u64| {
+        std::env::var(name)
+            .ok()
+            .map_or(default, |value| value.parse().expect(name))
+    };
+    let cases = env_number("STRESS_CASES", default_cases);
+    let first_seed = env_number("STRESS_SEED", 0);
+
+    let mut failures = Vec::new();
+    for seed in first_seed..first_seed + cases {
+        let mut rng = Rng(seed);
+        let options = GenOptions {
+            identifier_len: (rng.between(1, 6), rng.between(6, 30)),
+            max_depth: rng.between(1, 4),
+            max_chain_len: rng.between(1, 6),
+        };
+        let line_width = [60, 80, 100, 120][rng.below(4)];
+        let config = config(line_width);
+        let input = Generator { rng, options }.compilation_unit();
+
+        let problems = match format_text_single_pass(Path::new("Stress.java"), &input, &config) {
+            Ok(result) => {
+                let output = result.unwrap_or_else(|| input.clone());
+                invariants
+                    .iter()
+                    .filter_map(|invariant| invariant(&input, &output, &config))
+                    .collect()
+            }
+            Err(e) => vec![format!("failed to format: {}", e)],
+        };
+        if !problems.is_empty() {
+            failures.push(format!(
+                "seed {} (lineWidth {}, {:?}): {}\n{}",
+                seed,
+                line_width,
+                options,
+                problems.join("; "),
+                input
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} generated files broke an invariant:\n\n{}",
+        failures.len(),
+        cases,
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn generated_java_formats() {
+    run_stress(DEFAULT_CASES, &[keeps_text]);
+}
+
+/// Line width still fails on many generated files, mostly nested
+/// conditionals and deep argument nesting, so this runs on demand:
+///
+///     cargo test --test stress_test -- --ignored
+#[test]
+#[ignore = "known failures; run on demand to find wrap estimator bugs"]
+fn generated_java_is_stable_and_fits() {
+    run_stress(DEFAULT_CASES, &[keeps_text, is_idempotent, fits_line_width]);
+}
@@ -1,9 +1,15 @@
 // Helper test to update all spec files with current formatter output
 // Run with: cargo test --test update_specs -- --ignored
 
-use dprint_core::configuration::NewLineKind;
-use dprint_plugin_java::configuration::Configuration;
+mod common;
+
+use dprint_core::configuration::{GlobalConfiguration, NewLineKind};
+use dprint_plugin_java::configuration::{
+    AnnotationPlacement, ArgumentPacking, BraceStyle, Configuration, MethodChainWrapStyle,
+    StaticImportPosition, resolve_config,
+};
 use dprint_plugin_java::format_text::format_text;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -12,11 +18,56 @@ fn default_config() -> Configuration {
         line_width: 120,
         indent_width: 4,
         use_tabs: false,
+        tab_width: 4,
         new_line_kind: NewLineKind::LineFeed,
         format_javadoc: false,
         method_chain_threshold: 80,
+        method_chain_min_calls_to_break: 0,
+        break_stream_pipelines: false,
+        method_chain_wrap_style: MethodChainWrapStyle::ContinuationIndent,
         inline_lambdas: true,
+        argument_packing: ArgumentPacking::Greedy,
+        brace_style: BraceStyle::SameLine,
+        else_on_new_line: false,
+        catch_on_new_line: false,
+        finally_on_new_line: false,
+        while_on_new_line: false,
+        enum_constant_annotations: AnnotationPlacement::OwnLine,
+        align_switch_arrows: false,
+        import_order: Vec::new(),
+        static_import_position: StaticImportPosition::Top,
+        sort_imports: true,
+        remove_unused_imports: false,
+        allow_single_line_if: false,
+        keep_simple_methods_on_one_line: false,
+        keep_simple_anonymous_classes_on_one_line: false,
+        tight_double_brace_initialization: false,
+        annotation_policies: BTreeMap::new(),
+        format_chains: true,
+        wrap_field_access_chains: false,
+        chain_terminal_calls: Vec::new(),
+        format_imports: true,
+        format_strings_in_annotations: true,
+        format_with_syntax_errors: false,
+        verify_tokens: false,
+        strict: false,
+        max_changed_lines_percent: 100,
+        max_blank_lines: 1,
+        blank_lines_after_package: 1,
+        blank_lines_before_class_end: 0,
+        blank_lines_between_members: 1,
+        debug_wrap_reasons: false,
+    }
+}
+
+/// Resolve the `key = value` options found before the first section (see spec_test.rs).
+fn spec_config(header: &str) -> Configuration {
+    let options = common::parse_options(header.lines())
+        .unwrap_or_else(|line| panic!("Invalid spec option line: {}", line));
+    if options.is_empty() {
+        return default_config();
     }
+    resolve_config(options, &GlobalConfiguration::default()).config
 }
 
 fn update_spec_file(path: &std::path::Path) -> Result<bool, Box<dyn std::error::Error>> {
@@ -24,29 +75,39 @@ fn update_spec_file(path: &std::path::Path) -> Result<bool, Box<dyn std::error::
 
     let input_marker = "== input ==";
     let output_marker = "== output ==";
+    let input_output_marker = "== input-output ==";
 
-    if !content.contains(input_marker) || !content.contains(output_marker) {
+    let (header_end, input) = if let Some(start) = content.find(input_output_marker) {
+        (start, content[start + input_output_marker.len()..].trim())
+    } else if let (Some(start), Some(output_start)) =
+        (content.find(input_marker), content.find(output_marker))
+    {
+        (
+            start,
+            content[start + input_marker.len()..output_start].trim(),
+        )
+    } else {
         return Ok(false);
-    }
-
-    let input_start = content.find(input_marker).unwrap() + input_marker.len();
-    let output_start_marker = content.find(output_marker).unwrap();
-    let input = content[input_start..output_start_marker].trim();
+    };
+    let header = &content[..header_end];
     let input_with_nl = format!("{}\n", input);
 
     // Format the input
-    let config = default_config();
+    let config = spec_config(header);
     let result = format_text(Path::new("Test.java"), &input_with_nl, &config)?;
     let formatted = result.unwrap_or_else(|| input_with_nl.clone());
     let formatted_trimmed = formatted.trim();
 
-    // Reconstruct the file
-    let new_content = format!(
-        "{}== input ==\n{}\n== output ==\n{}\n",
-        &content[..content.find(input_marker).unwrap()],
-        input,
-        formatted_trimmed
-    );
+    // Reconstruct the file, keeping a single section while the input is
+    // already formatted
+    let new_content = if formatted_trimmed == input && content.contains(input_output_marker) {
+        format!("{}== input-output ==\n{}\n", header, input)
+    } else {
+        format!(
+            "{}== input ==\n{}\n== output ==\n{}\n",
+            header, input, formatted_trimmed
+        )
+    };
 
     if new_content != content {
         fs::write(path, new_content)?;
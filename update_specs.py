@@ -8,23 +8,29 @@ from pathlib import Path
 def update_spec_file(spec_path):
     """Update a single spec file with actual formatter output."""
     content = spec_path.read_text()
+    single_section = "== input-output ==" in content
+
+    if single_section:
+        # The input is already formatted and is its own expected output
+        before_input, input_code = content.split("== input-output ==", 1)
+        input_code = input_code.strip() + "\n"
+    else:
+        if "== input ==" not in content or "== output ==" not in content:
+            return False
 
-    if "== input ==" not in content or "== output ==" not in content:
-        return False
-
-    # Extract input section
-    parts = content.split("== input ==")
-    if len(parts) != 2:
-        return False
+        # Extract input section
+        parts = content.split("== input ==")
+        if len(parts) != 2:
+            return False
 
-    before_input = parts[0]
-    after_input = parts[1]
+        before_input = parts[0]
+        after_input = parts[1]
 
-    output_parts = after_input.split("== output ==")
-    if len(output_parts) != 2:
-        return False
+        output_parts = after_input.split("== output ==")
+        if len(output_parts) != 2:
+            return False
 
-    input_code = output_parts[0].strip() + "\n"
+        input_code = output_parts[0].strip() + "\n"
 
     # Run formatter on input
     try:
@@ -43,7 +49,10 @@ def update_spec_file(spec_path):
         formatted_output = result.stdout
 
         # Reconstruct spec file
-        new_content = f"{before_input}== input ==\n{input_code}== output ==\n{formatted_output}"
+        if single_section and formatted_output == input_code:
+            new_content = f"{before_input}== input-output ==\n{input_code}"
+        else:
+            new_content = f"{before_input}== input ==\n{input_code}== output ==\n{formatted_output}"
 
         if new_content != content:
             spec_path.write_text(new_content)